@@ -0,0 +1,163 @@
+//! Self-contained HTML report of a check.
+
+use smt2parser::vmt::VMTModel;
+use std::{collections::BTreeMap, error, io::Write, path::Path};
+use z3tracer::{
+    report::{html_escape, write_html_sections, HtmlReportConfig, HTML_STYLE},
+    Model,
+};
+
+use super::{load_log, write_file, Error, Timings, Verdict};
+
+/// Description of a check to report.
+pub struct Run<'a> {
+    /// Path to the VMT file.
+    pub input: &'a Path,
+    pub vmt: &'a VMTModel,
+    pub bound: u8,
+    pub verdict: &'a Verdict,
+    pub timings: &'a Timings,
+    /// Z3 trace of the check, if any.
+    pub trace_log: Option<&'a Path>,
+}
+
+/// Write the report of a check to `path`: statistics of the model, verdict, timings,
+/// values of the counterexample at each step, and summary of the quantifier
+/// instantiations of the Z3 trace.
+pub fn write_report(path: &Path, run: &Run) -> Result<(), Error> {
+    let model = match run.trace_log {
+        Some(log) => Some(
+            load_log(log).map_err(|error| Error::Parse(format!("{}: {}", log.display(), error)))?,
+        ),
+        None => None,
+    };
+    let mut html = Vec::new();
+    write_page(&mut html, run, model.as_ref())
+        .map_err(|error| Error::Parse(format!("Unable to render the report: {}", error)))?;
+    write_file(path, html)
+}
+
+fn write_page<W: Write>(
+    out: &mut W,
+    run: &Run,
+    model: Option<&Model>,
+) -> Result<(), Box<dyn error::Error>> {
+    let title = format!("berty: {}", run.input.display());
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{}</title>", html_escape(&title))?;
+    writeln!(out, "<style>{}</style>", HTML_STYLE)?;
+    writeln!(out, "</head>\n<body>")?;
+    writeln!(out, "<h1>{}</h1>", html_escape(&title))?;
+
+    writeln!(out, "<h2>Model</h2>")?;
+    writeln!(out, "<ul>")?;
+    for (name, count) in run.vmt.stats() {
+        writeln!(out, "<li>{}: {}</li>", html_escape(name), count)?;
+    }
+    writeln!(out, "</ul>")?;
+    writeln!(out, "<table>")?;
+    writeln!(
+        out,
+        "<tr><th>Formula</th><th>Nodes</th><th>Depth</th><th>Quantifiers</th><th>select</th><th>store</th></tr>"
+    )?;
+    for (name, metrics) in run.vmt.metrics() {
+        let count = |operator: &str| metrics.operators.get(operator).copied().unwrap_or(0);
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            html_escape(name),
+            metrics.size,
+            metrics.depth,
+            metrics.quantifiers,
+            count("select"),
+            count("store")
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    writeln!(out, "<h2>Verdict</h2>")?;
+    let verdict = match run.verdict {
+        Verdict::Safe => format!("safe up to bound {}", run.bound),
+        Verdict::Proven => "safe for all steps".to_string(),
+        Verdict::Unsafe { .. } => format!("unsafe within bound {}", run.bound),
+        Verdict::Unknown { reason } => format!("unknown ({})", reason),
+    };
+    writeln!(out, "<p>{}</p>", html_escape(&verdict))?;
+    writeln!(out, "<table>")?;
+    writeln!(out, "<tr><th>Phase</th><th>Time (s)</th></tr>")?;
+    for (phase, duration) in &run.timings.0 {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{:.3}</td></tr>",
+            html_escape(phase),
+            duration.as_secs_f64()
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    if let Verdict::Unsafe { counterexample } = run.verdict {
+        writeln!(out, "<h2>Counterexample</h2>")?;
+        write_counterexample(out, counterexample)?;
+    }
+
+    if let Some(model) = model {
+        writeln!(out, "<h2>Quantifier instantiations</h2>")?;
+        write_html_sections(model, &HtmlReportConfig::default(), out)?;
+    }
+    writeln!(out, "</body>\n</html>")?;
+    Ok(())
+}
+
+// Table of the values of the variables (rows) at each step (columns). Values of other
+// constants are listed after the table.
+fn write_counterexample<W: Write>(out: &mut W, counterexample: &str) -> std::io::Result<()> {
+    let mut steps = 0;
+    let mut variables = BTreeMap::<&str, BTreeMap<usize, &str>>::new();
+    let mut others = Vec::new();
+    for line in counterexample.lines() {
+        let (name, value) = match line.split_once(" -> ") {
+            Some(assignment) => assignment,
+            None => continue,
+        };
+        let step = name
+            .rsplit_once('@')
+            .and_then(|(variable, step)| Some((variable, step.parse::<usize>().ok()?)));
+        match step {
+            Some((variable, step)) => {
+                steps = steps.max(step + 1);
+                variables.entry(variable).or_default().insert(step, value);
+            }
+            None => others.push((name, value)),
+        }
+    }
+    writeln!(out, "<table>")?;
+    write!(out, "<tr><th>Variable</th>")?;
+    for step in 0..steps {
+        write!(out, "<th>{}</th>", step)?;
+    }
+    writeln!(out, "</tr>")?;
+    for (variable, values) in &variables {
+        write!(out, "<tr><td>{}</td>", html_escape(variable))?;
+        for step in 0..steps {
+            let value = values.get(&step).copied().unwrap_or("");
+            write!(out, "<td>{}</td>", html_escape(value))?;
+        }
+        writeln!(out, "</tr>")?;
+    }
+    writeln!(out, "</table>")?;
+    if !others.is_empty() {
+        writeln!(out, "<ul>")?;
+        others.sort_unstable();
+        for (name, value) in others {
+            writeln!(
+                out,
+                "<li>{} = {}</li>",
+                html_escape(name),
+                html_escape(value)
+            )?;
+        }
+        writeln!(out, "</ul>")?;
+    }
+    Ok(())
+}
@@ -1,125 +1,1943 @@
+#[macro_use]
+extern crate log;
+extern crate serde_json;
+extern crate smt2parser;
+extern crate structopt;
 extern crate z3;
+extern crate z3_sys;
 extern crate z3tracer;
+
+mod html;
+mod repl;
+mod translate;
+
+use smt2parser::{
+    abstraction::{array_axioms, ArrayAbstractor},
+    concrete::{Command, Identifier, QualIdentifier, SyntaxBuilder, Term},
+    diff::diff_commands,
+    inlining::{RecursiveUnfolder, SortExpander},
+    logic::Fragment,
+    mining::{InvariantMiner, Template},
+    portfolio::Portfolio,
+    proof::NamedProblem,
+    responses::{split_responses, Response},
+    sat::SatSolver,
+    schema::VMTModelJson,
+    solver::{self, ProcessSolver, SolverConfig, SolverError, SolverKind},
+    vmt::{SMTProblem, VMTModel},
+    CommandStream,
+};
 use std::{
-    collections::HashMap,
-    fs,
-    path::PathBuf,
+    collections::BTreeSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex, MutexGuard,
+    },
+    thread,
+    time::{Duration, Instant},
 };
-use z3::{Config, Context, SatResult, Solver};
+use structopt::StructOpt;
+use translate::Translator;
+use z3::{Config, Context, Params, SatResult, Solver, Tactic};
 use z3tracer::{
-    get_instantiations, parser::ParserConfig, report::process_file,
-    syntax::Ident, Model, ModelConfig,
+    get_instantiations,
+    parser::ParserConfig,
+    report::{process_file, ProcessError},
+    syntax::Ident,
+    Model, ModelConfig,
 };
 
+/// Bounded model checking of VMT models, and analysis of Z3 traces.
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "berty",
+    after_help = "EXIT STATUS:\n    0    the property holds up to the bound, or the operation succeeded\n    \
+                  10   a counterexample was found\n    20   the check was inconclusive\n    \
+                  30   an input could not be read or parsed\n    40   the solver failed\n    \
+                  50   an output could not be written"
+)]
+struct Options {
+    /// Log progress to the standard error. Repeat for more details (-vv, -vvv).
+    #[structopt(short, long, parse(from_occurrences), global = true)]
+    verbose: u8,
+
+    /// Operation
+    #[structopt(subcommand)]
+    operation: Operation,
+}
+
+#[derive(Debug, StructOpt)]
+enum Operation {
+    /// Unroll the transition relation of a VMT model and check the property.
+    Bmc {
+        /// Path to the VMT file.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        /// Number of unrolling steps.
+        #[structopt(short = "k", long, default_value = "10")]
+        bound: u8,
+
+        /// Where to write the SMT-LIB problem sent to the solver.
+        #[structopt(long, parse(from_os_str))]
+        output: Option<PathBuf>,
+
+        /// Write the problem in canonical form, with sorted declarations, so that the
+        /// output files of several runs can be compared.
+        #[structopt(long)]
+        canonical: bool,
+
+        /// Start the problem with a `set-logic` command, naming the smallest standard
+        /// SMT-LIB logic of the problem, or `ALL`.
+        #[structopt(long)]
+        logic: bool,
+
+        /// Path to an SMT-LIB solver executable to use instead of the built-in Z3.
+        /// The problem is passed to the solver as a file argument.
+        #[structopt(long, parse(from_os_str))]
+        solver: Option<PathBuf>,
+
+        /// Let Z3 trace its quantifier instantiations, then analyze the trace. When an
+        /// external solver is used, it must accept Z3's command-line options.
+        #[structopt(long)]
+        trace: bool,
+
+        /// Where to write the Z3 trace (default: a temporary file). Implies `--trace`.
+        /// The trace is only analyzed with the text format.
+        #[structopt(long, parse(from_os_str))]
+        trace_log: Option<PathBuf>,
+
+        /// Fold the constants of the problem before solving it.
+        #[structopt(long)]
+        simplify: bool,
+
+        /// Run BMC, CEGAR and k-induction up to the bound concurrently with the built-in Z3,
+        /// and report the first counterexample or proof with the engine that found it.
+        /// Otherwise, report that the property holds up to the bound if some engine found
+        /// no counterexample.
+        #[structopt(long, conflicts_with_all = &["solver", "trace", "trace-log"])]
+        portfolio: bool,
+
+        /// Maximum number of refinements of the CEGAR engine of the portfolio.
+        #[structopt(long, default_value = "10")]
+        max_refinements: usize,
+
+        /// Run the given external solvers concurrently on the problem, e.g. `z3,cvc5`, and
+        /// report the first safe or unsafe verdict with the solver that found it. Z3 tactics
+        /// can be raced with `z3:TACTIC`. The timeout bounds the whole run.
+        #[structopt(
+            long,
+            use_delimiter = true,
+            conflicts_with_all = &["solver", "trace", "trace-log", "portfolio"]
+        )]
+        solvers: Vec<String>,
+
+        /// Path to a SAT solver reading DIMACS CNF on its standard input, e.g. kissat, to
+        /// check purely boolean models after their Tseitin encoding. The timeout bounds
+        /// the check; other solver options are ignored.
+        #[structopt(
+            long,
+            parse(from_os_str),
+            conflicts_with_all = &["solver", "trace", "trace-log", "portfolio", "solvers"]
+        )]
+        sat_solver: Option<PathBuf>,
+
+        /// Output format: `text` or `json`.
+        #[structopt(long, default_value = "text")]
+        format: Format,
+
+        /// Where to write a self-contained HTML report of the run.
+        #[structopt(long, parse(from_os_str))]
+        html: Option<PathBuf>,
+
+        #[structopt(flatten)]
+        solver_options: SolverOptions,
+    },
+    /// Check the property by refining an abstraction of arrays with lemmas obtained
+    /// from the instantiations of array axioms.
+    Cegar {
+        /// Path to the VMT file.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        /// Number of unrolling steps.
+        #[structopt(short = "k", long, default_value = "10")]
+        bound: u8,
+
+        /// Maximum number of refinements before giving up.
+        #[structopt(long, default_value = "10")]
+        max_refinements: usize,
+
+        /// Time limit in milliseconds for checking a counterexample against the array
+        /// axioms (default: the time limit per query, or 10000).
+        #[structopt(long)]
+        axioms_timeout: Option<u32>,
+
+        /// SMT-LIB file of lemmas learned by a previous run, as assertions. Lemmas about
+        /// steps beyond the bound are ignored, and so are lemmas that do not follow from
+        /// the array axioms, e.g. lemmas learned on another model.
+        #[structopt(long, parse(from_os_str))]
+        lemmas_in: Option<PathBuf>,
+
+        /// Where to write the lemmas known at the end of the run, as SMT-LIB assertions.
+        #[structopt(long, parse(from_os_str))]
+        lemmas_out: Option<PathBuf>,
+
+        /// Output format: `text` or `json`.
+        #[structopt(long, default_value = "text")]
+        format: Format,
+
+        /// Where to write a self-contained HTML report of the run.
+        #[structopt(long, parse(from_os_str))]
+        html: Option<PathBuf>,
+
+        #[structopt(flatten)]
+        solver_options: SolverOptions,
+    },
+    /// Prove the property of a VMT model for all steps by induction, strengthening it
+    /// with candidate invariants if needed.
+    Induction {
+        /// Path to the VMT file.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        /// SMT-LIB file of candidate invariants over the state variables, as assertions.
+        /// Candidates that are not inductive are dropped.
+        #[structopt(long, parse(from_os_str))]
+        candidates: Option<PathBuf>,
+
+        /// Add candidates mined from the states reachable in up to this number of steps,
+        /// sampled with Z3 as a subprocess.
+        #[structopt(long)]
+        mine: Option<u8>,
+
+        /// Template of mined candidates: `equalities`, `octagons` or `array-ranges`. Can
+        /// be repeated. All templates are used by default.
+        #[structopt(long = "template", number_of_values = 1)]
+        templates: Vec<Template>,
+
+        /// Number of states sampled for each number of steps when mining candidates.
+        #[structopt(long, default_value = "3")]
+        samples: usize,
+
+        /// Where to write the model with the inductive invariant as property, in VMT
+        /// format, when the property is proven.
+        #[structopt(long, parse(from_os_str))]
+        invariant_out: Option<PathBuf>,
+
+        /// Output format: `text` or `json`.
+        #[structopt(long, default_value = "text")]
+        format: Format,
+
+        #[structopt(flatten)]
+        solver_options: SolverOptions,
+    },
+    /// Check a BMC problem with Z3 as a subprocess and report which steps of the unrolling
+    /// and which lemmas appear in an unsat core, when the property holds up to the bound.
+    Core {
+        /// Path to the VMT file.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        /// Number of unrolling steps.
+        #[structopt(short = "k", long, default_value = "10")]
+        bound: u8,
+
+        /// SMT-LIB file of lemmas over the variables of the steps, e.g. `x@0`, as
+        /// assertions.
+        #[structopt(long, parse(from_os_str))]
+        lemmas: Option<PathBuf>,
+
+        /// Output format: `text` or `json`.
+        #[structopt(long, default_value = "text")]
+        format: Format,
+
+        #[structopt(flatten)]
+        solver_options: SolverOptions,
+    },
+    /// Explore a BMC problem and the Z3 trace of its resolution interactively.
+    Repl {
+        /// Path to the VMT file.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        /// Number of unrolling steps.
+        #[structopt(short = "k", long, default_value = "10")]
+        bound: u8,
+
+        /// Abstract arrays and add the array axioms to the problem.
+        #[structopt(long = "abstract")]
+        abstract_arrays: bool,
+
+        #[structopt(flatten)]
+        solver_options: SolverOptions,
+    },
+    /// Abstract arrays by uninterpreted sorts and functions.
+    Abstract {
+        /// Path to the VMT file.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        /// Where to write the abstracted VMT model (default: standard output).
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Print statistics on a VMT model.
+    Stats {
+        /// Path to the VMT file.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        /// Output format: `text`, or `json` to print the model itself.
+        #[structopt(long, default_value = "text")]
+        format: Format,
+    },
+    /// Print the structural differences between two SMT-LIB scripts or VMT models, e.g.
+    /// before and after an abstraction.
+    Diff {
+        /// Path to the first file.
+        #[structopt(parse(from_os_str))]
+        before: PathBuf,
+
+        /// Path to the second file.
+        #[structopt(parse(from_os_str))]
+        after: PathBuf,
+    },
+    /// Print the dependencies between the state variables of a VMT model in DOT format.
+    Dependencies {
+        /// Path to the VMT file.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+
+        /// Where to write the graph (default: standard output).
+        #[structopt(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Analyze the quantifier instantiations of a Z3 trace log.
+    TraceAnalyze {
+        /// Path to the Z3 log file.
+        #[structopt(parse(from_os_str))]
+        log: PathBuf,
+
+        /// Output format: `text`, or `json` to print a summary of the trace and its
+        /// instantiation profile.
+        #[structopt(long, default_value = "text")]
+        format: Format,
+    },
+}
+
+/// Options forwarded to the solver.
+#[derive(Debug, Clone, Default, StructOpt)]
+struct SolverOptions {
+    /// Solver option `KEY=VALUE`, e.g. `smt.mbqi=false` for Z3 or `finite-model-find=true`
+    /// for cvc5. Can be repeated.
+    #[structopt(
+        long = "solver-opt",
+        number_of_values = 1,
+        parse(try_from_str = parse_solver_option)
+    )]
+    options: Vec<(String, String)>,
+
+    /// Random seed of the solver.
+    #[structopt(long)]
+    seed: Option<u32>,
+
+    /// Time limit per query in milliseconds. With `cegar`, this also limits the checks
+    /// against the array axioms unless `--axioms-timeout` is given.
+    #[structopt(long)]
+    timeout: Option<u32>,
+
+    /// Z3 tactic used to check queries, e.g. `qfufbv`.
+    #[structopt(long)]
+    tactic: Option<String>,
+
+    /// Once set, checks with the built-in Z3 are interrupted and answer unknown, e.g. in
+    /// the engines that lost a portfolio race.
+    #[structopt(skip)]
+    cancel: Option<Arc<AtomicBool>>,
+}
+
+fn parse_solver_option(s: &str) -> Result<(String, String), String> {
+    match s.find('=') {
+        Some(index) => Ok((s[..index].to_string(), s[index + 1..].to_string())),
+        None => Err(format!("Expected KEY=VALUE: {}", s)),
+    }
+}
+
+impl SolverOptions {
+    // Z3 parameters for the options, except the timeout and the tactic.
+    fn z3_params(&self) -> Vec<(String, String)> {
+        let mut params = self.options.clone();
+        if let Some(seed) = self.seed {
+            params.push(("smt.random_seed".to_string(), seed.to_string()));
+        }
+        params
+    }
+
+    // Command-line arguments of an external solver, in the syntax of cvc5 or of Z3.
+    fn command_args(&self, cvc5: bool) -> Vec<String> {
+        if cvc5 {
+            let mut args = self
+                .options
+                .iter()
+                .map(|(key, value)| format!("--{}={}", key, value))
+                .collect::<Vec<_>>();
+            args.extend(self.seed.map(|seed| format!("--seed={}", seed)));
+            args.extend(
+                self.timeout
+                    .map(|timeout| format!("--tlimit-per={}", timeout)),
+            );
+            args
+        } else {
+            let mut args = self
+                .z3_params()
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>();
+            args.extend(self.timeout.map(|timeout| format!("-t:{}", timeout)));
+            args
+        }
+    }
+
+    // The `check-sat` command ending a problem.
+    fn check_sat(&self) -> String {
+        match &self.tactic {
+            Some(tactic) => format!("(check-sat-using {})", tactic),
+            None => "(check-sat)".to_string(),
+        }
+    }
+
+    // Start Z3 as a subprocess with the options and use it. The timeout bounds each
+    // response, and the session is cancelled once the cancellation flag is set.
+    fn with_z3_process<T>(
+        &self,
+        f: impl FnOnce(&mut ProcessSolver) -> Result<T, SolverError>,
+    ) -> Result<T, SolverError> {
+        let mut config = SolverConfig::new(SolverKind::Z3);
+        config.options.extend(self.z3_params());
+        if let Some(tactic) = &self.tactic {
+            config
+                .options
+                .push(("tactic.default_tactic".to_string(), tactic.clone()));
+        }
+        let mut solver = ProcessSolver::spawn(&config)?;
+        solver.set_timeout(
+            self.timeout
+                .map(|timeout| Duration::from_millis(timeout.into())),
+        );
+        match &self.cancel {
+            Some(cancel) => {
+                let (handle, done) = (solver.cancel_handle(), AtomicBool::new(false));
+                thread::scope(|scope| {
+                    scope.spawn(|| {
+                        while !done.load(Ordering::SeqCst) {
+                            if cancel.load(Ordering::SeqCst) {
+                                handle.cancel();
+                                break;
+                            }
+                            thread::sleep(CANCEL_POLL_PERIOD);
+                        }
+                    });
+                    let result = f(&mut solver);
+                    done.store(true, Ordering::SeqCst);
+                    result
+                })
+            }
+            None => f(&mut solver),
+        }
+    }
+}
+
+/// Output format of the result of a check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Text,
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!("Unknown format: {}", s)),
+        }
+    }
+}
+
+/// Exit status of berty. Invalid arguments exit with status 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Safe = 0,
+    Unsafe = 10,
+    Unknown = 20,
+    ParseError = 30,
+    SolverError = 40,
+    WriteError = 50,
+}
+
+/// Errors reported by berty.
+#[derive(Debug)]
+enum Error {
+    /// An input could not be read or parsed.
+    Parse(String),
+    /// The solver failed or gave an unexpected answer.
+    Solver(String),
+    /// An output file could not be written.
+    Write(String),
+}
+
+impl Error {
+    fn status(&self) -> Status {
+        match self {
+            Error::Parse(_) => Status::ParseError,
+            Error::Solver(_) => Status::SolverError,
+            Error::Write(_) => Status::WriteError,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Parse(message) => write!(f, "Parse error: {}", message),
+            Error::Solver(message) => write!(f, "Solver error: {}", message),
+            Error::Write(message) => write!(f, "Write error: {}", message),
+        }
+    }
+}
+
+/// Logger printing the enabled records of berty and of the libraries of this workspace
+/// to the standard error. Records of other crates, such as z3, are ignored.
+struct Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        let krate = metadata.target().split("::").next();
+        metadata.level() <= log::max_level()
+            && ["berty", "smt2parser", "z3tracer"].contains(&krate.unwrap_or_default())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: Logger = Logger;
+
 fn main() {
+    let options = Options::from_args();
+    log::set_logger(&LOGGER).expect("Unable to set the logger.");
+    log::set_max_level(match options.verbose {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    });
+    let status = match run(options.operation) {
+        Ok(status) => status,
+        Err(error) => {
+            eprintln!("{}", error);
+            error.status()
+        }
+    };
+    process::exit(status as i32);
+}
+
+fn run(operation: Operation) -> Result<Status, Error> {
+    match operation {
+        Operation::Bmc {
+            input,
+            bound,
+            output,
+            canonical,
+            logic,
+            solver,
+            trace,
+            trace_log,
+            simplify,
+            portfolio,
+            max_refinements,
+            solvers,
+            sat_solver,
+            format,
+            html,
+            solver_options,
+        } => {
+            let mut timings = Timings::default();
+            let vmt = timings.measure("parse", || read_vmt(&input))?;
+            let mut smt = timings.measure("unroll", || vmt.unroll(bound));
+            if simplify {
+                timings.measure("simplify", || smt.simplify());
+            }
+            if logic {
+                smt = smt.with_logic();
+            }
+            let script = if canonical {
+                smt.to_canonical_smtlib2()
+            } else {
+                smt.to_smtlib2() + "\n"
+            };
+            let problem = format!("{}{}\n", script, solver_options.check_sat());
+            if let Some(output) = &output {
+                write_file(output, &problem)?;
+            }
+            let temp_log = match trace_log {
+                None if trace => Some(TempFile::new("log")),
+                _ => None,
+            };
+            let trace_log = trace_log
+                .as_deref()
+                .or(temp_log.as_ref().map(TempFile::path));
+            let (verdict, engine) = timings.measure("solve", || match &solver {
+                None if portfolio => run_portfolio(&input, bound, max_refinements, &solver_options)
+                    .map(|(verdict, engine)| (verdict, engine.map(String::from))),
+                None if !solvers.is_empty() => {
+                    run_solver_portfolio(&smt, &solvers, &solver_options)
+                }
+                None if sat_solver.is_some() => {
+                    let sat_solver = sat_solver.as_deref().unwrap();
+                    run_sat_solver(&smt, sat_solver, &solver_options).map(|verdict| (verdict, None))
+                }
+                None => solve_with_z3(&smt.to_commands(), trace_log, &solver_options)
+                    .map(|verdict| (verdict, None)),
+                Some(solver) => solve_with_command(
+                    solver,
+                    &problem,
+                    output.as_deref(),
+                    trace_log,
+                    &solver_options,
+                )
+                .map(|verdict| (verdict, None)),
+            })?;
+            report(&verdict, bound, None, engine.as_deref(), &timings, format);
+            if let Some(path) = &html {
+                let run = html::Run {
+                    input: &input,
+                    vmt: &vmt,
+                    bound,
+                    verdict: &verdict,
+                    timings: &timings,
+                    trace_log,
+                };
+                html::write_report(path, &run)?;
+            }
+            if let (Some(trace_log), Format::Text) = (trace_log, format) {
+                analyze_log(trace_log)?;
+            }
+            Ok(verdict.status())
+        }
+        Operation::Cegar {
+            input,
+            bound,
+            max_refinements,
+            axioms_timeout,
+            lemmas_in,
+            lemmas_out,
+            format,
+            html,
+            solver_options,
+        } => {
+            let mut timings = Timings::default();
+            let mut lemmas = match &lemmas_in {
+                Some(path) => read_lemmas(path)?,
+                None => Vec::new(),
+            };
+            let (verdict, refinements) = cegar(
+                &input,
+                bound,
+                max_refinements,
+                axioms_timeout,
+                &solver_options,
+                &mut lemmas,
+                &mut timings,
+            )?;
+            if let Some(path) = &lemmas_out {
+                let content: String = lemmas
+                    .iter()
+                    .map(|lemma| lemma.to_string() + "\n")
+                    .collect();
+                write_file(path, content)?;
+            }
+            report(&verdict, bound, Some(refinements), None, &timings, format);
+            if let Some(path) = &html {
+                let run = html::Run {
+                    input: &input,
+                    vmt: &read_vmt(&input)?,
+                    bound,
+                    verdict: &verdict,
+                    timings: &timings,
+                    trace_log: None,
+                };
+                html::write_report(path, &run)?;
+            }
+            Ok(verdict.status())
+        }
+        Operation::Induction {
+            input,
+            candidates,
+            mine,
+            templates,
+            samples,
+            invariant_out,
+            format,
+            solver_options,
+        } => {
+            let vmt = read_vmt(&input)?;
+            let mut candidates: Vec<Term> = match &candidates {
+                Some(path) => read_lemmas(path)?
+                    .into_iter()
+                    .filter_map(|command| match command {
+                        Command::Assert { term } => Some(term),
+                        _ => None,
+                    })
+                    .collect(),
+                None => Vec::new(),
+            };
+            if let Some(bound) = mine {
+                candidates.extend(mine_candidates(
+                    &vmt,
+                    bound,
+                    &templates,
+                    samples,
+                    &solver_options,
+                )?);
+            }
+            let (verdict, invariants) = prove_by_induction(&vmt, candidates, &solver_options)?;
+            match (&verdict, format) {
+                (Verdict::Proven, Format::Text) => {
+                    println!(
+                        "safe (inductive invariant of {} conjuncts)",
+                        invariants.len()
+                    )
+                }
+                (Verdict::Unsafe { counterexample }, Format::Text) => {
+                    println!("unsafe in an initial state\n{}", counterexample)
+                }
+                (Verdict::Unknown { reason }, Format::Text) => println!("unknown ({})", reason),
+                (Verdict::Safe, Format::Text) => unreachable!("induction is not bounded"),
+                (verdict, Format::Json) => {
+                    let (counterexample, reason) = match verdict {
+                        Verdict::Safe | Verdict::Proven => (None, None),
+                        Verdict::Unsafe { counterexample } => (Some(counterexample), None),
+                        Verdict::Unknown { reason } => (None, Some(reason)),
+                    };
+                    let invariant = invariants
+                        .iter()
+                        .map(|conjunct| conjunct.to_string())
+                        .collect::<Vec<_>>();
+                    let value = serde_json::json!({
+                        "verdict": verdict.name(),
+                        "invariant": invariant,
+                        "counterexample": counterexample,
+                        "reason": reason,
+                    });
+                    println!("{}", serde_json::to_string_pretty(&value).unwrap());
+                }
+            }
+            if let (Verdict::Proven, Some(path)) = (&verdict, &invariant_out) {
+                let model = vmt.with_property(conjunction(invariants)).to_vmt_string();
+                write_file(path, model)?;
+            }
+            Ok(verdict.status())
+        }
+        Operation::Core {
+            input,
+            bound,
+            lemmas,
+            format,
+            solver_options,
+        } => {
+            let vmt = read_vmt(&input)?;
+            let lemmas = match &lemmas {
+                Some(path) => read_lemmas(path)?
+                    .into_iter()
+                    .filter_map(|command| match command {
+                        Command::Assert { term } => Some(term),
+                        _ => None,
+                    })
+                    .collect(),
+                None => Vec::new(),
+            };
+            let problem = NamedProblem::new(&vmt.unroll(bound), &lemmas);
+            let report = match solver_options.with_z3_process(|solver| problem.check(solver)) {
+                Ok(report) => report,
+                Err(SolverError::Timeout(_)) => {
+                    println!("no proof: timeout");
+                    return Ok(Status::Unknown);
+                }
+                Err(error) => return Err(Error::Solver(error.to_string())),
+            };
+            match (&report, format) {
+                (Some(report), Format::Text) => println!("{}", report),
+                (Some(report), Format::Json) => {
+                    println!("{}", serde_json::to_string_pretty(report).unwrap())
+                }
+                (None, _) => println!("no proof: the problem is not unsatisfiable"),
+            }
+            Ok(match report {
+                Some(_) => Status::Safe,
+                None => Status::Unknown,
+            })
+        }
+        Operation::Repl {
+            input,
+            bound,
+            abstract_arrays,
+            solver_options,
+        } => {
+            let problem = if abstract_arrays {
+                let (problem, axioms) = abstract_bmc_problem(read_commands(&input)?, bound)?;
+                [problem, axioms].concat()
+            } else {
+                read_vmt(&input)?.unroll(bound).to_commands()
+            };
+            repl::run(problem, solver_options)?;
+            Ok(Status::Safe)
+        }
+        Operation::Abstract { input, output } => {
+            let commands = ArrayAbstractor::abstract_commands(read_commands(&input)?)
+                .map_err(|error| Error::Parse(error.to_string()))?;
+            let vmt = commands
+                .iter()
+                .map(|command| command.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            match &output {
+                Some(output) => write_file(output, vmt + "\n")?,
+                None => println!("{}", vmt),
+            }
+            Ok(Status::Safe)
+        }
+        Operation::Stats { input, format } => {
+            let vmt = read_vmt(&input)?;
+            match format {
+                Format::Text => vmt.print_stats(),
+                Format::Json => {
+                    let value = VMTModelJson::from(&vmt);
+                    println!("{}", serde_json::to_string_pretty(&value).unwrap());
+                }
+            }
+            Ok(Status::Safe)
+        }
+        Operation::Diff { before, after } => {
+            for difference in diff_commands(&read_commands(&before)?, &read_commands(&after)?) {
+                println!("{}", difference);
+            }
+            Ok(Status::Safe)
+        }
+        Operation::Dependencies { input, output } => {
+            let dot = read_vmt(&input)?.dependency_graph().to_dot();
+            match &output {
+                Some(output) => write_file(output, dot)?,
+                None => print!("{}", dot),
+            }
+            Ok(Status::Safe)
+        }
+        Operation::TraceAnalyze { log, format } => {
+            match format {
+                Format::Text => analyze_log(&log)?,
+                Format::Json => {
+                    let value = trace_json(&log)?;
+                    println!("{}", serde_json::to_string_pretty(&value).unwrap());
+                }
+            }
+            Ok(Status::Safe)
+        }
+    }
+}
+
+fn read_commands(path: &Path) -> Result<Vec<Command>, Error> {
+    let input =
+        fs::read(path).map_err(|error| Error::Parse(format!("{}: {}", path.display(), error)))?;
+    let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+    smt2parser::parse_commands_parallel(&input, path.to_str().map(String::from), threads)
+        .map_err(|error| Error::Parse(error.to_string()))
+}
+
+// Number of times the recursive functions of a VMT model are unfolded.
+const RECURSION_UNFOLDING_DEPTH: usize = 3;
+
+fn read_vmt(path: &Path) -> Result<VMTModel, Error> {
+    let commands = SortExpander::expand_commands(read_commands(path)?)
+        .and_then(|commands| {
+            RecursiveUnfolder::new(RECURSION_UNFOLDING_DEPTH).unfold_commands(commands)
+        })
+        .map_err(|error| Error::Parse(error.to_string()))?;
+    vmt_from_commands(commands)
+}
+
+fn vmt_from_commands(commands: Vec<Command>) -> Result<VMTModel, Error> {
+    let vmt = VMTModel::checked_from(commands)
+        .map_err(|error| Error::Parse(format!("Ill-formed VMT model: {}", error)))?;
+    let fragment = vmt.fragment();
+    if fragment > Fragment::ArrayProperty {
+        log::warn!(
+            "The model is outside the array property fragment ({:?})",
+            fragment
+        );
+    }
+    Ok(vmt)
+}
+
+// Write an output file.
+fn write_file<C: AsRef<[u8]>>(path: &Path, content: C) -> Result<(), Error> {
+    fs::write(path, content).map_err(|error| Error::Write(format!("{}: {}", path.display(), error)))
+}
+
+/// Temporary file specific to this process, removed when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(extension: &str) -> Self {
+        TempFile(std::env::temp_dir().join(format!("berty-{}.{}", process::id(), extension)))
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        fs::remove_file(&self.0).ok();
+    }
+}
+
+// Z3 options enabling the tracing of quantifier instantiations.
+fn trace_params(trace_log: &Path) -> Vec<(&'static str, String)> {
+    vec![
+        ("trace", "true".to_string()),
+        ("proof", "true".to_string()),
+        ("trace_file_name", trace_log.display().to_string()),
+    ]
+}
+
+// Check a problem with the built-in Z3. Return the model in case of SAT.
+fn check_with_z3(
+    problem: &[Command],
+    trace_log: Option<&Path>,
+    options: &SolverOptions,
+) -> Result<(SatResult, Option<String>), Error> {
+    // Global parameters are read when contexts and solvers are created: keep concurrent
+    // checks from mixing them up, and later checks from inheriting them.
+    let global_params = GlobalParams::lock();
+    for (key, _) in options.z3_params() {
+        if z3::get_global_param(&key).is_none() {
+            return Err(Error::Solver(format!("Unknown Z3 option: {}", key)));
+        }
+    }
+    match trace_log {
+        Some(trace_log) => {
+            for (key, value) in trace_params(trace_log) {
+                z3::set_global_param(key, &value);
+            }
+        }
+        None => z3::set_global_param("trace", "false"),
+    }
+    for (key, value) in options.z3_params() {
+        z3::set_global_param(&key, &value);
+    }
     let config = Config::new();
     let context = Context::new(&config);
-    let solver = Solver::new(&context);
+    let solver = match &options.tactic {
+        Some(tactic) => {
+            if !Tactic::list_all(&context).any(|name| name.as_deref() == Ok(tactic)) {
+                return Err(Error::Solver(format!("Unknown tactic: {}", tactic)));
+            }
+            Tactic::new(&context, tactic).solver()
+        }
+        None => Solver::new(&context),
+    };
+    if let Some(timeout) = options.timeout {
+        let mut params = Params::new(&context);
+        params.set_u32("timeout", timeout);
+        solver.set_params(&params);
+    }
+
+    let mut translator = Translator::new(&context);
+    translator.add_commands(&solver, problem)?;
+    // Solvers are only created by Z3 with the first assertion.
+    drop(global_params);
+    debug!(
+        "Checking {} assertions with Z3",
+        solver.get_assertions().len()
+    );
+
+    let result = match &options.cancel {
+        Some(cancel) => {
+            // Interrupt the check until it stops, as interruptions are lost when no check
+            // is running yet.
+            let (handle, done) = (context.handle(), AtomicBool::new(false));
+            thread::scope(|scope| {
+                scope.spawn(|| {
+                    while !done.load(Ordering::SeqCst) {
+                        if cancel.load(Ordering::SeqCst) {
+                            handle.interrupt();
+                        }
+                        thread::sleep(CANCEL_POLL_PERIOD);
+                    }
+                });
+                let result = solver.check();
+                done.store(true, Ordering::SeqCst);
+                result
+            })
+        }
+        None => solver.check(),
+    };
+    debug!("Z3 answered {:?}", result);
+    // Print the values in SMT-LIB syntax, in the format of Z3, unless some value cannot be
+    // converted. Arrays are printed by their entries, e.g. `[1 -> 2, else 0]`.
+    let model = match result {
+        SatResult::Sat => solver
+            .get_model()
+            .map(|model| match translator.model_values(&model) {
+                Ok(values) => values
+                    .iter()
+                    .map(|(name, value)| format!("{} -> {}\n", name, format_value(value)))
+                    .collect(),
+                Err(_) => format!("{:?}", model),
+            }),
+        _ => None,
+    };
+    Ok((result, model))
+}
+
+// Arrays given by chains of `store`s on a constant array are printed by their entries.
+fn format_value(term: &Term) -> String {
+    match smt2parser::model::Model::default().value_of(term) {
+        array @ smt2parser::model::Value::Array { .. } => array.to_string(),
+        _ => term.to_string(),
+    }
+}
+
+fn solve_with_z3(
+    problem: &[Command],
+    trace_log: Option<&Path>,
+    options: &SolverOptions,
+) -> Result<Verdict, Error> {
+    let verdict = match check_with_z3(problem, trace_log, options)? {
+        (SatResult::Unsat, _) => Verdict::Safe,
+        (SatResult::Unknown, _) => Verdict::Unknown {
+            reason: "solver".to_string(),
+        },
+        (SatResult::Sat, model) => Verdict::Unsafe {
+            counterexample: model.unwrap_or_default(),
+        },
+    };
+    Ok(verdict)
+}
+
+/// Outcome of a check.
+enum Verdict {
+    /// No counterexample exists within the bound.
+    Safe,
+    /// The property holds in all reachable states.
+    Proven,
+    /// A counterexample exists within the bound.
+    Unsafe { counterexample: String },
+    /// The check could not conclude.
+    Unknown { reason: String },
+}
+
+impl Verdict {
+    fn status(&self) -> Status {
+        match self {
+            Verdict::Safe | Verdict::Proven => Status::Safe,
+            Verdict::Unsafe { .. } => Status::Unsafe,
+            Verdict::Unknown { .. } => Status::Unknown,
+        }
+    }
+
+    // Name of the verdict in JSON outputs.
+    fn name(&self) -> &'static str {
+        match self {
+            Verdict::Safe => "safe",
+            Verdict::Proven => "proven",
+            Verdict::Unsafe { .. } => "unsafe",
+            Verdict::Unknown { .. } => "unknown",
+        }
+    }
+}
+
+/// Lock held while setting the global parameters of Z3 and creating a context and a
+/// solver.
+static GLOBAL_PARAMS: Mutex<()> = Mutex::new(());
+
+/// Lock on the global parameters of Z3, which are reset when it is released.
+struct GlobalParams(MutexGuard<'static, ()>);
+
+impl GlobalParams {
+    fn lock() -> Self {
+        GlobalParams(
+            GLOBAL_PARAMS
+                .lock()
+                .unwrap_or_else(|error| error.into_inner()),
+        )
+    }
+}
+
+impl Drop for GlobalParams {
+    fn drop(&mut self) {
+        z3::reset_all_global_params();
+    }
+}
+
+/// How often cancellable checks with the built-in Z3 poll their cancellation flag.
+const CANCEL_POLL_PERIOD: Duration = Duration::from_millis(10);
+
+/// Time spent in each phase of a check, in order of first occurrence.
+#[derive(Default)]
+struct Timings(Vec<(&'static str, Duration)>);
+
+impl Timings {
+    fn measure<T, F: FnOnce() -> T>(&mut self, phase: &'static str, f: F) -> T {
+        let start = Instant::now();
+        let result = f();
+        let elapsed = start.elapsed();
+        debug!("Phase {} took {:.3}s", phase, elapsed.as_secs_f64());
+        match self.0.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, duration)) => *duration += elapsed,
+            None => self.0.push((phase, elapsed)),
+        }
+        result
+    }
+}
+
+// Print the result of a check. The number of refinements is only given by CEGAR, and
+// the engine by the portfolio.
+fn report(
+    verdict: &Verdict,
+    bound: u8,
+    refinements: Option<usize>,
+    engine: Option<&str>,
+    timings: &Timings,
+    format: Format,
+) {
+    match format {
+        Format::Text => {
+            let notes = refinements
+                .map(|refinements| format!("{} refinements", refinements))
+                .into_iter()
+                .chain(engine.map(|engine| format!("found by {}", engine)))
+                .collect::<Vec<_>>();
+            let notes = if notes.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", notes.join(", "))
+            };
+            match verdict {
+                Verdict::Safe => println!("safe up to bound {}{}", bound, notes),
+                Verdict::Proven => println!("safe for all steps{}", notes),
+                Verdict::Unsafe { counterexample } => {
+                    println!("unsafe{}\n{}", notes, counterexample)
+                }
+                Verdict::Unknown { reason } => println!("unknown ({})", reason),
+            }
+        }
+        Format::Json => {
+            let (counterexample, reason) = match verdict {
+                Verdict::Safe | Verdict::Proven => (None, None),
+                Verdict::Unsafe { counterexample } => (Some(counterexample), None),
+                Verdict::Unknown { reason } => (None, Some(reason)),
+            };
+            let timings = timings
+                .0
+                .iter()
+                .map(|(phase, duration)| (phase.to_string(), duration.as_secs_f64().into()))
+                .collect::<serde_json::Map<_, _>>();
+            let value = serde_json::json!({
+                "verdict": verdict.name(),
+                "bound": bound,
+                "refinements": refinements,
+                "engine": engine,
+                "counterexample": counterexample,
+                "reason": reason,
+                "timings": timings,
+            });
+            println!("{}", serde_json::to_string_pretty(&value).unwrap());
+        }
+    }
+}
+
+/// Engines of the portfolio.
+const ENGINES: &[&str] = &["bmc", "cegar", "k-induction"];
+
+// Run the engines of the portfolio concurrently. Return the first counterexample or proof
+// with the engine that found it. Otherwise, once all the engines are done, return a
+// bounded safe verdict if some engine found no counterexample within the bound, or an
+// unknown verdict. The other engines are stopped before returning.
+fn run_portfolio(
+    input: &Path,
+    bound: u8,
+    max_refinements: usize,
+    options: &SolverOptions,
+) -> Result<(Verdict, Option<&'static str>), Error> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let options = SolverOptions {
+        cancel: Some(cancel.clone()),
+        ..options.clone()
+    };
+    let (sender, receiver) = mpsc::channel();
+    let engines = ENGINES
+        .iter()
+        .map(|&engine| {
+            let (sender, input, options) = (sender.clone(), input.to_path_buf(), options.clone());
+            thread::spawn(move || {
+                let verdict = match engine {
+                    "bmc" => read_vmt(&input).and_then(|vmt| {
+                        solve_with_z3(&vmt.unroll(bound).to_commands(), None, &options)
+                    }),
+                    "cegar" => {
+                        let mut timings = Timings::default();
+                        let mut lemmas = Vec::new();
+                        cegar(
+                            &input,
+                            bound,
+                            max_refinements,
+                            None,
+                            &options,
+                            &mut lemmas,
+                            &mut timings,
+                        )
+                        .map(|(verdict, _)| verdict)
+                    }
+                    _ => {
+                        read_vmt(&input).and_then(|vmt| prove_by_k_induction(&vmt, bound, &options))
+                    }
+                };
+                sender.send((engine, verdict)).ok();
+            })
+        })
+        .collect::<Vec<_>>();
+    drop(sender);
+    let mut winner = None;
+    let mut bounded = None;
+    let mut reasons = Vec::new();
+    let mut errors = Vec::new();
+    for (engine, verdict) in receiver.iter() {
+        match verdict {
+            Ok(Verdict::Unknown { reason }) => reasons.push(format!("{}: {}", engine, reason)),
+            // Finding no counterexample within the bound does not conclude: another
+            // engine may still prove the property for all steps.
+            Ok(Verdict::Safe) => {
+                bounded.get_or_insert(engine);
+            }
+            Ok(verdict) => {
+                winner = Some((verdict, Some(engine)));
+                break;
+            }
+            Err(error) => {
+                warn!("Engine {} failed: {}", engine, error);
+                errors.push(error);
+            }
+        }
+    }
+    cancel.store(true, Ordering::SeqCst);
+    for engine in engines {
+        engine.join().ok();
+    }
+    if let Some(winner) = winner {
+        return Ok(winner);
+    }
+    if let Some(engine) = bounded {
+        return Ok((Verdict::Safe, Some(engine)));
+    }
+    if reasons.is_empty() && !errors.is_empty() {
+        return Err(errors.remove(0));
+    }
+    let reason = reasons.join(", ");
+    Ok((Verdict::Unknown { reason }, None))
+}
+
+// Check an unrolled problem with several external solvers concurrently. Solvers are
+// given as `KIND` or `z3:TACTIC`. Return the verdict and the solver that found it.
+fn run_solver_portfolio(
+    smt: &SMTProblem,
+    solvers: &[String],
+    options: &SolverOptions,
+) -> Result<(Verdict, Option<String>), Error> {
+    let mut portfolio = Portfolio::new();
+    for spec in solvers {
+        let (kind, tactic) = match spec.find(':') {
+            Some(index) => (&spec[..index], Some(&spec[index + 1..])),
+            None => (spec.as_str(), None),
+        };
+        let kind = SolverKind::from_str(kind).map_err(Error::Solver)?;
+        let mut config = SolverConfig::new(kind);
+        if let Some(seed) = options.seed {
+            config
+                .options
+                .push(("random-seed".to_string(), seed.to_string()));
+        }
+        match tactic {
+            Some(tactic) if kind == SolverKind::Z3 => config
+                .options
+                .push(("tactic.default_tactic".to_string(), tactic.to_string())),
+            Some(_) => {
+                return Err(Error::Solver(format!(
+                    "Tactics are only supported by Z3: {}",
+                    spec
+                )))
+            }
+            None => (),
+        }
+        portfolio = portfolio.with_solver(spec.as_str(), config);
+    }
+    if let Some(timeout) = options.timeout {
+        portfolio = portfolio.with_budget(Duration::from_millis(timeout.into()));
+    }
+    let result = portfolio.check_problem(smt);
+    for run in &result.runs {
+        match &run.answer {
+            Ok(answer) => info!(
+                "Solver {} answered {:?} after {:.3}s",
+                run.name,
+                answer,
+                run.elapsed.as_secs_f64()
+            ),
+            Err(error) => warn!(
+                "Solver {} failed after {:.3}s: {}",
+                run.name,
+                run.elapsed.as_secs_f64(),
+                error
+            ),
+        }
+    }
+    let engine = result.winner().map(|run| run.name.clone());
+    let verdict = match result.answer {
+        solver::SatResult::Unsat => Verdict::Safe,
+        // The winning solver is stopped with the others: the model is not retrieved.
+        solver::SatResult::Sat => Verdict::Unsafe {
+            counterexample: String::new(),
+        },
+        solver::SatResult::Unknown => {
+            let mut reasons = Vec::new();
+            let mut errors = Vec::new();
+            for run in result.runs {
+                match run.answer {
+                    Ok(_) => reasons.push(format!("{}: solver", run.name)),
+                    Err(SolverError::Timeout(_)) => reasons.push(format!("{}: timeout", run.name)),
+                    Err(error) => errors.push(error),
+                }
+            }
+            if reasons.is_empty() && !errors.is_empty() {
+                return Err(Error::Solver(errors.remove(0).to_string()));
+            }
+            Verdict::Unknown {
+                reason: reasons.join(", "),
+            }
+        }
+    };
+    Ok((verdict, engine))
+}
+
+// Check a purely boolean unrolled problem with a DIMACS SAT solver. The counterexample
+// gives the values of the boolean constants at each step.
+fn run_sat_solver(
+    smt: &SMTProblem,
+    path: &Path,
+    options: &SolverOptions,
+) -> Result<Verdict, Error> {
+    let timeout = options
+        .timeout
+        .map(|timeout| Duration::from_millis(timeout.into()));
+    let solver = SatSolver::new(path.to_path_buf()).with_timeout(timeout);
+    match solver.check_problem(smt) {
+        Ok((solver::SatResult::Unsat, _)) => Ok(Verdict::Safe),
+        Ok((solver::SatResult::Sat, model)) => {
+            let counterexample = model
+                .iter()
+                .flat_map(|model| model.iter())
+                .map(|(name, value)| format!("(define-fun {} () Bool {})", name, value))
+                .collect::<Vec<_>>()
+                .join("\n");
+            Ok(Verdict::Unsafe { counterexample })
+        }
+        Ok((solver::SatResult::Unknown, _)) => Ok(Verdict::Unknown {
+            reason: "solver".to_string(),
+        }),
+        Err(SolverError::Timeout(_)) => Ok(Verdict::Unknown {
+            reason: "timeout".to_string(),
+        }),
+        Err(error) => Err(Error::Solver(error.to_string())),
+    }
+}
+
+// Abstract the arrays of a VMT model and unroll it. Return the SMT-LIB problem, without
+// `check-sat`, and the array axioms.
+fn abstract_bmc_problem(
+    commands: Vec<Command>,
+    bound: u8,
+) -> Result<(Vec<Command>, Vec<Command>), Error> {
+    let parse_error = |error: smt2parser::Error| Error::Parse(error.to_string());
+    let commands = ArrayAbstractor::abstract_commands(commands).map_err(parse_error)?;
+    let axioms = array_axioms(&commands);
+    let vmt = vmt_from_commands(commands)?;
+    // Skolem constants introduced by Z3 cannot appear in lemmas: use our own instead.
+    let mut smt = vmt.unroll(bound);
+    smt.skolemize_property()
+        .map_err(|error| Error::Parse(error.to_string()))?;
+    Ok((smt.to_commands(), axioms))
+}
+
+// Candidate invariants that hold in the states sampled by Z3, run as a subprocess,
+// within `bound` steps.
+fn mine_candidates(
+    vmt: &VMTModel,
+    bound: u8,
+    templates: &[Template],
+    samples: usize,
+    options: &SolverOptions,
+) -> Result<Vec<Term>, Error> {
+    let templates = if templates.is_empty() {
+        &Template::ALL[..]
+    } else {
+        templates
+    };
+    let miner = InvariantMiner::new(vmt, templates);
+    let states = options
+        .with_z3_process(|solver| miner.sample(vmt, solver, bound, samples))
+        .map_err(|error| Error::Solver(error.to_string()))?;
+    let candidates = miner.candidates(&states);
+    info!(
+        "Mined {} candidates from {} states",
+        candidates.len(),
+        states.len()
+    );
+    Ok(candidates)
+}
+
+// Find an inductive invariant made of the property and of some of the candidates, by
+// dropping the candidates that do not hold initially or are not inductive relative to
+// the others until a fixpoint (Houdini). Return the conjuncts of the invariant if the
+// property is proven.
+fn prove_by_induction(
+    vmt: &VMTModel,
+    candidates: Vec<Term>,
+    options: &SolverOptions,
+) -> Result<(Verdict, Vec<Term>), Error> {
+    if let (SatResult::Sat, model) = check_with_z3(&vmt.unroll(0).to_commands(), None, options)? {
+        let counterexample = model.unwrap_or_default();
+        return Ok((Verdict::Unsafe { counterexample }, Vec::new()));
+    }
+    let mut invariants = vec![vmt.property().clone()];
+    for candidate in candidates {
+        let initial = vmt.with_property(candidate.clone()).unroll(0).to_commands();
+        match check_with_z3(&initial, None, options)? {
+            (SatResult::Unsat, _) => invariants.push(candidate),
+            _ => info!("Dropping candidate {}: not initial", candidate),
+        }
+    }
+    loop {
+        let assumption = conjunction(invariants.clone());
+        let mut inductive = Vec::new();
+        for (index, invariant) in invariants.iter().enumerate() {
+            let step = vmt
+                .with_property(invariant.clone())
+                .induction_step(&assumption);
+            match check_with_z3(&step.to_commands(), None, options)? {
+                (SatResult::Unsat, _) => inductive.push(invariant.clone()),
+                _ if index == 0 => {
+                    let reason = "property not inductive".to_string();
+                    return Ok((Verdict::Unknown { reason }, Vec::new()));
+                }
+                _ => info!("Dropping candidate {}: not inductive", invariant),
+            }
+        }
+        if inductive.len() == invariants.len() {
+            return Ok((Verdict::Proven, inductive));
+        }
+        invariants = inductive;
+    }
+}
+
+// Prove the property by k-induction for k from 1 to `bound`: check that no counterexample
+// exists within k - 1 steps, then whether the property holding in k consecutive states
+// implies that it holds in the next one. If the property is not k-inductive for these k,
+// the verdict is bounded by the absence of counterexamples within `bound` steps.
+fn prove_by_k_induction(
+    vmt: &VMTModel,
+    bound: u8,
+    options: &SolverOptions,
+) -> Result<Verdict, Error> {
+    for k in 1..=bound {
+        match solve_with_z3(&vmt.unroll(k - 1).to_commands(), None, options)? {
+            Verdict::Safe => (),
+            verdict => return Ok(verdict),
+        }
+        match check_with_z3(&vmt.k_induction_step(k).to_commands(), None, options)? {
+            (SatResult::Unsat, _) => {
+                info!("The property is {}-inductive", k);
+                return Ok(Verdict::Proven);
+            }
+            _ => debug!("The property is not {}-inductive", k),
+        }
+    }
+    solve_with_z3(&vmt.unroll(bound).to_commands(), None, options)
+}
+
+fn negation(term: Term) -> Term {
+    Term::Application {
+        qual_identifier: QualIdentifier::Simple {
+            identifier: Identifier::Simple {
+                symbol: smt2parser::concrete::Symbol("not".to_string()),
+            },
+        },
+        arguments: vec![term],
+    }
+}
+
+fn conjunction(mut terms: Vec<Term>) -> Term {
+    if terms.len() == 1 {
+        return terms.remove(0);
+    }
+    Term::Application {
+        qual_identifier: QualIdentifier::Simple {
+            identifier: Identifier::Simple {
+                symbol: smt2parser::concrete::Symbol("and".to_string()),
+            },
+        },
+        arguments: terms,
+    }
+}
+
+// Read SMT-LIB assertions.
+fn read_lemmas(path: &Path) -> Result<Vec<Command>, Error> {
+    Ok(read_commands(path)?
+        .into_iter()
+        .filter(|command| matches!(command, Command::Assert { .. }))
+        .collect())
+}
+
+// Whether the constants of a ground SMT-LIB assertion are all declared. Symbols in
+// function position are not checked.
+fn is_declared_lemma(lemma: &Command, declared: &BTreeSet<String>) -> bool {
+    fn has_declared_constants(term: &Term, declared: &BTreeSet<String>) -> bool {
+        match term {
+            Term::QualIdentifier(QualIdentifier::Simple {
+                identifier: Identifier::Simple { symbol },
+            }) => symbol.0 == "true" || symbol.0 == "false" || declared.contains(&symbol.0),
+            Term::Application { arguments, .. } => arguments
+                .iter()
+                .all(|argument| has_declared_constants(argument, declared)),
+            Term::Attributes { term, .. } => has_declared_constants(term, declared),
+            _ => true,
+        }
+    }
+
+    match lemma {
+        Command::Assert { term } => has_declared_constants(term, declared),
+        _ => false,
+    }
+}
+
+// Whether a lemma follows from the declarations and axioms of a problem.
+fn is_valid_lemma(
+    context: &[Command],
+    lemma: &Command,
+    options: &SolverOptions,
+) -> Result<bool, Error> {
+    let term = match lemma {
+        Command::Assert { term } => term.clone(),
+        _ => return Ok(false),
+    };
+    let negation = Command::Assert {
+        term: negation(term),
+    };
+    let problem = [context, &[negation]].concat();
+    let (result, _) = check_with_z3(&problem, None, options)?;
+    Ok(result == SatResult::Unsat)
+}
+
+// Refine the array abstraction of the unrolled model until the property is proven
+// within the bound or a counterexample is found. The lemmas given initially are used
+// to refine the abstraction from the start, and new lemmas are added to them. Return the
+// number of refinements.
+fn cegar(
+    input: &Path,
+    bound: u8,
+    max_refinements: usize,
+    axioms_timeout: Option<u32>,
+    options: &SolverOptions,
+    lemmas: &mut Vec<Command>,
+    timings: &mut Timings,
+) -> Result<(Verdict, usize), Error> {
+    let concrete_commands = timings.measure("parse", || read_commands(input))?;
+    let (problem, axioms) = timings.measure("abstract", || {
+        abstract_bmc_problem(concrete_commands.clone(), bound)
+    })?;
+    let declared = declared_symbols(&problem);
+    let axioms_options = SolverOptions {
+        timeout: Some(axioms_timeout.or(options.timeout).unwrap_or(10000)),
+        ..options.clone()
+    };
+
+    let given_lemmas = lemmas.len();
+    lemmas.retain(|lemma| is_declared_lemma(lemma, &declared));
+    // Unless they follow from the array axioms, given lemmas could make the abstraction
+    // unsound.
+    let context = problem
+        .iter()
+        .filter(|command| !matches!(command, Command::Assert { .. }))
+        .chain(&axioms)
+        .cloned()
+        .collect::<Vec<_>>();
+    for lemma in std::mem::take(lemmas) {
+        if timings.measure("check", || {
+            is_valid_lemma(&context, &lemma, &axioms_options)
+        })? {
+            lemmas.push(lemma);
+        } else {
+            warn!("Ignoring an invalid lemma: {}", lemma);
+        }
+    }
+    if !lemmas.is_empty() || given_lemmas > 0 {
+        info!(
+            "Starting from {} of {} given lemmas",
+            lemmas.len(),
+            given_lemmas
+        );
+    }
+    let mut concrete_checked = false;
+    for refinements in 0..=max_refinements {
+        info!(
+            "Refinement {}: checking the abstraction with {} lemmas",
+            refinements,
+            lemmas.len()
+        );
+        let abstract_problem = [&problem[..], lemmas].concat();
+        let result =
+            timings.measure("solve", || check_with_z3(&abstract_problem, None, options))?;
+        match result {
+            (SatResult::Unsat, _) => return Ok((Verdict::Safe, refinements)),
+            (SatResult::Unknown, _) => {
+                let reason = "abstract problem".to_string();
+                return Ok((Verdict::Unknown { reason }, refinements));
+            }
+            (SatResult::Sat, _) => (),
+        }
+        // Check the abstract counterexample against the array axioms and mine the
+        // instances of the axioms found by Z3.
+        let trace_log = TempFile::new("log");
+        let axioms_problem = [abstract_problem, axioms.clone()].concat();
+        let result = timings.measure("check", || {
+            check_with_z3(&axioms_problem, Some(trace_log.path()), &axioms_options)
+        })?;
+        match result {
+            (SatResult::Sat, model) => {
+                let counterexample = model.unwrap_or_default();
+                return Ok((Verdict::Unsafe { counterexample }, refinements));
+            }
+            // Quantified axioms are hard to satisfy: look for a counterexample in the
+            // theory of arrays instead.
+            (SatResult::Unknown, _) if !concrete_checked => {
+                info!("Inconclusive check of the axioms: checking the concrete problem");
+                concrete_checked = true;
+                let vmt =
+                    timings.measure("parse", || vmt_from_commands(concrete_commands.clone()))?;
+                let concrete_problem =
+                    timings.measure("unroll", || vmt.unroll(bound).to_commands());
+                let result =
+                    timings.measure("check", || check_with_z3(&concrete_problem, None, options))?;
+                if let (SatResult::Sat, model) = result {
+                    let counterexample = model.unwrap_or_default();
+                    return Ok((Verdict::Unsafe { counterexample }, refinements));
+                }
+            }
+            _ => (),
+        }
+        let mut new_lemmas: Vec<Command> = Vec::new();
+        for lemma in timings.measure("mine", || axiom_instances(trace_log.path(), &declared))? {
+            if !lemmas
+                .iter()
+                .chain(&new_lemmas)
+                .any(|known| same_lemma(known, &lemma))
+            {
+                new_lemmas.push(lemma);
+            }
+        }
+        info!("Found {} new lemmas", new_lemmas.len());
+        for lemma in &new_lemmas {
+            trace!("New lemma: {}", lemma);
+        }
+        if new_lemmas.is_empty() {
+            let reason = "no new lemmas".to_string();
+            return Ok((Verdict::Unknown { reason }, refinements));
+        }
+        lemmas.extend(new_lemmas);
+    }
+    let reason = format!("{} refinements", max_refinements);
+    Ok((Verdict::Unknown { reason }, max_refinements))
+}
 
-    let vmt = fs::read("./examples/test.smt2").expect("Unable to read VMT file.");
+// Symbols declared by an SMT-LIB problem.
+fn declared_symbols(problem: &[Command]) -> BTreeSet<String> {
+    problem
+        .iter()
+        .filter_map(|command| match command {
+            Command::DeclareConst { symbol, .. } | Command::DeclareFun { symbol, .. } => {
+                Some(symbol.0.clone())
+            }
+            _ => None,
+        })
+        .collect()
+}
 
-    solver.from_string(vmt);
+// Whether two lemmas are equal up to the names of their bound variables, so that
+// instances mined again under other names are not considered as new.
+fn same_lemma(lemma: &Command, other: &Command) -> bool {
+    match (lemma, other) {
+        (Command::Assert { term }, Command::Assert { term: other }) => term.alpha_eq(other),
+        _ => lemma == other,
+    }
+}
 
-    match solver.check() {
-        SatResult::Unsat => todo!(),
-        SatResult::Unknown => todo!(),
-        SatResult::Sat => println!("{:?}", solver.get_model().unwrap()),
+// Whether the constants of a term in a Z3 trace are all declared by the problem. This
+// excludes the fresh constants introduced by Z3, such as Skolem constants.
+fn has_declared_constants(model: &Model, id: &Ident, declared: &BTreeSet<String>) -> bool {
+    match model.term(id) {
+        Ok(z3tracer::syntax::Term::App {
+            name,
+            args,
+            meaning,
+        }) if args.is_empty() => {
+            meaning.is_some() || name == "true" || name == "false" || declared.contains(name)
+        }
+        Ok(z3tracer::syntax::Term::App { args, .. }) => args
+            .iter()
+            .all(|arg| has_declared_constants(model, arg, declared)),
+        _ => false,
     }
+}
 
-    let mut p_config = ParserConfig::default();
-    p_config.skip_z3_version_check = true;
-    let mut model_config = ModelConfig::default();
-    model_config.parser_config = p_config;
+// Ground instances of the array axioms in a Z3 trace, as assertions. Instances
+// mentioning symbols unknown to the problem are ignored.
+fn axiom_instances(log: &Path, declared: &BTreeSet<String>) -> Result<Vec<Command>, Error> {
+    let model = load_log(log).map_err(|error| Error::Solver(error.to_string()))?;
+    let keys = model.instantiations().iter().filter_map(|(key, inst)| {
+        match model.term(inst.frame.quantifier()) {
+            Ok(z3tracer::syntax::Term::Quant { name, .. })
+                if name.starts_with("array_")
+                    && !inst.instances.is_empty()
+                    && inst
+                        .frame
+                        .terms()
+                        .iter()
+                        .all(|id| has_declared_constants(&model, id, declared)) =>
+            {
+                Some(key)
+            }
+            _ => None,
+        }
+    });
+    let lemmas = model
+        .instantiated_lemmas(keys)
+        .map_err(|error| Error::Solver(error.to_string()))?
+        .join("\n");
+    CommandStream::new(lemmas.as_bytes(), SyntaxBuilder, None)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| Error::Solver(error.to_string()))
+}
 
-    let model = process_file(model_config, &PathBuf::from("./z3_logs/german.log"));
+fn solve_with_command(
+    solver: &Path,
+    problem: &str,
+    problem_path: Option<&Path>,
+    trace_log: Option<&Path>,
+    options: &SolverOptions,
+) -> Result<Verdict, Error> {
+    let temp_problem;
+    let problem_path = match problem_path {
+        Some(path) => path,
+        None => {
+            temp_problem = TempFile::new("smt2");
+            write_file(temp_problem.path(), problem)?;
+            temp_problem.path()
+        }
+    };
+    let mut command = process::Command::new(solver);
+    // Solvers other than cvc5 are assumed to accept Z3's command-line syntax.
+    let cvc5 = solver
+        .file_name()
+        .map_or(false, |name| name.to_string_lossy().contains("cvc5"));
+    command.args(options.command_args(cvc5));
+    if let Some(trace_log) = trace_log {
+        for (key, value) in trace_params(trace_log) {
+            command.arg(format!("{}={}", key, value));
+        }
+    }
+    command.arg(problem_path);
+    debug!("Running {:?}", command);
+    let output = command
+        .output()
+        .map_err(|error| Error::Solver(format!("{}: {}", solver.display(), error)))?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim();
+    // A solver that crashed may have answered `unknown`, or nothing.
+    let failed = !output.status.success() || !stderr.is_empty();
+    let failure = || {
+        let message = format!(
+            "{} failed ({}): {}",
+            solver.display(),
+            output.status,
+            stderr
+        );
+        Error::Solver(message)
+    };
+    // The first response is the answer to `check-sat`, possibly followed by a model.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let responses = split_responses(&stdout).map_err(|error| Error::Solver(error.to_string()))?;
+    let answer = match responses.first() {
+        Some(answer) => Response::parse(answer, &Command::CheckSat).map_err(|error| {
+            if failed {
+                failure()
+            } else {
+                Error::Solver(error.to_string())
+            }
+        })?,
+        None if failed => return Err(failure()),
+        None => return Err(Error::Solver("No answer".to_string())),
+    };
+    if failed && matches!(answer, Response::Sat | Response::Unsat) {
+        warn!("{}", failure());
+    }
+    match answer {
+        Response::Unsat => Ok(Verdict::Safe),
+        Response::Sat => Ok(Verdict::Unsafe {
+            counterexample: responses[1..].join("\n"),
+        }),
+        Response::Unknown if failed => Err(failure()),
+        Response::Unknown => Ok(Verdict::Unknown {
+            reason: "solver".to_string(),
+        }),
+        Response::Error(message) => Err(Error::Solver(message)),
+        answer => Err(Error::Solver(format!("Unexpected answer: {:?}", answer))),
+    }
+}
+
+fn load_log(log: &Path) -> std::io::Result<Model> {
+    let p_config = ParserConfig {
+        skip_z3_version_check: true,
+        ..ParserConfig::default()
+    };
+    let model_config = ModelConfig {
+        parser_config: p_config,
+        ..ModelConfig::default()
+    };
+    match process_file(model_config, log) {
+        Ok(model) => Ok(model),
+        // The traces of interrupted checks are truncated.
+        Err(ProcessError::Log { error, model }) => {
+            warn!("Error at {:?}: {:?}", error.position, error.error);
+            Ok(*model)
+        }
+        Err(ProcessError::Io(error)) => Err(error),
+        Err(error) => Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            error.to_string(),
+        )),
+    }
+}
+
+fn analyze_log(log: &Path) -> Result<(), Error> {
+    let model = load_log(log);
 
     match model {
         Ok(model) => {
-            //println!("Model: {:#?}", model);
+            trace!("Model: {:#?}", model);
             println!("Rough num conflicts: {:#?}", model.conflicts().size_hint());
             println!("Num instansitations: {}", model.instantiations().len());
             println!(
                 "Most instansiated terms: {:#?}",
                 model.most_instantiated_terms()
             );
-            let (times, id) = model.most_instantiated_terms().pop().unwrap();
-            println!("Instantiations: {:?}", get_instantiations(&model));
-
-            for dd in model.most_instantiated_terms() {
-                println!("INST: {} {:?}", dd.0, dd.1);
-            }
-
-            let mut timestamp_to_id = HashMap::new();
-            for (id, data) in model.terms() {
-                timestamp_to_id.insert(data.timestamp, id);
-            }
+            debug!("Instantiations: {:?}", get_instantiations(&model));
 
             for (inst_type, timestamps) in get_instantiations(&model) {
                 println!("Instantiations for {}:", inst_type);
                 for timestamp in timestamps {
-                    // Subtract 1 to get the reference to the actual term and not the application.
-                    let term = timestamp_to_id.get(&(timestamp - 1)).unwrap();
-                    let decoded = decode_array_instantiation(&model, term);
+                    // Decode the term produced by the instance started at this timestamp.
+                    let decoded = model
+                        .instance_at_timestamp(timestamp)
+                        .and_then(|(_, instance)| instance.term.as_ref())
+                        .ok_or(())
+                        .and_then(|term| decode_array_instantiation(&model, term));
                     match decoded {
                         Ok(inst_decoded) => println!("timestamp {}: {}", timestamp, inst_decoded),
-                        Err(_) => println!("Timestamp {} failed to decode!", timestamp),
+                        Err(_) => debug!("Timestamp {} failed to decode!", timestamp),
                     }
                 }
             }
+            Ok(())
         }
-        Err(z3_tracer_error) => {
-            panic!("Z3Tracer failed: {}", z3_tracer_error);
-        }
+        Err(z3_tracer_error) => Err(Error::Parse(format!(
+            "{}: {}",
+            log.display(),
+            z3_tracer_error
+        ))),
     }
 }
 
+// Summary of a Z3 trace log and its instantiation profile, in JSON.
+fn trace_json(log: &Path) -> Result<serde_json::Value, Error> {
+    let parse_error = |error: String| Error::Parse(format!("{}: {}", log.display(), error));
+    let model = load_log(log).map_err(|error| parse_error(error.to_string()))?;
+    let trace = model
+        .to_json()
+        .map_err(|error| parse_error(error.to_string()))?;
+    let profile = model
+        .instantiation_profile_json()
+        .map_err(|error| parse_error(error.to_string()))?;
+    Ok(serde_json::json!({ "trace": trace, "profile": profile }))
+}
+
 fn decode_array_instantiation(model: &Model, id: &Ident) -> Result<String, ()> {
     match model.term(id) {
         Ok(term) => match term {
-            z3tracer::syntax::Term::App {
-                name,
-                args,
-                meaning,
-            } => {
+            z3tracer::syntax::Term::App { name, args, .. } => {
                 if args.is_empty() {
                     Ok(name.to_string())
                 } else {
                     let args = args
                         .iter()
-                        .map(|arg| decode_array_instantiation(model, arg).unwrap())
-                        .collect::<Vec<String>>()
+                        .map(|arg| decode_array_instantiation(model, arg))
+                        .collect::<Result<Vec<String>, ()>>()?
                         .join(" ");
                     Ok(format!("({} {})", name, args))
                 }
             }
             z3tracer::syntax::Term::Var { index } => Ok(format!("{}", index)),
             z3tracer::syntax::Term::Quant {
-                name,
-                params,
-                triggers,
-                body,
-                var_names,
+                body, var_names, ..
             } => {
                 let var_name_string = match var_names {
-                    Some(names) => {
-                        names.iter().map(|var| format!("{}: {}", var.name.0, var.sort.0)).collect::<Vec<String>>().join(" ")
-                    }
+                    Some(names) => names
+                        .iter()
+                        .map(|var| format!("{}: {}", var.name.0, var.sort.0))
+                        .collect::<Vec<String>>()
+                        .join(" "),
                     None => "".to_string(),
                 };
-                Ok(format!("(Q ({}): {}", var_name_string, decode_array_instantiation(model, body).unwrap()))
-            }
-            z3tracer::syntax::Term::Lambda {
-                name,
-                params,
-                triggers,
-                body,
-            } => todo!(),
-            z3tracer::syntax::Term::Proof {
-                name,
-                args,
-                property,
-            } => todo!(),
-            z3tracer::syntax::Term::Builtin { name } => todo!(),
+                Ok(format!(
+                    "(Q ({}): {}",
+                    var_name_string,
+                    decode_array_instantiation(model, body)?
+                ))
+            }
+            z3tracer::syntax::Term::Lambda { .. }
+            | z3tracer::syntax::Term::Proof { .. }
+            | z3tracer::syntax::Term::Builtin { .. } => Err(()),
         },
         _ => Err(()),
     }
@@ -0,0 +1,234 @@
+//! Interactive exploration of a BMC problem and of the Z3 trace of its resolution.
+
+use smt2parser::{
+    concrete::{Command, SyntaxBuilder},
+    CommandStream,
+};
+use std::{
+    collections::BTreeMap,
+    io::{self, BufRead, Write},
+    str::FromStr,
+};
+use z3::SatResult;
+use z3tracer::{
+    syntax::{Ident, QiKey, Term},
+    Model,
+};
+
+use super::{check_with_z3, load_log, Error, SolverOptions, TempFile};
+
+const HELP: &str = "\
+status                     result of the last check
+value <var> [<step>]       value of a variable in the model, at every step by default
+quantifiers                quantifiers of the trace and their number of instantiations
+instantiations <name>      instances of the quantifier <name>
+term <id>                  term of the trace with identifier <id>, e.g. #42
+lemma <term>               assert an extra lemma and check the problem again
+help                       this message
+quit                       leave the session";
+
+/// State of an interactive session.
+struct Session {
+    /// The problem, without `check-sat`.
+    problem: Vec<Command>,
+    /// Lemmas added during the session, as assertions.
+    lemmas: Vec<Command>,
+    options: SolverOptions,
+    /// Result of the last check.
+    result: SatResult,
+    /// Values of the constants in the model of the last check, if any.
+    values: BTreeMap<String, String>,
+    /// Z3 trace of the last check.
+    trace: Model,
+}
+
+/// Read commands from the standard input until `quit` or the end of the input.
+pub fn run(problem: Vec<Command>, options: SolverOptions) -> Result<(), Error> {
+    let mut session = Session {
+        problem,
+        lemmas: Vec::new(),
+        options,
+        result: SatResult::Unknown,
+        values: BTreeMap::new(),
+        trace: Model::default(),
+    };
+    session.check()?;
+    session.print_status();
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("berty> ");
+        io::stdout().flush().ok();
+        let line = match lines.next() {
+            Some(Ok(line)) => line,
+            _ => return Ok(()),
+        };
+        let line = line.trim();
+        let (command, argument) = match line.find(char::is_whitespace) {
+            Some(index) => (&line[..index], line[index..].trim()),
+            None => (line, ""),
+        };
+        match command {
+            "" => (),
+            "quit" | "exit" => return Ok(()),
+            "help" => println!("{}", HELP),
+            "status" => session.print_status(),
+            "value" => session.print_value(argument),
+            "quantifiers" => session.print_quantifiers(),
+            "instantiations" => session.print_instantiations(argument),
+            "term" => session.print_term(argument),
+            "lemma" => {
+                let lemma = format!("(assert {})", argument);
+                match CommandStream::new(lemma.as_bytes(), SyntaxBuilder, None).next() {
+                    Some(Ok(lemma)) => {
+                        session.lemmas.push(lemma);
+                        match session.check() {
+                            Ok(()) => session.print_status(),
+                            // Ill-sorted lemmas or unknown symbols: forget the lemma.
+                            Err(error @ Error::Parse(_)) => {
+                                session.lemmas.pop();
+                                println!("{}", error);
+                            }
+                            Err(error) => return Err(error),
+                        }
+                    }
+                    Some(Err(error)) => println!("{}", error),
+                    None => println!("Usage: lemma <term>"),
+                }
+            }
+            _ => println!("Unknown command: {} (try `help`)", command),
+        }
+    }
+}
+
+impl Session {
+    // Check the problem with the current lemmas and load the trace.
+    fn check(&mut self) -> Result<(), Error> {
+        let trace_log = TempFile::new("log");
+        let problem = [&self.problem[..], &self.lemmas].concat();
+        let (result, model) = check_with_z3(&problem, Some(trace_log.path()), &self.options)?;
+        self.trace =
+            load_log(trace_log.path()).map_err(|error| Error::Solver(error.to_string()))?;
+        self.result = result;
+        self.values = model.as_deref().map(parse_values).unwrap_or_default();
+        Ok(())
+    }
+
+    fn print_status(&self) {
+        let result = match self.result {
+            SatResult::Sat => "sat",
+            SatResult::Unsat => "unsat",
+            SatResult::Unknown => "unknown",
+        };
+        println!(
+            "{} ({} lemmas, {} instantiations in the trace)",
+            result,
+            self.lemmas.len(),
+            self.trace.instantiations().len()
+        );
+    }
+
+    fn print_value(&self, argument: &str) {
+        let mut words = argument.split_whitespace();
+        let variable = match words.next() {
+            Some(variable) => variable,
+            None => return println!("Usage: value <var> [<step>]"),
+        };
+        let values = match words.next() {
+            Some(step) => {
+                let name = format!("{}@{}", variable, step);
+                self.values.get_key_value(&name).into_iter().collect()
+            }
+            None => {
+                let mut values = self
+                    .values
+                    .iter()
+                    .filter(|(name, _)| name.rsplit_once('@').map(|(var, _)| var) == Some(variable))
+                    .collect::<Vec<_>>();
+                values.sort_by_key(|(name, _)| {
+                    name.rsplit('@')
+                        .next()
+                        .and_then(|step| step.parse::<usize>().ok())
+                });
+                values
+            }
+        };
+        if values.is_empty() {
+            println!("No value for {}", argument);
+        }
+        for (name, value) in values {
+            println!("{} = {}", name, value);
+        }
+    }
+
+    fn print_quantifiers(&self) {
+        let mut counts = BTreeMap::new();
+        for inst in self.trace.instantiations().values() {
+            *counts
+                .entry(self.quantifier_name(inst.frame.quantifier()))
+                .or_insert(0) += 1;
+        }
+        for (name, count) in counts {
+            println!("{} {}", count, name);
+        }
+    }
+
+    fn print_instantiations(&self, name: &str) {
+        for (key, inst) in self.trace.instantiations() {
+            if self.quantifier_name(inst.frame.quantifier()) == name {
+                match self.trace.instantiated_lemma(key) {
+                    Ok(lemma) => println!("{} {}", fmt_key(key), lemma),
+                    Err(error) => println!("{} {}", fmt_key(key), error),
+                }
+            }
+        }
+    }
+
+    fn print_term(&self, argument: &str) {
+        let result = Ident::from_str(argument)
+            .map_err(|error| error.to_string())
+            .and_then(|id| {
+                self.trace
+                    .id_to_sexp(&BTreeMap::new(), &id)
+                    .map_err(|error| error.to_string())
+            });
+        match result {
+            Ok(term) => println!("{}", term),
+            Err(error) => println!("{}", error),
+        }
+    }
+
+    fn quantifier_name(&self, id: &Ident) -> String {
+        match self.trace.term(id) {
+            Ok(Term::Quant { name, .. }) => name.clone(),
+            _ => format!("{:?}", id),
+        }
+    }
+}
+
+fn fmt_key(key: &QiKey) -> String {
+    format!("{:#x}", key.key)
+}
+
+// Parse a Z3 model printed as lines `name -> value`. Values may span several lines.
+fn parse_values(model: &str) -> BTreeMap<String, String> {
+    let mut values = BTreeMap::new();
+    let mut current: Option<(String, String)> = None;
+    for line in model.lines() {
+        match line.find(" -> ") {
+            Some(index) if !line.starts_with(char::is_whitespace) => {
+                values.extend(current.take());
+                current = Some((line[..index].to_string(), line[index + 4..].to_string()));
+            }
+            _ => {
+                if let Some((_, value)) = &mut current {
+                    value.push('\n');
+                    value.push_str(line);
+                }
+            }
+        }
+    }
+    values.extend(current);
+    values
+}
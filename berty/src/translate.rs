@@ -0,0 +1,869 @@
+//! Construction of Z3 terms from parsed SMT-LIB commands, and back.
+
+use smt2parser::{
+    concrete::{AttributeValue, Command, Constant, Identifier, QualIdentifier, SExpr, Sort, Term},
+    visitors::Index,
+    Numeral,
+};
+use std::{collections::HashMap, convert::TryFrom, ffi::CString, mem, ptr};
+use z3::{
+    ast::{self, Array, Ast, Bool, Dynamic, Int, Real},
+    AstKind, Context, DeclKind, FuncDecl, Pattern, Solver, SortKind, Symbol,
+};
+use z3_sys::Z3_context;
+
+use super::Error;
+
+/// Translation of SMT-LIB commands into assertions of a Z3 solver.
+///
+/// Declarations are kept across calls, so that a problem can be extended incrementally.
+/// Assertions that cannot be translated, e.g. because they use bit-vectors, datatypes or
+/// recursive definitions, are parsed by Z3 together with the declarations so far.
+pub struct Translator<'ctx> {
+    context: &'ctx Context,
+    sorts: HashMap<String, z3::Sort<'ctx>>,
+    /// Declared functions, with the sorts of their parameters.
+    functions: HashMap<String, (Vec<z3::Sort<'ctx>>, FuncDecl<'ctx>)>,
+    /// Defined functions, with the names of their parameters, expanded when applied.
+    definitions: HashMap<String, (Vec<String>, Term)>,
+    /// Variables bound by the enclosing quantifiers and `let`s, innermost last.
+    bindings: Vec<(String, Dynamic<'ctx>)>,
+    /// Declarations and definitions so far, as SMT-LIB text.
+    declarations: String,
+    fresh_names: usize,
+}
+
+impl<'ctx> Translator<'ctx> {
+    pub fn new(context: &'ctx Context) -> Self {
+        Translator {
+            context,
+            sorts: HashMap::new(),
+            functions: HashMap::new(),
+            definitions: HashMap::new(),
+            bindings: Vec::new(),
+            declarations: String::new(),
+            fresh_names: 0,
+        }
+    }
+
+    /// Record the declarations and definitions of the given commands and add their
+    /// assertions to the solver. `push` and `pop` are forwarded to the solver. Other
+    /// commands, such as `check-sat`, are ignored.
+    pub fn add_commands(
+        &mut self,
+        solver: &Solver<'ctx>,
+        commands: &[Command],
+    ) -> Result<(), Error> {
+        for command in commands {
+            self.add_command(solver, command)?;
+        }
+        Ok(())
+    }
+
+    fn add_command(&mut self, solver: &Solver<'ctx>, command: &Command) -> Result<(), Error> {
+        match command {
+            Command::DeclareSort { symbol, arity } if *arity == Numeral::from(0u32) => {
+                let sort = z3::Sort::uninterpreted(self.context, Symbol::String(symbol.0.clone()));
+                self.sorts.insert(symbol.0.clone(), sort);
+            }
+            Command::DeclareConst { symbol, sort } => self.declare(&symbol.0, &[], sort),
+            Command::DeclareFun {
+                symbol,
+                parameters,
+                sort,
+            } => self.declare(&symbol.0, parameters, sort),
+            Command::DefineFun { sig, term } => {
+                let parameters = sig.parameters.iter().map(|(symbol, _)| symbol.0.clone());
+                self.definitions
+                    .insert(sig.name.0.clone(), (parameters.collect(), term.clone()));
+            }
+            Command::Assert { term } => {
+                match self.term(term).and_then(|value| self.boolean(&value, term)) {
+                    Ok(assertion) => solver.assert(&assertion),
+                    Err(error) => {
+                        trace!("Parsing the assertion with Z3: {}", error);
+                        let count = solver.get_assertions().len();
+                        solver.from_string(format!("{}{}\n", self.declarations, command));
+                        if solver.get_assertions().len() != count + 1 {
+                            return Err(error);
+                        }
+                    }
+                }
+                return Ok(());
+            }
+            Command::Push { level } => {
+                for _ in 0..level_u32(level)? {
+                    solver.push();
+                }
+                return Ok(());
+            }
+            Command::Pop { level } => {
+                solver.pop(level_u32(level)?);
+                return Ok(());
+            }
+            // Only known to the Z3 parser, through `declarations`.
+            Command::DeclareSort { .. }
+            | Command::DeclareDatatype { .. }
+            | Command::DeclareDatatypes { .. }
+            | Command::DefineFunRec { .. }
+            | Command::DefineFunsRec { .. }
+            | Command::DefineSort { .. } => (),
+            _ => return Ok(()),
+        }
+        self.declarations += &format!("{}\n", command);
+        Ok(())
+    }
+
+    /// The values of the declared constants in a model, sorted by name. Fails if some
+    /// declared function has parameters or some value cannot be converted, e.g. an array
+    /// given by a lambda.
+    pub fn model_values(&self, model: &z3::Model<'ctx>) -> Result<Vec<(String, Term)>, Error> {
+        let mut names = self.functions.keys().collect::<Vec<_>>();
+        names.sort();
+        names
+            .into_iter()
+            .map(|name| {
+                let (parameters, function) = &self.functions[name];
+                if !parameters.is_empty() {
+                    return Err(Error::Parse(format!("Unsupported model value: {}", name)));
+                }
+                let value = model
+                    .eval(&function.apply(&[]), true)
+                    .ok_or_else(|| Error::Parse(format!("No model value: {}", name)))?;
+                Ok((name.clone(), from_z3_term(&value)?))
+            })
+            .collect()
+    }
+
+    // Declare a function. Functions of unsupported sorts are only known to the Z3 parser.
+    fn declare(&mut self, name: &str, parameters: &[Sort], sort: &Sort) {
+        let sorts = parameters
+            .iter()
+            .map(|parameter| self.sort(parameter))
+            .collect::<Result<Vec<_>, _>>()
+            .and_then(|parameters| Ok((parameters, self.sort(sort)?)));
+        match sorts {
+            Ok((parameters, sort)) => {
+                let function = FuncDecl::new(
+                    self.context,
+                    name,
+                    &parameters.iter().collect::<Vec<_>>(),
+                    &sort,
+                );
+                self.functions
+                    .insert(name.to_string(), (parameters, function));
+            }
+            Err(error) => {
+                trace!("Leaving the declaration of {} to Z3: {}", name, error);
+                self.functions.remove(name);
+            }
+        }
+    }
+
+    /// The Z3 sort of a sort, built-in or declared so far.
+    pub fn sort(&self, sort: &Sort) -> Result<z3::Sort<'ctx>, Error> {
+        match sort {
+            Sort::Simple {
+                identifier: Identifier::Simple { symbol },
+            } => match symbol.0.as_str() {
+                "Bool" => Ok(z3::Sort::bool(self.context)),
+                "Int" => Ok(z3::Sort::int(self.context)),
+                "Real" => Ok(z3::Sort::real(self.context)),
+                name => self
+                    .sorts
+                    .get(name)
+                    .cloned()
+                    .ok_or_else(|| Error::Parse(format!("Unknown sort: {}", name))),
+            },
+            Sort::Parameterized {
+                identifier: Identifier::Simple { symbol },
+                parameters,
+            } if symbol.0 == "Array" && parameters.len() == 2 => Ok(z3::Sort::array(
+                self.context,
+                &self.sort(&parameters[0])?,
+                &self.sort(&parameters[1])?,
+            )),
+            _ => Err(Error::Parse(format!("Unsupported sort: {}", sort))),
+        }
+    }
+
+    /// The Z3 term of a term, whose symbols are built-in or declared or defined so far.
+    /// Defined functions are expanded.
+    pub fn term(&mut self, term: &Term) -> Result<Dynamic<'ctx>, Error> {
+        match term {
+            Term::Constant(Constant::Numeral(value)) => {
+                let value = Int::from_str(self.context, &value.to_string()).unwrap();
+                Ok(Dynamic::from_ast(&value))
+            }
+            Term::Constant(Constant::Decimal(value)) => {
+                let (numerator, denominator) =
+                    (value.numer().to_string(), value.denom().to_string());
+                let value = Real::from_real_str(self.context, &numerator, &denominator).unwrap();
+                Ok(Dynamic::from_ast(&value))
+            }
+            Term::QualIdentifier(qual_identifier) => {
+                let name = simple_name(qual_identifier)
+                    .ok_or_else(|| Error::Parse(format!("Unsupported term: {}", term)))?;
+                if let Some((_, value)) =
+                    self.bindings.iter().rev().find(|(bound, _)| bound == name)
+                {
+                    return Ok(value.clone());
+                }
+                match name {
+                    "true" | "false" => Ok(Dynamic::from_ast(&Bool::from_bool(
+                        self.context,
+                        name == "true",
+                    ))),
+                    _ => self.apply(name, Vec::new(), term),
+                }
+            }
+            Term::Application {
+                qual_identifier:
+                    QualIdentifier::Sorted {
+                        identifier: Identifier::Simple { symbol },
+                        sort:
+                            Sort::Parameterized {
+                                identifier: Identifier::Simple { symbol: array },
+                                parameters,
+                            },
+                    },
+                arguments,
+            } if symbol.0 == "const"
+                && array.0 == "Array"
+                && parameters.len() == 2
+                && arguments.len() == 1 =>
+            {
+                let domain = self.sort(&parameters[0])?;
+                let range = self.sort(&parameters[1])?;
+                let value = coerce(self.term(&arguments[0])?, &range);
+                if range != value.get_sort() {
+                    return Err(Error::Parse(format!("Ill-sorted term: {}", term)));
+                }
+                Ok(Dynamic::from_ast(&Array::const_array(
+                    self.context,
+                    &domain,
+                    &value,
+                )))
+            }
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } => {
+                let name = simple_name(qual_identifier)
+                    .ok_or_else(|| Error::Parse(format!("Unsupported term: {}", term)))?;
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| self.term(argument))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.apply(name, arguments, term)
+            }
+            Term::Let { var_bindings, term } => {
+                let values = var_bindings
+                    .iter()
+                    .map(|(symbol, value)| Ok((symbol.0.clone(), self.term(value)?)))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                let depth = self.bindings.len();
+                self.bindings.extend(values);
+                let result = self.term(term);
+                self.bindings.truncate(depth);
+                result
+            }
+            Term::Forall { vars, term: body } | Term::Exists { vars, term: body } => {
+                let sorts = vars
+                    .iter()
+                    .map(|(_, sort)| self.sort(sort))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let depth = self.bindings.len();
+                for ((symbol, _), sort) in vars.iter().zip(sorts) {
+                    // Fresh constants, so that bound variables cannot capture others.
+                    self.fresh_names += 1;
+                    let name = format!("{}!{}", symbol.0, self.fresh_names);
+                    let variable = FuncDecl::new(self.context, name, &[], &sort).apply(&[]);
+                    self.bindings.push((symbol.0.clone(), variable));
+                }
+                let universal = matches!(term, Term::Forall { .. });
+                let result = self.quantifier(universal, term.qid(), depth, body);
+                self.bindings.truncate(depth);
+                result
+            }
+            Term::Attributes { term, .. } => self.term(term),
+            _ => Err(Error::Parse(format!("Unsupported term: {}", term))),
+        }
+    }
+
+    // Quantify the body over the variables bound after the given depth, with the given
+    // name if any.
+    fn quantifier(
+        &mut self,
+        universal: bool,
+        qid: Option<&str>,
+        depth: usize,
+        body: &Term,
+    ) -> Result<Dynamic<'ctx>, Error> {
+        let mut triggers = Vec::new();
+        if let Term::Attributes { attributes, .. } = body {
+            for (keyword, value) in attributes {
+                if let ("pattern", AttributeValue::SExpr(terms)) = (keyword.0.as_str(), value) {
+                    let terms = terms
+                        .iter()
+                        .map(|term| self.term(&sexpr_term(term)))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    triggers.push(terms);
+                }
+            }
+        }
+        let value = self.term(body)?;
+        let value = self.boolean(&value, body)?;
+        let variables = self.bindings[depth..]
+            .iter()
+            .map(|(_, variable)| variable)
+            .collect::<Vec<_>>();
+        if let Some(qid) = qid {
+            return named_quantifier(self.context, universal, qid, &variables, &triggers, &value)
+                .map(|result| Dynamic::from_ast(&result));
+        }
+        let bounds = variables
+            .iter()
+            .map(|variable| *variable as &dyn Ast)
+            .collect::<Vec<_>>();
+        let patterns = triggers
+            .iter()
+            .map(|terms| {
+                let terms = terms
+                    .iter()
+                    .map(|term| term as &dyn Ast)
+                    .collect::<Vec<_>>();
+                Pattern::new(self.context, &terms)
+            })
+            .collect::<Vec<_>>();
+        let patterns = patterns.iter().collect::<Vec<_>>();
+        let result = if universal {
+            ast::forall_const(self.context, &bounds, &patterns, &value)
+        } else {
+            ast::exists_const(self.context, &bounds, &patterns, &value)
+        };
+        Ok(Dynamic::from_ast(&result))
+    }
+
+    // Apply a defined, declared or built-in function.
+    fn apply(
+        &mut self,
+        name: &str,
+        mut arguments: Vec<Dynamic<'ctx>>,
+        term: &Term,
+    ) -> Result<Dynamic<'ctx>, Error> {
+        let ill_sorted = || Error::Parse(format!("Ill-sorted term: {}", term));
+        if let Some((parameters, body)) = self.definitions.get(name).cloned() {
+            if parameters.len() != arguments.len() {
+                return Err(ill_sorted());
+            }
+            // Definitions are closed: only their parameters are bound in their body.
+            let bindings = std::mem::replace(
+                &mut self.bindings,
+                parameters.into_iter().zip(arguments).collect(),
+            );
+            let result = self.term(&body);
+            self.bindings = bindings;
+            return result;
+        }
+        if let Some((parameters, function)) = self.functions.get(name) {
+            if parameters.len() != arguments.len() {
+                return Err(ill_sorted());
+            }
+            let arguments = parameters
+                .iter()
+                .zip(arguments)
+                .map(|(sort, argument)| coerce(argument, sort))
+                .collect::<Vec<_>>();
+            if parameters
+                .iter()
+                .zip(&arguments)
+                .any(|(sort, argument)| *sort != argument.get_sort())
+            {
+                return Err(ill_sorted());
+            }
+            let arguments = arguments
+                .iter()
+                .map(|argument| argument as &dyn Ast)
+                .collect::<Vec<_>>();
+            return Ok(function.apply(&arguments));
+        }
+
+        match (name, arguments.len()) {
+            ("=", _) | ("distinct", _) => unify(&mut arguments),
+            ("ite", 3) => unify(&mut arguments[1..]),
+            _ => (),
+        }
+        let context = self.context;
+        let booleans = || {
+            arguments
+                .iter()
+                .map(|argument| argument.as_bool())
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(ill_sorted)
+        };
+        let result = match (name, arguments.len()) {
+            ("not", 1) => Dynamic::from_ast(&booleans()?[0].not()),
+            ("and", _) => {
+                Dynamic::from_ast(&Bool::and(context, &booleans()?.iter().collect::<Vec<_>>()))
+            }
+            ("or", _) => {
+                Dynamic::from_ast(&Bool::or(context, &booleans()?.iter().collect::<Vec<_>>()))
+            }
+            ("xor", n) if n >= 2 => {
+                let values = booleans()?;
+                Dynamic::from_ast(
+                    &values[1..]
+                        .iter()
+                        .fold(values[0].clone(), |left, right| left.xor(right)),
+                )
+            }
+            ("=>", n) if n >= 2 => {
+                let values = booleans()?;
+                let (last, premises) = values.split_last().unwrap();
+                Dynamic::from_ast(
+                    &premises
+                        .iter()
+                        .rev()
+                        .fold(last.clone(), |right, left| left.implies(&right)),
+                )
+            }
+            ("ite", 3) => {
+                let condition = arguments[0].as_bool().ok_or_else(ill_sorted)?;
+                if arguments[1].get_sort() != arguments[2].get_sort() {
+                    return Err(ill_sorted());
+                }
+                condition.ite(&arguments[1], &arguments[2])
+            }
+            ("=", n) | ("distinct", n) if n >= 2 => {
+                if arguments
+                    .iter()
+                    .any(|argument| argument.get_sort() != arguments[0].get_sort())
+                {
+                    return Err(ill_sorted());
+                }
+                if name == "distinct" {
+                    Dynamic::from_ast(&Dynamic::distinct(
+                        context,
+                        &arguments.iter().collect::<Vec<_>>(),
+                    ))
+                } else {
+                    let equalities = arguments
+                        .windows(2)
+                        .map(|pair| pair[0]._eq(&pair[1]))
+                        .collect::<Vec<_>>();
+                    conjunction(context, equalities)
+                }
+            }
+            ("select", 2) => {
+                let array = arguments[0].as_array().ok_or_else(ill_sorted)?;
+                let sort = array.get_sort();
+                let domain = sort.array_domain().ok_or_else(ill_sorted)?;
+                let index = coerce(arguments[1].clone(), &domain);
+                if domain != index.get_sort() {
+                    return Err(ill_sorted());
+                }
+                array.select(&index)
+            }
+            ("store", 3) => {
+                let array = arguments[0].as_array().ok_or_else(ill_sorted)?;
+                let sort = array.get_sort();
+                let domain = sort.array_domain().ok_or_else(ill_sorted)?;
+                let range = sort.array_range().ok_or_else(ill_sorted)?;
+                let index = coerce(arguments[1].clone(), &domain);
+                let value = coerce(arguments[2].clone(), &range);
+                if domain != index.get_sort() || range != value.get_sort() {
+                    return Err(ill_sorted());
+                }
+                Dynamic::from_ast(&array.store(&index, &value))
+            }
+            ("+", _)
+            | ("-", _)
+            | ("*", _)
+            | ("/", _)
+            | ("div", _)
+            | ("mod", _)
+            | ("abs", _)
+            | ("<", _)
+            | ("<=", _)
+            | (">", _)
+            | (">=", _)
+            | ("to_real", _)
+            | ("to_int", _) => arithmetic(context, name, &arguments).ok_or_else(ill_sorted)?,
+            _ => return Err(Error::Parse(format!("Unknown symbol: {}", name))),
+        };
+        Ok(result)
+    }
+
+    fn boolean(&self, value: &Dynamic<'ctx>, term: &Term) -> Result<Bool<'ctx>, Error> {
+        value
+            .as_bool()
+            .ok_or_else(|| Error::Parse(format!("Expected a Boolean term: {}", term)))
+    }
+}
+
+/// The term of a Z3 term, e.g. a value of a Z3 model. Quantifiers and bound variables are
+/// not supported.
+pub fn from_z3_term(value: &Dynamic) -> Result<Term, Error> {
+    let unsupported = || Error::Parse(format!("Unsupported Z3 term: {}", value));
+    match value.kind() {
+        // Negative numerals and rationals are printed as applications of `-` and `/`.
+        AstKind::Numeral => smt2parser::parse_term(&value.to_string()).map_err(|_| unsupported()),
+        AstKind::App => {
+            let decl = value.decl();
+            let arguments = value
+                .children()
+                .iter()
+                .map(from_z3_term)
+                .collect::<Result<Vec<_>, _>>()?;
+            let identifier = Identifier::Simple {
+                symbol: smt2parser::concrete::Symbol(decl.name()),
+            };
+            let qual_identifier = match decl.kind() {
+                DeclKind::CONST_ARRAY => QualIdentifier::Sorted {
+                    identifier,
+                    sort: from_z3_sort(&value.get_sort())?,
+                },
+                _ => QualIdentifier::Simple { identifier },
+            };
+            if arguments.is_empty() {
+                Ok(Term::QualIdentifier(qual_identifier))
+            } else {
+                Ok(Term::Application {
+                    qual_identifier,
+                    arguments,
+                })
+            }
+        }
+        _ => Err(unsupported()),
+    }
+}
+
+/// The sort of a Z3 sort. Only Booleans, integers, reals, bit-vectors, arrays and
+/// uninterpreted sorts are supported.
+pub fn from_z3_sort(sort: &z3::Sort) -> Result<Sort, Error> {
+    let simple = |name: String| Identifier::Simple {
+        symbol: smt2parser::concrete::Symbol(name),
+    };
+    match sort.kind() {
+        SortKind::Bool | SortKind::Int | SortKind::Real | SortKind::Uninterpreted => {
+            Ok(Sort::Simple {
+                identifier: simple(sort.to_string()),
+            })
+        }
+        SortKind::BV => {
+            // Z3 prints bit-vector sorts as `(_ BitVec n)`.
+            let width = sort
+                .to_string()
+                .trim_start_matches("(_ BitVec ")
+                .trim_end_matches(')')
+                .parse::<Numeral>()
+                .map_err(|_| Error::Parse(format!("Unsupported Z3 sort: {}", sort)))?;
+            Ok(Sort::Simple {
+                identifier: Identifier::Indexed {
+                    symbol: smt2parser::concrete::Symbol("BitVec".to_string()),
+                    indices: vec![Index::Numeral(width)],
+                },
+            })
+        }
+        SortKind::Array => {
+            let unsupported = || Error::Parse(format!("Unsupported Z3 sort: {}", sort));
+            let domain = sort.array_domain().ok_or_else(unsupported)?;
+            let range = sort.array_range().ok_or_else(unsupported)?;
+            Ok(Sort::Parameterized {
+                identifier: simple("Array".to_string()),
+                parameters: vec![from_z3_sort(&domain)?, from_z3_sort(&range)?],
+            })
+        }
+        _ => Err(Error::Parse(format!("Unsupported Z3 sort: {}", sort))),
+    }
+}
+
+// Convert an integer to a real where a real is expected, as the Z3 parser does.
+fn coerce<'ctx>(value: Dynamic<'ctx>, sort: &z3::Sort) -> Dynamic<'ctx> {
+    match value.as_int() {
+        Some(integer) if sort.kind() == SortKind::Real => Dynamic::from_ast(&integer.to_real()),
+        _ => value,
+    }
+}
+
+// Convert integers to reals if some values are reals.
+fn unify(values: &mut [Dynamic]) {
+    if let Some(real) = values.iter().find_map(|value| value.as_real()) {
+        let sort = real.get_sort();
+        for value in values.iter_mut() {
+            *value = coerce(value.clone(), &sort);
+        }
+    }
+}
+
+// Arithmetic operations, on integers or reals. Integers are converted to reals when
+// mixed with reals.
+fn arithmetic<'ctx>(
+    context: &'ctx Context,
+    name: &str,
+    arguments: &[Dynamic<'ctx>],
+) -> Option<Dynamic<'ctx>> {
+    let integers = arguments
+        .iter()
+        .map(|argument| argument.as_int())
+        .collect::<Option<Vec<_>>>();
+    let reals = || {
+        arguments
+            .iter()
+            .map(|argument| {
+                argument
+                    .as_real()
+                    .or_else(|| argument.as_int().map(|value| value.to_real()))
+            })
+            .collect::<Option<Vec<_>>>()
+    };
+    let result = match (name, integers) {
+        ("to_real", Some(values)) if values.len() == 1 => Dynamic::from_ast(&values[0].to_real()),
+        ("to_int", None) if arguments.len() == 1 => {
+            Dynamic::from_ast(&arguments[0].as_real()?.to_int())
+        }
+        ("div", Some(values)) if values.len() == 2 => Dynamic::from_ast(&values[0].div(&values[1])),
+        ("mod", Some(values)) if values.len() == 2 => {
+            Dynamic::from_ast(&values[0].modulo(&values[1]))
+        }
+        ("abs", Some(values)) if values.len() == 1 => {
+            let zero = Int::from_i64(context, 0);
+            Dynamic::from_ast(
+                &values[0]
+                    .lt(&zero)
+                    .ite(&values[0].unary_minus(), &values[0]),
+            )
+        }
+        ("abs", None) if arguments.len() == 1 => {
+            let value = arguments[0].as_real()?;
+            let zero = Real::from_real(context, 0, 1);
+            Dynamic::from_ast(&value.lt(&zero).ite(&value.unary_minus(), &value))
+        }
+        ("-", Some(values)) if values.len() == 1 => Dynamic::from_ast(&values[0].unary_minus()),
+        ("-", None) if arguments.len() == 1 => {
+            Dynamic::from_ast(&arguments[0].as_real()?.unary_minus())
+        }
+        ("+", Some(values)) => {
+            Dynamic::from_ast(&Int::add(context, &values.iter().collect::<Vec<_>>()))
+        }
+        ("-", Some(values)) => {
+            Dynamic::from_ast(&Int::sub(context, &values.iter().collect::<Vec<_>>()))
+        }
+        ("*", Some(values)) => {
+            Dynamic::from_ast(&Int::mul(context, &values.iter().collect::<Vec<_>>()))
+        }
+        ("+", None) => Dynamic::from_ast(&Real::add(context, &reals()?.iter().collect::<Vec<_>>())),
+        ("-", None) => Dynamic::from_ast(&Real::sub(context, &reals()?.iter().collect::<Vec<_>>())),
+        ("*", None) => Dynamic::from_ast(&Real::mul(context, &reals()?.iter().collect::<Vec<_>>())),
+        ("/", _) => {
+            let values = reals()?;
+            Dynamic::from_ast(
+                &values[1..]
+                    .iter()
+                    .fold(values.first()?.clone(), |left, right| left.div(right)),
+            )
+        }
+        ("<", Some(values)) => chain(context, &values, |left, right| left.lt(right)),
+        ("<=", Some(values)) => chain(context, &values, |left, right| left.le(right)),
+        (">", Some(values)) => chain(context, &values, |left, right| left.gt(right)),
+        (">=", Some(values)) => chain(context, &values, |left, right| left.ge(right)),
+        ("<", None) => chain(context, &reals()?, |left, right| left.lt(right)),
+        ("<=", None) => chain(context, &reals()?, |left, right| left.le(right)),
+        (">", None) => chain(context, &reals()?, |left, right| left.gt(right)),
+        (">=", None) => chain(context, &reals()?, |left, right| left.ge(right)),
+        _ => return None,
+    };
+    Some(result)
+}
+
+// Conjunction of a chainable comparison between consecutive values.
+fn chain<'ctx, T, F>(context: &'ctx Context, values: &[T], compare: F) -> Dynamic<'ctx>
+where
+    F: Fn(&T, &T) -> Bool<'ctx>,
+{
+    let comparisons = values
+        .windows(2)
+        .map(|pair| compare(&pair[0], &pair[1]))
+        .collect::<Vec<_>>();
+    conjunction(context, comparisons)
+}
+
+// Conjunction of Boolean values, without `and` for a single value.
+fn conjunction<'ctx>(context: &'ctx Context, mut values: Vec<Bool<'ctx>>) -> Dynamic<'ctx> {
+    match values.len() {
+        1 => Dynamic::from_ast(&values.remove(0)),
+        _ => Dynamic::from_ast(&Bool::and(context, &values.iter().collect::<Vec<_>>())),
+    }
+}
+
+fn simple_name(qual_identifier: &QualIdentifier) -> Option<&str> {
+    match qual_identifier {
+        QualIdentifier::Simple {
+            identifier: Identifier::Simple { symbol },
+        }
+        | QualIdentifier::Sorted {
+            identifier: Identifier::Simple { symbol },
+            ..
+        } => Some(&symbol.0),
+        _ => None,
+    }
+}
+
+// A quantifier named by a `:qid`, as Z3 traces identify quantifiers by name. The z3 crate
+// cannot name quantifiers, so this uses the C API directly.
+fn named_quantifier<'ctx>(
+    context: &'ctx Context,
+    universal: bool,
+    name: &str,
+    variables: &[&Dynamic<'ctx>],
+    triggers: &[Vec<Dynamic<'ctx>>],
+    body: &Bool<'ctx>,
+) -> Result<Bool<'ctx>, Error> {
+    let qid = CString::new(name).map_err(|_| Error::Parse(format!("Invalid qid: {}", name)))?;
+    let ctx = raw_context(context);
+    unsafe {
+        let qid = z3_sys::Z3_mk_string_symbol(ctx, qid.as_ptr());
+        let bounds = variables
+            .iter()
+            .map(|variable| z3_sys::Z3_to_app(ctx, variable.get_z3_ast()))
+            .collect::<Vec<_>>();
+        // Patterns are reference-counted ASTs, kept alive until the quantifier is built.
+        let patterns = triggers
+            .iter()
+            .map(|terms| {
+                let terms = terms
+                    .iter()
+                    .map(|term| term.get_z3_ast())
+                    .collect::<Vec<_>>();
+                let pattern = z3_sys::Z3_mk_pattern(ctx, terms.len() as u32, terms.as_ptr());
+                z3_sys::Z3_inc_ref(ctx, z3_sys::Z3_pattern_to_ast(ctx, pattern));
+                pattern
+            })
+            .collect::<Vec<_>>();
+        let result = z3_sys::Z3_mk_quantifier_const_ex(
+            ctx,
+            universal,
+            0,
+            qid,
+            ptr::null_mut(),
+            bounds.len() as u32,
+            bounds.as_ptr(),
+            patterns.len() as u32,
+            patterns.as_ptr(),
+            0,
+            ptr::null(),
+            body.get_z3_ast(),
+        );
+        let result = if result.is_null() {
+            Err(Error::Parse(format!("Ill-formed quantifier: {}", name)))
+        } else {
+            Ok(Bool::wrap(context, result))
+        };
+        for pattern in patterns {
+            z3_sys::Z3_dec_ref(ctx, z3_sys::Z3_pattern_to_ast(ctx, pattern));
+        }
+        result
+    }
+}
+
+// The Z3 context of a context, which the z3 crate keeps as the only field of `Context`.
+fn raw_context(context: &Context) -> Z3_context {
+    const _: () = assert!(mem::size_of::<Context>() == mem::size_of::<Z3_context>());
+    unsafe { *(context as *const Context as *const Z3_context) }
+}
+
+// Term of a pattern, given as an S-expression.
+fn sexpr_term(sexpr: &SExpr) -> Term {
+    let qual_identifier = |symbol: &smt2parser::concrete::Symbol| QualIdentifier::Simple {
+        identifier: Identifier::Simple {
+            symbol: symbol.clone(),
+        },
+    };
+    match sexpr {
+        SExpr::Constant(constant) => Term::Constant(constant.clone()),
+        SExpr::Application(sexprs) => match sexprs.split_first() {
+            Some((SExpr::Symbol(symbol), arguments)) => Term::Application {
+                qual_identifier: qual_identifier(symbol),
+                arguments: arguments.iter().map(sexpr_term).collect(),
+            },
+            _ => Term::Constant(Constant::String(sexpr.to_string())),
+        },
+        SExpr::Symbol(symbol) => Term::QualIdentifier(qual_identifier(symbol)),
+        SExpr::Keyword(_) => Term::Constant(Constant::String(sexpr.to_string())),
+    }
+}
+
+fn level_u32(level: &Numeral) -> Result<u32, Error> {
+    u32::try_from(level).map_err(|_| Error::Parse(format!("Invalid level: {}", level)))
+}
+
+#[test]
+fn test_round_trip() {
+    let context = Context::new(&z3::Config::new());
+    let solver = Solver::new(&context);
+    let mut translator = Translator::new(&context);
+    let declarations = smt2parser::parse_commands(
+        "(declare-sort U 0) (declare-fun u () U) (declare-fun f (U) Int) \
+         (declare-fun x () Int) (declare-fun a () (Array Int Bool))",
+    )
+    .unwrap();
+    translator.add_commands(&solver, &declarations).unwrap();
+
+    for input in &["Bool", "Int", "Real", "U", "(Array Int (Array U Bool))"] {
+        let sort = match smt2parser::parse_commands(&format!("(declare-const s {})", input)) {
+            Ok(mut commands) => match commands.pop() {
+                Some(Command::DeclareConst { sort, .. }) => sort,
+                _ => unreachable!(),
+            },
+            Err(error) => panic!("{}", error),
+        };
+        assert_eq!(
+            from_z3_sort(&translator.sort(&sort).unwrap()).unwrap(),
+            sort
+        );
+    }
+    for input in &[
+        "true",
+        "(+ x (f u) 1)",
+        "(and (select a x) (not (= x 2)))",
+        "(store a (* 2 x) false)",
+        "((as const (Array Int Bool)) true)",
+        "(=> (<= 0 x) (> (f u) x))",
+    ] {
+        let term = smt2parser::parse_term(input).unwrap();
+        let value = translator.term(&term).unwrap();
+        assert_eq!(from_z3_term(&value).unwrap(), term, "{}", input);
+    }
+
+    // Bound variables and unknown symbols are not translated back or forth.
+    let quantified = smt2parser::parse_term("(forall ((y Int)) (> y x))").unwrap();
+    assert!(from_z3_term(&translator.term(&quantified).unwrap()).is_err());
+    assert!(translator
+        .term(&smt2parser::parse_term("(g x)").unwrap())
+        .is_err());
+    assert!(translator
+        .term(&smt2parser::parse_term("(f x)").unwrap())
+        .is_err());
+}
+
+#[test]
+fn test_model_values() {
+    let context = Context::new(&z3::Config::new());
+    let solver = Solver::new(&context);
+    let mut translator = Translator::new(&context);
+    let commands = smt2parser::parse_commands(
+        "(declare-fun x () Int) (declare-fun r () Real) (declare-fun b () Bool) \
+         (assert (= x (- 3))) (assert (= r (/ 1.0 2.0))) (assert b)",
+    )
+    .unwrap();
+    translator.add_commands(&solver, &commands).unwrap();
+    assert_eq!(solver.check(), z3::SatResult::Sat);
+    let values = translator
+        .model_values(&solver.get_model().unwrap())
+        .unwrap()
+        .into_iter()
+        .map(|(name, value)| format!("{} {}", name, value))
+        .collect::<Vec<_>>();
+    assert_eq!(values, vec!["b true", "r (/ 1.0 2.0)", "x (- 3)"]);
+}
@@ -0,0 +1,87 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Timings of `VMTModel::unroll` on deep and wide transition relations, e.g. with
+//! `cargo bench -p smt2parser --bench unroll`. The time per node should not grow with the
+//! size of the transition relation.
+
+use smt2parser::vmt::VMTModel;
+use std::time::Instant;
+
+const STEPS: u8 = 5;
+const RUNS: u32 = 50;
+
+// A model with the state variables `x`, `y`, and `v0` to `v{variables - 1}`.
+fn model(transition: &str, variables: usize) -> VMTModel {
+    let mut input = String::new();
+    let names = vec!["x".to_string(), "y".to_string()]
+        .into_iter()
+        .chain((0..variables).map(|i| format!("v{}", i)));
+    for name in names {
+        input += &format!(
+            "(declare-fun {0} () Int)\n\
+             (declare-fun {0}_next () Int)\n\
+             (define-fun .{0} () Int (! {0} :next {0}_next))\n",
+            name
+        );
+    }
+    input += &format!(
+        "(define-fun init () Bool (! (= x 0) :init true))\n\
+         (define-fun trans () Bool (! {} :trans true))\n\
+         (define-fun property () Bool (! (>= x 0) :invar-property 0))\n",
+        transition
+    );
+    VMTModel::checked_from(smt2parser::parse_commands(&input).unwrap()).unwrap()
+}
+
+// `(= x_next (+ (+ ... x 1) 1))` with `depth` additions.
+fn deep(depth: usize) -> String {
+    format!("(= x_next {}x{})", "(+ ".repeat(depth), " 1)".repeat(depth))
+}
+
+// A conjunction of `width` constraints, half of them without state variables.
+fn wide(width: usize) -> String {
+    let conjuncts = (0..width)
+        .map(|i| match i % 2 {
+            0 => format!("(>= (+ x_next y {}) (* x {}))", i, i),
+            _ => format!("(forall ((z Int)) (>= (* z z) (- {})))", i),
+        })
+        .collect::<Vec<_>>();
+    format!("(and (= y_next y) {})", conjuncts.join(" "))
+}
+
+// Increments of `variables` state variables.
+fn increments(variables: usize) -> String {
+    let conjuncts = (0..variables)
+        .map(|i| format!("(= v{0}_next (+ v{0} 1))", i))
+        .collect::<Vec<_>>();
+    format!("(and (= x_next x) (= y_next y) {})", conjuncts.join(" "))
+}
+
+fn main() {
+    let cases = [
+        ("depth 100", deep(100), 100),
+        ("depth 200", deep(200), 200),
+        ("depth 400", deep(400), 400),
+        ("width 2000", wide(2000), 2000),
+        ("width 8000", wide(8000), 8000),
+        ("vars 500", increments(500), 500),
+        ("vars 2000", increments(2000), 2000),
+    ];
+    for (name, transition, size) in cases {
+        let variables = if name.starts_with("vars") { size } else { 0 };
+        let vmt = model(&transition, variables);
+        let start = Instant::now();
+        for _ in 0..RUNS {
+            vmt.unroll(STEPS);
+        }
+        let elapsed = start.elapsed() / RUNS;
+        println!(
+            "unroll({}) {:>10}: {:>10.3?} ({:.3?} per node)",
+            STEPS,
+            name,
+            elapsed,
+            elapsed / size as u32
+        );
+    }
+}
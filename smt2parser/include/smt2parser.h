@@ -0,0 +1,51 @@
+/*
+ * Copyright (c) Facebook, Inc. and its affiliates
+ * SPDX-License-Identifier: MIT OR Apache-2.0
+ *
+ * C bindings for the VMT front end of smt2parser, built with
+ * `cargo rustc --lib --features ffi --crate-type cdylib`.
+ *
+ * Models are opaque handles owned by the caller. Strings returned by the library are
+ * owned by the caller and must be released with `smt2_string_free`. On failure,
+ * functions return NULL and, if `error` is not NULL, store an error message in `*error`.
+ */
+
+#ifndef SMT2PARSER_H
+#define SMT2PARSER_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Opaque handle on a VMT model. */
+typedef struct smt2_vmt_model smt2_vmt_model;
+
+/* Parse a VMT model from NUL-terminated SMT-LIB text. */
+smt2_vmt_model *smt2_vmt_parse(const char *text, char **error);
+
+/* Release a VMT model. Does nothing if `model` is NULL. */
+void smt2_vmt_free(smt2_vmt_model *model);
+
+/* Print a VMT model as SMT-LIB text. */
+char *smt2_vmt_to_string(const smt2_vmt_model *model);
+
+/* SMT-LIB script checking the property of a VMT model up to the given bound, without
+ * `check-sat`. */
+char *smt2_vmt_unroll(const smt2_vmt_model *model, uint8_t bound);
+
+/* Abstract the arrays of a VMT model with uninterpreted functions. Return a new model
+ * and, if `axioms` is not NULL, store the array axioms in `*axioms` as SMT-LIB
+ * assertions, one per line. */
+smt2_vmt_model *smt2_vmt_abstract_arrays(const smt2_vmt_model *model, char **axioms,
+                                         char **error);
+
+/* Release a string returned by the library. Does nothing if `s` is NULL. */
+void smt2_string_free(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SMT2PARSER_H */
@@ -0,0 +1,414 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Abstraction of the theory of arrays by uninterpreted sorts and functions.
+
+use std::collections::HashMap;
+
+use crate::{
+    concrete::{
+        AttributeValue, Command, Error, Keyword, QualIdentifier, SExpr, Sort, Symbol,
+        SyntaxBuilder, Term,
+    },
+    inlining::SortExpander,
+    rewriter::Rewriter,
+    visitors::{FunctionDec, Identifier},
+    Numeral,
+};
+
+/// Rewriter replacing each array sort `(Array A B)` by an uninterpreted sort
+/// `Array_A_B`, and the operations `select`, `store`, and `(as const (Array A B))` on
+/// such arrays by applications of the uninterpreted functions `Read_A_B`, `Write_A_B`,
+/// and `Const_A_B`.
+///
+/// Sorts of arrays are inferred from the declarations of constants and 0-ary
+/// functions. Operations on arrays bound by quantifiers or `let` are not abstracted.
+#[derive(Debug, Default)]
+pub struct ArrayAbstractor {
+    visitor: SyntaxBuilder,
+    // Sorts of the constants declared so far.
+    symbol_sorts: HashMap<String, String>,
+    // Index and value sorts of the abstract array sorts.
+    array_sorts: HashMap<String, (Sort, Sort)>,
+    // Abstract array sorts introduced but not declared yet.
+    new_sorts: Vec<String>,
+}
+
+impl ArrayAbstractor {
+    /// Abstract a list of commands. The declarations of the new sorts and functions
+    /// are inserted before the first command that uses them. Sort definitions are
+    /// expanded first so that arrays are recognized behind aliases.
+    pub fn abstract_commands(commands: Vec<Command>) -> Result<Vec<Command>, Error> {
+        let commands = SortExpander::expand_commands(commands)?;
+        let mut abstractor = Self::default();
+        let mut result = Vec::new();
+        for command in commands {
+            let command = command.accept(&mut abstractor)?;
+            for name in std::mem::take(&mut abstractor.new_sorts) {
+                result.extend(abstractor.declarations(&name));
+            }
+            abstractor.declare(&command);
+            result.push(command);
+        }
+        Ok(result)
+    }
+
+    // Declarations of an abstract array sort and its functions.
+    fn declarations(&self, name: &str) -> Vec<Command> {
+        let (index, value) = self.array_sorts[name].clone();
+        let array = simple_sort(name);
+        vec![
+            Command::DeclareSort {
+                symbol: Symbol(name.to_string()),
+                arity: Numeral::from(0u32),
+            },
+            Command::DeclareFun {
+                symbol: Symbol(read_function(name)),
+                parameters: vec![array.clone(), index.clone()],
+                sort: value.clone(),
+            },
+            Command::DeclareFun {
+                symbol: Symbol(write_function(name)),
+                parameters: vec![array.clone(), index, value.clone()],
+                sort: array.clone(),
+            },
+            Command::DeclareFun {
+                symbol: Symbol(const_function(name)),
+                parameters: vec![value],
+                sort: array,
+            },
+        ]
+    }
+
+    // Record the sort of a declared constant.
+    fn declare(&mut self, command: &Command) {
+        let (symbol, sort) = match command {
+            Command::DeclareConst { symbol, sort } => (symbol, sort),
+            Command::DeclareFun {
+                symbol,
+                parameters,
+                sort,
+            } if parameters.is_empty() => (symbol, sort),
+            Command::DefineFun {
+                sig:
+                    FunctionDec {
+                        name,
+                        parameters,
+                        result,
+                    },
+                ..
+            } if parameters.is_empty() => (name, result),
+            _ => return,
+        };
+        if let Sort::Simple {
+            identifier: Identifier::Simple { symbol: sort },
+        } = sort
+        {
+            self.symbol_sorts.insert(symbol.0.clone(), sort.0.clone());
+        }
+    }
+
+    // Abstract array sort of a term, if known.
+    fn array_sort(&self, term: &Term) -> Option<String> {
+        let name = match term {
+            Term::QualIdentifier(QualIdentifier::Simple {
+                identifier: Identifier::Simple { symbol },
+            }) => self.symbol_sorts.get(&symbol.0)?.clone(),
+            Term::Application {
+                qual_identifier:
+                    QualIdentifier::Simple {
+                        identifier: Identifier::Simple { symbol },
+                    },
+                arguments,
+            } => match symbol.0.as_str() {
+                "ite" => return self.array_sort(arguments.get(1)?),
+                s => format!(
+                    "Array_{}",
+                    s.strip_prefix("Write_")
+                        .or_else(|| s.strip_prefix("Const_"))?
+                ),
+            },
+            Term::Attributes { term, .. } => return self.array_sort(term),
+            _ => return None,
+        };
+        if self.array_sorts.contains_key(&name) {
+            Some(name)
+        } else {
+            None
+        }
+    }
+}
+
+/// Quantified axioms of the theory of arrays for each abstract array sort declared in
+/// the given (abstracted) commands: reading at the index of a write, reading at
+/// another index, and reading a constant array. Extensionality is not included.
+///
+/// Each axiom is named with a `:qid` starting with `array_`, so that its instances
+/// can be identified in Z3 traces.
+pub fn array_axioms(commands: &[Command]) -> Vec<Command> {
+    let mut axioms = Vec::new();
+    for command in commands {
+        if let Command::DeclareFun {
+            symbol,
+            parameters,
+            sort: value,
+        } = command
+        {
+            let name = match symbol.0.strip_prefix("Read_") {
+                Some(name) if parameters.len() == 2 => name,
+                _ => continue,
+            };
+            let (array, index) = (&parameters[0], &parameters[1]);
+            log::debug!("Adding the array axioms of {}", array);
+            let read = |array, index| application(format!("Read_{}", name), vec![array, index]);
+            let write = || {
+                let arguments = vec![variable("a"), variable("i"), variable("v")];
+                application(format!("Write_{}", name), arguments)
+            };
+            let constant = || application(format!("Const_{}", name), vec![variable("v")]);
+            axioms.push(axiom(
+                &[("a", array), ("i", index), ("v", value)],
+                equal(read(write(), variable("i")), variable("v")),
+                write(),
+                format!("array_read_write_{}", name),
+            ));
+            axioms.push(axiom(
+                &[("a", array), ("i", index), ("j", index), ("v", value)],
+                application(
+                    "or".to_string(),
+                    vec![
+                        equal(variable("i"), variable("j")),
+                        equal(
+                            read(write(), variable("j")),
+                            read(variable("a"), variable("j")),
+                        ),
+                    ],
+                ),
+                read(write(), variable("j")),
+                format!("array_read_other_{}", name),
+            ));
+            axioms.push(axiom(
+                &[("i", index), ("v", value)],
+                equal(read(constant(), variable("i")), variable("v")),
+                read(constant(), variable("i")),
+                format!("array_read_const_{}", name),
+            ));
+        }
+    }
+    axioms
+}
+
+// The assertion of a universally quantified axiom with a single trigger.
+fn axiom(vars: &[(&str, &Sort)], body: Term, trigger: Term, qid: String) -> Command {
+    let attributes = vec![
+        (
+            Keyword("pattern".to_string()),
+            AttributeValue::SExpr(vec![pattern(&trigger)]),
+        ),
+        (
+            Keyword("qid".to_string()),
+            AttributeValue::Symbol(Symbol(qid)),
+        ),
+    ];
+    Command::Assert {
+        term: Term::Forall {
+            vars: vars
+                .iter()
+                .map(|(name, sort)| (Symbol(name.to_string()), (*sort).clone()))
+                .collect(),
+            term: Box::new(Term::Attributes {
+                term: Box::new(body),
+                attributes,
+            }),
+        },
+    }
+}
+
+// The s-expression of a trigger made of variables and applications.
+fn pattern(term: &Term) -> SExpr {
+    match term {
+        Term::QualIdentifier(QualIdentifier::Simple {
+            identifier: Identifier::Simple { symbol },
+        }) => SExpr::Symbol(symbol.clone()),
+        Term::Application {
+            qual_identifier:
+                QualIdentifier::Simple {
+                    identifier: Identifier::Simple { symbol },
+                },
+            arguments,
+        } => SExpr::Application(
+            std::iter::once(SExpr::Symbol(symbol.clone()))
+                .chain(arguments.iter().map(pattern))
+                .collect(),
+        ),
+        _ => unreachable!("Triggers of array axioms are applications of symbols"),
+    }
+}
+
+fn variable(name: &str) -> Term {
+    Term::QualIdentifier(QualIdentifier::Simple {
+        identifier: Identifier::Simple {
+            symbol: Symbol(name.to_string()),
+        },
+    })
+}
+
+fn application(name: String, arguments: Vec<Term>) -> Term {
+    Term::Application {
+        qual_identifier: QualIdentifier::Simple {
+            identifier: Identifier::Simple {
+                symbol: Symbol(name),
+            },
+        },
+        arguments,
+    }
+}
+
+fn equal(left: Term, right: Term) -> Term {
+    application("=".to_string(), vec![left, right])
+}
+
+fn simple_sort(name: &str) -> Sort {
+    Sort::Simple {
+        identifier: Identifier::Simple {
+            symbol: Symbol(name.to_string()),
+        },
+    }
+}
+
+fn read_function(sort_name: &str) -> String {
+    format!("Read_{}", &sort_name["Array_".len()..])
+}
+
+fn write_function(sort_name: &str) -> String {
+    format!("Write_{}", &sort_name["Array_".len()..])
+}
+
+fn const_function(sort_name: &str) -> String {
+    format!("Const_{}", &sort_name["Array_".len()..])
+}
+
+impl Rewriter for ArrayAbstractor {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn process_sort(&mut self, value: Sort) -> Result<Sort, Self::Error> {
+        match value {
+            Sort::Parameterized {
+                identifier: Identifier::Simple { symbol },
+                mut parameters,
+            } if symbol.0 == "Array" && parameters.len() == 2 => {
+                let value = parameters.pop().unwrap();
+                let index = parameters.pop().unwrap();
+                let name = format!("Array_{}_{}", index, value);
+                if !self.array_sorts.contains_key(&name) {
+                    log::debug!("Abstracting (Array {} {}) as {}", index, value, name);
+                    self.array_sorts.insert(name.clone(), (index, value));
+                    self.new_sorts.push(name.clone());
+                }
+                Ok(simple_sort(&name))
+            }
+            value => Ok(value),
+        }
+    }
+
+    fn process_term(&mut self, value: Term) -> Result<Term, Self::Error> {
+        match value {
+            Term::Application {
+                qual_identifier:
+                    QualIdentifier::Simple {
+                        identifier: Identifier::Simple { symbol },
+                    },
+                arguments,
+            } if (symbol.0 == "select" && arguments.len() == 2)
+                || (symbol.0 == "store" && arguments.len() == 3) =>
+            {
+                let function = match self.array_sort(&arguments[0]) {
+                    Some(name) if symbol.0 == "select" => read_function(&name),
+                    Some(name) => write_function(&name),
+                    None => symbol.0,
+                };
+                Ok(Term::Application {
+                    qual_identifier: QualIdentifier::Simple {
+                        identifier: Identifier::Simple {
+                            symbol: Symbol(function),
+                        },
+                    },
+                    arguments,
+                })
+            }
+            Term::Application {
+                qual_identifier:
+                    QualIdentifier::Sorted {
+                        identifier: Identifier::Simple { symbol },
+                        sort:
+                            Sort::Simple {
+                                identifier: Identifier::Simple { symbol: sort },
+                            },
+                    },
+                arguments,
+            } if symbol.0 == "const"
+                && arguments.len() == 1
+                && self.array_sorts.contains_key(&sort.0) =>
+            {
+                Ok(Term::Application {
+                    qual_identifier: QualIdentifier::Simple {
+                        identifier: Identifier::Simple {
+                            symbol: Symbol(const_function(&sort.0)),
+                        },
+                    },
+                    arguments,
+                })
+            }
+            value => Ok(value),
+        }
+    }
+}
+
+#[test]
+fn test_array_abstraction() {
+    let input = r#"
+(declare-sort client 0)
+(declare-fun c () client)
+(declare-fun a () (Array client Bool))
+(declare-fun b () (Array client Bool))
+(assert (= b (store a c true)))
+(assert (select (ite (select a c) a (store b c false)) c))
+(assert (= a ((as const (Array client Bool)) false)))
+"#;
+    let commands = crate::parse_commands(input).unwrap();
+    let commands = ArrayAbstractor::abstract_commands(commands).unwrap();
+    assert_eq!(
+        commands
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>(),
+        vec![
+            "(declare-sort client 0)",
+            "(declare-fun c () client)",
+            "(declare-sort Array_client_Bool 0)",
+            "(declare-fun Read_client_Bool (Array_client_Bool client) Bool)",
+            "(declare-fun Write_client_Bool (Array_client_Bool client Bool) Array_client_Bool)",
+            "(declare-fun Const_client_Bool (Bool) Array_client_Bool)",
+            "(declare-fun a () Array_client_Bool)",
+            "(declare-fun b () Array_client_Bool)",
+            "(assert (= b (Write_client_Bool a c true)))",
+            "(assert (Read_client_Bool (ite (Read_client_Bool a c) a (Write_client_Bool b c false)) c))",
+            "(assert (= a (Const_client_Bool false)))",
+        ]
+    );
+
+    let axioms = array_axioms(&commands);
+    assert_eq!(axioms.len(), 3);
+    assert_eq!(
+        axioms[0].to_string(),
+        "(assert (forall ((a Array_client_Bool) (i client) (v Bool)) (! (= (Read_client_Bool (Write_client_Bool a i v) i) v) :pattern ((Write_client_Bool a i v)) :qid array_read_write_client_Bool)))"
+    );
+    // The axioms are the same as when parsed.
+    let printed = axioms.iter().map(|c| c.to_string()).collect::<String>();
+    assert_eq!(crate::parse_commands(&printed).unwrap(), axioms);
+}
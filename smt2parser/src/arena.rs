@@ -0,0 +1,517 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Syntax whose terms are built in a [`TermPool`]. Terms are indices in the pool instead
+//! of boxed trees, so that parsing and rewriting large benchmarks do not allocate each
+//! node separately and equal subterms are stored once. Owned terms and commands are
+//! rebuilt from the pool when needed, e.g. to print them or to pass them to other APIs.
+
+use crate::{
+    concrete::{
+        self, Constant, Error, Keyword, QualIdentifier, SExpr, Sort, Symbol, SyntaxBuilder,
+    },
+    hashcons::{TermId, TermNode, TermPool},
+    visitors::{
+        AttributeValue, CommandVisitor, ConstantVisitor, DatatypeDec, FunctionDec, Identifier,
+        KeywordVisitor, QualIdentifierVisitor, SExprVisitor, Smt2Visitor, SortVisitor, SymbolKind,
+        SymbolVisitor, TermVisitor,
+    },
+    Binary, Decimal, Hexadecimal, Numeral,
+};
+
+pub type Command = concrete::Command<TermId>;
+
+/// An implementation of [`Smt2Visitor`] that adds the terms that it builds to a
+/// [`TermPool`]. Scripts are parsed into the pool by passing this visitor to
+/// `CommandStream`, and rewriters build their terms in the pool when they delegate to
+/// this visitor. Concrete terms are added to the pool with `term.accept_ref(&mut builder)`.
+#[derive(Debug, Default, Clone)]
+pub struct ArenaSyntaxBuilder {
+    pool: TermPool,
+}
+
+impl ArenaSyntaxBuilder {
+    pub fn new(pool: TermPool) -> Self {
+        Self { pool }
+    }
+
+    /// The pool of the terms built so far.
+    pub fn pool(&self) -> &TermPool {
+        &self.pool
+    }
+
+    pub fn into_pool(self) -> TermPool {
+        self.pool
+    }
+
+    /// Rebuild the owned term of an index.
+    pub fn to_term(&self, id: TermId) -> concrete::Term {
+        self.pool.to_term(id)
+    }
+
+    /// Rebuild the owned command of a command whose terms are in the pool.
+    pub fn to_command(&self, command: Command) -> concrete::Command {
+        use concrete::Command as C;
+        match command {
+            C::Assert { term } => C::Assert {
+                term: self.to_term(term),
+            },
+            C::CheckSat => C::CheckSat,
+            C::CheckSatAssuming { literals } => C::CheckSatAssuming { literals },
+            C::DeclareConst { symbol, sort } => C::DeclareConst { symbol, sort },
+            C::DeclareDatatype { symbol, datatype } => C::DeclareDatatype { symbol, datatype },
+            C::DeclareDatatypes { datatypes } => C::DeclareDatatypes { datatypes },
+            C::DeclareFun {
+                symbol,
+                parameters,
+                sort,
+            } => C::DeclareFun {
+                symbol,
+                parameters,
+                sort,
+            },
+            C::DeclareSort { symbol, arity } => C::DeclareSort { symbol, arity },
+            C::DefineFun { sig, term } => C::DefineFun {
+                sig,
+                term: self.to_term(term),
+            },
+            C::DefineFunRec { sig, term } => C::DefineFunRec {
+                sig,
+                term: self.to_term(term),
+            },
+            C::DefineFunsRec { funs } => C::DefineFunsRec {
+                funs: funs
+                    .into_iter()
+                    .map(|(sig, term)| (sig, self.to_term(term)))
+                    .collect(),
+            },
+            C::DefineSort {
+                symbol,
+                parameters,
+                sort,
+            } => C::DefineSort {
+                symbol,
+                parameters,
+                sort,
+            },
+            C::Echo { message } => C::Echo { message },
+            C::Exit => C::Exit,
+            C::GetAssertions => C::GetAssertions,
+            C::GetAssignment => C::GetAssignment,
+            C::GetInfo { flag } => C::GetInfo { flag },
+            C::GetModel => C::GetModel,
+            C::GetOption { keyword } => C::GetOption { keyword },
+            C::GetProof => C::GetProof,
+            C::GetUnsatAssumptions => C::GetUnsatAssumptions,
+            C::GetUnsatCore => C::GetUnsatCore,
+            C::GetValue { terms } => C::GetValue {
+                terms: terms.into_iter().map(|term| self.to_term(term)).collect(),
+            },
+            C::Pop { level } => C::Pop { level },
+            C::Push { level } => C::Push { level },
+            C::Reset => C::Reset,
+            C::ResetAssertions => C::ResetAssertions,
+            C::SetInfo { keyword, value } => C::SetInfo { keyword, value },
+            C::SetLogic { symbol } => C::SetLogic { symbol },
+            C::SetOption { keyword, value } => C::SetOption { keyword, value },
+        }
+    }
+}
+
+impl ConstantVisitor for ArenaSyntaxBuilder {
+    type T = Constant;
+    type E = Error;
+
+    fn visit_numeral_constant(&mut self, value: Numeral) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_numeral_constant(value)
+    }
+    fn visit_decimal_constant(&mut self, value: Decimal) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_decimal_constant(value)
+    }
+    fn visit_hexadecimal_constant(&mut self, value: Hexadecimal) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_hexadecimal_constant(value)
+    }
+    fn visit_binary_constant(&mut self, value: Binary) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_binary_constant(value)
+    }
+    fn visit_string_constant(&mut self, value: String) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_string_constant(value)
+    }
+}
+
+impl SymbolVisitor for ArenaSyntaxBuilder {
+    type T = Symbol;
+    type E = Error;
+
+    fn visit_fresh_symbol(&mut self, value: String, kind: SymbolKind) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_fresh_symbol(value, kind)
+    }
+}
+
+impl KeywordVisitor for ArenaSyntaxBuilder {
+    type T = Keyword;
+    type E = Error;
+
+    fn visit_keyword(&mut self, value: String) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_keyword(value)
+    }
+}
+
+impl SExprVisitor<Constant, Symbol, Keyword> for ArenaSyntaxBuilder {
+    type T = SExpr;
+    type E = Error;
+
+    fn visit_constant_s_expr(&mut self, value: Constant) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_constant_s_expr(value)
+    }
+
+    fn visit_symbol_s_expr(&mut self, value: Symbol) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_symbol_s_expr(value)
+    }
+
+    fn visit_keyword_s_expr(&mut self, value: Keyword) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_keyword_s_expr(value)
+    }
+
+    fn visit_application_s_expr(&mut self, values: Vec<Self::T>) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_application_s_expr(values)
+    }
+}
+
+impl SortVisitor<Symbol> for ArenaSyntaxBuilder {
+    type T = Sort;
+    type E = Error;
+
+    fn visit_simple_sort(&mut self, identifier: Identifier) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_simple_sort(identifier)
+    }
+
+    fn visit_parameterized_sort(
+        &mut self,
+        identifier: Identifier,
+        parameters: Vec<Self::T>,
+    ) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_parameterized_sort(identifier, parameters)
+    }
+}
+
+impl QualIdentifierVisitor<Identifier, Sort> for ArenaSyntaxBuilder {
+    type T = QualIdentifier;
+    type E = Error;
+
+    fn visit_simple_identifier(&mut self, identifier: Identifier) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_simple_identifier(identifier)
+    }
+
+    fn visit_sorted_identifier(
+        &mut self,
+        identifier: Identifier,
+        sort: Sort,
+    ) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_sorted_identifier(identifier, sort)
+    }
+}
+
+impl TermVisitor<Constant, QualIdentifier, Keyword, SExpr, Symbol, Sort> for ArenaSyntaxBuilder {
+    type T = TermId;
+    type E = Error;
+
+    fn visit_constant(&mut self, constant: Constant) -> Result<Self::T, Self::E> {
+        Ok(self.pool.add(TermNode::Constant(constant)))
+    }
+
+    fn visit_qual_identifier(
+        &mut self,
+        qual_identifier: QualIdentifier,
+    ) -> Result<Self::T, Self::E> {
+        Ok(self.pool.add(TermNode::QualIdentifier(qual_identifier)))
+    }
+
+    fn visit_application(
+        &mut self,
+        qual_identifier: QualIdentifier,
+        arguments: Vec<Self::T>,
+    ) -> Result<Self::T, Self::E> {
+        Ok(self.pool.add(TermNode::Application {
+            qual_identifier,
+            arguments,
+        }))
+    }
+
+    fn visit_let(
+        &mut self,
+        var_bindings: Vec<(Symbol, Self::T)>,
+        term: Self::T,
+    ) -> Result<Self::T, Self::E> {
+        Ok(self.pool.add(TermNode::Let { var_bindings, term }))
+    }
+
+    fn visit_forall(
+        &mut self,
+        vars: Vec<(Symbol, Sort)>,
+        term: Self::T,
+    ) -> Result<Self::T, Self::E> {
+        Ok(self.pool.add(TermNode::Forall { vars, term }))
+    }
+
+    fn visit_exists(
+        &mut self,
+        vars: Vec<(Symbol, Sort)>,
+        term: Self::T,
+    ) -> Result<Self::T, Self::E> {
+        Ok(self.pool.add(TermNode::Exists { vars, term }))
+    }
+
+    fn visit_match(
+        &mut self,
+        term: Self::T,
+        cases: Vec<(Vec<Symbol>, Self::T)>,
+    ) -> Result<Self::T, Self::E> {
+        Ok(self.pool.add(TermNode::Match { term, cases }))
+    }
+
+    fn visit_attributes(
+        &mut self,
+        term: Self::T,
+        attributes: Vec<(Keyword, AttributeValue<Constant, Symbol, SExpr>)>,
+    ) -> Result<Self::T, Self::E> {
+        Ok(self.pool.add(TermNode::Attributes { term, attributes }))
+    }
+}
+
+impl CommandVisitor<TermId, Symbol, Sort, Keyword, Constant, SExpr> for ArenaSyntaxBuilder {
+    type T = Command;
+    type E = Error;
+
+    fn visit_assert(&mut self, term: TermId) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_assert(term)
+    }
+
+    fn visit_check_sat(&mut self) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_check_sat()
+    }
+
+    fn visit_check_sat_assuming(
+        &mut self,
+        literals: Vec<(Symbol, bool)>,
+    ) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_check_sat_assuming(literals)
+    }
+
+    fn visit_declare_const(&mut self, symbol: Symbol, sort: Sort) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_declare_const(symbol, sort)
+    }
+
+    fn visit_declare_datatype(
+        &mut self,
+        symbol: Symbol,
+        datatype: DatatypeDec<Symbol, Sort>,
+    ) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_declare_datatype(symbol, datatype)
+    }
+
+    fn visit_declare_datatypes(
+        &mut self,
+        datatypes: Vec<(Symbol, Numeral, DatatypeDec<Symbol, Sort>)>,
+    ) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_declare_datatypes(datatypes)
+    }
+
+    fn visit_declare_fun(
+        &mut self,
+        symbol: Symbol,
+        parameters: Vec<Sort>,
+        sort: Sort,
+    ) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_declare_fun(symbol, parameters, sort)
+    }
+
+    fn visit_declare_sort(&mut self, symbol: Symbol, arity: Numeral) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_declare_sort(symbol, arity)
+    }
+
+    fn visit_define_fun(
+        &mut self,
+        sig: FunctionDec<Symbol, Sort>,
+        term: TermId,
+    ) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_define_fun(sig, term)
+    }
+
+    fn visit_define_fun_rec(
+        &mut self,
+        sig: FunctionDec<Symbol, Sort>,
+        term: TermId,
+    ) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_define_fun_rec(sig, term)
+    }
+
+    fn visit_define_funs_rec(
+        &mut self,
+        funs: Vec<(FunctionDec<Symbol, Sort>, TermId)>,
+    ) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_define_funs_rec(funs)
+    }
+
+    fn visit_define_sort(
+        &mut self,
+        symbol: Symbol,
+        parameters: Vec<Symbol>,
+        sort: Sort,
+    ) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_define_sort(symbol, parameters, sort)
+    }
+
+    fn visit_echo(&mut self, message: String) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_echo(message)
+    }
+
+    fn visit_exit(&mut self) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_exit()
+    }
+
+    fn visit_get_assertions(&mut self) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_get_assertions()
+    }
+
+    fn visit_get_assignment(&mut self) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_get_assignment()
+    }
+
+    fn visit_get_info(&mut self, flag: Keyword) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_get_info(flag)
+    }
+
+    fn visit_get_model(&mut self) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_get_model()
+    }
+
+    fn visit_get_option(&mut self, keyword: Keyword) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_get_option(keyword)
+    }
+
+    fn visit_get_proof(&mut self) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_get_proof()
+    }
+
+    fn visit_get_unsat_assumptions(&mut self) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_get_unsat_assumptions()
+    }
+
+    fn visit_get_unsat_core(&mut self) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_get_unsat_core()
+    }
+
+    fn visit_get_value(&mut self, terms: Vec<TermId>) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_get_value(terms)
+    }
+
+    fn visit_pop(&mut self, level: Numeral) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_pop(level)
+    }
+
+    fn visit_push(&mut self, level: Numeral) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_push(level)
+    }
+
+    fn visit_reset(&mut self) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_reset()
+    }
+
+    fn visit_reset_assertions(&mut self) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_reset_assertions()
+    }
+
+    fn visit_set_info(
+        &mut self,
+        keyword: Keyword,
+        value: AttributeValue<Constant, Symbol, SExpr>,
+    ) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_set_info(keyword, value)
+    }
+
+    fn visit_set_logic(&mut self, symbol: Symbol) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_set_logic(symbol)
+    }
+
+    fn visit_set_option(
+        &mut self,
+        keyword: Keyword,
+        value: AttributeValue<Constant, Symbol, SExpr>,
+    ) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_set_option(keyword, value)
+    }
+}
+
+impl Smt2Visitor for ArenaSyntaxBuilder {
+    type Error = Error;
+    type Constant = Constant;
+    type QualIdentifier = QualIdentifier;
+    type Keyword = Keyword;
+    type Sort = Sort;
+    type SExpr = SExpr;
+    type Symbol = Symbol;
+    type Term = TermId;
+    type Command = Command;
+
+    fn syntax_error(&mut self, position: crate::Position, s: String) -> Self::Error {
+        Error::SyntaxError(position, s)
+    }
+
+    fn parsing_error(&mut self, position: crate::Position, s: String) -> Self::Error {
+        Error::ParsingError(position, s)
+    }
+}
+
+#[test]
+fn test_arena_syntax_builder() {
+    use crate::rewriter::Rewriter;
+
+    let input = br#"
+(declare-fun x () Int)
+(assert (and (= (f x) (g (f x))) (forall ((y Int)) (! (= (f x) y) :pattern ((f y))))))
+(assert (= (f x) (g (f x))))
+"#;
+    let mut stream = crate::CommandStream::new(&input[..], ArenaSyntaxBuilder::default(), None);
+    let commands = (&mut stream).collect::<Result<Vec<_>, _>>().unwrap();
+    let builder = stream.into_visitor();
+    // x, (f x), (g (f x)), the equality, y, (= (f x) y), the attributed term, the
+    // quantifier, and the conjunction. Patterns are s-expressions, not terms.
+    assert_eq!(builder.pool().len(), 9);
+    let commands = commands
+        .into_iter()
+        .map(|command| builder.to_command(command))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        commands,
+        crate::parse_commands(std::str::from_utf8(input).unwrap()).unwrap()
+    );
+
+    // Rewrite `x` into `z` in the pool.
+    struct Renamer(ArenaSyntaxBuilder);
+
+    impl Rewriter for Renamer {
+        type V = ArenaSyntaxBuilder;
+        type Error = Error;
+
+        fn visitor(&mut self) -> &mut Self::V {
+            &mut self.0
+        }
+
+        fn process_symbol(&mut self, value: Symbol) -> Result<Symbol, Self::Error> {
+            match value.0.as_str() {
+                "x" => Ok(Symbol("z".to_string())),
+                _ => Ok(value),
+            }
+        }
+    }
+
+    let mut renamer = Renamer(builder);
+    let term = match &commands[2] {
+        concrete::Command::Assert { term } => term,
+        _ => unreachable!(),
+    };
+    let id = term.accept_ref(&mut renamer).unwrap();
+    assert_eq!(renamer.0.to_term(id).to_string(), "(= (f z) (g (f z)))");
+    assert_eq!(renamer.0.pool().len(), 13);
+}
@@ -0,0 +1,121 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Binary caching of parsed scripts, so that large benchmarks are lexed and parsed only
+//! once across runs.
+
+use crate::concrete::Command;
+use std::{
+    fs,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+/// Version of the binary format. It must be increased whenever the concrete syntax
+/// changes, so that stale caches are rejected instead of misread.
+pub const FORMAT_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 8] = b"SMT2BIN\0";
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Write commands in binary form, after a header made of a magic number and the
+/// format version.
+pub fn write_commands<W: Write>(mut writer: W, commands: &[Command]) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&FORMAT_VERSION.to_le_bytes())?;
+    bincode::serialize_into(&mut writer, commands).map_err(invalid_data)?;
+    writer.flush()
+}
+
+/// Read commands written by [`write_commands`]. Inputs with a different magic number or
+/// format version are rejected with an error of kind `InvalidData`.
+pub fn read_commands<R: Read>(mut reader: R) -> io::Result<Vec<Command>> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not a binary SMT2 script"));
+    }
+    let mut version = [0u8; 4];
+    reader.read_exact(&mut version)?;
+    let version = u32::from_le_bytes(version);
+    if version != FORMAT_VERSION {
+        return Err(invalid_data(format!(
+            "unsupported binary format version {} (expected {})",
+            version, FORMAT_VERSION
+        )));
+    }
+    bincode::deserialize_from(reader).map_err(invalid_data)
+}
+
+/// Save commands in a binary file.
+pub fn save_commands<P: AsRef<Path>>(path: P, commands: &[Command]) -> io::Result<()> {
+    write_commands(BufWriter::new(fs::File::create(path)?), commands)
+}
+
+/// Load commands from a binary file.
+pub fn load_commands<P: AsRef<Path>>(path: P) -> io::Result<Vec<Command>> {
+    read_commands(BufReader::new(fs::File::open(path)?))
+}
+
+/// Parse the script at `path`, going through the binary file at `cache`. The cache is
+/// used when it is at least as recent as the script and readable; otherwise the script
+/// is parsed and the cache is rewritten. Failing to write the cache is not an error,
+/// while parsing errors are reported with kind `InvalidData`.
+pub fn parse_file_cached<P, Q>(path: P, cache: Q) -> io::Result<Vec<Command>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let (path, cache) = (path.as_ref(), cache.as_ref());
+    let modified = |p: &Path| fs::metadata(p).and_then(|m| m.modified()).ok();
+    if let (Some(source), Some(cached)) = (modified(path), modified(cache)) {
+        if cached >= source {
+            match load_commands(cache) {
+                Ok(commands) => return Ok(commands),
+                Err(error) => log::debug!("Ignoring cache {}: {}", cache.display(), error),
+            }
+        }
+    }
+    let input = fs::read(path)?;
+    let commands = crate::parse_commands_parallel(
+        &input,
+        path.to_str().map(String::from),
+        std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+    )
+    .map_err(invalid_data)?;
+    if let Err(error) = save_commands(cache, &commands) {
+        log::warn!("Failed to write cache {}: {}", cache.display(), error);
+    }
+    Ok(commands)
+}
+
+#[test]
+fn test_binary_cache() {
+    let commands = crate::parse_commands(
+        r#"
+(set-logic QF_LIA)
+(declare-fun x () Int)
+(define-fun f ((y Int)) Int (+ y #x1F 2.5))
+(assert (! (forall ((z Int)) (=> (> z 0) (> (f z) x))) :named ax))
+(echo "done ""quoted""")
+(check-sat)
+"#,
+    )
+    .unwrap();
+    let mut bytes = Vec::new();
+    write_commands(&mut bytes, &commands).unwrap();
+    assert_eq!(&bytes[..8], MAGIC);
+    assert_eq!(read_commands(&bytes[..]).unwrap(), commands);
+
+    let mut stale = bytes.clone();
+    stale[8] = stale[8].wrapping_add(1);
+    let error = read_commands(&stale[..]).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    assert!(read_commands(&b"(check-sat)"[..]).is_err());
+}
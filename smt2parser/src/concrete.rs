@@ -16,7 +16,7 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 /// Concrete syntax for a constant.
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Constant {
     Numeral(Numeral),
     Decimal(Decimal),
@@ -36,7 +36,7 @@ pub struct Keyword(pub String);
 pub use crate::visitors::Identifier;
 
 /// Concrete syntax for an S-expression.
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum SExpr<Constant = self::Constant, Symbol = self::Symbol, Keyword = self::Keyword> {
     Constant(Constant),
     Symbol(Symbol),
@@ -47,7 +47,7 @@ pub enum SExpr<Constant = self::Constant, Symbol = self::Symbol, Keyword = self:
 pub use crate::visitors::{AttributeValue, DatatypeDec, FunctionDec};
 
 /// Concrete syntax for a sort.
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Sort<Identifier = self::Identifier> {
     Simple {
         identifier: Identifier,
@@ -59,14 +59,14 @@ pub enum Sort<Identifier = self::Identifier> {
 }
 
 /// Concrete syntax for a qualified-identifier.
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum QualIdentifier<Identifier = self::Identifier, Sort = self::Sort> {
     Simple { identifier: Identifier },
     Sorted { identifier: Identifier, sort: Sort },
 }
 
 /// Concrete syntax for a term.
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Term<
     Constant = self::Constant,
     QualIdentifier = self::QualIdentifier,
@@ -104,7 +104,7 @@ pub enum Term<
 }
 
 /// Concrete syntax for a command.
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Command<
     Term = self::Term,
     Symbol = self::Symbol,
@@ -503,6 +503,9 @@ impl Term {
             + TermVisitor<S4, S3, S5, S6, S1, S2, T = T, E = E>,
     {
         use Term::*;
+        if let Some(result) = visitor.skip_term(&self) {
+            return result;
+        }
         match self {
             Constant(value) => {
                 let c = value.accept(visitor)?;
@@ -628,6 +631,150 @@ impl Term {
             }
         }
     }
+
+    /// Visit a concrete term by reference, with the same calls to the visitor as
+    /// `self.clone().accept(visitor)`. Only the constants, symbols, sorts, and attributes
+    /// passed to the visitor are cloned, so subterms skipped by `skip_term` are not copied.
+    pub fn accept_ref<V, T, E, S1, S2, S3, S4, S5, S6>(&self, visitor: &mut V) -> Result<T, E>
+    where
+        V: SortVisitor<S1, T = S2, E = E>
+            + SymbolVisitor<T = S1, E = E>
+            + QualIdentifierVisitor<Identifier<S1>, S2, T = S3, E = E>
+            + ConstantVisitor<T = S4, E = E>
+            + KeywordVisitor<T = S5, E = E>
+            + SExprVisitor<S4, S1, S5, T = S6, E = E>
+            + TermVisitor<S4, S3, S5, S6, S1, S2, T = T, E = E>,
+    {
+        use Term::*;
+        if let Some(result) = visitor.skip_term(self) {
+            return result;
+        }
+        match self {
+            Constant(value) => {
+                let c = value.clone().accept(visitor)?;
+                visitor.visit_constant(c)
+            }
+            QualIdentifier(value) => {
+                let qi = value.clone().accept(visitor)?;
+                visitor.visit_qual_identifier(qi)
+            }
+            Application {
+                qual_identifier,
+                arguments,
+            } => {
+                let qi = qual_identifier.clone().accept(visitor)?;
+                let mut ts = Vec::new();
+                for t in arguments {
+                    ts.push(t.accept_ref(visitor)?);
+                }
+                visitor.visit_application(qi, ts)
+            }
+            Let { var_bindings, term } => {
+                let bs = var_bindings
+                    .iter()
+                    .map(|(s, t)| {
+                        Ok((
+                            visitor.visit_fresh_symbol(s.0.clone(), SymbolKind::Variable)?,
+                            t.accept_ref(visitor)?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, E>>()?;
+                bs.iter().for_each(|(s, _)| visitor.bind_symbol(s));
+                let t = term.accept_ref(visitor)?;
+                bs.iter().for_each(|(s, _)| visitor.unbind_symbol(s));
+                visitor.visit_let(bs, t)
+            }
+            Forall { vars, term } => {
+                let vs = vars
+                    .iter()
+                    .map(|(v, s)| {
+                        Ok((
+                            visitor.visit_fresh_symbol(v.0.clone(), SymbolKind::Variable)?,
+                            s.clone().accept(visitor)?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, E>>()?;
+                vs.iter().for_each(|(s, _)| visitor.bind_symbol(s));
+                let t = term.accept_ref(visitor)?;
+                vs.iter().for_each(|(s, _)| visitor.unbind_symbol(s));
+                visitor.visit_forall(vs, t)
+            }
+            Exists { vars, term } => {
+                let vs = vars
+                    .iter()
+                    .map(|(v, s)| {
+                        Ok((
+                            visitor.visit_fresh_symbol(v.0.clone(), SymbolKind::Variable)?,
+                            s.clone().accept(visitor)?,
+                        ))
+                    })
+                    .collect::<Result<Vec<_>, E>>()?;
+                vs.iter().for_each(|(s, _)| visitor.bind_symbol(s));
+                let t = term.accept_ref(visitor)?;
+                vs.iter().for_each(|(s, _)| visitor.unbind_symbol(s));
+                visitor.visit_exists(vs, t)
+            }
+            Match { term, cases } => {
+                let t = term.accept_ref(visitor)?;
+                let cs = cases
+                    .iter()
+                    .map(|(ss, t)| {
+                        let mut symbols = Vec::new();
+                        let mut ss = ss.iter();
+                        let mut has_fresh_first_symbol = false;
+                        if let Some(s) = ss.next() {
+                            // First symbol may be a constructor.
+                            symbols.push(visitor.visit_bound_symbol(s.0.clone()).or_else(
+                                |_| {
+                                    has_fresh_first_symbol = true;
+                                    let s = visitor
+                                        .visit_fresh_symbol(s.0.clone(), SymbolKind::Variable)?;
+                                    visitor.bind_symbol(&s);
+                                    Ok(s)
+                                },
+                            )?);
+                        }
+                        for s in ss {
+                            let s =
+                                visitor.visit_fresh_symbol(s.0.clone(), SymbolKind::Variable)?;
+                            visitor.bind_symbol(&s);
+                            symbols.push(s);
+                        }
+                        let term = t.accept_ref(visitor)?;
+                        let mut ss = symbols.iter();
+                        if let Some(s) = ss.next() {
+                            if has_fresh_first_symbol {
+                                visitor.unbind_symbol(s);
+                            }
+                        }
+                        for s in ss {
+                            visitor.unbind_symbol(s);
+                        }
+                        Ok((symbols, term))
+                    })
+                    .collect::<Result<_, E>>()?;
+                visitor.visit_match(t, cs)
+            }
+            Attributes { term, attributes } => {
+                let t = term.accept_ref(visitor)?;
+                let xs = attributes
+                    .iter()
+                    .map(|(k, x)| {
+                        Ok((
+                            k.clone().accept(visitor)?,
+                            x.clone().remap(
+                                visitor,
+                                |v, c: self::Constant| c.accept(v),
+                                |v, s: Symbol| v.visit_bound_symbol(s.0),
+                                |v, e: SExpr| e.accept(v),
+                            )?,
+                        ))
+                    })
+                    .collect::<Result<_, E>>()?;
+                visitor.visit_attributes(t, xs)
+            }
+        }
+    }
 }
 
 impl<Term, Symbol, Sort, Keyword, Constant, SExpr>
@@ -843,11 +990,18 @@ impl Command {
                 visitor.bind_symbol(&s);
                 let dt = datatype.remap(
                     visitor,
-                    |_, _| panic!("unexpected type parameters in DeclareDatatype"),
+                    |v, s| {
+                        // Bind type parameters before visiting sorts.
+                        let s = v.visit_fresh_symbol(s.0, SymbolKind::TypeVar)?;
+                        v.bind_symbol(&s);
+                        Ok(s)
+                    },
                     |v, s| v.visit_fresh_symbol(s.0, SymbolKind::Constructor),
                     |v, s| v.visit_fresh_symbol(s.0, SymbolKind::Selector),
                     |v, sort| sort.accept(v),
                 )?;
+                // Unbind type parameters.
+                dt.parameters.iter().for_each(|s| visitor.unbind_symbol(s));
                 // Bind constructor symbols and selectors.
                 dt.constructors.iter().for_each(|c| {
                     visitor.bind_symbol(&c.symbol);
@@ -1043,6 +1197,43 @@ impl Command {
             }
         }
     }
+
+    /// The SMT-LIB name of the command, e.g. `check-sat`.
+    pub fn name(&self) -> &'static str {
+        use Command::*;
+        match self {
+            Assert { .. } => "assert",
+            CheckSat => "check-sat",
+            CheckSatAssuming { .. } => "check-sat-assuming",
+            DeclareConst { .. } => "declare-const",
+            DeclareDatatype { .. } => "declare-datatype",
+            DeclareDatatypes { .. } => "declare-datatypes",
+            DeclareFun { .. } => "declare-fun",
+            DeclareSort { .. } => "declare-sort",
+            DefineFun { .. } => "define-fun",
+            DefineFunRec { .. } => "define-fun-rec",
+            DefineFunsRec { .. } => "define-funs-rec",
+            DefineSort { .. } => "define-sort",
+            Echo { .. } => "echo",
+            Exit => "exit",
+            GetAssertions => "get-assertions",
+            GetAssignment => "get-assignment",
+            GetInfo { .. } => "get-info",
+            GetModel => "get-model",
+            GetOption { .. } => "get-option",
+            GetProof => "get-proof",
+            GetUnsatAssumptions => "get-unsat-assumptions",
+            GetUnsatCore => "get-unsat-core",
+            GetValue { .. } => "get-value",
+            Pop { .. } => "pop",
+            Push { .. } => "push",
+            Reset => "reset",
+            ResetAssertions => "reset-assertions",
+            SetInfo { .. } => "set-info",
+            SetLogic { .. } => "set-logic",
+            SetOption { .. } => "set-option",
+        }
+    }
 }
 
 impl Smt2Visitor for SyntaxBuilder {
@@ -1109,7 +1300,7 @@ impl std::fmt::Display for Constant {
 
 impl std::fmt::Display for Symbol {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let c = self.0.as_bytes().get(0);
+        let c = self.0.as_bytes().first();
         if c.is_some()
             && lexer::is_non_digit_symbol_byte(*c.unwrap())
             && self.0.as_bytes().iter().all(|c| lexer::is_symbol_byte(*c))
@@ -1162,9 +1353,10 @@ where
     }
 }
 
-impl<Identifier> std::fmt::Display for QualIdentifier<Identifier>
+impl<Identifier, Sort> std::fmt::Display for QualIdentifier<Identifier, Sort>
 where
     Identifier: std::fmt::Display,
+    Sort: std::fmt::Display,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         // ⟨identifier⟩ | ( as ⟨identifier⟩ ⟨sort⟩ )
@@ -1336,11 +1528,13 @@ where
             }
             DefineFunRec { sig, term } => {
                 // ( define-fun-rec ⟨function_dec⟩ ⟨term⟩ )
-                write!(f, "(define-fun {} {})", sig, term)
+                write!(f, "(define-fun-rec {} {})", sig, term)
             }
             DefineFunsRec { funs } => {
                 // ( define-funs-rec ( ( ⟨function_dec⟩ )n+1 ) ( ⟨term⟩n+1 ) )
-                let sigs = funs.iter().format_with(" ", |(sig, _), f| f(sig));
+                let sigs = funs
+                    .iter()
+                    .format_with(" ", |(sig, _), f| f(&format_args!("({})", sig)));
                 let terms = funs.iter().format_with(" ", |(_, t), f| f(t));
                 write!(f, "(define-funs-rec ({}) ({}))", sigs, terms)
             }
@@ -1438,7 +1632,69 @@ fn test_syntax_visitor() {
             }),
         },
     };
-    let mut builder = SyntaxBuilder::default();
+    let mut builder = SyntaxBuilder;
     let command2 = command.clone().accept(&mut builder).unwrap();
     assert_eq!(command, command2);
 }
+
+#[test]
+fn test_serde_round_trip() {
+    let input = r#"
+(declare-datatypes ((List 0)) (((nil) (cons (head Int) (tail List)))))
+(define-fun f ((x Int)) Real (let ((y (* x #b101))) (! (to_real (+ y 1.5)) :named f_body)))
+(assert (forall ((l List)) (match l ((nil true) ((cons h t) (> (f h) 0.0))))))
+(set-info :source |a "quoted" source|)
+"#;
+    let commands = crate::parse_commands(input).unwrap();
+    let json = serde_json::to_string(&commands).unwrap();
+    let decoded: Vec<Command> = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, commands);
+}
+
+#[test]
+fn test_ordered_syntax() {
+    use std::collections::{BTreeSet, HashSet};
+
+    let commands = crate::parse_commands(
+        "(assert (> x 1)) (assert (> x 0)) (assert (> x 1)) (assert (> (as x Int) 0))",
+    )
+    .unwrap();
+    let hashed = commands.iter().collect::<HashSet<_>>();
+    assert_eq!(hashed.len(), 3);
+    let ordered = commands.iter().cloned().collect::<BTreeSet<_>>();
+    assert_eq!(ordered.len(), 3);
+    assert!(ordered
+        .iter()
+        .zip(ordered.iter().skip(1))
+        .all(|(a, b)| a < b));
+    let term = crate::parse_term("(+ y x 1 x)").unwrap();
+    let terms = term.children().into_iter().collect::<BTreeSet<_>>();
+    assert_eq!(terms.len(), 3);
+}
+
+#[test]
+fn test_accept_ref() {
+    use crate::renaming::BoundVariableRenamer;
+
+    let inputs = vec![
+        "(f #b101 #x1F 42 1.5 \"s\" (_ bv3 8) ((_ extract 3 0) y) (as nil (List Int)))".to_string(),
+        "(let ((x (f x)) (y x)) (let ((x (g x y))) (and x y)))".to_string(),
+        "(forall ((x Int) (y Int)) (exists ((x Bool)) (! (and x (> y 0)) :pattern ((p x y)) :qid q)))"
+            .to_string(),
+        "(match l ((nil 0) ((cons x t) (let ((t x)) (+ x t)))))".to_string(),
+        // A deep term.
+        format!("(and {}x{})", "(not ".repeat(100), ")".repeat(100)),
+    ];
+    for input in inputs {
+        let term = crate::parse_term(&input).unwrap();
+        assert_eq!(term.accept_ref(&mut SyntaxBuilder).unwrap(), term);
+
+        // Binders are visited in the same order, so shadowed variables get the same names.
+        let mut owned = BoundVariableRenamer::default();
+        let mut borrowed = BoundVariableRenamer::default();
+        assert_eq!(
+            term.accept_ref(&mut borrowed).unwrap(),
+            term.clone().accept(&mut owned).unwrap()
+        );
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Counterexamples to induction (CTIs) of VMT models.
+//!
+//! When the property is not inductive relative to an assumption, the solver finds a state
+//! satisfying the assumption with a successor that violates the property. The state is
+//! returned as a [`Cube`] of equalities between state variables and values, which can be
+//! generalized by dropping equalities, e.g. to block the predecessors of bad states in
+//! PDR or to refine an abstraction.
+
+use crate::{
+    concrete::{Symbol, Term},
+    model::Value,
+    solver::{SmtSolver, SolverError},
+    vmt::{conjunction, solve, VMTModel},
+};
+use std::collections::BTreeMap;
+
+/// A conjunction of equalities `(= x v)` between state variables and values.
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cube {
+    pub literals: BTreeMap<String, Value>,
+}
+
+impl Cube {
+    pub fn len(&self) -> usize {
+        self.literals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.literals.is_empty()
+    }
+
+    /// The same cube without the equality on a variable.
+    pub fn without(&self, name: &str) -> Self {
+        let mut literals = self.literals.clone();
+        literals.remove(name);
+        Self { literals }
+    }
+
+    /// The equalities of the cube, over the current state variables.
+    pub fn to_terms(&self) -> Vec<Term> {
+        self.literals
+            .iter()
+            .filter_map(|(name, value)| {
+                Some(crate::smt2!(
+                    term: "(= {} {})",
+                    Symbol(name.clone()),
+                    value.to_term()?
+                ))
+            })
+            .collect()
+    }
+
+    /// The conjunction of the equalities, `true` if there are none.
+    pub fn to_term(&self) -> Term {
+        match self.to_terms() {
+            terms if terms.is_empty() => crate::smt2!(term: "true"),
+            terms => conjunction(&terms),
+        }
+    }
+}
+
+impl std::fmt::Display for Cube {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.to_term())
+    }
+}
+
+impl VMTModel {
+    /// A state satisfying the assumption with a successor that violates the property, if
+    /// the property is not inductive relative to the assumption. The values of state
+    /// variables that cannot be written as terms, e.g. arrays, are left out of the cube.
+    /// The solver must produce models.
+    pub fn counterexample_to_induction<S: SmtSolver>(
+        &self,
+        assumption: &Term,
+        solver: &mut S,
+    ) -> Result<Option<Cube>, SolverError> {
+        let model = match solve(solver, &self.induction_step(assumption))? {
+            Some(model) => model,
+            None => return Ok(None),
+        };
+        let literals = self
+            .state_variables()
+            .into_iter()
+            .filter_map(|(name, _)| {
+                let value = model.get(&format!("{}@0", name))?;
+                value.to_term()?;
+                Some((name, value.clone()))
+            })
+            .collect();
+        Ok(Some(Cube { literals }))
+    }
+
+    /// Drop equalities of a CTI while every state of the cube that satisfies the
+    /// assumption has only successors that violate the property. Equalities are tried in
+    /// the order of the variable names.
+    pub fn generalize_counterexample<S: SmtSolver>(
+        &self,
+        cube: &Cube,
+        assumption: &Term,
+        solver: &mut S,
+    ) -> Result<Cube, SolverError> {
+        // The property holds after a transition from a state of the cube.
+        let escapes = self.with_property(crate::smt2!(term: "(not {})", self.property()));
+        let mut cube = cube.clone();
+        for name in cube.literals.keys().cloned().collect::<Vec<_>>() {
+            let candidate = cube.without(&name);
+            let mut terms = candidate.to_terms();
+            terms.push(assumption.clone());
+            let step = escapes.induction_step(&conjunction(&terms));
+            if solve(solver, &step)?.is_none() {
+                cube = candidate;
+            }
+        }
+        Ok(cube)
+    }
+}
+
+#[test]
+fn test_counterexample_to_induction() {
+    use crate::{concrete::Command, model::Model, responses::Response};
+    use std::collections::VecDeque;
+
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun n () Int)
+(declare-fun n_next () Int)
+(define-fun .n () Int (! n :next n_next))
+(declare-fun m () Int)
+(declare-fun m_next () Int)
+(define-fun .m () Int (! m :next m_next))
+(define-fun init () Bool (! (and (= n 0) (= m 0)) :init true))
+(define-fun trans () Bool (! (and (= n_next (+ n 1)) (= m_next m)) :trans true))
+(define-fun property () Bool (! (< n 3) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let vmt = VMTModel::checked_from(commands).unwrap();
+
+    // A solver answering `check-sat` with the given models, or unsat, and recording the
+    // assertions.
+    struct Scripted(VecDeque<Option<&'static str>>, Vec<String>);
+
+    impl SmtSolver for Scripted {
+        fn execute(&mut self, command: &Command) -> Result<Response, SolverError> {
+            Ok(match command {
+                Command::Assert { term } => {
+                    self.1.push(term.to_string());
+                    Response::Success
+                }
+                Command::CheckSat => match self.0.pop_front().unwrap() {
+                    Some(model) => {
+                        self.0.push_front(Some(model));
+                        Response::Sat
+                    }
+                    None => Response::Unsat,
+                },
+                Command::GetModel => {
+                    let model = self.0.pop_front().unwrap().unwrap();
+                    Response::Model(Model::parse(model).unwrap())
+                }
+                _ => Response::Success,
+            })
+        }
+    }
+
+    let mut solver = Scripted(
+        vec![
+            Some("((define-fun n@0 () Int 2) (define-fun m@0 () Int 5) (define-fun n@1 () Int 3))"),
+            // Without `(= m 5)`, the successors still violate the property.
+            None,
+            // Without `(= n 2)`, they do not.
+            Some("()"),
+        ]
+        .into(),
+        Vec::new(),
+    );
+    let property = vmt.property().clone();
+    let cube = vmt
+        .counterexample_to_induction(&property, &mut solver)
+        .unwrap()
+        .unwrap();
+    assert_eq!(cube.to_string(), "(and (= m 5) (= n 2))");
+    solver.1.clear();
+    let cube = vmt
+        .generalize_counterexample(&cube, &property, &mut solver)
+        .unwrap();
+    assert_eq!(cube.to_string(), "(= n 2)");
+    assert!(solver.1.contains(&"(and (= n@0 2) (< n@0 3))".to_string()));
+    assert!(solver.1.contains(&"(not (not (< n@1 3)))".to_string()));
+    assert!(solver.0.is_empty());
+}
@@ -0,0 +1,427 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Conversion of terms between named binders and de Bruijn indices.
+
+use crate::{
+    concrete::{Constant, Keyword, QualIdentifier, SExpr, Sort, Symbol, Term},
+    visitors::{AttributeValue, Identifier},
+};
+use std::collections::BTreeSet;
+
+/// A symbol of an s-expression, which may refer to a bound variable.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IndexedSymbol {
+    Free(Symbol),
+    Bound(u64),
+}
+
+/// An s-expression whose symbols may refer to bound variables.
+pub type IndexedSExpr = SExpr<Constant, IndexedSymbol, Keyword>;
+
+/// A term whose bound variables are represented by de Bruijn indices, as in Z3 traces:
+/// index 0 refers to the last variable of the innermost binder, index 1 to the variable
+/// declared before it, and so on through the enclosing binders. Alpha-equivalent terms
+/// have equal representations.
+///
+/// Patterns made of a single symbol in `match` cases are considered as constructors.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IndexedTerm {
+    Constant(Constant),
+    /// A free symbol.
+    QualIdentifier(QualIdentifier),
+    /// A bound variable.
+    Var {
+        index: u64,
+    },
+    Application {
+        qual_identifier: QualIdentifier,
+        arguments: Vec<IndexedTerm>,
+    },
+    /// Parallel bindings: the values are outside the scope of the variables.
+    Let {
+        values: Vec<IndexedTerm>,
+        term: Box<IndexedTerm>,
+    },
+    Forall {
+        sorts: Vec<Sort>,
+        term: Box<IndexedTerm>,
+    },
+    Exists {
+        sorts: Vec<Sort>,
+        term: Box<IndexedTerm>,
+    },
+    /// Each case gives the constructor of the pattern and its number of variables.
+    Match {
+        term: Box<IndexedTerm>,
+        cases: Vec<(Symbol, usize, IndexedTerm)>,
+    },
+    Attributes {
+        term: Box<IndexedTerm>,
+        attributes: Vec<(Keyword, AttributeValue<Constant, Symbol, IndexedSExpr>)>,
+    },
+}
+
+impl Term {
+    /// Replace the bound variables of the term by de Bruijn indices.
+    pub fn to_de_bruijn(&self) -> IndexedTerm {
+        Indexer::default().term(self)
+    }
+}
+
+impl IndexedTerm {
+    /// Name the bound variables `x0`, `x1`, etc. after the number of enclosing variables,
+    /// adding a suffix to avoid the free symbols. Indices that are not bound, if any,
+    /// are named `_0`, `_1`, etc.
+    pub fn to_named(&self) -> Term {
+        let mut free = BTreeSet::new();
+        self.collect_free_symbols(&mut free);
+        Namer {
+            free,
+            bound: Vec::new(),
+        }
+        .term(self)
+    }
+
+    fn collect_free_symbols(&self, symbols: &mut BTreeSet<String>) {
+        match self {
+            IndexedTerm::Constant(_) | IndexedTerm::Var { .. } => (),
+            IndexedTerm::QualIdentifier(qual_identifier) => {
+                symbols.insert(identifier_symbol(qual_identifier).0.clone());
+            }
+            IndexedTerm::Application {
+                qual_identifier,
+                arguments,
+            } => {
+                symbols.insert(identifier_symbol(qual_identifier).0.clone());
+                for argument in arguments {
+                    argument.collect_free_symbols(symbols);
+                }
+            }
+            IndexedTerm::Let { values, term } => {
+                for value in values {
+                    value.collect_free_symbols(symbols);
+                }
+                term.collect_free_symbols(symbols);
+            }
+            IndexedTerm::Forall { term, .. } | IndexedTerm::Exists { term, .. } => {
+                term.collect_free_symbols(symbols)
+            }
+            IndexedTerm::Match { term, cases } => {
+                term.collect_free_symbols(symbols);
+                for (constructor, _, body) in cases {
+                    symbols.insert(constructor.0.clone());
+                    body.collect_free_symbols(symbols);
+                }
+            }
+            IndexedTerm::Attributes { term, attributes } => {
+                term.collect_free_symbols(symbols);
+                for (_, value) in attributes {
+                    if let AttributeValue::SExpr(values) = value {
+                        for value in values {
+                            collect_s_expr_symbols(value, symbols);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn collect_s_expr_symbols(value: &IndexedSExpr, symbols: &mut BTreeSet<String>) {
+    match value {
+        SExpr::Symbol(IndexedSymbol::Free(symbol)) => {
+            symbols.insert(symbol.0.clone());
+        }
+        SExpr::Application(values) => {
+            for value in values {
+                collect_s_expr_symbols(value, symbols);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn identifier_symbol(qual_identifier: &QualIdentifier) -> &Symbol {
+    match qual_identifier {
+        QualIdentifier::Simple { identifier } | QualIdentifier::Sorted { identifier, .. } => {
+            match identifier {
+                Identifier::Simple { symbol } | Identifier::Indexed { symbol, .. } => symbol,
+            }
+        }
+    }
+}
+
+/// Bound variables of the converted term, innermost last.
+#[derive(Debug, Default)]
+struct Indexer {
+    bound: Vec<String>,
+}
+
+impl Indexer {
+    fn index(&self, symbol: &Symbol) -> Option<u64> {
+        let position = self.bound.iter().rposition(|name| *name == symbol.0)?;
+        Some((self.bound.len() - 1 - position) as u64)
+    }
+
+    fn term(&mut self, term: &Term) -> IndexedTerm {
+        match term {
+            Term::Constant(constant) => IndexedTerm::Constant(constant.clone()),
+            Term::QualIdentifier(qual_identifier) => match qual_identifier {
+                QualIdentifier::Simple {
+                    identifier: Identifier::Simple { symbol },
+                }
+                | QualIdentifier::Sorted {
+                    identifier: Identifier::Simple { symbol },
+                    ..
+                } => match self.index(symbol) {
+                    Some(index) => IndexedTerm::Var { index },
+                    None => IndexedTerm::QualIdentifier(qual_identifier.clone()),
+                },
+                _ => IndexedTerm::QualIdentifier(qual_identifier.clone()),
+            },
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } => IndexedTerm::Application {
+                qual_identifier: qual_identifier.clone(),
+                arguments: arguments.iter().map(|a| self.term(a)).collect(),
+            },
+            Term::Let { var_bindings, term } => {
+                let values = var_bindings.iter().map(|(_, v)| self.term(v)).collect();
+                let term = self.bound(var_bindings.iter().map(|(s, _)| s), term);
+                IndexedTerm::Let { values, term }
+            }
+            Term::Forall { vars, term } => IndexedTerm::Forall {
+                sorts: vars.iter().map(|(_, sort)| sort.clone()).collect(),
+                term: self.bound(vars.iter().map(|(s, _)| s), term),
+            },
+            Term::Exists { vars, term } => IndexedTerm::Exists {
+                sorts: vars.iter().map(|(_, sort)| sort.clone()).collect(),
+                term: self.bound(vars.iter().map(|(s, _)| s), term),
+            },
+            Term::Match { term, cases } => IndexedTerm::Match {
+                term: Box::new(self.term(term)),
+                cases: cases
+                    .iter()
+                    .map(|(pattern, body)| {
+                        let body = self.bound(pattern[1..].iter(), body);
+                        (pattern[0].clone(), pattern.len() - 1, *body)
+                    })
+                    .collect(),
+            },
+            Term::Attributes { term, attributes } => IndexedTerm::Attributes {
+                term: Box::new(self.term(term)),
+                attributes: attributes
+                    .iter()
+                    .map(|(key, value)| {
+                        let value = match value {
+                            AttributeValue::None => AttributeValue::None,
+                            AttributeValue::Constant(c) => AttributeValue::Constant(c.clone()),
+                            AttributeValue::Symbol(s) => AttributeValue::Symbol(s.clone()),
+                            AttributeValue::SExpr(values) => AttributeValue::SExpr(
+                                values.iter().map(|v| self.s_expr(v)).collect(),
+                            ),
+                        };
+                        (key.clone(), value)
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    fn s_expr(&self, value: &SExpr) -> IndexedSExpr {
+        match value {
+            SExpr::Constant(c) => SExpr::Constant(c.clone()),
+            SExpr::Symbol(symbol) => SExpr::Symbol(match self.index(symbol) {
+                Some(index) => IndexedSymbol::Bound(index),
+                None => IndexedSymbol::Free(symbol.clone()),
+            }),
+            SExpr::Keyword(k) => SExpr::Keyword(k.clone()),
+            SExpr::Application(values) => {
+                SExpr::Application(values.iter().map(|v| self.s_expr(v)).collect())
+            }
+        }
+    }
+
+    fn bound<'a, I>(&mut self, symbols: I, term: &Term) -> Box<IndexedTerm>
+    where
+        I: Iterator<Item = &'a Symbol>,
+    {
+        let len = self.bound.len();
+        self.bound.extend(symbols.map(|s| s.0.clone()));
+        let term = self.term(term);
+        self.bound.truncate(len);
+        Box::new(term)
+    }
+}
+
+/// Names of the bound variables of the converted term, innermost last.
+#[derive(Debug)]
+struct Namer {
+    free: BTreeSet<String>,
+    bound: Vec<Symbol>,
+}
+
+impl Namer {
+    fn name(&self, index: u64) -> Symbol {
+        match (self.bound.len() as u64).checked_sub(index + 1) {
+            Some(position) => self.bound[position as usize].clone(),
+            None => Symbol(format!("_{}", index)),
+        }
+    }
+
+    fn term(&mut self, term: &IndexedTerm) -> Term {
+        match term {
+            IndexedTerm::Constant(constant) => Term::Constant(constant.clone()),
+            IndexedTerm::QualIdentifier(qual_identifier) => {
+                Term::QualIdentifier(qual_identifier.clone())
+            }
+            IndexedTerm::Var { index } => Term::QualIdentifier(QualIdentifier::Simple {
+                identifier: Identifier::Simple {
+                    symbol: self.name(*index),
+                },
+            }),
+            IndexedTerm::Application {
+                qual_identifier,
+                arguments,
+            } => Term::Application {
+                qual_identifier: qual_identifier.clone(),
+                arguments: arguments.iter().map(|a| self.term(a)).collect(),
+            },
+            IndexedTerm::Let { values, term } => {
+                let values = values.iter().map(|v| self.term(v)).collect::<Vec<_>>();
+                let (symbols, term) = self.bound(values.len(), term);
+                Term::Let {
+                    var_bindings: symbols.into_iter().zip(values).collect(),
+                    term,
+                }
+            }
+            IndexedTerm::Forall { sorts, term } => {
+                let (symbols, term) = self.bound(sorts.len(), term);
+                Term::Forall {
+                    vars: symbols.into_iter().zip(sorts.iter().cloned()).collect(),
+                    term,
+                }
+            }
+            IndexedTerm::Exists { sorts, term } => {
+                let (symbols, term) = self.bound(sorts.len(), term);
+                Term::Exists {
+                    vars: symbols.into_iter().zip(sorts.iter().cloned()).collect(),
+                    term,
+                }
+            }
+            IndexedTerm::Match { term, cases } => Term::Match {
+                term: Box::new(self.term(term)),
+                cases: cases
+                    .iter()
+                    .map(|(constructor, arity, body)| {
+                        let (symbols, body) = self.bound(*arity, body);
+                        let pattern = std::iter::once(constructor.clone())
+                            .chain(symbols)
+                            .collect();
+                        (pattern, *body)
+                    })
+                    .collect(),
+            },
+            IndexedTerm::Attributes { term, attributes } => Term::Attributes {
+                term: Box::new(self.term(term)),
+                attributes: attributes
+                    .iter()
+                    .map(|(key, value)| {
+                        let value = match value {
+                            AttributeValue::None => AttributeValue::None,
+                            AttributeValue::Constant(c) => AttributeValue::Constant(c.clone()),
+                            AttributeValue::Symbol(s) => AttributeValue::Symbol(s.clone()),
+                            AttributeValue::SExpr(values) => AttributeValue::SExpr(
+                                values.iter().map(|v| self.s_expr(v)).collect(),
+                            ),
+                        };
+                        (key.clone(), value)
+                    })
+                    .collect(),
+            },
+        }
+    }
+
+    fn s_expr(&self, value: &IndexedSExpr) -> SExpr {
+        match value {
+            SExpr::Constant(c) => SExpr::Constant(c.clone()),
+            SExpr::Symbol(IndexedSymbol::Free(symbol)) => SExpr::Symbol(symbol.clone()),
+            SExpr::Symbol(IndexedSymbol::Bound(index)) => SExpr::Symbol(self.name(*index)),
+            SExpr::Keyword(k) => SExpr::Keyword(k.clone()),
+            SExpr::Application(values) => {
+                SExpr::Application(values.iter().map(|v| self.s_expr(v)).collect())
+            }
+        }
+    }
+
+    fn bound(&mut self, count: usize, term: &IndexedTerm) -> (Vec<Symbol>, Box<Term>) {
+        let len = self.bound.len();
+        for level in len..len + count {
+            let mut name = format!("x{}", level);
+            let mut suffix = 0;
+            while self.free.contains(&name) {
+                suffix += 1;
+                name = format!("x{}!{}", level, suffix);
+            }
+            self.bound.push(Symbol(name));
+        }
+        let symbols = self.bound[len..].to_vec();
+        let term = self.term(term);
+        self.bound.truncate(len);
+        (symbols, Box::new(term))
+    }
+}
+
+#[test]
+fn test_de_bruijn_conversion() {
+    let term = |input: &str| crate::parse_term(input).unwrap();
+    let t = term("(forall ((x Int) (y Int)) (! (exists ((z Int)) (P x y z)) :pattern ((Q x))))");
+    match t.to_de_bruijn() {
+        IndexedTerm::Forall { term, .. } => match *term {
+            IndexedTerm::Attributes { term, .. } => match *term {
+                IndexedTerm::Exists { term, .. } => assert_eq!(
+                    *term,
+                    IndexedTerm::Application {
+                        qual_identifier: QualIdentifier::Simple {
+                            identifier: Identifier::Simple {
+                                symbol: Symbol("P".into())
+                            }
+                        },
+                        arguments: vec![
+                            IndexedTerm::Var { index: 2 },
+                            IndexedTerm::Var { index: 1 },
+                            IndexedTerm::Var { index: 0 },
+                        ],
+                    }
+                ),
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+    assert_eq!(
+        t.to_de_bruijn().to_named().to_string(),
+        "(forall ((x0 Int) (x1 Int)) (! (exists ((x2 Int)) (P x0 x1 x2)) :pattern ((Q x0))))"
+    );
+
+    let t = term("(let ((x0 1)) (match l ((nil x0) ((cons h t) (let ((x0 h)) (+ x0 t))))))");
+    assert_eq!(
+        t.to_de_bruijn().to_named().to_string(),
+        "(let ((x0 1)) (match l ((nil x0) ((cons x1 x2) (let ((x3 x1)) (+ x3 x2))))))"
+    );
+    assert_eq!(
+        term("(forall ((y Int)) (P x0 y))")
+            .to_de_bruijn()
+            .to_named()
+            .to_string(),
+        "(forall ((x0!1 Int)) (P x0 x0!1))"
+    );
+    assert!(t.to_de_bruijn().to_named().alpha_eq(&t));
+    assert_eq!(
+        term("(exists ((a Int)) (let ((b a)) (= b c)))").to_de_bruijn(),
+        term("(exists ((b Int)) (let ((a b)) (= a c)))").to_de_bruijn()
+    );
+}
@@ -0,0 +1,85 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Dependencies between the state variables of VMT models.
+
+use crate::concrete::{Identifier, QualIdentifier, Term};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Graph of the current state variables and actions on which the next value of each
+/// state variable depends, as computed by
+/// [`VMTModel::dependency_graph`](crate::vmt::VMTModel::dependency_graph).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyGraph {
+    /// State variables, in order of declaration.
+    pub variables: Vec<String>,
+    /// Actions, in order of declaration.
+    pub actions: Vec<String>,
+    /// For each state variable, the state variables and actions its next value depends on.
+    pub dependencies: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl DependencyGraph {
+    pub fn new(variables: Vec<String>, actions: Vec<String>) -> Self {
+        let dependencies = variables
+            .iter()
+            .map(|variable| (variable.clone(), BTreeSet::new()))
+            .collect();
+        Self {
+            variables,
+            actions,
+            dependencies,
+        }
+    }
+
+    /// Record that the next value of `variable` depends on `source`.
+    pub fn add_dependency(&mut self, variable: &str, source: &str) {
+        self.dependencies
+            .entry(variable.to_string())
+            .or_default()
+            .insert(source.to_string());
+    }
+
+    /// The state variables and actions the next value of `variable` depends on.
+    pub fn dependencies(&self, variable: &str) -> Option<&BTreeSet<String>> {
+        self.dependencies.get(variable)
+    }
+
+    /// Graphviz (DOT) description of the graph, with an edge from `x` to `y` when the next
+    /// value of `y` depends on `x`. Actions are drawn as boxes.
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph dependencies {\n".to_string();
+        for variable in &self.variables {
+            dot += &format!("  \"{}\";\n", escape(variable));
+        }
+        for action in &self.actions {
+            dot += &format!("  \"{}\" [shape=box];\n", escape(action));
+        }
+        for variable in &self.variables {
+            for source in &self.dependencies[variable] {
+                dot += &format!("  \"{}\" -> \"{}\";\n", escape(source), escape(variable));
+            }
+        }
+        dot + "}\n"
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The conjuncts of a term, looking through nested conjunctions and annotations.
+pub(crate) fn conjuncts(term: &Term) -> Vec<&Term> {
+    match term {
+        Term::Application {
+            qual_identifier:
+                QualIdentifier::Simple {
+                    identifier: Identifier::Simple { symbol },
+                },
+            arguments,
+        } if symbol.0 == "and" => arguments.iter().flat_map(conjuncts).collect(),
+        Term::Attributes { term, .. } => conjuncts(term),
+        term => vec![term],
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Structural differences between scripts, VMT models, and terms, e.g. to see what an
+//! abstraction or a simplification changed.
+//!
+//! Declarations and definitions are matched by the symbol they introduce, and the other
+//! commands by their position among the commands that introduce no symbol. The terms of
+//! matched definitions and assertions are compared subterm by subterm, so that a change
+//! is reported at the deepest path where the terms differ in more than their children.
+
+use crate::{
+    concrete::{Command, Term},
+    query::Path,
+    vmt::VMTModel,
+};
+use std::collections::{HashMap, VecDeque};
+
+/// A difference between two scripts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// A command of the second script only.
+    Added(Command),
+    /// A command of the first script only.
+    Removed(Command),
+    /// A command replaced by another one, e.g. a declaration with another sort.
+    Changed { before: Command, after: Command },
+    /// A subterm that changed in a definition or an assertion, e.g. `define-fun trans` or
+    /// `assert 0` for the first assertion.
+    Term {
+        context: String,
+        path: Path,
+        before: Term,
+        after: Term,
+    },
+}
+
+/// The differences between two scripts, in the order of the first script, then the
+/// commands added by the second one in its own order.
+pub fn diff_commands(before: &[Command], after: &[Command]) -> Vec<Difference> {
+    // Indices of the commands of the second script, by symbol or by position.
+    let mut keyed = HashMap::<&str, VecDeque<usize>>::new();
+    let mut others = VecDeque::new();
+    for (index, command) in after.iter().enumerate() {
+        match key(command) {
+            Some(key) => keyed.entry(key).or_default().push_back(index),
+            None => others.push_back(index),
+        }
+    }
+    let mut matched = vec![false; after.len()];
+    let mut differences = Vec::new();
+    let mut assertions = 0;
+    for command in before {
+        let other = match key(command) {
+            Some(key) => keyed.get_mut(key).and_then(VecDeque::pop_front),
+            None => others.pop_front(),
+        };
+        let context = match (command, key(command)) {
+            (Command::Assert { .. }, _) => {
+                assertions += 1;
+                format!("assert {}", assertions - 1)
+            }
+            (command, Some(key)) => format!("{} {}", command.name(), key),
+            (command, None) => command.name().to_string(),
+        };
+        match other {
+            Some(index) => {
+                matched[index] = true;
+                diff_command(&context, command, &after[index], &mut differences);
+            }
+            None => differences.push(Difference::Removed(command.clone())),
+        }
+    }
+    differences.extend(
+        after
+            .iter()
+            .zip(matched)
+            .filter(|(_, matched)| !matched)
+            .map(|(command, _)| Difference::Added(command.clone())),
+    );
+    differences
+}
+
+/// The differences between the commands of two VMT models.
+pub fn diff_models(before: &VMTModel, after: &VMTModel) -> Vec<Difference> {
+    diff_commands(&before.to_commands(), &after.to_commands())
+}
+
+/// The subterms that differ between two terms, with their paths, in pre-order. Subterms
+/// are compared when their parents only differ by their children.
+pub fn diff_terms(before: &Term, after: &Term) -> Vec<(Path, Term, Term)> {
+    let mut differences = Vec::new();
+    diff_subterms(&mut Vec::new(), before, after, &mut differences);
+    differences
+}
+
+fn diff_subterms(
+    path: &mut Path,
+    before: &Term,
+    after: &Term,
+    differences: &mut Vec<(Path, Term, Term)>,
+) {
+    if before == after {
+        return;
+    }
+    if !same_node(before, after) {
+        differences.push((path.clone(), before.clone(), after.clone()));
+        return;
+    }
+    let children = before.children().into_iter().zip(after.children());
+    for (i, (before, after)) in children.enumerate() {
+        path.push(i);
+        diff_subterms(path, before, after, differences);
+        path.pop();
+    }
+}
+
+// Whether two terms have the same root, including their symbols, binders, and
+// attributes, and the same number of children.
+fn same_node(before: &Term, after: &Term) -> bool {
+    match (before, after) {
+        (
+            Term::Application {
+                qual_identifier: f,
+                arguments: xs,
+            },
+            Term::Application {
+                qual_identifier: g,
+                arguments: ys,
+            },
+        ) => f == g && xs.len() == ys.len(),
+        (
+            Term::Let {
+                var_bindings: xs, ..
+            },
+            Term::Let {
+                var_bindings: ys, ..
+            },
+        ) => xs.len() == ys.len() && xs.iter().zip(ys).all(|((x, _), (y, _))| x == y),
+        (Term::Forall { vars: xs, .. }, Term::Forall { vars: ys, .. })
+        | (Term::Exists { vars: xs, .. }, Term::Exists { vars: ys, .. }) => xs == ys,
+        (Term::Match { cases: xs, .. }, Term::Match { cases: ys, .. }) => {
+            xs.len() == ys.len() && xs.iter().zip(ys).all(|((x, _), (y, _))| x == y)
+        }
+        (Term::Attributes { attributes: xs, .. }, Term::Attributes { attributes: ys, .. }) => {
+            xs == ys
+        }
+        _ => false,
+    }
+}
+
+fn diff_command(
+    context: &str,
+    before: &Command,
+    after: &Command,
+    differences: &mut Vec<Difference>,
+) {
+    let terms = match (before, after) {
+        _ if before == after => return,
+        (Command::Assert { term: x }, Command::Assert { term: y }) => Some((x, y)),
+        (Command::DefineFun { sig: f, term: x }, Command::DefineFun { sig: g, term: y })
+        | (Command::DefineFunRec { sig: f, term: x }, Command::DefineFunRec { sig: g, term: y })
+            if f == g =>
+        {
+            Some((x, y))
+        }
+        _ => None,
+    };
+    match terms {
+        Some((before, after)) => differences.extend(diff_terms(before, after).into_iter().map(
+            |(path, before, after)| Difference::Term {
+                context: context.to_string(),
+                path,
+                before,
+                after,
+            },
+        )),
+        None => differences.push(Difference::Changed {
+            before: before.clone(),
+            after: after.clone(),
+        }),
+    }
+}
+
+// The symbol introduced by a declaration or a definition.
+fn key(command: &Command) -> Option<&str> {
+    match command {
+        Command::DeclareConst { symbol, .. }
+        | Command::DeclareDatatype { symbol, .. }
+        | Command::DeclareFun { symbol, .. }
+        | Command::DeclareSort { symbol, .. }
+        | Command::DefineSort { symbol, .. } => Some(&symbol.0),
+        Command::DefineFun { sig, .. } | Command::DefineFunRec { sig, .. } => Some(&sig.name.0),
+        _ => None,
+    }
+}
+
+impl std::fmt::Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Difference::Added(command) => write!(f, "+ {}", command),
+            Difference::Removed(command) => write!(f, "- {}", command),
+            Difference::Changed { before, after } => write!(f, "- {}\n+ {}", before, after),
+            Difference::Term {
+                context,
+                path,
+                before,
+                after,
+            } => write!(f, "~ {} {:?}\n- {}\n+ {}", context, path, before, after),
+        }
+    }
+}
+
+#[test]
+fn test_diff_commands() {
+    let before = crate::parse_commands(
+        r#"
+(declare-fun a () (Array Int Int))
+(declare-fun i () Int)
+(declare-fun j () Int)
+(define-fun f ((x Int)) Int (+ (select a x) (* 2 1)))
+(assert (> (f i) 0))
+(assert (forall ((k Int)) (= (select a k) 0)))
+(check-sat)
+"#,
+    )
+    .unwrap();
+    let after = crate::parse_commands(
+        r#"
+(declare-sort Array_Int_Int 0)
+(declare-fun a () Array_Int_Int)
+(declare-fun i () Int)
+(define-fun f ((x Int)) Int (+ (read a x) 2))
+(assert (> (f i) 0))
+(assert (forall ((k Int)) (= (read a k) 0)))
+(check-sat)
+(get-model)
+"#,
+    )
+    .unwrap();
+    let differences = diff_commands(&before, &after)
+        .iter()
+        .map(|difference| difference.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        differences,
+        vec![
+            "- (declare-fun a () (Array Int Int))\n+ (declare-fun a () Array_Int_Int)",
+            "- (declare-fun j () Int)",
+            "~ define-fun f [0]\n- (select a x)\n+ (read a x)",
+            "~ define-fun f [1]\n- (* 2 1)\n+ 2",
+            "~ assert 1 [0, 0]\n- (select a k)\n+ (read a k)",
+            "+ (declare-sort Array_Int_Int 0)",
+            "+ (get-model)",
+        ]
+    );
+    let before = crate::parse_term("(let ((y (f (g x) y))) (h y))").unwrap();
+    let after = crate::parse_term("(let ((y (f (g z) y))) (h y 1))").unwrap();
+    assert_eq!(
+        diff_terms(&before, &after)
+            .iter()
+            .map(|(path, before, after)| format!("{:?} {} {}", path, before, after))
+            .collect::<Vec<_>>(),
+        vec!["[0, 0, 0] x z", "[1] (h y) (h y 1)"]
+    );
+}
@@ -0,0 +1,210 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Scoped environments tracking the symbols in scope while walking a command stream:
+//! declarations saved and restored by `push` and `pop`, and variables bound by `let`,
+//! quantifiers, and `match` cases, which shadow the declared symbols.
+
+use crate::{
+    concrete::{Command, Sort, Term},
+    symbols::{SymbolInfo, SymbolKind, SymbolTable},
+};
+use num::ToPrimitive;
+
+/// What a symbol refers to in an [`Environment`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding<'a> {
+    /// A variable bound by a `let`, a quantifier, or a `match` case, with its sort when
+    /// known. The sorts of `let` variables and of `match` variables are not inferred.
+    Local(Option<&'a Sort>),
+    /// A declared or defined symbol.
+    Global(&'a SymbolInfo),
+}
+
+/// The symbols in scope at some point of a command stream.
+#[derive(Debug, Default, Clone)]
+pub struct Environment {
+    symbols: SymbolTable,
+    // Symbol tables saved by `push` commands.
+    saved: Vec<SymbolTable>,
+    // Bound variables, innermost last.
+    locals: Vec<(String, Option<Sort>)>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the environment after a command: record declarations and definitions, and
+    /// handle `push`, `pop`, `reset`, and `reset-assertions`.
+    pub fn add_command(&mut self, command: &Command) {
+        match command {
+            Command::Push { level } => {
+                for _ in 0..level.to_usize().expect("too many levels") {
+                    self.saved.push(self.symbols.clone());
+                }
+            }
+            Command::Pop { level } => {
+                for _ in 0..level.to_usize().expect("too many levels") {
+                    if let Some(symbols) = self.saved.pop() {
+                        self.symbols = symbols;
+                    }
+                }
+            }
+            Command::Reset => *self = Self::default(),
+            Command::ResetAssertions => {
+                if !self.saved.is_empty() {
+                    self.symbols = self.saved.swap_remove(0);
+                    self.saved.clear();
+                }
+            }
+            command => self.symbols.add(command),
+        }
+    }
+
+    /// The number of `push` levels.
+    pub fn level(&self) -> usize {
+        self.saved.len()
+    }
+
+    /// The declared and defined symbols of the current level.
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// The declared and defined symbols of the current level, consuming the environment.
+    pub fn into_symbols(self) -> SymbolTable {
+        self.symbols
+    }
+
+    /// What a symbol refers to. Bound variables shadow declared symbols and outer bound
+    /// variables.
+    pub fn lookup(&self, name: &str) -> Option<Binding<'_>> {
+        match self.locals.iter().rev().find(|(local, _)| local == name) {
+            Some((_, sort)) => Some(Binding::Local(sort.as_ref())),
+            None => self.symbols.get(name).map(Binding::Global),
+        }
+    }
+
+    /// Whether a symbol refers to a bound variable.
+    pub fn is_local(&self, name: &str) -> bool {
+        self.locals.iter().any(|(local, _)| local == name)
+    }
+
+    /// Bind a variable, shadowing the previous meaning of its name.
+    pub fn bind(&mut self, name: &str, sort: Option<Sort>) {
+        self.locals.push((name.to_string(), sort));
+    }
+
+    /// Remove the last `count` bound variables.
+    pub fn unbind(&mut self, count: usize) {
+        let len = self.locals.len().saturating_sub(count);
+        self.locals.truncate(len);
+    }
+
+    /// Call `f` on the term and its subterms in pre-order, with the variables of the
+    /// enclosing binders in scope. The values of a `let` are visited in the outer scope,
+    /// since `let` bindings are parallel.
+    pub fn walk_term<F>(&mut self, term: &Term, f: &mut F)
+    where
+        F: FnMut(&Term, &Environment),
+    {
+        f(term, self);
+        match term {
+            Term::Constant(_) | Term::QualIdentifier(_) => (),
+            Term::Application { arguments, .. } => {
+                for argument in arguments {
+                    self.walk_term(argument, f);
+                }
+            }
+            Term::Let { var_bindings, term } => {
+                for (_, value) in var_bindings {
+                    self.walk_term(value, f);
+                }
+                for (symbol, _) in var_bindings {
+                    self.bind(&symbol.0, None);
+                }
+                self.walk_term(term, f);
+                self.unbind(var_bindings.len());
+            }
+            Term::Forall { vars, term } | Term::Exists { vars, term } => {
+                for (symbol, sort) in vars {
+                    self.bind(&symbol.0, Some(sort.clone()));
+                }
+                self.walk_term(term, f);
+                self.unbind(vars.len());
+            }
+            Term::Match { term, cases } => {
+                self.walk_term(term, f);
+                for (pattern, body) in cases {
+                    // A single symbol is either a nullary constructor or a variable.
+                    let variables = match pattern.as_slice() {
+                        [symbol] if self.is_constructor(&symbol.0) => &[][..],
+                        [symbol] => std::slice::from_ref(symbol),
+                        [_, variables @ ..] => variables,
+                        [] => &[][..],
+                    };
+                    for symbol in variables {
+                        self.bind(&symbol.0, None);
+                    }
+                    self.walk_term(body, f);
+                    self.unbind(variables.len());
+                }
+            }
+            Term::Attributes { term, .. } => self.walk_term(term, f),
+        }
+    }
+
+    fn is_constructor(&self, name: &str) -> bool {
+        matches!(
+            self.lookup(name),
+            Some(Binding::Global(SymbolInfo {
+                kind: SymbolKind::Constructor,
+                ..
+            }))
+        )
+    }
+}
+
+#[test]
+fn test_environment() {
+    use crate::concrete::QualIdentifier;
+
+    let commands = crate::parse_commands(
+        r#"
+(declare-datatypes ((Opt 0)) (((none) (some (val Int)))))
+(declare-fun x () Int)
+(push 1)
+(declare-fun y () Bool)
+"#,
+    )
+    .unwrap();
+    let mut env = Environment::new();
+    for command in &commands {
+        env.add_command(command);
+    }
+    assert_eq!(env.level(), 1);
+    assert!(matches!(env.lookup("y"), Some(Binding::Global(_))));
+
+    // Record which occurrences of `x` refer to the declared constant.
+    let term = crate::parse_term(
+        "(and (= x 0) (forall ((x Bool)) x) (let ((x (+ x 1))) (= x 2)) (match o ((none x) ((some x) (= x 3)))))",
+    )
+    .unwrap();
+    let mut globals = Vec::new();
+    env.walk_term(&term, &mut |term, env| {
+        if let Term::QualIdentifier(QualIdentifier::Simple { identifier }) = term {
+            if identifier.to_string() == "x" {
+                globals.push(matches!(env.lookup("x"), Some(Binding::Global(_))));
+            }
+        }
+    });
+    assert_eq!(globals, vec![true, false, true, false, true, false]);
+    assert!(!env.is_local("x"));
+
+    env.add_command(&crate::parse_commands("(pop 1)").unwrap()[0]);
+    assert_eq!(env.level(), 0);
+    assert_eq!(env.lookup("y"), None);
+    assert!(env.lookup("x").is_some());
+}
@@ -0,0 +1,667 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Evaluation of quantifier-free terms under the assignment of a [`Model`], e.g. to
+//! replay traces, check counterexamples, or simplify terms under partial models.
+
+use crate::{
+    concrete::{Command, QualIdentifier, Term},
+    model::{constant_value, Model, Value},
+    visitors::{Identifier, Index},
+    Numeral,
+};
+use num::{BigInt, BigRational, Integer, One, Signed, ToPrimitive, Zero};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Error reported by an [`Evaluator`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum EvaluationError {
+    #[error("no value for symbol `{0}`")]
+    Unassigned(String),
+    #[error("cannot evaluate term `{0}`")]
+    Unsupported(Term),
+    #[error("ill-sorted term `{0}`")]
+    IllSorted(Term),
+    #[error("division by zero in `{0}`")]
+    DivisionByZero(Term),
+}
+
+/// Evaluator of quantifier-free terms. Symbols are given their values in the model, and
+/// the functions defined by the model are applied. The core, integer, real, array,
+/// bit-vector, and datatype theories are supported; datatype selectors and `match`
+/// require the declarations of the datatypes, given by [`Evaluator::with_datatypes`].
+#[derive(Debug, Clone)]
+pub struct Evaluator<'a> {
+    model: &'a Model,
+    // Number of fields of the datatype constructors.
+    constructors: BTreeMap<String, usize>,
+    // Constructor and position of the datatype selectors.
+    selectors: BTreeMap<String, (String, usize)>,
+    // Bound variables, innermost last. Variables without values shadow the model.
+    locals: Vec<(String, Option<Value>)>,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(model: &'a Model) -> Self {
+        Self {
+            model,
+            constructors: BTreeMap::new(),
+            selectors: BTreeMap::new(),
+            locals: Vec::new(),
+        }
+    }
+
+    /// Record the constructors and selectors of the datatypes declared by the commands.
+    pub fn with_datatypes(mut self, commands: &[Command]) -> Self {
+        for command in commands {
+            let datatypes = match command {
+                Command::DeclareDatatype { datatype, .. } => vec![datatype],
+                Command::DeclareDatatypes { datatypes } => {
+                    datatypes.iter().map(|(_, _, datatype)| datatype).collect()
+                }
+                _ => continue,
+            };
+            for constructor in datatypes.into_iter().flat_map(|d| &d.constructors) {
+                let name = &constructor.symbol.0;
+                self.constructors
+                    .insert(name.clone(), constructor.selectors.len());
+                for (i, (selector, _)) in constructor.selectors.iter().enumerate() {
+                    self.selectors.insert(selector.0.clone(), (name.clone(), i));
+                }
+            }
+        }
+        self
+    }
+
+    /// Compute the value of a term. Conjunctions, disjunctions, and implications are
+    /// evaluated lazily, so that they may have values even when some operands do not.
+    pub fn evaluate(&mut self, term: &Term) -> Result<Value, EvaluationError> {
+        match term {
+            Term::Constant(constant) => Ok(constant_value(constant)),
+            Term::QualIdentifier(qual_identifier) => {
+                self.evaluate_application(term, qual_identifier, &[])
+            }
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } => self.evaluate_application(term, qual_identifier, arguments),
+            Term::Let { var_bindings, term } => {
+                let values = var_bindings
+                    .iter()
+                    .map(|(symbol, value)| Ok((symbol.0.clone(), Some(self.evaluate(value)?))))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.with_locals(values, |evaluator| evaluator.evaluate(term))
+            }
+            Term::Match {
+                term: scrutinee,
+                cases,
+            } => {
+                let value = self.evaluate(scrutinee)?;
+                for (pattern, body) in cases {
+                    if let Some(bindings) = self.match_pattern(pattern, &value) {
+                        return self.with_locals(bindings, |evaluator| evaluator.evaluate(body));
+                    }
+                }
+                Err(EvaluationError::IllSorted(term.clone()))
+            }
+            Term::Attributes { term, .. } => self.evaluate(term),
+            Term::Forall { .. } | Term::Exists { .. } => {
+                Err(EvaluationError::Unsupported(term.clone()))
+            }
+        }
+    }
+
+    /// Replace the subterms that can be evaluated by their values, leaving the others,
+    /// such as the terms mentioning symbols without values, unchanged. Arrays are not
+    /// replaced since their values are not terms.
+    pub fn simplify(&mut self, term: &Term) -> Term {
+        let term = match term {
+            Term::Constant(_) | Term::QualIdentifier(_) => term.clone(),
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } => Term::Application {
+                qual_identifier: qual_identifier.clone(),
+                arguments: arguments.iter().map(|a| self.simplify(a)).collect(),
+            },
+            Term::Let { var_bindings, term } => {
+                let var_bindings = var_bindings
+                    .iter()
+                    .map(|(symbol, value)| (symbol.clone(), self.simplify(value)))
+                    .collect::<Vec<_>>();
+                let shadowed = var_bindings
+                    .iter()
+                    .map(|(symbol, _)| (symbol.0.clone(), None))
+                    .collect();
+                let term = self.with_locals(shadowed, |evaluator| evaluator.simplify(term));
+                Term::Let {
+                    var_bindings,
+                    term: Box::new(term),
+                }
+            }
+            Term::Forall { vars, term: body } | Term::Exists { vars, term: body } => {
+                let shadowed = vars.iter().map(|(symbol, _)| (symbol.0.clone(), None));
+                let body = Box::new(
+                    self.with_locals(shadowed.collect(), |evaluator| evaluator.simplify(body)),
+                );
+                let vars = vars.clone();
+                if matches!(term, Term::Forall { .. }) {
+                    Term::Forall { vars, term: body }
+                } else {
+                    Term::Exists { vars, term: body }
+                }
+            }
+            _ => term.clone(),
+        };
+        match self.evaluate(&term).ok().and_then(|value| value.to_term()) {
+            Some(value) => value,
+            None => term,
+        }
+    }
+
+    fn with_locals<T, F>(&mut self, locals: Vec<(String, Option<Value>)>, f: F) -> T
+    where
+        F: FnOnce(&mut Self) -> T,
+    {
+        let count = locals.len();
+        self.locals.extend(locals);
+        let result = f(self);
+        self.locals.truncate(self.locals.len() - count);
+        result
+    }
+
+    fn match_pattern(
+        &self,
+        pattern: &[crate::concrete::Symbol],
+        value: &Value,
+    ) -> Option<Vec<(String, Option<Value>)>> {
+        let (constructor, arguments) = match value {
+            Value::Datatype {
+                constructor,
+                arguments,
+            } => (constructor, arguments),
+            _ => return None,
+        };
+        match pattern {
+            [symbol] if symbol.0 == *constructor && arguments.is_empty() => Some(Vec::new()),
+            [symbol] if !self.constructors.contains_key(&symbol.0) => {
+                Some(vec![(symbol.0.clone(), Some(value.clone()))])
+            }
+            [symbol, variables @ ..]
+                if symbol.0 == *constructor && variables.len() == arguments.len() =>
+            {
+                Some(
+                    variables
+                        .iter()
+                        .zip(arguments)
+                        .map(|(variable, value)| (variable.0.clone(), Some(value.clone())))
+                        .collect(),
+                )
+            }
+            _ => None,
+        }
+    }
+
+    fn evaluate_application(
+        &mut self,
+        term: &Term,
+        qual_identifier: &QualIdentifier,
+        arguments: &[Term],
+    ) -> Result<Value, EvaluationError> {
+        let ill_sorted = || EvaluationError::IllSorted(term.clone());
+        let (identifier, sorted) = match qual_identifier {
+            QualIdentifier::Simple { identifier } => (identifier, false),
+            QualIdentifier::Sorted { identifier, .. } => (identifier, true),
+        };
+        let (name, indices) = match identifier {
+            Identifier::Simple { symbol } => (symbol.0.as_str(), &[][..]),
+            Identifier::Indexed { symbol, indices } => (symbol.0.as_str(), indices.as_slice()),
+        };
+
+        // Lazy Boolean operators.
+        match name {
+            "and" | "or" => {
+                let short_circuit = name == "or";
+                let mut error = None;
+                for argument in arguments {
+                    match self.evaluate(argument).and_then(|v| as_bool(&v, term)) {
+                        Ok(b) if b == short_circuit => return Ok(Value::Bool(b)),
+                        Ok(_) => (),
+                        Err(e) => error = error.or(Some(e)),
+                    }
+                }
+                return match error {
+                    Some(error) => Err(error),
+                    None => Ok(Value::Bool(!short_circuit)),
+                };
+            }
+            "=>" if !arguments.is_empty() => {
+                // Right-associative: the implication holds if the conclusion holds or
+                // some premise does not.
+                let (premises, conclusion) = arguments.split_at(arguments.len() - 1);
+                let mut error = None;
+                match self
+                    .evaluate(&conclusion[0])
+                    .and_then(|v| as_bool(&v, term))
+                {
+                    Ok(true) => return Ok(Value::Bool(true)),
+                    Ok(false) => (),
+                    Err(e) => error = Some(e),
+                }
+                for premise in premises {
+                    match self.evaluate(premise).and_then(|v| as_bool(&v, term)) {
+                        Ok(false) => return Ok(Value::Bool(true)),
+                        Ok(true) => (),
+                        Err(e) => error = error.or(Some(e)),
+                    }
+                }
+                return match error {
+                    Some(error) => Err(error),
+                    None => Ok(Value::Bool(false)),
+                };
+            }
+            "ite" if arguments.len() == 3 => {
+                let condition = as_bool(&self.evaluate(&arguments[0])?, term)?;
+                return self.evaluate(&arguments[if condition { 1 } else { 2 }]);
+            }
+            _ => (),
+        }
+
+        // Variables and constants.
+        if arguments.is_empty() && indices.is_empty() {
+            if let Some((_, value)) = self.locals.iter().rev().find(|(local, _)| local == name) {
+                return value
+                    .clone()
+                    .ok_or_else(|| EvaluationError::Unassigned(name.to_string()));
+            }
+            if let Some(value) = self.model.get(name) {
+                return Ok(value.clone());
+            }
+        }
+
+        let values = arguments
+            .iter()
+            .map(|argument| self.evaluate(argument))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Functions defined by the model.
+        if let Some((sig, body)) = self.model.function(name) {
+            if sig.parameters.len() != values.len() {
+                return Err(ill_sorted());
+            }
+            // The body of a definition only refers to its parameters.
+            let locals = sig
+                .parameters
+                .iter()
+                .zip(values)
+                .map(|((symbol, _), value)| (symbol.0.clone(), Some(value)))
+                .collect();
+            let saved = std::mem::replace(&mut self.locals, locals);
+            let result = self.evaluate(body);
+            self.locals = saved;
+            return result;
+        }
+
+        match (name, indices, values.as_slice()) {
+            ("true", [], []) => Ok(Value::Bool(true)),
+            ("false", [], []) => Ok(Value::Bool(false)),
+            ("not", [], [a]) => Ok(Value::Bool(!as_bool(a, term)?)),
+            ("xor", [], values) => values.iter().try_fold(Value::Bool(false), |acc, v| {
+                Ok(Value::Bool(as_bool(&acc, term)? != as_bool(v, term)?))
+            }),
+            ("=", [], values) if !values.is_empty() => Ok(Value::Bool(
+                values
+                    .windows(2)
+                    .all(|pair| values_equal(&pair[0], &pair[1])),
+            )),
+            ("distinct", [], values) => {
+                Ok(Value::Bool(values.iter().enumerate().all(|(i, a)| {
+                    values[i + 1..].iter().all(|b| !values_equal(a, b))
+                })))
+            }
+
+            // Arithmetic.
+            ("+" | "*", [], values) if !values.is_empty() => {
+                let add = name == "+";
+                arithmetic(values, term, |a, b| if add { a + b } else { a * b })
+            }
+            ("-", [], [a]) => match a {
+                Value::Int(n) => Ok(Value::Int(-n)),
+                Value::Real(r) => Ok(Value::Real(-r)),
+                _ => Err(ill_sorted()),
+            },
+            ("-", [], values) if values.len() > 1 => arithmetic(values, term, |a, b| a - b),
+            ("/", [], values) if values.len() > 1 => {
+                let mut result = as_rational(&values[0], term)?;
+                for value in &values[1..] {
+                    let divisor = as_rational(value, term)?;
+                    if divisor.is_zero() {
+                        return Err(EvaluationError::DivisionByZero(term.clone()));
+                    }
+                    result /= divisor;
+                }
+                Ok(Value::Real(result))
+            }
+            ("div" | "mod", [], [a, b]) => {
+                let (a, b) = (as_int(a, term)?, as_int(b, term)?);
+                if b.is_zero() {
+                    return Err(EvaluationError::DivisionByZero(term.clone()));
+                }
+                // Euclidean division: the remainder is never negative.
+                let remainder = a.mod_floor(&b.abs());
+                if name == "mod" {
+                    Ok(Value::Int(remainder))
+                } else {
+                    Ok(Value::Int((a - remainder) / b))
+                }
+            }
+            ("abs", [], [a]) => Ok(Value::Int(as_int(a, term)?.abs())),
+            ("<" | "<=" | ">" | ">=", [], values) if values.len() > 1 => {
+                let values = values
+                    .iter()
+                    .map(|v| as_rational(v, term))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Bool(values.windows(2).all(|pair| match name {
+                    "<" => pair[0] < pair[1],
+                    "<=" => pair[0] <= pair[1],
+                    ">" => pair[0] > pair[1],
+                    _ => pair[0] >= pair[1],
+                })))
+            }
+            ("to_real", [], [a]) => Ok(Value::Real(as_rational(a, term)?)),
+            ("to_int", [], [a]) => Ok(Value::Int(as_rational(a, term)?.floor().to_integer())),
+            ("is_int", [], [a]) => Ok(Value::Bool(as_rational(a, term)?.is_integer())),
+
+            // Arrays.
+            ("const", [], [default]) if sorted => Ok(Value::Array {
+                entries: BTreeMap::new(),
+                default: Some(Box::new(default.clone())),
+            }),
+            ("select", [], [array, index]) => match array {
+                Value::Array { .. } => array
+                    .select(index)
+                    .cloned()
+                    .ok_or_else(|| EvaluationError::Unassigned(format!("{}", term))),
+                _ => Err(ill_sorted()),
+            },
+            ("store", [], [array @ Value::Array { .. }, index, element]) => {
+                Ok(array.clone().store(index.clone(), element.clone()))
+            }
+
+            // Bit-vectors.
+            (bv, [Index::Numeral(width)], []) if bv.starts_with("bv") => Ok(Value::BitVec {
+                width: width.to_usize().ok_or_else(ill_sorted)?,
+                value: bv[2..].parse().map_err(|_| ill_sorted())?,
+            }),
+            ("extract", [Index::Numeral(i), Index::Numeral(j)], [a]) => {
+                let (_, value) = as_bit_vec(a, term)?;
+                let (i, j) = (
+                    i.to_usize().ok_or_else(ill_sorted)?,
+                    j.to_usize().ok_or_else(ill_sorted)?,
+                );
+                if j > i {
+                    return Err(ill_sorted());
+                }
+                Ok(bit_vec(i - j + 1, value >> j))
+            }
+            ("concat", [], [a, b]) => {
+                let ((high_width, high), (low_width, low)) =
+                    (as_bit_vec(a, term)?, as_bit_vec(b, term)?);
+                Ok(bit_vec(high_width + low_width, (high << low_width) | low))
+            }
+            ("bvnot", [], [a]) => {
+                let (width, a) = as_bit_vec(a, term)?;
+                Ok(bit_vec(width, mask(width) ^ a))
+            }
+            ("bvneg", [], [a]) => {
+                let (width, a) = as_bit_vec(a, term)?;
+                Ok(bit_vec(width, (mask(width) ^ a) + 1u32))
+            }
+            (
+                "bvadd" | "bvsub" | "bvmul" | "bvand" | "bvor" | "bvxor" | "bvudiv" | "bvurem"
+                | "bvshl" | "bvlshr",
+                [],
+                [a, b],
+            ) => {
+                let ((width, a), (other_width, b)) = (as_bit_vec(a, term)?, as_bit_vec(b, term)?);
+                if width != other_width {
+                    return Err(ill_sorted());
+                }
+                let value = match name {
+                    "bvadd" => a + b,
+                    // Add 2^width to stay positive.
+                    "bvsub" => (a + (Numeral::one() << width)) - b,
+                    "bvmul" => a * b,
+                    "bvand" => a & b,
+                    "bvor" => a | b,
+                    "bvxor" => a ^ b,
+                    // Division by zero is all ones, and the remainder is the dividend.
+                    "bvudiv" if b.is_zero() => mask(width),
+                    "bvudiv" => a / b,
+                    "bvurem" if b.is_zero() => a,
+                    "bvurem" => a % b,
+                    "bvshl" => match b.to_usize() {
+                        Some(shift) if shift < width => a << shift,
+                        _ => Numeral::zero(),
+                    },
+                    _ => match b.to_usize() {
+                        Some(shift) if shift < width => a >> shift,
+                        _ => Numeral::zero(),
+                    },
+                };
+                Ok(bit_vec(width, value))
+            }
+            ("bvult" | "bvule" | "bvugt" | "bvuge", [], [a, b]) => {
+                let ((_, a), (_, b)) = (as_bit_vec(a, term)?, as_bit_vec(b, term)?);
+                Ok(Value::Bool(match name {
+                    "bvult" => a < b,
+                    "bvule" => a <= b,
+                    "bvugt" => a > b,
+                    _ => a >= b,
+                }))
+            }
+
+            // Datatypes.
+            ("is", [Index::Symbol(constructor)], [value]) => {
+                is_constructor(value, &constructor.0, term)
+            }
+            (tester, [], [value]) if tester.starts_with("is-") => {
+                is_constructor(value, &tester[3..], term)
+            }
+            (selector, [], [value]) if self.selectors.contains_key(selector) => {
+                let (constructor, position) = &self.selectors[selector];
+                match value {
+                    Value::Datatype {
+                        constructor: actual,
+                        arguments,
+                    } if actual == constructor => Ok(arguments[*position].clone()),
+                    // Selectors of other constructors are unspecified.
+                    Value::Datatype { .. } => Err(EvaluationError::Unassigned(term.to_string())),
+                    _ => Err(ill_sorted()),
+                }
+            }
+            (constructor, [], values)
+                if self.constructors.get(constructor) == Some(&values.len()) =>
+            {
+                Ok(Value::Datatype {
+                    constructor: constructor.to_string(),
+                    arguments: values.to_vec(),
+                })
+            }
+
+            (name, [], []) => Err(EvaluationError::Unassigned(name.to_string())),
+            _ => Err(EvaluationError::Unsupported(term.clone())),
+        }
+    }
+}
+
+impl Model {
+    /// Compute the value of a quantifier-free term in the model. See [`Evaluator`].
+    pub fn evaluate(&self, term: &Term) -> Result<Value, EvaluationError> {
+        Evaluator::new(self).evaluate(term)
+    }
+}
+
+fn as_bool(value: &Value, term: &Term) -> Result<bool, EvaluationError> {
+    match value {
+        Value::Bool(b) => Ok(*b),
+        _ => Err(EvaluationError::IllSorted(term.clone())),
+    }
+}
+
+fn as_int(value: &Value, term: &Term) -> Result<BigInt, EvaluationError> {
+    match value {
+        Value::Int(n) => Ok(n.clone()),
+        _ => Err(EvaluationError::IllSorted(term.clone())),
+    }
+}
+
+fn as_rational(value: &Value, term: &Term) -> Result<BigRational, EvaluationError> {
+    match value {
+        Value::Int(n) => Ok(BigRational::from_integer(n.clone())),
+        Value::Real(r) => Ok(r.clone()),
+        _ => Err(EvaluationError::IllSorted(term.clone())),
+    }
+}
+
+fn as_bit_vec(value: &Value, term: &Term) -> Result<(usize, Numeral), EvaluationError> {
+    match value {
+        Value::BitVec { width, value } => Ok((*width, value.clone())),
+        _ => Err(EvaluationError::IllSorted(term.clone())),
+    }
+}
+
+fn mask(width: usize) -> Numeral {
+    (Numeral::one() << width) - 1u32
+}
+
+fn bit_vec(width: usize, value: Numeral) -> Value {
+    Value::BitVec {
+        width,
+        value: value & mask(width),
+    }
+}
+
+// Apply an operation on integers or reals from left to right. The result is an integer
+// if all the operands are.
+fn arithmetic<F>(values: &[Value], term: &Term, op: F) -> Result<Value, EvaluationError>
+where
+    F: Fn(BigRational, BigRational) -> BigRational,
+{
+    let integers = values.iter().all(|v| matches!(v, Value::Int(_)));
+    let mut result = as_rational(&values[0], term)?;
+    for value in &values[1..] {
+        result = op(result, as_rational(value, term)?);
+    }
+    if integers {
+        Ok(Value::Int(result.to_integer()))
+    } else {
+        Ok(Value::Real(result))
+    }
+}
+
+fn is_constructor(value: &Value, name: &str, term: &Term) -> Result<Value, EvaluationError> {
+    match value {
+        Value::Datatype { constructor, .. } => Ok(Value::Bool(constructor == name)),
+        _ => Err(EvaluationError::IllSorted(term.clone())),
+    }
+}
+
+// Values are equal, comparing arrays extensionally when their default values are known.
+fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (
+            Value::Array {
+                entries: a_entries,
+                default: Some(a_default),
+            },
+            Value::Array {
+                entries: b_entries,
+                default: Some(b_default),
+            },
+        ) => {
+            values_equal(a_default, b_default)
+                && a_entries.keys().chain(b_entries.keys()).all(|index| {
+                    match (a.select(index), b.select(index)) {
+                        (Some(x), Some(y)) => values_equal(x, y),
+                        _ => false,
+                    }
+                })
+        }
+        (
+            Value::Datatype {
+                constructor: c,
+                arguments: x,
+            },
+            Value::Datatype {
+                constructor: d,
+                arguments: y,
+            },
+        ) => c == d && x.len() == y.len() && x.iter().zip(y).all(|(x, y)| values_equal(x, y)),
+        (Value::Int(n), Value::Real(r)) | (Value::Real(r), Value::Int(n)) => {
+            BigRational::from_integer(n.clone()) == *r
+        }
+        (a, b) => a == b,
+    }
+}
+
+#[test]
+fn test_evaluate() {
+    let model = Model::parse(
+        r#"(
+(define-fun x () Int (- 7))
+(define-fun r () Real (/ 1.0 2.0))
+(define-fun b () (_ BitVec 4) #b1010)
+(define-fun a () (Array Int Int) (store ((as const (Array Int Int)) 0) 1 5))
+(define-fun o () Opt (some 3))
+(define-fun f ((x!0 Int)) Int (ite (= x!0 1) 2 0))
+)"#,
+    )
+    .unwrap();
+    let datatypes =
+        crate::parse_commands("(declare-datatypes ((Opt 0)) (((none) (some (val Int)))))").unwrap();
+    let mut evaluator = Evaluator::new(&model).with_datatypes(&datatypes);
+    let mut eval = |input: &str| {
+        evaluator
+            .evaluate(&crate::parse_term(input).unwrap())
+            .map(|value| value.to_string())
+    };
+    assert_eq!(eval("(+ x 1 (* 2 3))"), Ok("0".into()));
+    assert_eq!(eval("(div x 2)"), Ok("(- 4)".into()));
+    assert_eq!(eval("(mod x 2)"), Ok("1".into()));
+    assert_eq!(eval("(+ r 1)"), Ok("(/ 3 2)".into()));
+    assert_eq!(eval("(< x r 1)"), Ok("true".into()));
+    assert_eq!(eval("(bvadd b #b1000)"), Ok("(_ bv2 4)".into()));
+    assert_eq!(eval("((_ extract 3 2) b)"), Ok("(_ bv2 2)".into()));
+    assert_eq!(eval("(concat b #b1)"), Ok("(_ bv21 5)".into()));
+    assert_eq!(eval("(bvneg #b0001)"), Ok("(_ bv15 4)".into()));
+    assert_eq!(eval("(select a 1)"), Ok("5".into()));
+    assert_eq!(
+        eval("(= a (store ((as const (Array Int Int)) 0) 1 (+ 2 3)))"),
+        Ok("true".into())
+    );
+    assert_eq!(eval("(f (+ 0 1))"), Ok("2".into()));
+    assert_eq!(eval("(let ((x 1)) (f x))"), Ok("2".into()));
+    assert_eq!(eval("(val o)"), Ok("3".into()));
+    assert_eq!(eval("((_ is none) o)"), Ok("false".into()));
+    assert_eq!(
+        eval("(match o ((none 0) ((some v) (+ v 1))))"),
+        Ok("4".into())
+    );
+    assert_eq!(eval("(or (> y 0) (= x (- 7)))"), Ok("true".into()));
+    assert_eq!(
+        eval("(and (> y 0) true)"),
+        Err(EvaluationError::Unassigned("y".into()))
+    );
+    assert!(matches!(
+        eval("(div x 0)"),
+        Err(EvaluationError::DivisionByZero(_))
+    ));
+
+    let term = crate::parse_term("(and (> y (+ x 1)) (forall ((x Int)) (> x (f 1))))").unwrap();
+    assert_eq!(
+        Evaluator::new(&model).simplify(&term).to_string(),
+        "(and (> y (- 6)) (forall ((x Int)) (> x 2)))"
+    );
+}
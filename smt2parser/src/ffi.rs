@@ -0,0 +1,218 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! C bindings for parsing, unrolling, and abstracting VMT models, declared in
+//! `include/smt2parser.h`:
+//!
+//! ```c
+//! char *error = NULL;
+//! smt2_vmt_model *model = smt2_vmt_parse(text, &error);
+//! if (model == NULL) {
+//!     fprintf(stderr, "%s\n", error);
+//!     smt2_string_free(error);
+//!     return 1;
+//! }
+//! char *script = smt2_vmt_unroll(model, 10);
+//! /* ... */
+//! smt2_string_free(script);
+//! smt2_vmt_free(model);
+//! ```
+//!
+//! Models are opaque handles owned by the caller. Strings returned by the library are
+//! owned by the caller and must be released with `smt2_string_free`. On failure,
+//! functions return `NULL` and, if `error` is not `NULL`, store an error message in
+//! `*error`.
+
+use std::{
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    panic::{catch_unwind, AssertUnwindSafe},
+};
+
+use crate::{
+    abstraction::{array_axioms, ArrayAbstractor},
+    concrete::Command,
+    vmt::VMTModel,
+};
+
+/// Opaque handle on a VMT model.
+#[allow(non_camel_case_types)]
+pub struct smt2_vmt_model {
+    model: VMTModel,
+}
+
+// Run `f`, turning errors and panics into a message stored in `error`.
+unsafe fn guard<T, F>(error: *mut *mut c_char, f: F) -> Option<T>
+where
+    F: FnOnce() -> Result<T, String>,
+{
+    let message = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => return Some(value),
+        Ok(Err(message)) => message,
+        Err(panic) => panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "internal error".to_string()),
+    };
+    if !error.is_null() {
+        *error = into_c_string(message);
+    }
+    None
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+    // SMT-LIB strings may not contain NUL characters, but error messages could.
+    let s = CString::new(s.replace('\0', "")).expect("NUL characters were removed");
+    s.into_raw()
+}
+
+fn print_commands(commands: &[Command]) -> String {
+    commands
+        .iter()
+        .map(|command| command.to_string() + "\n")
+        .collect()
+}
+
+/// Parse a VMT model from NUL-terminated SMT-LIB text.
+///
+/// # Safety
+/// `text` must be a valid NUL-terminated string. `error` must be `NULL` or a valid
+/// pointer.
+#[no_mangle]
+pub unsafe extern "C" fn smt2_vmt_parse(
+    text: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut smt2_vmt_model {
+    if text.is_null() {
+        guard::<(), _>(error, || Err("null input".to_string()));
+        return std::ptr::null_mut();
+    }
+    let text = CStr::from_ptr(text);
+    guard(error, || {
+        let text = text.to_str().map_err(|e| e.to_string())?;
+        let commands = crate::parse_commands(text).map_err(|e| e.to_string())?;
+        let model = VMTModel::checked_from(commands).map_err(|e| e.to_string())?;
+        Ok(Box::into_raw(Box::new(smt2_vmt_model { model })))
+    })
+    .unwrap_or(std::ptr::null_mut())
+}
+
+/// Release a VMT model. Does nothing if `model` is `NULL`.
+///
+/// # Safety
+/// `model` must be `NULL` or a handle returned by this library that was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn smt2_vmt_free(model: *mut smt2_vmt_model) {
+    if !model.is_null() {
+        drop(Box::from_raw(model));
+    }
+}
+
+/// Print a VMT model as SMT-LIB text.
+///
+/// # Safety
+/// `model` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn smt2_vmt_to_string(model: *const smt2_vmt_model) -> *mut c_char {
+    let model = &(*model).model;
+    guard(std::ptr::null_mut(), || {
+        Ok(into_c_string(print_commands(&model.to_commands())))
+    })
+    .unwrap_or(std::ptr::null_mut())
+}
+
+/// SMT-LIB script checking the property of a VMT model up to the given bound, without
+/// `check-sat`.
+///
+/// # Safety
+/// `model` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn smt2_vmt_unroll(model: *const smt2_vmt_model, bound: u8) -> *mut c_char {
+    let model = &(*model).model;
+    guard(std::ptr::null_mut(), || {
+        Ok(into_c_string(model.unroll(bound).to_smtlib2()))
+    })
+    .unwrap_or(std::ptr::null_mut())
+}
+
+/// Abstract the arrays of a VMT model with uninterpreted functions. Return a new model
+/// and, if `axioms` is not `NULL`, store the array axioms in `*axioms` as SMT-LIB
+/// assertions, one per line.
+///
+/// # Safety
+/// `model` must be a valid handle. `axioms` and `error` must be `NULL` or valid pointers.
+#[no_mangle]
+pub unsafe extern "C" fn smt2_vmt_abstract_arrays(
+    model: *const smt2_vmt_model,
+    axioms: *mut *mut c_char,
+    error: *mut *mut c_char,
+) -> *mut smt2_vmt_model {
+    let model = &(*model).model;
+    let result = guard(error, || {
+        let commands =
+            ArrayAbstractor::abstract_commands(model.to_commands()).map_err(|e| e.to_string())?;
+        let array_axioms = array_axioms(&commands);
+        let model = VMTModel::checked_from(commands).map_err(|e| e.to_string())?;
+        Ok((model, print_commands(&array_axioms)))
+    });
+    match result {
+        Some((model, array_axioms)) => {
+            if !axioms.is_null() {
+                *axioms = into_c_string(array_axioms);
+            }
+            Box::into_raw(Box::new(smt2_vmt_model { model }))
+        }
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// Release a string returned by this library. Does nothing if `s` is `NULL`.
+///
+/// # Safety
+/// `s` must be `NULL` or a string returned by this library that was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn smt2_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[test]
+fn test_ffi() {
+    let text = CString::new(
+        r#"
+(declare-fun x () (Array Int Int))
+(declare-fun x_next () (Array Int Int))
+(define-fun .x () (Array Int Int) (! x :next x_next))
+(define-fun init () Bool (! (= (select x 0) 0) :init true))
+(define-fun trans () Bool (! (= x_next (store x 0 (+ (select x 0) 1))) :trans true))
+(define-fun property () Bool (! (>= (select x 0) 0) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let take = |s: *mut c_char| unsafe {
+        let result = CStr::from_ptr(s).to_str().unwrap().to_string();
+        smt2_string_free(s);
+        result
+    };
+    unsafe {
+        let mut error = std::ptr::null_mut();
+        let model = smt2_vmt_parse(text.as_ptr(), &mut error);
+        assert!(!model.is_null());
+        assert!(error.is_null());
+        assert!(take(smt2_vmt_unroll(model, 1)).contains("(select x@1 0)"));
+
+        let mut axioms = std::ptr::null_mut();
+        let abstraction = smt2_vmt_abstract_arrays(model, &mut axioms, &mut error);
+        assert!(!abstraction.is_null());
+        assert!(take(axioms).contains("Read_Int_Int"));
+        assert!(!take(smt2_vmt_to_string(abstraction)).contains("select"));
+        smt2_vmt_free(abstraction);
+        smt2_vmt_free(model);
+
+        let invalid = CString::new("(assert").unwrap();
+        assert!(smt2_vmt_parse(invalid.as_ptr(), &mut error).is_null());
+        assert!(take(error).contains("unexpected end of input"));
+    }
+}
@@ -0,0 +1,377 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Hash-consing of terms.
+
+use crate::concrete::{AttributeValue, Constant, Keyword, QualIdentifier, Sort, Symbol, Term};
+use itertools::Itertools;
+use std::collections::HashMap;
+
+/// Index of a term in a [`TermPool`]. Two indices of the same pool are equal if and only
+/// if the terms are structurally equal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TermId(u32);
+
+impl TermId {
+    /// Position of the term in the pool, in order of creation.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A term whose subterms are stored in a [`TermPool`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum TermNode {
+    Constant(Constant),
+    QualIdentifier(QualIdentifier),
+    Application {
+        qual_identifier: QualIdentifier,
+        arguments: Vec<TermId>,
+    },
+    Let {
+        var_bindings: Vec<(Symbol, TermId)>,
+        term: TermId,
+    },
+    Forall {
+        vars: Vec<(Symbol, Sort)>,
+        term: TermId,
+    },
+    Exists {
+        vars: Vec<(Symbol, Sort)>,
+        term: TermId,
+    },
+    Match {
+        term: TermId,
+        cases: Vec<(Vec<Symbol>, TermId)>,
+    },
+    Attributes {
+        term: TermId,
+        attributes: Vec<(Keyword, AttributeValue)>,
+    },
+}
+
+/// Arena storing each distinct term once. Subterms are shared, so that equal terms are
+/// compared in constant time and common subterms are stored once.
+#[derive(Debug, Default, Clone)]
+pub struct TermPool {
+    nodes: Vec<TermNode>,
+    ids: HashMap<TermNode, TermId>,
+}
+
+impl TermPool {
+    /// Return the index of a node, adding the node to the pool if needed.
+    pub fn add(&mut self, node: TermNode) -> TermId {
+        if let Some(id) = self.ids.get(&node) {
+            return *id;
+        }
+        let id = TermId(self.nodes.len() as u32);
+        self.nodes.push(node.clone());
+        self.ids.insert(node, id);
+        id
+    }
+
+    /// Add a term and all its subterms to the pool.
+    pub fn intern(&mut self, term: &Term) -> TermId {
+        let node = match term {
+            Term::Constant(constant) => TermNode::Constant(constant.clone()),
+            Term::QualIdentifier(qual_identifier) => {
+                TermNode::QualIdentifier(qual_identifier.clone())
+            }
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } => TermNode::Application {
+                qual_identifier: qual_identifier.clone(),
+                arguments: arguments.iter().map(|a| self.intern(a)).collect(),
+            },
+            Term::Let { var_bindings, term } => TermNode::Let {
+                var_bindings: var_bindings
+                    .iter()
+                    .map(|(symbol, t)| (symbol.clone(), self.intern(t)))
+                    .collect(),
+                term: self.intern(term),
+            },
+            Term::Forall { vars, term } => TermNode::Forall {
+                vars: vars.clone(),
+                term: self.intern(term),
+            },
+            Term::Exists { vars, term } => TermNode::Exists {
+                vars: vars.clone(),
+                term: self.intern(term),
+            },
+            Term::Match { term, cases } => TermNode::Match {
+                term: self.intern(term),
+                cases: cases
+                    .iter()
+                    .map(|(pattern, t)| (pattern.clone(), self.intern(t)))
+                    .collect(),
+            },
+            Term::Attributes { term, attributes } => TermNode::Attributes {
+                term: self.intern(term),
+                attributes: attributes.clone(),
+            },
+        };
+        self.add(node)
+    }
+
+    /// Return the index of a term if it is in the pool.
+    pub fn get(&self, term: &Term) -> Option<TermId> {
+        let node = match term {
+            Term::Constant(constant) => TermNode::Constant(constant.clone()),
+            Term::QualIdentifier(qual_identifier) => {
+                TermNode::QualIdentifier(qual_identifier.clone())
+            }
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } => TermNode::Application {
+                qual_identifier: qual_identifier.clone(),
+                arguments: arguments
+                    .iter()
+                    .map(|a| self.get(a))
+                    .collect::<Option<_>>()?,
+            },
+            Term::Let { var_bindings, term } => TermNode::Let {
+                var_bindings: var_bindings
+                    .iter()
+                    .map(|(symbol, t)| Some((symbol.clone(), self.get(t)?)))
+                    .collect::<Option<_>>()?,
+                term: self.get(term)?,
+            },
+            Term::Forall { vars, term } => TermNode::Forall {
+                vars: vars.clone(),
+                term: self.get(term)?,
+            },
+            Term::Exists { vars, term } => TermNode::Exists {
+                vars: vars.clone(),
+                term: self.get(term)?,
+            },
+            Term::Match { term, cases } => TermNode::Match {
+                term: self.get(term)?,
+                cases: cases
+                    .iter()
+                    .map(|(pattern, t)| Some((pattern.clone(), self.get(t)?)))
+                    .collect::<Option<_>>()?,
+            },
+            Term::Attributes { term, attributes } => TermNode::Attributes {
+                term: self.get(term)?,
+                attributes: attributes.clone(),
+            },
+        };
+        self.ids.get(&node).copied()
+    }
+
+    /// Return the node of a term of the pool.
+    pub fn node(&self, id: TermId) -> &TermNode {
+        &self.nodes[id.index()]
+    }
+
+    /// Rebuild the owned term of an index.
+    pub fn to_term(&self, id: TermId) -> Term {
+        match self.node(id) {
+            TermNode::Constant(constant) => Term::Constant(constant.clone()),
+            TermNode::QualIdentifier(qual_identifier) => {
+                Term::QualIdentifier(qual_identifier.clone())
+            }
+            TermNode::Application {
+                qual_identifier,
+                arguments,
+            } => Term::Application {
+                qual_identifier: qual_identifier.clone(),
+                arguments: arguments.iter().map(|a| self.to_term(*a)).collect(),
+            },
+            TermNode::Let { var_bindings, term } => Term::Let {
+                var_bindings: var_bindings
+                    .iter()
+                    .map(|(symbol, t)| (symbol.clone(), self.to_term(*t)))
+                    .collect(),
+                term: Box::new(self.to_term(*term)),
+            },
+            TermNode::Forall { vars, term } => Term::Forall {
+                vars: vars.clone(),
+                term: Box::new(self.to_term(*term)),
+            },
+            TermNode::Exists { vars, term } => Term::Exists {
+                vars: vars.clone(),
+                term: Box::new(self.to_term(*term)),
+            },
+            TermNode::Match { term, cases } => Term::Match {
+                term: Box::new(self.to_term(*term)),
+                cases: cases
+                    .iter()
+                    .map(|(pattern, t)| (pattern.clone(), self.to_term(*t)))
+                    .collect(),
+            },
+            TermNode::Attributes { term, attributes } => Term::Attributes {
+                term: Box::new(self.to_term(*term)),
+                attributes: attributes.clone(),
+            },
+        }
+    }
+
+    /// Number of distinct terms in the pool.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Graphviz (DOT) description of the terms of the pool, with a node per distinct term
+    /// and edges to its subterms. Edges are labeled by the position of the argument, the
+    /// variable of the binding, or the pattern of the case, when there are several.
+    pub fn to_dot(&self) -> String {
+        let mut dot = "digraph term {\n".to_string();
+        for (index, node) in self.nodes.iter().enumerate() {
+            let (label, children) = match node {
+                TermNode::Constant(constant) => (constant.to_string(), Vec::new()),
+                TermNode::QualIdentifier(qual_identifier) => {
+                    (qual_identifier.to_string(), Vec::new())
+                }
+                TermNode::Application {
+                    qual_identifier,
+                    arguments,
+                } => {
+                    let labeled = arguments.len() > 1;
+                    let children = arguments
+                        .iter()
+                        .enumerate()
+                        .map(|(i, id)| (labeled.then(|| i.to_string()), *id))
+                        .collect();
+                    (qual_identifier.to_string(), children)
+                }
+                TermNode::Let { var_bindings, term } => {
+                    let mut children = var_bindings
+                        .iter()
+                        .map(|(symbol, id)| (Some(symbol.to_string()), *id))
+                        .collect::<Vec<_>>();
+                    children.push((None, *term));
+                    ("let".to_string(), children)
+                }
+                TermNode::Forall { vars, term } => {
+                    (format!("forall {}", sorted_vars(vars)), vec![(None, *term)])
+                }
+                TermNode::Exists { vars, term } => {
+                    (format!("exists {}", sorted_vars(vars)), vec![(None, *term)])
+                }
+                TermNode::Match { term, cases } => {
+                    let mut children = vec![(None, *term)];
+                    children.extend(cases.iter().map(|(pattern, id)| {
+                        let pattern = match pattern.as_slice() {
+                            [constructor] => constructor.to_string(),
+                            _ => format!("({})", pattern.iter().format(" ")),
+                        };
+                        (Some(pattern), *id)
+                    }));
+                    ("match".to_string(), children)
+                }
+                TermNode::Attributes { term, attributes } => {
+                    let label = attributes
+                        .iter()
+                        .format_with("", |(key, value), f| f(&format_args!(" {} {}", key, value)));
+                    (format!("!{}", label), vec![(None, *term)])
+                }
+            };
+            dot += &format!("  n{} [label=\"{}\"];\n", index, escape(&label));
+            for (label, id) in children {
+                match label {
+                    Some(label) => {
+                        dot += &format!(
+                            "  n{} -> n{} [label=\"{}\"];\n",
+                            index,
+                            id.index(),
+                            escape(&label)
+                        )
+                    }
+                    None => dot += &format!("  n{} -> n{};\n", index, id.index()),
+                }
+            }
+        }
+        dot + "}\n"
+    }
+}
+
+fn sorted_vars(vars: &[(Symbol, Sort)]) -> String {
+    format!(
+        "({})",
+        vars.iter()
+            .format_with(" ", |(symbol, sort), f| f(&format_args!(
+                "({} {})",
+                symbol, sort
+            )))
+    )
+}
+
+// Escape a label of the DOT language.
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl Term {
+    /// Graphviz (DOT) description of the term as a DAG, where equal subterms are merged.
+    /// See [`TermPool::to_dot`].
+    pub fn to_dot(&self) -> String {
+        let mut pool = TermPool::default();
+        pool.intern(self);
+        pool.to_dot()
+    }
+}
+
+#[test]
+fn test_term_pool() {
+    let terms = crate::parse_assertions(
+        r#"
+(assert (and (= (f x) (g (f x))) (forall ((y Int)) (! (= (f x) y) :pattern ((f y))))))
+(assert (= (f x) (g (f x))))
+(assert (= (f y) (g (f x))))
+"#,
+    );
+    let mut pool = TermPool::default();
+    let ids = terms.iter().map(|t| pool.intern(t)).collect::<Vec<_>>();
+    // x, (f x), (g (f x)), (= (f x) (g (f x))), y, (= (f x) y), the attributed term, the
+    // quantifier, the conjunction, (f y), and (= (f y) (g (f x))).
+    assert_eq!(pool.len(), 11);
+    assert!(matches!(
+        pool.node(ids[0]),
+        TermNode::Application { arguments, .. } if arguments[0] == ids[1]
+    ));
+    assert_ne!(ids[1], ids[2]);
+    assert_eq!(pool.get(&terms[2]), Some(ids[2]));
+    for (term, id) in terms.iter().zip(ids) {
+        assert_eq!(&pool.to_term(id), term);
+    }
+    let z = Term::QualIdentifier(QualIdentifier::Simple {
+        identifier: crate::visitors::Identifier::Simple {
+            symbol: Symbol("z".into()),
+        },
+    });
+    assert_eq!(pool.get(&z), None);
+}
+
+#[test]
+fn test_to_dot() {
+    let term = crate::parse_term(r#"(let ((y (f x))) (and (= y (f x)) (P "a""b")))"#).unwrap();
+    assert_eq!(
+        term.to_dot(),
+        r#"digraph term {
+  n0 [label="x"];
+  n1 [label="f"];
+  n1 -> n0;
+  n2 [label="y"];
+  n3 [label="="];
+  n3 -> n2 [label="0"];
+  n3 -> n1 [label="1"];
+  n4 [label="\"a\"\"b\""];
+  n5 [label="P"];
+  n5 -> n4;
+  n6 [label="and"];
+  n6 -> n3 [label="0"];
+  n6 -> n5 [label="1"];
+  n7 [label="let"];
+  n7 -> n1 [label="y"];
+  n7 -> n6;
+}
+"#
+    );
+}
@@ -0,0 +1,488 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Inlining of the functions defined by `define-fun` and of the sorts defined by
+//! `define-sort`.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    concrete::{Command, Error, FunctionDec, QualIdentifier, Sort, Symbol, SyntaxBuilder, Term},
+    renaming::SymbolFactory,
+    rewriter::{free_symbols, Rewriter, SubstitutionRewriter},
+    typing::substitute_sort,
+    visitors::Identifier,
+};
+use num::ToPrimitive;
+
+/// Rewriter replacing the applications of defined functions and the occurrences of
+/// defined constants by the bodies of their definitions.
+///
+/// Only the definitions given to [`DefinitionInliner::define`] are inlined. Recursive
+/// definitions are not supported.
+#[derive(Debug, Default)]
+pub struct DefinitionInliner {
+    visitor: SyntaxBuilder,
+    // Parameters and inlined bodies of the functions with parameters.
+    functions: Functions,
+    // Inlined bodies of the constants.
+    constants: BTreeMap<String, Term>,
+    // Definitions saved by `push` commands.
+    scopes: Vec<(Functions, BTreeMap<String, Term>)>,
+}
+
+type Functions = BTreeMap<String, (Vec<String>, Term)>;
+
+impl DefinitionInliner {
+    /// Inline the definitions of a script and remove them, so that the result only
+    /// declares symbols. Definitions made after a `push` are forgotten by the matching
+    /// `pop`.
+    pub fn inline_commands(commands: Vec<Command>) -> Result<Vec<Command>, Error> {
+        let mut inliner = Self::default();
+        let mut result = Vec::new();
+        for command in commands {
+            match inliner.inline_command(command)? {
+                Command::DefineFun { sig, term } => inliner.define(sig, term),
+                command => {
+                    inliner.update_scopes(&command);
+                    result.push(command);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Inline the known definitions in a command.
+    pub fn inline_command(&mut self, command: Command) -> Result<Command, Error> {
+        match command {
+            Command::Assert { term } => Ok(Command::Assert {
+                term: self.inline_term(term, &[])?,
+            }),
+            Command::DefineFun { mut sig, term } => {
+                // Rename the parameters that would capture the symbols of the inlined
+                // constants.
+                let captured = self
+                    .constants
+                    .values()
+                    .flat_map(free_symbols)
+                    .collect::<Vec<_>>();
+                let mut symbols = SymbolFactory::default();
+                symbols.avoid_term(&term);
+                for symbol in &captured {
+                    symbols.avoid(symbol);
+                }
+                let mut renaming = BTreeMap::new();
+                for (parameter, _) in &mut sig.parameters {
+                    if captured.contains(&parameter.0) {
+                        let name = symbols.fresh(&parameter.0);
+                        renaming.insert(parameter.0.clone(), variable_term(&name));
+                        parameter.0 = name;
+                    }
+                }
+                let term = SubstitutionRewriter::new(renaming).substitute(term)?;
+                let parameters = sig
+                    .parameters
+                    .iter()
+                    .map(|(symbol, _)| symbol.0.clone())
+                    .collect::<Vec<_>>();
+                let term = self.inline_term(term, &parameters)?;
+                Ok(Command::DefineFun { sig, term })
+            }
+            command => command.accept(self),
+        }
+    }
+
+    /// Record a definition, whose body is already inlined.
+    pub fn define(&mut self, sig: FunctionDec, term: Term) {
+        log::debug!("Inlining the definition of {}", sig.name);
+        if sig.parameters.is_empty() {
+            self.constants.insert(sig.name.0, term);
+        } else {
+            let parameters = sig
+                .parameters
+                .into_iter()
+                .map(|(symbol, _)| symbol.0)
+                .collect();
+            self.functions.insert(sig.name.0, (parameters, term));
+        }
+    }
+
+    // Inline the definitions in a term where the given parameters are bound.
+    fn inline_term(&mut self, term: Term, parameters: &[String]) -> Result<Term, Error> {
+        let term = term.accept(self)?;
+        let mut constants = self.constants.clone();
+        for parameter in parameters {
+            constants.remove(parameter);
+        }
+        SubstitutionRewriter::new(constants).substitute(term)
+    }
+
+    fn update_scopes(&mut self, command: &Command) {
+        match command {
+            Command::Push { level } => {
+                for _ in 0..level.to_usize().expect("too many levels") {
+                    self.scopes
+                        .push((self.functions.clone(), self.constants.clone()));
+                }
+            }
+            Command::Pop { level } => {
+                for _ in 0..level.to_usize().expect("too many levels") {
+                    if let Some((functions, constants)) = self.scopes.pop() {
+                        self.functions = functions;
+                        self.constants = constants;
+                    }
+                }
+            }
+            Command::Reset => *self = Self::default(),
+            _ => (),
+        }
+    }
+}
+
+fn variable_term(name: &str) -> Term {
+    Term::QualIdentifier(QualIdentifier::Simple {
+        identifier: Identifier::Simple {
+            symbol: Symbol(name.to_string()),
+        },
+    })
+}
+
+impl Rewriter for DefinitionInliner {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn process_term(&mut self, value: Term) -> Result<Term, Self::Error> {
+        match value {
+            Term::Application {
+                qual_identifier:
+                    QualIdentifier::Simple {
+                        identifier: Identifier::Simple { symbol },
+                    },
+                arguments,
+            } if self
+                .functions
+                .get(&symbol.0)
+                .map_or(false, |(parameters, _)| parameters.len() == arguments.len()) =>
+            {
+                let (parameters, body) = self.functions[&symbol.0].clone();
+                let substitution = parameters.into_iter().zip(arguments).collect();
+                SubstitutionRewriter::new(substitution).substitute(body)
+            }
+            value => Ok(value),
+        }
+    }
+}
+
+/// Rewriter unfolding the applications of the functions defined by `define-fun-rec` and
+/// `define-funs-rec` a bounded number of times.
+///
+/// Each round replaces every application of a recursive function by the body of its
+/// definition. The applications that remain after the last round are kept: the
+/// recursive functions are then declared as uninterpreted functions.
+#[derive(Debug, Default)]
+pub struct RecursiveUnfolder {
+    visitor: SyntaxBuilder,
+    // Number of rounds.
+    depth: usize,
+    // Parameters and bodies of the recursive functions.
+    functions: Functions,
+}
+
+impl RecursiveUnfolder {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            ..Self::default()
+        }
+    }
+
+    /// Replace the recursive definitions of a script by declarations and unfold the
+    /// applications of the recursive functions in the other commands.
+    pub fn unfold_commands(&mut self, commands: Vec<Command>) -> Result<Vec<Command>, Error> {
+        let mut result = Vec::new();
+        for command in commands {
+            match command {
+                Command::DefineFunRec { sig, term } => result.push(self.define(sig, term)),
+                Command::DefineFunsRec { funs } => {
+                    for (sig, term) in funs {
+                        result.push(self.define(sig, term));
+                    }
+                }
+                command => {
+                    let command = (0..self.depth).try_fold(command, |c, _| c.accept(self))?;
+                    result.push(command);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Unfold the applications of the known recursive functions in a term.
+    pub fn unfold_term(&mut self, term: Term) -> Result<Term, Error> {
+        (0..self.depth).try_fold(term, |t, _| t.accept(self))
+    }
+
+    // Record a recursive definition and return the declaration of the function.
+    fn define(&mut self, sig: FunctionDec, term: Term) -> Command {
+        log::debug!("Unfolding the recursive definition of {}", sig.name);
+        let parameters = sig
+            .parameters
+            .iter()
+            .map(|(symbol, _)| symbol.0.clone())
+            .collect();
+        self.functions
+            .insert(sig.name.0.clone(), (parameters, term));
+        Command::DeclareFun {
+            symbol: sig.name,
+            parameters: sig.parameters.into_iter().map(|(_, sort)| sort).collect(),
+            sort: sig.result,
+        }
+    }
+}
+
+impl Rewriter for RecursiveUnfolder {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn process_term(&mut self, value: Term) -> Result<Term, Self::Error> {
+        match value {
+            Term::Application {
+                qual_identifier:
+                    QualIdentifier::Simple {
+                        identifier: Identifier::Simple { symbol },
+                    },
+                arguments,
+            } if self
+                .functions
+                .get(&symbol.0)
+                .map_or(false, |(parameters, _)| parameters.len() == arguments.len()) =>
+            {
+                let (parameters, body) = self.functions[&symbol.0].clone();
+                let substitution = parameters.into_iter().zip(arguments).collect();
+                SubstitutionRewriter::new(substitution).substitute(body)
+            }
+            value => Ok(value),
+        }
+    }
+}
+
+/// Rewriter replacing the sorts defined by `define-sort` by their definitions, so that
+/// later passes only see declared and theory sorts.
+#[derive(Debug, Default)]
+pub struct SortExpander {
+    visitor: SyntaxBuilder,
+    // Parameters and expanded definitions of the sorts.
+    aliases: Aliases,
+    // Definitions saved by `push` commands.
+    scopes: Vec<Aliases>,
+}
+
+type Aliases = BTreeMap<String, (Vec<String>, Sort)>;
+
+impl SortExpander {
+    /// Expand the sort definitions of a script and remove them. Definitions made after a
+    /// `push` are forgotten by the matching `pop`.
+    pub fn expand_commands(commands: Vec<Command>) -> Result<Vec<Command>, Error> {
+        let mut expander = Self::default();
+        let mut result = Vec::new();
+        for command in commands {
+            match command {
+                Command::DefineSort {
+                    symbol,
+                    parameters,
+                    sort,
+                } => {
+                    // The parameters shadow the sorts with the same names.
+                    let shadowed = parameters
+                        .iter()
+                        .filter_map(|p| Some((p.0.clone(), expander.aliases.remove(&p.0)?)))
+                        .collect::<Vec<_>>();
+                    let sort = sort.accept(&mut expander)?;
+                    expander.aliases.extend(shadowed);
+                    log::debug!("Expanding the definition of sort {}", symbol);
+                    let parameters = parameters.into_iter().map(|p| p.0).collect();
+                    expander.aliases.insert(symbol.0, (parameters, sort));
+                }
+                command => {
+                    let command = command.accept(&mut expander)?;
+                    expander.update_scopes(&command);
+                    result.push(command);
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    fn update_scopes(&mut self, command: &Command) {
+        match command {
+            Command::Push { level } => {
+                for _ in 0..level.to_usize().expect("too many levels") {
+                    self.scopes.push(self.aliases.clone());
+                }
+            }
+            Command::Pop { level } => {
+                for _ in 0..level.to_usize().expect("too many levels") {
+                    if let Some(aliases) = self.scopes.pop() {
+                        self.aliases = aliases;
+                    }
+                }
+            }
+            Command::Reset => *self = Self::default(),
+            _ => (),
+        }
+    }
+}
+
+impl Rewriter for SortExpander {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn process_sort(&mut self, value: Sort) -> Result<Sort, Self::Error> {
+        let (symbol, arguments) = match &value {
+            Sort::Simple {
+                identifier: Identifier::Simple { symbol },
+            } => (symbol, &[][..]),
+            Sort::Parameterized {
+                identifier: Identifier::Simple { symbol },
+                parameters,
+            } => (symbol, &parameters[..]),
+            _ => return Ok(value),
+        };
+        match self.aliases.get(&symbol.0) {
+            Some((parameters, definition)) if parameters.len() == arguments.len() => {
+                let bindings = parameters.iter().cloned().zip(arguments.to_vec()).collect();
+                Ok(substitute_sort(definition, &bindings))
+            }
+            _ => Ok(value),
+        }
+    }
+}
+
+#[test]
+fn test_definition_inlining() {
+    let input = r#"
+(declare-fun y () Int)
+(define-fun c () Int (+ y 1))
+(define-fun f ((x Int) (y Int)) Int (* x y c))
+(push 1)
+(define-fun g ((x Int)) Bool (> (f x x) c))
+(assert (forall ((c Int)) (g c)))
+(pop 1)
+(assert (= (f c c) 0))
+(assert (g 1))
+"#;
+    let commands = crate::parse_commands(input).unwrap();
+    let commands = DefinitionInliner::inline_commands(commands).unwrap();
+    assert_eq!(
+        commands.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+        vec![
+            "(declare-fun y () Int)",
+            "(push 1)",
+            "(assert (forall ((c Int)) (> (* c c (+ y 1)) (+ y 1))))",
+            "(pop 1)",
+            "(assert (= (* (+ y 1) (+ y 1) (+ y 1)) 0))",
+            "(assert (g 1))",
+        ]
+    );
+}
+
+#[test]
+fn test_recursive_unfolding() {
+    let input = r#"
+(define-fun-rec sum ((n Int)) Int (ite (<= n 0) 0 (+ n (sum (- n 1)))))
+(define-funs-rec ((even ((n Int)) Bool) (odd ((n Int)) Bool)) ((or (= n 0) (odd (- n 1))) (and (> n 0) (even (- n 1)))))
+(assert (= (sum x) 3))
+(assert (even x))
+"#;
+    let commands = crate::parse_commands(input).unwrap();
+    let commands = RecursiveUnfolder::new(2).unfold_commands(commands).unwrap();
+    assert_eq!(
+        commands.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+        vec![
+            "(declare-fun sum (Int) Int)",
+            "(declare-fun even (Int) Bool)",
+            "(declare-fun odd (Int) Bool)",
+            "(assert (= (ite (<= x 0) 0 (+ x (ite (<= (- x 1) 0) 0 (+ (- x 1) (sum (- (- x 1) 1)))))) 3))",
+            "(assert (or (= x 0) (and (> (- x 1) 0) (even (- (- x 1) 1)))))",
+        ]
+    );
+}
+
+#[test]
+fn test_sort_expansion() {
+    let input = r#"
+(define-sort Elem () Int)
+(define-sort Map (K V) (Array K V))
+(define-sort Memory () (Map Int Elem))
+(declare-fun m () Memory)
+(push 1)
+(define-sort Elem () Bool)
+(declare-fun f ((Map Elem Elem)) Elem)
+(pop 1)
+(assert (forall ((x Elem)) (= (select m x) ((as const (Map Int Elem)) 0))))
+"#;
+    let commands = crate::parse_commands(input).unwrap();
+    let commands = SortExpander::expand_commands(commands).unwrap();
+    assert_eq!(
+        commands.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+        vec![
+            "(declare-fun m () (Array Int Int))",
+            "(push 1)",
+            "(declare-fun f ((Array Bool Bool)) Bool)",
+            "(pop 1)",
+            "(assert (forall ((x Int)) (= (select m x) ((as const (Array Int Int)) 0))))",
+        ]
+    );
+}
+
+#[test]
+fn test_definition_inlining_edge_cases() {
+    let inline = |input: &str| {
+        DefinitionInliner::inline_commands(crate::parse_commands(input).unwrap())
+            .unwrap()
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+    };
+    // Applications with the wrong number of arguments are kept.
+    assert_eq!(
+        inline("(define-fun f ((x Int)) Int (+ x 1)) (assert (= (f 1 2) (f 3)))"),
+        vec!["(assert (= (f 1 2) (+ 3 1)))"]
+    );
+    // Bound variables shadow the defined constants.
+    assert_eq!(
+        inline("(define-fun c () Int 1) (assert (and (let ((c 2)) (> c 0)) (forall ((c Int)) (> c 0)) (> c 0)))"),
+        vec!["(assert (and (let ((c 2)) (> c 0)) (forall ((c Int)) (> c 0)) (> 1 0)))"]
+    );
+    // The variables bound in a definition do not capture the arguments.
+    assert_eq!(
+        inline("(define-fun f ((x Int)) Bool (exists ((y Int)) (> y x))) (assert (f y))"),
+        vec!["(assert (exists ((y!1 Int)) (> y!1 y)))"]
+    );
+    // Chains of definitions are inlined in one pass.
+    let mut input = "(define-fun c0 () Int x)".to_string();
+    for i in 1..=50 {
+        input += &format!("(define-fun c{} () Int (+ c{} 1))", i, i - 1);
+    }
+    input += "(assert (> c50 0))";
+    assert_eq!(
+        inline(&input),
+        vec![format!(
+            "(assert (> {}x{} 0))",
+            "(+ ".repeat(50),
+            " 1)".repeat(50)
+        )]
+    );
+}
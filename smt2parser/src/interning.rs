@@ -0,0 +1,506 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Syntax with interned symbols, where equal symbols share their name. Cloning such
+//! terms and commands does not copy the names of symbols, which reduces the memory and
+//! the allocations needed to store and transform large scripts.
+
+use crate::{
+    concrete::{self, Constant, Error, Keyword, SyntaxBuilder},
+    visitors::{
+        self, AttributeValue, CommandVisitor, ConstantVisitor, DatatypeDec, FunctionDec,
+        KeywordVisitor, QualIdentifierVisitor, SExprVisitor, Smt2Visitor, SortVisitor, SymbolKind,
+        SymbolVisitor, TermVisitor,
+    },
+    Binary, Decimal, Hexadecimal, Numeral,
+};
+use std::{collections::HashSet, sync::Arc};
+
+/// A symbol whose name is shared with the equal symbols of the same [`Interner`].
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct InternedSymbol(Arc<str>);
+
+impl InternedSymbol {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether both symbols share their name, which holds for equal symbols of the same
+    /// interner.
+    pub fn ptr_eq(&self, other: &InternedSymbol) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl From<InternedSymbol> for concrete::Symbol {
+    fn from(symbol: InternedSymbol) -> Self {
+        concrete::Symbol(symbol.0.to_string())
+    }
+}
+
+impl std::fmt::Display for InternedSymbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bytes = self.0.as_bytes();
+        if bytes
+            .first()
+            .map_or(false, |c| crate::lexer::is_non_digit_symbol_byte(*c))
+            && bytes.iter().all(|c| crate::lexer::is_symbol_byte(*c))
+        {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "|{}|", self.0)
+        }
+    }
+}
+
+/// Set of the names of the interned symbols.
+#[derive(Debug, Default, Clone)]
+pub struct Interner {
+    names: HashSet<Arc<str>>,
+}
+
+impl Interner {
+    /// Return the symbol of a name, storing the name if needed.
+    pub fn intern(&mut self, name: &str) -> InternedSymbol {
+        if let Some(name) = self.names.get(name) {
+            return InternedSymbol(name.clone());
+        }
+        let name: Arc<str> = Arc::from(name);
+        self.names.insert(name.clone());
+        InternedSymbol(name)
+    }
+
+    /// Number of distinct names.
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+pub type Identifier = visitors::Identifier<InternedSymbol>;
+pub type Sort = concrete::Sort<Identifier>;
+pub type QualIdentifier = concrete::QualIdentifier<Identifier, Sort>;
+pub type SExpr = concrete::SExpr<Constant, InternedSymbol, Keyword>;
+pub type Term = concrete::Term<Constant, QualIdentifier, Keyword, SExpr, InternedSymbol, Sort>;
+pub type Command = concrete::Command<Term, InternedSymbol, Sort, Keyword, Constant, SExpr>;
+
+/// An implementation of [`Smt2Visitor`] that returns syntax values with interned symbols.
+/// Scripts are parsed with interned symbols by passing this visitor to `CommandStream`.
+#[derive(Debug, Default, Clone)]
+pub struct InterningSyntaxBuilder {
+    interner: Interner,
+}
+
+impl InterningSyntaxBuilder {
+    pub fn new(interner: Interner) -> Self {
+        Self { interner }
+    }
+
+    /// The interner of the symbols built so far.
+    pub fn interner(&self) -> &Interner {
+        &self.interner
+    }
+}
+
+impl ConstantVisitor for InterningSyntaxBuilder {
+    type T = Constant;
+    type E = Error;
+
+    fn visit_numeral_constant(&mut self, value: Numeral) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_numeral_constant(value)
+    }
+    fn visit_decimal_constant(&mut self, value: Decimal) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_decimal_constant(value)
+    }
+    fn visit_hexadecimal_constant(&mut self, value: Hexadecimal) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_hexadecimal_constant(value)
+    }
+    fn visit_binary_constant(&mut self, value: Binary) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_binary_constant(value)
+    }
+    fn visit_string_constant(&mut self, value: String) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_string_constant(value)
+    }
+}
+
+impl SymbolVisitor for InterningSyntaxBuilder {
+    type T = InternedSymbol;
+    type E = Error;
+
+    fn visit_fresh_symbol(&mut self, value: String, _kind: SymbolKind) -> Result<Self::T, Self::E> {
+        Ok(self.interner.intern(&value))
+    }
+}
+
+impl KeywordVisitor for InterningSyntaxBuilder {
+    type T = Keyword;
+    type E = Error;
+
+    fn visit_keyword(&mut self, value: String) -> Result<Self::T, Self::E> {
+        SyntaxBuilder.visit_keyword(value)
+    }
+}
+
+impl SExprVisitor<Constant, InternedSymbol, Keyword> for InterningSyntaxBuilder {
+    type T = SExpr;
+    type E = Error;
+
+    fn visit_constant_s_expr(&mut self, value: Constant) -> Result<Self::T, Self::E> {
+        Ok(SExpr::Constant(value))
+    }
+
+    fn visit_symbol_s_expr(&mut self, value: InternedSymbol) -> Result<Self::T, Self::E> {
+        Ok(SExpr::Symbol(value))
+    }
+
+    fn visit_keyword_s_expr(&mut self, value: Keyword) -> Result<Self::T, Self::E> {
+        Ok(SExpr::Keyword(value))
+    }
+
+    fn visit_application_s_expr(&mut self, values: Vec<Self::T>) -> Result<Self::T, Self::E> {
+        Ok(SExpr::Application(values))
+    }
+}
+
+impl SortVisitor<InternedSymbol> for InterningSyntaxBuilder {
+    type T = Sort;
+    type E = Error;
+
+    fn visit_simple_sort(&mut self, identifier: Identifier) -> Result<Self::T, Self::E> {
+        Ok(Sort::Simple { identifier })
+    }
+
+    fn visit_parameterized_sort(
+        &mut self,
+        identifier: Identifier,
+        parameters: Vec<Self::T>,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Sort::Parameterized {
+            identifier,
+            parameters,
+        })
+    }
+}
+
+impl QualIdentifierVisitor<Identifier, Sort> for InterningSyntaxBuilder {
+    type T = QualIdentifier;
+    type E = Error;
+
+    fn visit_simple_identifier(&mut self, identifier: Identifier) -> Result<Self::T, Self::E> {
+        Ok(QualIdentifier::Simple { identifier })
+    }
+
+    fn visit_sorted_identifier(
+        &mut self,
+        identifier: Identifier,
+        sort: Sort,
+    ) -> Result<Self::T, Self::E> {
+        Ok(QualIdentifier::Sorted { identifier, sort })
+    }
+}
+
+impl TermVisitor<Constant, QualIdentifier, Keyword, SExpr, InternedSymbol, Sort>
+    for InterningSyntaxBuilder
+{
+    type T = Term;
+    type E = Error;
+
+    fn visit_constant(&mut self, constant: Constant) -> Result<Self::T, Self::E> {
+        Ok(Term::Constant(constant))
+    }
+
+    fn visit_qual_identifier(
+        &mut self,
+        qual_identifier: QualIdentifier,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Term::QualIdentifier(qual_identifier))
+    }
+
+    fn visit_application(
+        &mut self,
+        qual_identifier: QualIdentifier,
+        arguments: Vec<Self::T>,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Term::Application {
+            qual_identifier,
+            arguments,
+        })
+    }
+
+    fn visit_let(
+        &mut self,
+        var_bindings: Vec<(InternedSymbol, Self::T)>,
+        term: Self::T,
+    ) -> Result<Self::T, Self::E> {
+        let term = Box::new(term);
+        Ok(Term::Let { var_bindings, term })
+    }
+
+    fn visit_forall(
+        &mut self,
+        vars: Vec<(InternedSymbol, Sort)>,
+        term: Self::T,
+    ) -> Result<Self::T, Self::E> {
+        let term = Box::new(term);
+        Ok(Term::Forall { vars, term })
+    }
+
+    fn visit_exists(
+        &mut self,
+        vars: Vec<(InternedSymbol, Sort)>,
+        term: Self::T,
+    ) -> Result<Self::T, Self::E> {
+        let term = Box::new(term);
+        Ok(Term::Exists { vars, term })
+    }
+
+    fn visit_match(
+        &mut self,
+        term: Self::T,
+        cases: Vec<(Vec<InternedSymbol>, Self::T)>,
+    ) -> Result<Self::T, Self::E> {
+        let term = Box::new(term);
+        Ok(Term::Match { term, cases })
+    }
+
+    fn visit_attributes(
+        &mut self,
+        term: Self::T,
+        attributes: Vec<(Keyword, AttributeValue<Constant, InternedSymbol, SExpr>)>,
+    ) -> Result<Self::T, Self::E> {
+        let term = Box::new(term);
+        Ok(Term::Attributes { term, attributes })
+    }
+}
+
+impl CommandVisitor<Term, InternedSymbol, Sort, Keyword, Constant, SExpr>
+    for InterningSyntaxBuilder
+{
+    type T = Command;
+    type E = Error;
+
+    fn visit_assert(&mut self, term: Term) -> Result<Self::T, Self::E> {
+        Ok(Command::Assert { term })
+    }
+
+    fn visit_check_sat(&mut self) -> Result<Self::T, Self::E> {
+        Ok(Command::CheckSat)
+    }
+
+    fn visit_check_sat_assuming(
+        &mut self,
+        literals: Vec<(InternedSymbol, bool)>,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Command::CheckSatAssuming { literals })
+    }
+
+    fn visit_declare_const(
+        &mut self,
+        symbol: InternedSymbol,
+        sort: Sort,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Command::DeclareConst { symbol, sort })
+    }
+
+    fn visit_declare_datatype(
+        &mut self,
+        symbol: InternedSymbol,
+        datatype: DatatypeDec<InternedSymbol, Sort>,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Command::DeclareDatatype { symbol, datatype })
+    }
+
+    fn visit_declare_datatypes(
+        &mut self,
+        datatypes: Vec<(InternedSymbol, Numeral, DatatypeDec<InternedSymbol, Sort>)>,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Command::DeclareDatatypes { datatypes })
+    }
+
+    fn visit_declare_fun(
+        &mut self,
+        symbol: InternedSymbol,
+        parameters: Vec<Sort>,
+        sort: Sort,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Command::DeclareFun {
+            symbol,
+            parameters,
+            sort,
+        })
+    }
+
+    fn visit_declare_sort(
+        &mut self,
+        symbol: InternedSymbol,
+        arity: Numeral,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Command::DeclareSort { symbol, arity })
+    }
+
+    fn visit_define_fun(
+        &mut self,
+        sig: FunctionDec<InternedSymbol, Sort>,
+        term: Term,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Command::DefineFun { sig, term })
+    }
+
+    fn visit_define_fun_rec(
+        &mut self,
+        sig: FunctionDec<InternedSymbol, Sort>,
+        term: Term,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Command::DefineFunRec { sig, term })
+    }
+
+    fn visit_define_funs_rec(
+        &mut self,
+        funs: Vec<(FunctionDec<InternedSymbol, Sort>, Term)>,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Command::DefineFunsRec { funs })
+    }
+
+    fn visit_define_sort(
+        &mut self,
+        symbol: InternedSymbol,
+        parameters: Vec<InternedSymbol>,
+        sort: Sort,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Command::DefineSort {
+            symbol,
+            parameters,
+            sort,
+        })
+    }
+
+    fn visit_echo(&mut self, message: String) -> Result<Self::T, Self::E> {
+        Ok(Command::Echo { message })
+    }
+
+    fn visit_exit(&mut self) -> Result<Self::T, Self::E> {
+        Ok(Command::Exit)
+    }
+
+    fn visit_get_assertions(&mut self) -> Result<Self::T, Self::E> {
+        Ok(Command::GetAssertions)
+    }
+
+    fn visit_get_assignment(&mut self) -> Result<Self::T, Self::E> {
+        Ok(Command::GetAssignment)
+    }
+
+    fn visit_get_info(&mut self, flag: Keyword) -> Result<Self::T, Self::E> {
+        Ok(Command::GetInfo { flag })
+    }
+
+    fn visit_get_model(&mut self) -> Result<Self::T, Self::E> {
+        Ok(Command::GetModel)
+    }
+
+    fn visit_get_option(&mut self, keyword: Keyword) -> Result<Self::T, Self::E> {
+        Ok(Command::GetOption { keyword })
+    }
+
+    fn visit_get_proof(&mut self) -> Result<Self::T, Self::E> {
+        Ok(Command::GetProof)
+    }
+
+    fn visit_get_unsat_assumptions(&mut self) -> Result<Self::T, Self::E> {
+        Ok(Command::GetUnsatAssumptions)
+    }
+
+    fn visit_get_unsat_core(&mut self) -> Result<Self::T, Self::E> {
+        Ok(Command::GetUnsatCore)
+    }
+
+    fn visit_get_value(&mut self, terms: Vec<Term>) -> Result<Self::T, Self::E> {
+        Ok(Command::GetValue { terms })
+    }
+
+    fn visit_pop(&mut self, level: Numeral) -> Result<Self::T, Self::E> {
+        Ok(Command::Pop { level })
+    }
+
+    fn visit_push(&mut self, level: Numeral) -> Result<Self::T, Self::E> {
+        Ok(Command::Push { level })
+    }
+
+    fn visit_reset(&mut self) -> Result<Self::T, Self::E> {
+        Ok(Command::Reset)
+    }
+
+    fn visit_reset_assertions(&mut self) -> Result<Self::T, Self::E> {
+        Ok(Command::ResetAssertions)
+    }
+
+    fn visit_set_info(
+        &mut self,
+        keyword: Keyword,
+        value: AttributeValue<Constant, InternedSymbol, SExpr>,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Command::SetInfo { keyword, value })
+    }
+
+    fn visit_set_logic(&mut self, symbol: InternedSymbol) -> Result<Self::T, Self::E> {
+        Ok(Command::SetLogic { symbol })
+    }
+
+    fn visit_set_option(
+        &mut self,
+        keyword: Keyword,
+        value: AttributeValue<Constant, InternedSymbol, SExpr>,
+    ) -> Result<Self::T, Self::E> {
+        Ok(Command::SetOption { keyword, value })
+    }
+}
+
+impl Smt2Visitor for InterningSyntaxBuilder {
+    type Error = Error;
+    type Constant = Constant;
+    type QualIdentifier = QualIdentifier;
+    type Keyword = Keyword;
+    type Sort = Sort;
+    type SExpr = SExpr;
+    type Symbol = InternedSymbol;
+    type Term = Term;
+    type Command = Command;
+
+    fn syntax_error(&mut self, position: crate::Position, s: String) -> Self::Error {
+        Error::SyntaxError(position, s)
+    }
+
+    fn parsing_error(&mut self, position: crate::Position, s: String) -> Self::Error {
+        Error::ParsingError(position, s)
+    }
+}
+
+#[test]
+fn test_interning_syntax_builder() {
+    let input = br#"
+(declare-fun |x y| () Int)
+(assert (forall ((z Int)) (! (= (f |x y|) (f z)) :pattern ((f z)))))
+"#;
+    let mut stream = crate::CommandStream::new(&input[..], InterningSyntaxBuilder::default(), None);
+    let commands = (&mut stream).collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(
+        commands.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+        vec![
+            "(declare-fun |x y| () Int)",
+            "(assert (forall ((z Int)) (! (= (f |x y|) (f z)) :pattern ((f z)))))"
+        ]
+    );
+    // `x y`, `Int`, `z`, `=`, and `f`.
+    assert_eq!(stream.into_visitor().interner().len(), 5);
+    let symbol = match &commands[0] {
+        Command::DeclareFun { symbol, .. } => symbol,
+        _ => unreachable!(),
+    };
+    let mut interner = Interner::default();
+    assert!(!interner.intern("x y").ptr_eq(symbol));
+    assert!(interner.intern("x y").ptr_eq(&interner.intern("x y")));
+    assert_eq!(concrete::Symbol::from(symbol.clone()).0, "x y");
+}
@@ -5,9 +5,10 @@
 
 use crate::{parser::Token, Decimal, Numeral};
 use num::Num;
+use serde::{Deserialize, Serialize};
 
 /// Record a position in the input stream.
-#[derive(Debug, Clone, Default, Eq, PartialEq)]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub struct Position {
     pub path: Option<String>,
     pub line: usize,
@@ -29,6 +30,23 @@ impl std::fmt::Display for Position {
     }
 }
 
+/// Record the range of the input covered by a command.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    /// Position of the first character.
+    pub start: Position,
+    /// Position following the last character.
+    pub end: Position,
+    /// Byte offsets of the first character and following the last character.
+    pub offsets: std::ops::Range<usize>,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}:{}", self.start, self.end.line, self.end.column)
+    }
+}
+
 pub(crate) struct Lexer<R> {
     reader: R,
     reserved_words: Vec<Token>,
@@ -36,6 +54,10 @@ pub(crate) struct Lexer<R> {
     current_offset: usize,
     current_line: usize,
     current_column: usize,
+    // Offset, line, and column of the first character of the last token.
+    token_start: (usize, usize, usize),
+    // Comments read so far, if they are kept.
+    comments: Option<Vec<String>>,
 }
 
 const KEYWORDS: &[(&str, Token)] = {
@@ -95,6 +117,8 @@ where
             current_offset: 0,
             current_line: 0,
             current_column: 0,
+            token_start: (0, 0, 0),
+            comments: None,
         }
     }
 
@@ -143,6 +167,57 @@ where
         pos.column = self.current_column + 1;
     }
 
+    /// Update a position to the first character of the last token.
+    #[inline]
+    pub(crate) fn update_token_position(&self, pos: &mut Position) {
+        let (_, line, column) = self.token_start;
+        pos.line = line + 1;
+        pos.column = column + 1;
+    }
+
+    /// After the end of the tokens, whether the lexer stopped at an invalid token rather
+    /// than at the end of the input. The first character of an invalid token is then
+    /// skipped, so that lexing can resume.
+    pub(crate) fn skip_invalid_token(&mut self) -> bool {
+        if self.current_offset > self.token_start.0 {
+            return true;
+        }
+        if self.peek_byte().is_some() {
+            self.consume_byte();
+            return true;
+        }
+        false
+    }
+
+    /// Keep the content of the comments instead of skipping them.
+    pub(crate) fn keep_comments(&mut self) {
+        self.comments.get_or_insert_with(Vec::new);
+    }
+
+    /// Return the comments read since the last call, if comments are kept.
+    pub(crate) fn take_comments(&mut self) -> Vec<String> {
+        self.comments
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Update the start of a span to the first character of the last token.
+    #[inline]
+    pub(crate) fn update_span_start(&self, span: &mut Span) {
+        let (offset, line, column) = self.token_start;
+        span.start.line = line + 1;
+        span.start.column = column + 1;
+        span.offsets.start = offset;
+    }
+
+    /// Update the end of a span to the character following the last token.
+    #[inline]
+    pub(crate) fn update_span_end(&self, span: &mut Span) {
+        self.update_position(&mut span.end);
+        span.offsets.end = self.current_offset;
+    }
+
     fn consume_byte(&mut self) {
         if let Some(c) = self.peek_byte() {
             if *c == b'\n' {
@@ -170,12 +245,12 @@ where
     }
 
     fn peek_byte(&mut self) -> Option<&u8> {
-        self.peek_bytes().get(0)
+        self.peek_bytes().first()
     }
 
     fn skip_whitespace(&mut self) -> bool {
         match self.peek_byte() {
-            Some(b) if matches!(b, b' ' | b'\n' | b'\t' | b'\r') => {
+            Some(b' ' | b'\n' | b'\t' | b'\r') => {
                 self.consume_byte();
                 true
             }
@@ -187,10 +262,18 @@ where
         match self.peek_byte() {
             Some(c) if *c == b';' => {
                 self.consume_byte();
+                let mut content = Vec::new();
                 while let Some(c) = self.read_byte() {
                     if c == b'\n' {
                         break;
                     }
+                    content.push(c);
+                }
+                if let Some(comments) = &mut self.comments {
+                    if content.last() == Some(&b'\r') {
+                        content.pop();
+                    }
+                    comments.push(String::from_utf8_lossy(&content).into_owned());
                 }
                 true
             }
@@ -207,6 +290,7 @@ where
 
     fn next(&mut self) -> Option<Token> {
         while self.skip_whitespace() || self.skip_comment() {}
+        self.token_start = (self.current_offset, self.current_line, self.current_column);
         match self.peek_byte() {
             // Parentheses
             Some(b'(') => {
@@ -380,7 +464,7 @@ where
 }
 
 fn is_digit_byte(c: u8) -> bool {
-    matches!(c, b'0'..=b'9')
+    c.is_ascii_digit()
 }
 
 pub(crate) fn is_symbol_byte(c: u8) -> bool {
@@ -435,6 +519,12 @@ fn test_error() {
     assert_eq!(lexer.current_line(), 0);
     assert_eq!(lexer.current_column(), input.len() - 1);
     assert_eq!(lexer.current_offset(), input.len() - 1);
+    let mut position = Position::default();
+    lexer.update_token_position(&mut position);
+    assert_eq!((position.line, position.column), (1, 5));
+    assert!(lexer.skip_invalid_token());
+    assert_eq!(lexer.next(), None);
+    assert!(!lexer.skip_invalid_token());
 }
 
 #[test]
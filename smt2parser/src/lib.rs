@@ -25,19 +25,58 @@
 //! assert_eq!(commands[0].to_string(), "(echo \"Hello world!\")");
 //! ```
 
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "ffi"), forbid(unsafe_code))]
+#![cfg_attr(feature = "ffi", deny(unsafe_code))]
 
 #[macro_use]
 extern crate pomelo;
 
+pub mod abstraction;
+pub mod arena;
+pub mod cache;
 pub mod concrete;
+pub mod cti;
+pub mod de_bruijn;
+pub mod dependencies;
+pub mod diff;
+pub mod environment;
+pub mod evaluation;
+#[cfg(feature = "ffi")]
+#[allow(unsafe_code)]
+pub mod ffi;
+pub mod hashcons;
+pub mod inlining;
+pub mod interning;
 mod lexer;
+pub mod logic;
+pub mod mining;
+pub mod model;
+pub mod names;
+pub mod normal_forms;
 mod parser;
+pub mod portfolio;
+pub mod printer;
+pub mod proof;
+pub mod proxy;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod query;
 pub mod renaming;
+pub mod responses;
 pub mod rewriter;
+pub mod sat;
+pub mod schema;
+pub mod simplification;
+pub mod solver;
 pub mod stats;
+pub mod symbols;
+pub mod tip;
+pub mod triggers;
+pub mod typing;
 pub mod visitors;
 pub mod vmt;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 /// SMT2 numeral values.
 pub type Numeral = num::bigint::BigUint;
@@ -54,8 +93,14 @@ pub type Binary = Vec<bool>;
 pub use concrete::Error;
 /// A position in the input.
 pub use lexer::Position;
+/// A range in the input.
+pub use lexer::Span;
 
-/// Parse the input data and return a stream of interpreted SMT2 commands
+/// Parse the input data and return a stream of interpreted SMT2 commands.
+///
+/// Commands are parsed lazily, one at a time, as the stream is iterated. Only the
+/// buffer of the reader and the current command are kept in memory, so that large
+/// scripts can be processed without building the syntax of the whole input.
 pub struct CommandStream<R, T>
 where
     R: std::io::BufRead,
@@ -64,6 +109,8 @@ where
     lexer: lexer::Lexer<R>,
     visitor: T,
     position: Position,
+    span: Span,
+    recover: bool,
 }
 
 impl<R, T> CommandStream<R, T>
@@ -72,16 +119,60 @@ where
     T: visitors::Smt2Visitor,
 {
     pub fn new(reader: R, visitor: T, path: Option<String>) -> Self {
+        let position = Position {
+            path,
+            ..Position::default()
+        };
         Self {
             lexer: lexer::Lexer::new(reader),
             visitor,
-            position: Position {
-                path,
-                ..Position::default()
+            span: Span {
+                start: position.clone(),
+                end: position.clone(),
+                offsets: 0..0,
             },
+            position,
+            recover: false,
         }
     }
 
+    /// After a syntax error, skip the rest of the faulty command so that the next
+    /// iteration resumes with the following command.
+    pub fn with_error_recovery(mut self) -> Self {
+        self.recover = true;
+        self
+    }
+
+    /// Parse the whole input, skipping the commands with syntax errors. Return the
+    /// valid commands and the errors.
+    pub fn parse_all(self) -> (Vec<T::Command>, Vec<T::Error>) {
+        let mut commands = Vec::new();
+        let mut errors = Vec::new();
+        for result in self.with_error_recovery() {
+            match result {
+                Ok(command) => commands.push(command),
+                Err(error) => errors.push(error),
+            }
+        }
+        (commands, errors)
+    }
+
+    /// The range of the input covered by the last command returned by the stream.
+    pub fn last_span(&self) -> &Span {
+        &self.span
+    }
+
+    /// Return an iterator over the commands together with the comments preceding them.
+    pub fn with_comments(mut self) -> CommentedCommandStream<R, T> {
+        self.lexer.keep_comments();
+        CommentedCommandStream(self)
+    }
+
+    /// Return an iterator over the commands together with their spans.
+    pub fn spanned(self) -> SpannedCommandStream<R, T> {
+        SpannedCommandStream(self)
+    }
+
     pub fn visitor(&self) -> &T {
         &self.visitor
     }
@@ -107,6 +198,9 @@ where
         let mut parser = parser::Parser::new((&mut self.visitor, &mut self.position));
         let mut unmatched_paren = 0;
         while let Some(token) = self.lexer.next() {
+            if unmatched_paren == 0 {
+                self.lexer.update_span_start(&mut self.span);
+            }
             match &token {
                 parser::Token::LeftParen => unmatched_paren += 1,
                 parser::Token::RightParen => {
@@ -116,36 +210,353 @@ where
                 }
                 _ => (),
             }
-            self.lexer.update_position(parser.extra_mut().1);
+            // Report errors at the first character of the offending token.
+            self.lexer.update_token_position(parser.extra_mut().1);
             if let Err(err) = parser.parse(token) {
+                if self.recover {
+                    self.skip_command(unmatched_paren);
+                }
                 return Some(Err(err));
             }
             if unmatched_paren == 0 {
+                self.lexer.update_span_end(&mut self.span);
                 return match parser.end_of_input() {
                     Ok((command, _)) => Some(Ok(command)),
                     Err(err) => Some(Err(err)),
                 };
             }
         }
-        if unmatched_paren > 0 {
-            // We ran out of valid tokens in the middle of a command.
-            let extra = parser.into_extra();
+        let extra = parser.into_extra();
+        if self.lexer.skip_invalid_token() {
+            self.lexer.update_token_position(extra.1);
+            let err = extra
+                .0
+                .syntax_error(extra.1.clone(), "invalid token".into());
+            if self.recover {
+                self.skip_command(unmatched_paren);
+            }
+            Some(Err(err))
+        } else if unmatched_paren > 0 {
+            // We ran out of tokens in the middle of a command.
+            self.lexer.update_position(extra.1);
             Some(Err(extra.0.parsing_error(
                 extra.1.clone(),
                 "unexpected end of input".into(),
             )))
         } else {
-            // There are no more valid tokens to parse.
-            // TODO: report invalid tokens as an error.
             None
         }
     }
 }
 
+impl<R> CommandStream<R, concrete::SyntaxBuilder>
+where
+    R: std::io::BufRead,
+{
+    /// Return a stream of concrete commands read lazily from the given reader.
+    pub fn from_reader(reader: R) -> Self {
+        Self::new(reader, concrete::SyntaxBuilder, None)
+    }
+}
+
+impl<R, T> CommandStream<R, T>
+where
+    R: std::io::BufRead,
+    T: visitors::Smt2Visitor,
+{
+    // Skip tokens until the given number of parentheses are closed.
+    fn skip_command(&mut self, mut unmatched_paren: i32) {
+        while unmatched_paren > 0 {
+            match self.lexer.next() {
+                Some(parser::Token::LeftParen) => unmatched_paren += 1,
+                Some(parser::Token::RightParen) => unmatched_paren -= 1,
+                Some(_) => (),
+                None if self.lexer.skip_invalid_token() => (),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Iterator over the commands of a [`CommandStream`] together with their spans.
+pub struct SpannedCommandStream<R, T>(CommandStream<R, T>)
+where
+    R: std::io::BufRead,
+    T: visitors::Smt2Visitor;
+
+impl<R, T> SpannedCommandStream<R, T>
+where
+    R: std::io::BufRead,
+    T: visitors::Smt2Visitor,
+{
+    pub fn into_inner(self) -> CommandStream<R, T> {
+        self.0
+    }
+}
+
+impl<R, T> Iterator for SpannedCommandStream<R, T>
+where
+    R: std::io::BufRead,
+    T: visitors::Smt2Visitor,
+{
+    type Item = Result<(T::Command, Span), T::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.0.next()?;
+        Some(result.map(|command| (command, self.0.span.clone())))
+    }
+}
+
+/// A command together with the comments preceding it in the input.
+#[derive(Debug, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Commented<C> {
+    /// Content of each comment, without the leading `;` and the end of line.
+    pub comments: Vec<String>,
+    pub command: C,
+}
+
+impl<C> std::fmt::Display for Commented<C>
+where
+    C: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for comment in &self.comments {
+            writeln!(f, ";{}", comment)?;
+        }
+        write!(f, "{}", self.command)
+    }
+}
+
+/// Iterator over the commands of a [`CommandStream`] together with their comments.
+/// Comments inside a command are attached to it as well.
+pub struct CommentedCommandStream<R, T>(CommandStream<R, T>)
+where
+    R: std::io::BufRead,
+    T: visitors::Smt2Visitor;
+
+impl<R, T> CommentedCommandStream<R, T>
+where
+    R: std::io::BufRead,
+    T: visitors::Smt2Visitor,
+{
+    /// Return the comments read after the last command, typically at the end of the
+    /// input.
+    pub fn take_trailing_comments(&mut self) -> Vec<String> {
+        self.0.lexer.take_comments()
+    }
+
+    pub fn into_inner(self) -> CommandStream<R, T> {
+        self.0
+    }
+}
+
+impl<R, T> Iterator for CommentedCommandStream<R, T>
+where
+    R: std::io::BufRead,
+    T: visitors::Smt2Visitor,
+{
+    type Item = Result<Commented<T::Command>, T::Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let result = self.0.next()?;
+        Some(result.map(|command| Commented {
+            comments: self.0.lexer.take_comments(),
+            command,
+        }))
+    }
+}
+
+/// Parse all the commands of a string into concrete syntax.
+pub fn parse_commands(input: &str) -> Result<Vec<concrete::Command>, Error> {
+    CommandStream::from_reader(input.as_bytes()).collect()
+}
+
+/// Parse all the commands of an input into concrete syntax on several threads. The input
+/// is cut between lines outside of commands into chunks of similar sizes, which are parsed
+/// concurrently. Commands are returned in order, and errors are located in the whole
+/// input, as for `CommandStream`. As with `CommandStream`, the input need not be valid
+/// UTF-8 outside of symbols and string literals.
+pub fn parse_commands_parallel(
+    input: &[u8],
+    path: Option<String>,
+    threads: usize,
+) -> Result<Vec<concrete::Command>, Error> {
+    std::thread::scope(|scope| {
+        let handles = split_commands(input, threads)
+            .into_iter()
+            .map(|(line, chunk)| {
+                let path = path.clone();
+                scope.spawn(move || {
+                    CommandStream::new(chunk, concrete::SyntaxBuilder, path)
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|error| match error {
+                            Error::SyntaxError(mut position, message) => {
+                                position.line += line;
+                                Error::SyntaxError(position, message)
+                            }
+                            Error::ParsingError(mut position, message) => {
+                                position.line += line;
+                                Error::ParsingError(position, message)
+                            }
+                        })
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut commands = Vec::new();
+        for handle in handles {
+            commands.extend(handle.join().expect("Parsing does not panic")?);
+        }
+        Ok(commands)
+    })
+}
+
+// Cut the input into at most `chunks` pieces made of whole lines, at the end of lines
+// outside of parentheses, strings, and quoted symbols. Return each piece with the number
+// of lines before it.
+fn split_commands(bytes: &[u8], chunks: usize) -> Vec<(usize, &[u8])> {
+    let target = bytes.len() / std::cmp::max(chunks, 1) + 1;
+    let mut pieces = Vec::new();
+    let (mut start, mut start_line, mut line, mut depth) = (0, 0, 0, 0usize);
+    let mut delimiter = None;
+    for (offset, byte) in bytes.iter().enumerate() {
+        match (delimiter, byte) {
+            (_, b'\n') => {
+                line += 1;
+                if delimiter == Some(b';') {
+                    delimiter = None;
+                }
+                if delimiter.is_none() && depth == 0 && offset + 1 - start >= target {
+                    pieces.push((start_line, &bytes[start..=offset]));
+                    start = offset + 1;
+                    start_line = line;
+                }
+            }
+            (Some(d), c) if d == *c && d != b';' => delimiter = None,
+            (Some(_), _) => (),
+            (None, b';' | b'"' | b'|') => delimiter = Some(*byte),
+            (None, b'(') => depth += 1,
+            (None, b')') => depth = depth.saturating_sub(1),
+            (None, _) => (),
+        }
+    }
+    if start < bytes.len() || pieces.is_empty() {
+        pieces.push((start_line, &bytes[start..]));
+    }
+    pieces
+}
+
+// Split the input into its top-level s-expressions and atoms, skipping whitespace and
+// comments. Return `None` if parentheses, strings, or quoted symbols are unbalanced.
+pub(crate) fn split_s_exprs(input: &str) -> Option<Vec<&str>> {
+    let mut items = Vec::new();
+    let (mut depth, mut start) = (0usize, None);
+    let mut delimiter = None;
+    for (offset, byte) in input.bytes().enumerate() {
+        match (delimiter, byte) {
+            (Some(b';'), b'\n') => delimiter = None,
+            (Some(d), c) if d == c && d != b';' => delimiter = None,
+            (Some(_), _) => (),
+            (None, b'(') => {
+                if depth == 0 {
+                    if let Some(start) = start.take() {
+                        items.push(&input[start..offset]);
+                    }
+                    start = Some(offset);
+                }
+                depth += 1;
+            }
+            (None, b')') => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    items.push(&input[start.take()?..=offset]);
+                }
+            }
+            (None, b';') => {
+                if depth == 0 {
+                    if let Some(start) = start.take() {
+                        items.push(&input[start..offset]);
+                    }
+                }
+                delimiter = Some(byte);
+            }
+            (None, c) if c.is_ascii_whitespace() => {
+                if depth == 0 {
+                    if let Some(start) = start.take() {
+                        items.push(&input[start..offset]);
+                    }
+                }
+            }
+            (None, c) => {
+                if depth == 0 && start.is_none() {
+                    start = Some(offset);
+                }
+                if c == b'"' || c == b'|' {
+                    delimiter = Some(c);
+                }
+            }
+        }
+    }
+    if depth > 0 || matches!(delimiter, Some(b'"' | b'|')) {
+        return None;
+    }
+    if let Some(start) = start {
+        items.push(&input[start..]);
+    }
+    Some(items)
+}
+
+/// Parse a string made of a single term into concrete syntax. The term is parsed as the
+/// argument of an `assert` command, so that reported columns on the first line are
+/// shifted by the length of `(assert `.
+pub fn parse_term(input: &str) -> Result<concrete::Term, Error> {
+    let mut commands = parse_commands(&format!("(assert {})", input))?;
+    match commands.pop() {
+        Some(concrete::Command::Assert { term }) if commands.is_empty() => Ok(term),
+        _ => Err(Error::ParsingError(
+            Position::default(),
+            format!("expected a single term: {}", input),
+        )),
+    }
+}
+
+/// Parse the terms of the assertions of a script, which should contain no other
+/// commands.
+#[cfg(test)]
+pub(crate) fn parse_assertions(input: &str) -> Vec<concrete::Term> {
+    parse_commands(input)
+        .unwrap()
+        .into_iter()
+        .map(|command| match command {
+            concrete::Command::Assert { term } => term,
+            command => panic!("expected an assertion: {}", command),
+        })
+        .collect()
+}
+
+/// Build concrete commands, or a term with the `term:` prefix, from SMT-LIB text.
+/// Arguments are interpolated as by `format!`, so that symbols, sorts, and terms can be
+/// inserted by their `Display` implementation. The text is parsed at runtime, and the
+/// macro panics if it is not valid.
+/// ```
+/// # use smt2parser::smt2;
+/// let x = smt2!(term: "(+ y 1)");
+/// let commands = smt2!("(declare-fun y () Int) (assert (> {} {}))", x, 0);
+/// assert_eq!(commands[1].to_string(), "(assert (> (+ y 1) 0))");
+/// ```
+#[macro_export]
+macro_rules! smt2 {
+    (term: $($arg:tt)+) => {
+        $crate::parse_term(&format!($($arg)+)).expect("invalid SMT2 term")
+    };
+    ($($arg:tt)+) => {
+        $crate::parse_commands(&format!($($arg)+)).expect("invalid SMT2 commands")
+    };
+}
+
 #[test]
 fn test_command_stream_error() {
     let input = b"(echo \"Hello world!\")(exit f)";
-    let builder = concrete::SyntaxBuilder::default();
+    let builder = concrete::SyntaxBuilder;
     let stream = CommandStream::new(&input[..], builder, None);
     let commands = stream.collect::<Vec<_>>();
     assert!(matches!(
@@ -160,23 +571,224 @@ fn test_command_stream_error() {
         commands[0].as_ref().unwrap().to_string(),
         "(echo \"Hello world!\")"
     );
+    assert_eq!(
+        commands[1].as_ref().unwrap_err().to_string(),
+        "error: unexpected token: Symbol(\"f\")\n   --> 1:28"
+    );
+}
+
+#[test]
+fn test_command_stream_error_recovery() {
+    let input =
+        b"(echo \"Hello world!\")(exit f)(declare-fun (x) Int)\n(assert (> (f (g)) 0))(check-sat)";
+    let stream = CommandStream::new(&input[..], concrete::SyntaxBuilder, None);
+    let (commands, errors) = stream.parse_all();
+    assert_eq!(
+        commands.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+        vec!["(echo \"Hello world!\")", "(check-sat)"]
+    );
+    assert_eq!(errors.len(), 3);
+    assert!(errors
+        .iter()
+        .all(|e| matches!(e, concrete::Error::SyntaxError(..))));
 }
 
 #[test]
 fn test_command_stream_invalid_token() {
-    let input = b"(echo \"Hello world!\")(exit \000)";
-    let builder = concrete::SyntaxBuilder::default();
+    let input = b"(echo \"Hello world!\")(exit \x0000)\n(check-sat) |x";
+    let builder = concrete::SyntaxBuilder;
     let stream = CommandStream::new(&input[..], builder, None);
-    let commands = stream.collect::<Vec<_>>();
-    assert!(matches!(
-        commands[..],
-        [
-            Ok(concrete::Command::Echo { .. }),
-            Err(concrete::Error::ParsingError(..)),
+    let (commands, errors) = stream.parse_all();
+    assert_eq!(
+        commands.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+        vec!["(echo \"Hello world!\")", "(check-sat)"]
+    );
+    assert_eq!(
+        errors.iter().map(|e| e.to_string()).collect::<Vec<_>>(),
+        vec![
+            "error: invalid token\n   --> 1:28",
+            "error: invalid token\n   --> 2:13"
         ]
-    ));
+    );
+}
+
+#[test]
+fn test_command_stream_spans() {
+    let input = b"(set-logic QF_LIA) ; comment\n  (assert\n  (> x 0))\n(exit)";
+    let stream = CommandStream::new(&input[..], concrete::SyntaxBuilder, Some("f".into()));
+    let spans = stream
+        .spanned()
+        .map(|result| {
+            let (_, span) = result.unwrap();
+            let text = std::str::from_utf8(&input[span.offsets.clone()]).unwrap();
+            (span.to_string(), text.to_string())
+        })
+        .collect::<Vec<_>>();
     assert_eq!(
-        commands[0].as_ref().unwrap().to_string(),
-        "(echo \"Hello world!\")"
+        spans,
+        vec![
+            ("f:1:1-1:19".to_string(), "(set-logic QF_LIA)".to_string()),
+            ("f:2:3-3:11".to_string(), "(assert\n  (> x 0))".to_string()),
+            ("f:4:1-4:7".to_string(), "(exit)".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_command_stream_from_reader() {
+    // An endless script: only the requested commands are parsed.
+    struct Endless(usize);
+
+    impl std::io::Read for Endless {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let command = format!("(assert (> x {}))\n", self.0);
+            self.0 += 1;
+            let n = std::cmp::min(buf.len(), command.len());
+            buf[..n].copy_from_slice(&command.as_bytes()[..n]);
+            Ok(n)
+        }
+    }
+
+    let reader = std::io::BufReader::with_capacity(64, Endless(0));
+    let commands = CommandStream::from_reader(reader)
+        .take(3)
+        .map(|command| command.unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        commands,
+        vec!["(assert (> x 0))", "(assert (> x 1))", "(assert (> x 2))"]
+    );
+}
+
+#[test]
+fn test_command_stream_comments() {
+    let input = b"; Model of a counter.\n(declare-fun x () Int) ; current value\n(assert\n  ; initially\n  (= x 0))\n; end\n";
+    let mut stream = CommandStream::from_reader(&input[..]).with_comments();
+    let commands = (&mut stream)
+        .map(|command| command.unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        commands,
+        vec![
+            "; Model of a counter.\n(declare-fun x () Int)",
+            "; current value\n; initially\n(assert (= x 0))",
+        ]
+    );
+    assert_eq!(stream.take_trailing_comments(), vec![" end".to_string()]);
+}
+
+#[test]
+fn test_smt2_macro() {
+    let index = concrete::Symbol("i".to_string());
+    let read = smt2!(term: "(select a {})", index);
+    assert_eq!(read.to_string(), "(select a i)");
+    let commands = smt2!(
+        "(declare-fun {name} () Int)\n(assert (= {name} {read}))",
+        name = "x",
+        read = read
+    );
+    assert_eq!(
+        commands.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+        vec!["(declare-fun x () Int)", "(assert (= x (select a i)))"]
+    );
+    assert!(parse_term("(f x)) (assert (g y)").is_err());
+    assert!(parse_term("(f x").is_err());
+}
+
+#[test]
+fn test_parse_commands_parallel() {
+    let input = r#"
+(declare-fun |a
+b| () Int) ; comment with (
+(assert (= |a
+b| 1))
+(echo "a "")"" (")
+(assert
+  (> |a
+b| 0))
+"#;
+    assert_eq!(
+        split_commands(input.as_bytes(), 3)
+            .into_iter()
+            .map(|(line, chunk)| (line, chunk.iter().filter(|c| **c == b'\n').count()))
+            .collect::<Vec<_>>(),
+        vec![(0, 3), (3, 3), (6, 3)]
+    );
+    for threads in 1..5 {
+        assert_eq!(
+            parse_commands_parallel(input.as_bytes(), None, threads).unwrap(),
+            parse_commands(input).unwrap()
+        );
+    }
+    // Comments are not decoded.
+    let input = b"; caf\xe9\n(assert true)\n; \xff\n(assert false)\n";
+    assert_eq!(
+        parse_commands_parallel(input, None, 2)
+            .unwrap()
+            .iter()
+            .map(|command| command.to_string())
+            .collect::<Vec<_>>(),
+        vec!["(assert true)", "(assert false)"]
+    );
+    let error =
+        parse_commands_parallel(b"(assert true)\n(assert (=> x))\n\n(assert ())\n", None, 3);
+    assert_eq!(
+        error.unwrap_err().to_string(),
+        parse_commands("(assert true)\n(assert (=> x))\n\n(assert ())\n")
+            .unwrap_err()
+            .to_string()
+    );
+}
+
+#[test]
+fn test_parse_error_positions() {
+    // Line and column of the first error, counted from 1.
+    let first_error = |input: &str| match CommandStream::from_reader(input.as_bytes())
+        .find_map(|result| result.err())
+    {
+        Some(concrete::Error::SyntaxError(position, message)) => {
+            (false, position.line, position.column, message)
+        }
+        Some(concrete::Error::ParsingError(position, message)) => {
+            (true, position.line, position.column, message)
+        }
+        None => panic!("no error in {:?}", input),
+    };
+    assert_eq!(
+        first_error("(declare-fun p () Bool)\n(assert (and p (not p)) extra)"),
+        (
+            false,
+            2,
+            25,
+            "unexpected token: Symbol(\"extra\")".to_string()
+        )
+    );
+    assert_eq!(
+        first_error("(assert\n  (= x #z1))"),
+        (false, 2, 8, "invalid token".to_string())
+    );
+    assert_eq!(
+        first_error("(assert (let ((x)) x))"),
+        (false, 1, 17, "unexpected token: RightParen".to_string())
+    );
+    assert_eq!(
+        first_error("\t(assert p))"),
+        (false, 1, 12, "unexpected token: RightParen".to_string())
+    );
+    assert_eq!(
+        first_error("(assert (and p\n  q)"),
+        (true, 2, 5, "unexpected end of input".to_string())
+    );
+
+    let error = CommandStream::new(
+        &b"(check-sat)\n\n  (exit 1 2)"[..],
+        concrete::SyntaxBuilder,
+        Some("input.smt2".to_string()),
+    )
+    .find_map(|result| result.err())
+    .unwrap();
+    assert_eq!(
+        error.to_string(),
+        "error: unexpected token: Numeral(1)\n   --> input.smt2:3:9"
     );
 }
@@ -0,0 +1,530 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Inference of the SMT-LIB logic of a script.
+
+use crate::{
+    concrete::{Command, Constant, Error, Identifier, Sort, Symbol, SyntaxBuilder, Term},
+    rewriter::{free_symbols, Rewriter},
+    simplification::symbol_of,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, fmt};
+
+/// An SMT-LIB logic, given by the theories and features that a script uses. The name of
+/// the logic is obtained with `to_string`, e.g. `QF_ALIA` or `UFNIA`: it is the name of
+/// the smallest standard SMT-LIB logic that includes the features, or `ALL` if there is
+/// none.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Logic {
+    pub quantifiers: bool,
+    pub arrays: bool,
+    pub uninterpreted_functions: bool,
+    pub bit_vectors: bool,
+    pub datatypes: bool,
+    pub strings: bool,
+    pub integers: bool,
+    pub reals: bool,
+    pub nonlinear: bool,
+}
+
+/// The logics of the SMT-LIB benchmark library that can be described by a [`Logic`].
+const STANDARD_LOGICS: &[&str] = &[
+    "ABV",
+    "ALIA",
+    "ANIA",
+    "AUFBV",
+    "AUFBVDTLIA",
+    "AUFBVDTNIA",
+    "AUFDTLIA",
+    "AUFDTLIRA",
+    "AUFDTNIRA",
+    "AUFLIA",
+    "AUFLIRA",
+    "AUFNIA",
+    "AUFNIRA",
+    "BV",
+    "LIA",
+    "LRA",
+    "NIA",
+    "NRA",
+    "UF",
+    "UFBV",
+    "UFBVLIA",
+    "UFDT",
+    "UFDTLIA",
+    "UFDTLIRA",
+    "UFDTNIA",
+    "UFDTNIRA",
+    "UFLIA",
+    "UFLRA",
+    "UFNIA",
+    "UFNIRA",
+    "QF_ABV",
+    "QF_ALIA",
+    "QF_ANIA",
+    "QF_AUFBV",
+    "QF_AUFBVLIA",
+    "QF_AUFBVNIA",
+    "QF_AUFLIA",
+    "QF_AUFNIA",
+    "QF_AX",
+    "QF_BV",
+    "QF_DT",
+    "QF_LIA",
+    "QF_LIRA",
+    "QF_LRA",
+    "QF_NIA",
+    "QF_NIRA",
+    "QF_NRA",
+    "QF_S",
+    "QF_SLIA",
+    "QF_SNIA",
+    "QF_UF",
+    "QF_UFBV",
+    "QF_UFBVLIA",
+    "QF_UFDT",
+    "QF_UFDTLIA",
+    "QF_UFDTLIRA",
+    "QF_UFDTNIA",
+    "QF_UFLIA",
+    "QF_UFLRA",
+    "QF_UFNIA",
+    "QF_UFNRA",
+];
+
+impl Logic {
+    /// The `set-logic` command of the logic.
+    pub fn to_command(self) -> Command {
+        Command::SetLogic {
+            symbol: Symbol(self.to_string()),
+        }
+    }
+
+    /// Whether the logic is exactly a standard SMT-LIB logic, as opposed to being
+    /// included in a larger one.
+    pub fn is_standard(self) -> bool {
+        STANDARD_LOGICS.contains(&self.feature_name().as_str())
+    }
+
+    // The larger logics obtained by adding some of the given features, smallest first.
+    fn extensions(self) -> Vec<Logic> {
+        let features: [fn(&mut Logic); 6] = [
+            |logic| logic.uninterpreted_functions = true,
+            |logic| logic.arrays = true,
+            |logic| logic.quantifiers = true,
+            |logic| logic.integers = true,
+            |logic| logic.reals = true,
+            |logic| logic.nonlinear = true,
+        ];
+        let mut extensions = (0..1u32 << features.len())
+            .map(|set| {
+                let mut logic = self;
+                for (i, add) in features.iter().enumerate() {
+                    if set & (1 << i) != 0 {
+                        add(&mut logic);
+                    }
+                }
+                (set.count_ones(), logic)
+            })
+            .collect::<Vec<_>>();
+        extensions.sort_by_key(|(size, _)| *size);
+        extensions.into_iter().map(|(_, logic)| logic).collect()
+    }
+
+    // The name of the logic after its features, following the SMT-LIB conventions, but
+    // possibly not standard, e.g. `QF_ADTLIA`.
+    fn feature_name(self) -> String {
+        let mut name = String::new();
+        if !self.quantifiers {
+            name.push_str("QF_");
+        }
+        let prefix = name.len();
+        if self.arrays {
+            name.push('A');
+        }
+        if self.uninterpreted_functions {
+            name.push_str("UF");
+        }
+        if self.bit_vectors {
+            name.push_str("BV");
+        }
+        if self.datatypes {
+            name.push_str("DT");
+        }
+        if self.strings {
+            name.push('S');
+        }
+        if self.integers || self.reals {
+            name.push(if self.nonlinear { 'N' } else { 'L' });
+            match (self.integers, self.reals) {
+                (true, false) => name.push_str("IA"),
+                (false, true) => name.push_str("RA"),
+                _ => name.push_str("IRA"),
+            }
+        }
+        // Logics without any theory are named after their uninterpreted symbols.
+        match &name[prefix..] {
+            "" => name.push_str("UF"),
+            "A" => name.push('X'),
+            _ => (),
+        }
+        name
+    }
+}
+
+impl fmt::Display for Logic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = self
+            .extensions()
+            .into_iter()
+            .find(|logic| logic.is_standard())
+            .map_or_else(|| "ALL".to_string(), Logic::feature_name);
+        write!(f, "{}", name)
+    }
+}
+
+/// Compute the smallest logic of a script from the sorts, operators and constants that it
+/// uses. Multiplications and divisions are linear only when at most one of their arguments
+/// is not a numeric constant.
+pub fn detect_logic(commands: &[Command]) -> Logic {
+    let mut detector = LogicDetector::default();
+    for command in commands {
+        command
+            .clone()
+            .accept(&mut detector)
+            .expect("Detecting a logic does not fail");
+    }
+    detector.logic()
+}
+
+#[derive(Debug, Default)]
+struct LogicDetector {
+    visitor: SyntaxBuilder,
+    logic: Logic,
+    numerals: bool,
+}
+
+impl LogicDetector {
+    fn logic(&self) -> Logic {
+        let mut logic = self.logic;
+        // Numerals are reals in purely real arithmetic.
+        if self.numerals && !logic.reals {
+            logic.integers = true;
+        }
+        logic
+    }
+}
+
+impl Rewriter for LogicDetector {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn process_sort(&mut self, value: Sort) -> Result<Sort, Self::Error> {
+        match &value {
+            Sort::Simple {
+                identifier: Identifier::Simple { symbol },
+            } => match symbol.0.as_str() {
+                "Int" => self.logic.integers = true,
+                "Real" => self.logic.reals = true,
+                "String" | "RegLan" => self.logic.strings = true,
+                _ => (),
+            },
+            Sort::Simple {
+                identifier: Identifier::Indexed { symbol, .. },
+            } if symbol.0 == "BitVec" => self.logic.bit_vectors = true,
+            Sort::Parameterized {
+                identifier: Identifier::Simple { symbol },
+                ..
+            } if symbol.0 == "Array" => self.logic.arrays = true,
+            _ => (),
+        }
+        Ok(value)
+    }
+
+    fn process_term(&mut self, value: Term) -> Result<Term, Self::Error> {
+        match &value {
+            Term::Constant(constant) => match constant {
+                Constant::Numeral(_) => self.numerals = true,
+                Constant::Decimal(_) => self.logic.reals = true,
+                Constant::Hexadecimal(_) | Constant::Binary(_) => self.logic.bit_vectors = true,
+                Constant::String(_) => self.logic.strings = true,
+            },
+            Term::Forall { .. } | Term::Exists { .. } => self.logic.quantifiers = true,
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } => match symbol_of(qual_identifier) {
+                Some("*") => {
+                    let variables = arguments
+                        .iter()
+                        .filter(|argument| !is_numeric_constant(argument))
+                        .count();
+                    self.logic.nonlinear |= variables > 1;
+                }
+                Some("/") => {
+                    self.logic.reals = true;
+                    self.logic.nonlinear |= !arguments.iter().skip(1).all(is_numeric_constant);
+                }
+                Some("div" | "mod") => {
+                    self.logic.integers = true;
+                    self.logic.nonlinear |= !arguments.iter().skip(1).all(is_numeric_constant);
+                }
+                Some("to_real" | "to_int" | "is_int") => {
+                    self.logic.integers = true;
+                    self.logic.reals = true;
+                }
+                _ => (),
+            },
+            _ => (),
+        }
+        Ok(value)
+    }
+
+    fn process_command(&mut self, value: Command) -> Result<Command, Self::Error> {
+        match &value {
+            Command::DeclareSort { .. } => self.logic.uninterpreted_functions = true,
+            Command::DeclareFun { parameters, .. } if !parameters.is_empty() => {
+                self.logic.uninterpreted_functions = true
+            }
+            Command::DeclareDatatype { .. } | Command::DeclareDatatypes { .. } => {
+                self.logic.datatypes = true
+            }
+            _ => (),
+        }
+        Ok(value)
+    }
+}
+
+fn is_numeric_constant(term: &Term) -> bool {
+    match term {
+        Term::Constant(Constant::Numeral(_) | Constant::Decimal(_)) => true,
+        Term::Application {
+            qual_identifier,
+            arguments,
+        } => {
+            matches!(symbol_of(qual_identifier), Some("-" | "+" | "*" | "/"))
+                && arguments.iter().all(is_numeric_constant)
+        }
+        _ => false,
+    }
+}
+
+/// Fragment of a problem, ordered from the most to the least restricted, which decides
+/// the strategies that can be applied to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Fragment {
+    /// Boolean combinations of propositional symbols.
+    Propositional,
+    /// Equality and uninterpreted functions, without arithmetic.
+    Euf,
+    /// Quantifier-free linear arithmetic, possibly with uninterpreted functions.
+    LinearArithmetic,
+    /// Arrays with linear arithmetic, where quantifiers are universal over integer
+    /// indices, which are only compared with each other or with ground terms, and read
+    /// from arrays independent of the indices.
+    ArrayProperty,
+    /// Quantifier-free nonlinear arithmetic.
+    NonlinearArithmetic,
+    /// Quantifiers outside the array property fragment.
+    Quantified,
+    /// Other theories, such as bit-vectors, datatypes and strings.
+    Other,
+}
+
+impl Term {
+    /// The fragment of the term. Symbols of unknown sorts are assumed to be boolean or
+    /// uninterpreted unless arithmetic operators are applied to them.
+    pub fn fragment(&self) -> Fragment {
+        let mut detector = LogicDetector::default();
+        self.clone()
+            .accept(&mut detector)
+            .expect("Detecting a logic does not fail");
+        classify(detector.logic(), &[self])
+    }
+}
+
+/// The fragment of the assertions and definitions of a script.
+pub fn detect_fragment(commands: &[Command]) -> Fragment {
+    let terms = commands
+        .iter()
+        .filter_map(|command| match command {
+            Command::Assert { term } | Command::DefineFun { term, .. } => Some(term),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    classify(detect_logic(commands), &terms)
+}
+
+fn classify(logic: Logic, terms: &[&Term]) -> Fragment {
+    let bound = BTreeSet::new();
+    if logic.bit_vectors || logic.datatypes || logic.strings {
+        Fragment::Other
+    } else if logic.quantifiers {
+        if !logic.nonlinear && terms.iter().all(|term| is_array_property(term, &bound)) {
+            Fragment::ArrayProperty
+        } else {
+            Fragment::Quantified
+        }
+    } else if logic.nonlinear {
+        Fragment::NonlinearArithmetic
+    } else if logic.arrays {
+        Fragment::ArrayProperty
+    } else if logic.integers || logic.reals {
+        Fragment::LinearArithmetic
+    } else if logic.uninterpreted_functions || !terms.iter().all(|term| is_propositional(term)) {
+        Fragment::Euf
+    } else {
+        Fragment::Propositional
+    }
+}
+
+fn is_propositional(term: &Term) -> bool {
+    match term {
+        Term::QualIdentifier(_) => true,
+        Term::Application {
+            qual_identifier,
+            arguments,
+        } => {
+            matches!(
+                symbol_of(qual_identifier),
+                Some("not" | "and" | "or" | "=>" | "xor")
+            ) && arguments.iter().all(is_propositional)
+        }
+        Term::Let { var_bindings, term } => {
+            var_bindings
+                .iter()
+                .all(|(_, value)| is_propositional(value))
+                && is_propositional(term)
+        }
+        Term::Attributes { term, .. } => is_propositional(term),
+        _ => false,
+    }
+}
+
+// Whether the variables in `bound` only occur as array indices or in comparisons.
+fn is_array_property(term: &Term, bound: &BTreeSet<&str>) -> bool {
+    let is_bound = |term: &Term| match term {
+        Term::QualIdentifier(qual_identifier) => {
+            symbol_of(qual_identifier).map_or(false, |symbol| bound.contains(symbol))
+        }
+        _ => false,
+    };
+    match term {
+        Term::Constant(_) => true,
+        Term::QualIdentifier(_) => !is_bound(term),
+        Term::Application {
+            qual_identifier,
+            arguments,
+        } => match symbol_of(qual_identifier) {
+            Some("select") if arguments.len() == 2 && is_bound(&arguments[1]) => {
+                let symbols = free_symbols(&arguments[0]);
+                bound.iter().all(|symbol| !symbols.contains(*symbol))
+            }
+            Some("=" | "<=" | "<" | ">=" | ">") => arguments
+                .iter()
+                .all(|argument| is_bound(argument) || is_array_property(argument, bound)),
+            _ => arguments
+                .iter()
+                .all(|argument| is_array_property(argument, bound)),
+        },
+        Term::Let { var_bindings, term } => {
+            var_bindings
+                .iter()
+                .all(|(_, value)| is_array_property(value, bound))
+                && is_array_property(term, bound)
+        }
+        Term::Forall { vars, term } => {
+            let is_index = |sort: &Sort| matches!(sort, Sort::Simple { identifier: Identifier::Simple { symbol } } if symbol.0 == "Int");
+            let mut bound = bound.clone();
+            bound.extend(vars.iter().map(|(symbol, _)| symbol.0.as_str()));
+            vars.iter().all(|(_, sort)| is_index(sort)) && is_array_property(term, &bound)
+        }
+        Term::Exists { .. } | Term::Match { .. } => false,
+        Term::Attributes { term, .. } => is_array_property(term, bound),
+    }
+}
+
+#[test]
+fn test_detect_logic() {
+    let logic = |input: &str| detect_logic(&crate::parse_commands(input).unwrap()).to_string();
+    assert_eq!(logic("(declare-fun p () Bool) (assert p)"), "QF_UF");
+    assert_eq!(
+        logic("(declare-fun a () (Array Int Int)) (assert (= (select a 0) 1))"),
+        "QF_ALIA"
+    );
+    assert_eq!(
+        logic("(declare-fun f (Int) Int) (assert (forall ((x Int)) (> (f x) (* 2 x))))"),
+        "UFLIA"
+    );
+    assert_eq!(
+        logic("(declare-fun x () Real) (assert (> (* x x) (/ 1.0 2)))"),
+        "QF_NRA"
+    );
+    assert_eq!(
+        logic("(declare-fun x () Int) (assert (> (to_real x) 0.5))"),
+        "QF_LIRA"
+    );
+    assert_eq!(
+        logic("(declare-fun a () (Array Bool Bool)) (assert (select a true))"),
+        "QF_AX"
+    );
+    assert_eq!(
+        logic("(declare-sort U 0) (declare-fun b () (_ BitVec 8)) (declare-fun u () U) (assert (= b #x00))"),
+        "QF_UFBV"
+    );
+    assert_eq!(
+        logic("(declare-datatypes ((L 0)) (((nil) (cons (hd Int) (tl L))))) (assert (exists ((l L)) (= (hd l) 1)))"),
+        "UFDTLIA"
+    );
+    assert_eq!(
+        logic("(declare-fun s () String) (declare-fun x () Real) (assert (= (str.len s) 1))"),
+        "ALL"
+    );
+    assert_eq!(
+        detect_logic(&crate::parse_commands("(assert (= 1 1))").unwrap())
+            .to_command()
+            .to_string(),
+        "(set-logic QF_LIA)"
+    );
+}
+
+#[test]
+fn test_fragments() {
+    let fragment = |input: &str| detect_fragment(&crate::parse_commands(input).unwrap());
+    let term = |input: &str| crate::parse_term(input).unwrap().fragment();
+    assert_eq!(term("(and p (or (not q) r))"), Fragment::Propositional);
+    assert_eq!(term("(=> p (= (f a) b))"), Fragment::Euf);
+    assert_eq!(term("(< (+ x 1) (* 2 y))"), Fragment::LinearArithmetic);
+    assert_eq!(term("(< (* x y) 1)"), Fragment::NonlinearArithmetic);
+    assert_eq!(
+        fragment(
+            "(declare-fun a () (Array Int Int)) (declare-fun n () Int) \
+             (assert (forall ((i Int) (j Int)) (=> (and (<= 0 i) (<= i j) (< j n)) (<= (select a i) (select a j)))))"
+        ),
+        Fragment::ArrayProperty
+    );
+    assert_eq!(
+        fragment(
+            "(declare-fun a () (Array Int Int)) \
+             (assert (forall ((i Int)) (= (select a (+ i 1)) (select a i))))"
+        ),
+        Fragment::Quantified
+    );
+    assert_eq!(
+        term("(forall ((i Int)) (= (select (store a i 0) i) 0))"),
+        Fragment::Quantified
+    );
+    assert_eq!(
+        fragment("(declare-sort U 0) (declare-fun u () U) (assert (= u u))"),
+        Fragment::Euf
+    );
+    assert_eq!(
+        fragment("(declare-fun b () (_ BitVec 4)) (assert (= b #x0))"),
+        Fragment::Other
+    );
+}
@@ -5,9 +5,15 @@
 
 use smt2parser::{
     concrete::SyntaxBuilder,
+    normal_forms::ACNormalizer,
+    printer::PrettyPrinter,
+    proxy::Proxy,
     renaming::{SymbolNormalizer, SymbolNormalizerConfig, TesterModernizer},
-    stats::Smt2Counters,
-    CommandStream,
+    solver::{ProcessSolver, SolverConfig, SolverKind},
+    stats::{ScriptStats, Smt2Counters},
+    tip::TipProblem,
+    typing::{SmtLibTheories, SortChecker},
+    CommandStream, Commented,
 };
 use std::path::PathBuf;
 use structopt::StructOpt;
@@ -41,6 +47,19 @@ enum Operation {
         #[structopt(long)]
         symbol_randomization_seed: Option<u64>,
 
+        /// Print the comments of the input before the commands that follow them.
+        #[structopt(long)]
+        keep_comments: bool,
+
+        /// Pretty-print commands on several lines of at most N characters when possible.
+        #[structopt(long)]
+        width: Option<usize>,
+
+        /// Flatten and sort the arguments of associative-commutative operators, so that
+        /// the outputs of equivalent scripts are easier to compare.
+        #[structopt(long)]
+        ac_normalize: bool,
+
         /// Path to the SMT2 files.
         #[structopt(parse(from_os_str))]
         inputs: Vec<PathBuf>,
@@ -54,35 +73,109 @@ enum Operation {
         #[structopt(long, parse(from_os_str))]
         symbols: Option<PathBuf>,
 
+        /// Print the statistics of the whole script instead: commands by kind, sorts of
+        /// declarations, maximal term depth, and occurrences of all symbols.
+        #[structopt(long)]
+        script: bool,
+
         /// Path to the SMT2 files.
         #[structopt(parse(from_os_str))]
         inputs: Vec<PathBuf>,
     },
+    /// Report the syntax errors and the location of the first ill-sorted command.
+    Check {
+        /// Path to the SMT2 files.
+        #[structopt(parse(from_os_str))]
+        inputs: Vec<PathBuf>,
+    },
+    /// Forward the commands of the standard input to a solver and print its responses, to
+    /// debug the integration of a client with the solver.
+    Proxy {
+        /// Solver: `z3`, `cvc5` or `yices`.
+        solver: SolverKind,
+
+        /// Path of the solver executable. By default, the solver is looked up in the
+        /// environment.
+        #[structopt(long, parse(from_os_str))]
+        path: Option<PathBuf>,
+
+        /// Where to write the session as an SMT-LIB script, with responses and timings as
+        /// comments.
+        #[structopt(long, parse(from_os_str))]
+        transcript: Option<PathBuf>,
+
+        /// Remove the annotations of terms, except names, before sending commands.
+        #[structopt(long)]
+        strip_annotations: bool,
+
+        /// Normalize bound symbols to x0, x1.. before sending commands.
+        #[structopt(long)]
+        normalize_symbols: bool,
+
+        /// Print the total time spent by the solver on each kind of command to the
+        /// standard error at the end of the session.
+        #[structopt(long)]
+        timings: bool,
+    },
+    /// Print one SMT-LIB script per goal of a TIP benchmark, which is unsatisfiable if and
+    /// only if the goal holds.
+    Tip {
+        /// Path to the TIP file.
+        #[structopt(parse(from_os_str))]
+        input: PathBuf,
+    },
     Vmt {
+        /// Print the CNF of the unrolled problem in the DIMACS format, if it is purely
+        /// boolean.
+        #[structopt(long)]
+        dimacs: bool,
+
+        /// Print the transition relation as a Graphviz graph, where equal subterms are
+        /// merged.
+        #[structopt(long)]
+        dot: bool,
+
         /// Path to the SMT2 files.
         #[structopt(parse(from_os_str))]
         input: PathBuf,
-    }
+    },
 }
 
-fn process_file<T, F>(state: T, file_path: PathBuf, mut f: F) -> std::io::Result<T>
+fn process_file<T, F>(
+    state: T,
+    file_path: PathBuf,
+    keep_comments: bool,
+    mut f: F,
+) -> std::io::Result<T>
 where
     T: smt2parser::visitors::Smt2Visitor,
     F: FnMut(T::Command),
     T::Error: std::fmt::Display,
 {
+    let print_comments = |comments: Vec<String>| {
+        if keep_comments {
+            for comment in comments {
+                println!(";{}", comment);
+            }
+        }
+    };
     let file = std::io::BufReader::new(std::fs::File::open(&file_path)?);
-    let mut stream = CommandStream::new(file, state, file_path.to_str().map(String::from));
+    let mut stream =
+        CommandStream::new(file, state, file_path.to_str().map(String::from)).with_comments();
     for result in &mut stream {
         match result {
-            Ok(command) => f(command),
+            Ok(Commented { comments, command }) => {
+                print_comments(comments);
+                f(command)
+            }
             Err(error) => {
                 eprintln!("{}", error);
                 break;
             }
         }
     }
-    Ok(stream.into_visitor())
+    print_comments(stream.take_trailing_comments());
+    Ok(stream.into_inner().into_visitor())
 }
 
 fn read_words(path: Option<PathBuf>) -> std::io::Result<Vec<String>> {
@@ -99,9 +192,10 @@ fn read_words(path: Option<PathBuf>) -> std::io::Result<Vec<String>> {
 fn main() -> std::io::Result<()> {
     let options = Options::from_args();
     match options.operation {
-        Operation::Vmt { input } => {
+        Operation::Vmt { dimacs, dot, input } => {
             let file = std::io::BufReader::new(std::fs::File::open(&input)?);
-            let command_stream = CommandStream::new(file, SyntaxBuilder, input.to_str().map(String::from));
+            let command_stream =
+                CommandStream::new(file, SyntaxBuilder, input.to_str().map(String::from));
             let mut commands = vec![];
             for result in command_stream {
                 match result {
@@ -111,13 +205,95 @@ fn main() -> std::io::Result<()> {
             }
             let vmt_model = VMTModel::checked_from(commands);
             match vmt_model {
+                Ok(vm) if dimacs => match vm.unroll(10).to_cnf().to_dimacs() {
+                    Some(dimacs) => print!("{}", dimacs),
+                    None => eprintln!("The unrolled problem is not purely boolean."),
+                },
+                Ok(vm) if dot => print!("{}", vm.transition().to_dot()),
                 Ok(vm) => {
                     vm.print_stats();
                     vm.print_raw_smtlib2();
                     let smt = vm.unroll(10);
                     println!("{}", smt.to_smtlib2());
                 }
-                Err(_) => panic!("Could not parse VMT.")
+                Err(error) => panic!("Could not parse VMT: {}", error),
+            }
+        }
+
+        Operation::Tip { input } => {
+            let text = std::fs::read_to_string(&input)?;
+            let problems = TipProblem::parse(&text)
+                .and_then(|problem| problem.problems())
+                .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+            for (index, problem) in problems.iter().enumerate() {
+                println!("; goal {}", index);
+                println!("{}", problem.to_smtlib2());
+                println!("(check-sat)");
+            }
+        }
+
+        Operation::Proxy {
+            solver,
+            path,
+            transcript,
+            strip_annotations,
+            normalize_symbols,
+            timings,
+        } => {
+            let config = SolverConfig {
+                kind: solver,
+                path,
+                options: Vec::new(),
+            };
+            let solver = ProcessSolver::spawn(&config)
+                .map_err(|error| std::io::Error::new(std::io::ErrorKind::Other, error))?;
+            let mut proxy = Proxy::new(solver);
+            if let Some(transcript) = transcript {
+                proxy = proxy.with_transcript(std::fs::File::create(transcript)?);
+            }
+            if strip_annotations {
+                proxy = proxy.with_stripped_annotations();
+            }
+            if normalize_symbols {
+                proxy = proxy.with_normalized_symbols(SymbolNormalizerConfig::default());
+            }
+            let stdin = std::io::stdin();
+            let stream = CommandStream::new(stdin.lock(), SyntaxBuilder, None);
+            for result in stream {
+                let command = match result {
+                    Ok(command) => command,
+                    Err(error) => {
+                        eprintln!("{}", error);
+                        break;
+                    }
+                };
+                let exit = command == smt2parser::concrete::Command::Exit;
+                match proxy.forward(command) {
+                    Ok(Some(response)) => {
+                        use std::io::Write;
+                        println!("{}", response);
+                        std::io::stdout().flush()?;
+                    }
+                    Ok(None) => (),
+                    Err(error) => {
+                        eprintln!("{}", error);
+                        break;
+                    }
+                }
+                if exit {
+                    break;
+                }
+            }
+            if timings {
+                let mut totals = std::collections::BTreeMap::<_, (usize, f64)>::new();
+                for timing in proxy.timings() {
+                    let total = totals.entry(timing.command.name()).or_default();
+                    total.0 += 1;
+                    total.1 += timing.elapsed.as_secs_f64();
+                }
+                for (name, (count, seconds)) in totals {
+                    eprintln!("{:<24} {:>6} {:>12.6}s", name, count, seconds);
+                }
             }
         }
 
@@ -125,6 +301,9 @@ fn main() -> std::io::Result<()> {
             normalize_symbols,
             max_randomized_symbols,
             symbol_randomization_seed,
+            keep_comments,
+            width,
+            ac_normalize,
             inputs,
         } => {
             let randomization_space = smt2parser::visitors::SymbolKind::iter()
@@ -135,32 +314,83 @@ fn main() -> std::io::Result<()> {
                 randomization_space,
                 randomization_seed,
             };
+            let printer = width.map(|width| PrettyPrinter::new(2, width));
+            let print = |command: smt2parser::concrete::Command| {
+                let command = if ac_normalize {
+                    command
+                        .accept(&mut ACNormalizer::default())
+                        .expect("Normalizing a command does not fail")
+                } else {
+                    command
+                };
+                match &printer {
+                    Some(printer) => println!("{}", command.to_pretty_string(printer)),
+                    None => println!("{}", command),
+                }
+            };
             if normalize_symbols {
                 let mut normalizer = SymbolNormalizer::new(SyntaxBuilder, config);
                 for input in inputs {
                     // 1. Parse input commands while rewriting `is-Foo` into `(_ is Foo)` on the fly with TesterModernizer.
-                    process_file(TesterModernizer::new(SyntaxBuilder), input, |command| {
-                        // 2. Re-visit the syntax for name resolution and normalization.
-                        let command = command.accept(&mut normalizer).unwrap();
-                        println!("{}", command);
-                    })?;
+                    process_file(
+                        TesterModernizer::new(SyntaxBuilder),
+                        input,
+                        keep_comments,
+                        |command| {
+                            // 2. Re-visit the syntax for name resolution and normalization.
+                            let command = command.accept(&mut normalizer).unwrap();
+                            print(command);
+                        },
+                    )?;
                 }
             } else {
                 for input in inputs {
-                    process_file(SyntaxBuilder, input, |command| println!("{}", command))?;
+                    process_file(SyntaxBuilder, input, keep_comments, print)?;
+                }
+            }
+        }
+        Operation::Check { inputs } => {
+            for input in inputs {
+                let file = std::io::BufReader::new(std::fs::File::open(&input)?);
+                let stream =
+                    CommandStream::new(file, SyntaxBuilder, input.to_str().map(String::from))
+                        .with_error_recovery();
+                let mut checker = SortChecker::new(SmtLibTheories);
+                for result in stream.spanned() {
+                    match result {
+                        Ok((command, span)) => {
+                            if let Err(error) = checker.check_command(&command) {
+                                eprintln!("error: {}\n   --> {}", error, span);
+                                break;
+                            }
+                        }
+                        Err(error) => eprintln!("{}", error),
+                    }
                 }
             }
         }
+        Operation::Count {
+            script: true,
+            inputs,
+            ..
+        } => {
+            let mut stats = ScriptStats::default();
+            for input in inputs {
+                process_file(SyntaxBuilder, input, false, |command| stats.add(&command))?;
+            }
+            println!("{:#?}", stats)
+        }
         Operation::Count {
             keywords,
             symbols,
             inputs,
+            ..
         } => {
             let keywords = read_words(keywords)?;
             let symbols = read_words(symbols)?;
             let mut state = Smt2Counters::new(keywords, symbols);
             for input in inputs {
-                state = process_file(state, input, |_| {})?;
+                state = process_file(state, input, false, |_| {})?;
             }
             println!("{:#?}", state)
         }
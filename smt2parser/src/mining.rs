@@ -0,0 +1,420 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Mining of candidate invariants of VMT models from reachable states.
+//!
+//! States are sampled with a solver, as runs of the transition relation of bounded
+//! length, or read from the models of unrolled problems. Candidates are instances of
+//! templates over the state variables that hold in all the states. They are not known
+//! to be invariants: the candidates that are not inductive should be dropped afterwards,
+//! e.g. with [`VMTModel::houdini`].
+
+use crate::{
+    concrete::{Sort, Symbol, Term},
+    model::{Model, Value},
+    solver::{SatResult, SmtSolver, SolverError},
+    vmt::VMTModel,
+};
+use num::{BigInt, BigRational};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Values of the state variables in a state.
+pub type State = BTreeMap<String, Value>;
+
+/// Families of candidate invariants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Template {
+    /// `(= x y)` for state variables of the same sort, except arrays.
+    Equalities,
+    /// Bounds `(<= x c)` and `(>= x c)` on integer and real state variables, and on their
+    /// sums and differences, as in the octagon domain.
+    Octagons,
+    /// `(forall ((k Int)) (=> (and (<= 0 k) (< k n)) (= (select a k) c)))` for arrays `a`
+    /// indexed by integers and integer state variables `n`.
+    ArrayRanges,
+}
+
+impl Template {
+    pub const ALL: [Template; 3] = [
+        Template::Equalities,
+        Template::Octagons,
+        Template::ArrayRanges,
+    ];
+}
+
+impl std::str::FromStr for Template {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "equalities" => Ok(Template::Equalities),
+            "octagons" => Ok(Template::Octagons),
+            "array-ranges" => Ok(Template::ArrayRanges),
+            _ => Err(format!("unknown template: {}", s)),
+        }
+    }
+}
+
+/// Miner of candidate invariants over the state variables of a VMT model.
+#[derive(Debug, Clone)]
+pub struct InvariantMiner {
+    variables: Vec<(String, Sort)>,
+    templates: BTreeSet<Template>,
+}
+
+impl InvariantMiner {
+    pub fn new(vmt: &VMTModel, templates: &[Template]) -> Self {
+        Self {
+            variables: vmt.state_variables(),
+            templates: templates.iter().copied().collect(),
+        }
+    }
+
+    /// The states at steps `0` to `steps` of a model of an unrolled problem, given by the
+    /// values of the constants `x@0`, ..., `x@steps`.
+    pub fn states_of_model(&self, model: &Model, steps: u8) -> Vec<State> {
+        (0..=steps)
+            .map(|step| {
+                self.variables
+                    .iter()
+                    .filter_map(|(name, _)| {
+                        let value = model.get(&format!("{}@{}", name, step))?;
+                        Some((name.clone(), value.clone()))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Sample reachable states with a solver: for each number of steps up to `bound`, up
+    /// to `samples` runs of the transition relation, each ending in a new state. The
+    /// solver must produce models.
+    pub fn sample<S: SmtSolver>(
+        &self,
+        vmt: &VMTModel,
+        solver: &mut S,
+        bound: u8,
+        samples: usize,
+    ) -> Result<Vec<State>, SolverError> {
+        // Every run violates the property `false`.
+        let runs = vmt.with_property(crate::smt2!(term: "false"));
+        let mut states = BTreeSet::new();
+        for steps in 0..=bound {
+            solver.push(1)?;
+            for command in runs.unroll(steps).to_commands() {
+                solver.run(&command)?;
+            }
+            for _ in 0..samples {
+                if solver.check_sat()? != SatResult::Sat {
+                    break;
+                }
+                let trace = self.states_of_model(&solver.get_model()?, steps);
+                let blocking = trace.last().and_then(|state| block(state, steps));
+                states.extend(trace);
+                match blocking {
+                    Some(term) => solver.assert(&term)?,
+                    None => break,
+                }
+            }
+            solver.pop(1)?;
+        }
+        Ok(states.into_iter().collect())
+    }
+
+    /// The instances of the templates that hold in all the states, without duplicates.
+    pub fn candidates(&self, states: &[State]) -> Vec<Term> {
+        let mut candidates = Vec::new();
+        if states.is_empty() {
+            return candidates;
+        }
+        let values = |name: &str| {
+            states
+                .iter()
+                .map(|state| state.get(name))
+                .collect::<Option<Vec<_>>>()
+        };
+        let scalars = self
+            .variables
+            .iter()
+            .filter(|(_, sort)| array_index_sort(sort).is_none())
+            .collect::<Vec<_>>();
+        if self.templates.contains(&Template::Equalities) {
+            for (i, (x, sort)) in scalars.iter().enumerate() {
+                for (y, _) in scalars[i + 1..].iter().filter(|(_, s)| s == sort) {
+                    if let (Some(xs), Some(ys)) = (values(x), values(y)) {
+                        if xs == ys {
+                            candidates.push(crate::smt2!(
+                                term: "(= {} {})",
+                                Symbol(x.clone()),
+                                Symbol(y.clone())
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        let numbers = |name: &str| {
+            values(name)?
+                .into_iter()
+                .map(as_rational)
+                .collect::<Option<Vec<_>>>()
+        };
+        if self.templates.contains(&Template::Octagons) {
+            let numeric = scalars
+                .iter()
+                .filter(|(_, sort)| is_numeric(sort))
+                .filter_map(|(name, sort)| Some((name, sort, numbers(name)?)))
+                .collect::<Vec<_>>();
+            for (i, (x, sort, xs)) in numeric.iter().enumerate() {
+                let x = Symbol(x.to_string());
+                push_bounds(&mut candidates, &x.to_string(), sort, xs.clone());
+                for (y, _, ys) in numeric[i + 1..].iter().filter(|(_, s, _)| s == sort) {
+                    let y = Symbol(y.to_string());
+                    let sums = xs.iter().zip(ys).map(|(a, b)| a + b).collect();
+                    push_bounds(&mut candidates, &format!("(+ {} {})", x, y), sort, sums);
+                    let differences = xs.iter().zip(ys).map(|(a, b)| a - b).collect();
+                    push_bounds(
+                        &mut candidates,
+                        &format!("(- {} {})", x, y),
+                        sort,
+                        differences,
+                    );
+                }
+            }
+        }
+        if self.templates.contains(&Template::ArrayRanges) {
+            let lower = BigInt::from(0);
+            let index = self.fresh_name("k");
+            for (array, sort) in &self.variables {
+                if array_index_sort(sort).map(|s| s.to_string()).as_deref() != Some("Int") {
+                    continue;
+                }
+                let arrays = match values(array) {
+                    Some(arrays) => arrays,
+                    None => continue,
+                };
+                for (upper, _) in scalars.iter().filter(|(_, s)| s.to_string() == "Int") {
+                    let uppers = match values(upper) {
+                        Some(uppers) => uppers,
+                        None => continue,
+                    };
+                    // The element at the lower bound, in a state where the range is not
+                    // empty.
+                    let element = arrays.iter().zip(&uppers).find_map(|(a, n)| match n {
+                        Value::Int(n) if *n > lower => a.select(&Value::Int(lower.clone())),
+                        _ => None,
+                    });
+                    let element = match element.and_then(|e| Some((e, e.to_term()?))) {
+                        Some(element) => element,
+                        None => continue,
+                    };
+                    let holds = arrays.iter().zip(&uppers).all(|(a, n)| match n {
+                        Value::Int(n) => range_holds(a, &lower, n, element.0),
+                        _ => false,
+                    });
+                    if holds {
+                        candidates.push(crate::smt2!(
+                            term: "(forall (({} Int)) (=> (and (<= {} {}) (< {} {})) (= (select {} {}) {})))",
+                            index,
+                            lower,
+                            index,
+                            index,
+                            Symbol(upper.clone()),
+                            Symbol(array.clone()),
+                            index,
+                            element.1
+                        ));
+                    }
+                }
+            }
+        }
+        let mut seen = BTreeSet::new();
+        candidates.retain(|candidate| seen.insert(candidate.clone()));
+        candidates
+    }
+
+    // A name that is not the name of a state variable.
+    fn fresh_name(&self, prefix: &str) -> Symbol {
+        let names = self
+            .variables
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<BTreeSet<_>>();
+        let name = (0..)
+            .map(|i| match i {
+                0 => prefix.to_string(),
+                i => format!("{}{}", prefix, i),
+            })
+            .find(|name| !names.contains(name.as_str()))
+            .expect("names are not exhausted");
+        Symbol(name)
+    }
+}
+
+// The negation of the final state of a run of `steps` steps, on the values that can be
+// written as terms, if any.
+fn block(state: &State, steps: u8) -> Option<Term> {
+    let equalities = state
+        .iter()
+        .filter(|(_, value)| !matches!(value, Value::Uninterpreted(_) | Value::Term(_)))
+        .filter_map(|(name, value)| {
+            Some(format!(
+                "(= {} {})",
+                Symbol(format!("{}@{}", name, steps)),
+                value.to_term()?
+            ))
+        })
+        .collect::<Vec<_>>();
+    match equalities.len() {
+        0 => None,
+        1 => Some(crate::smt2!(term: "(not {})", equalities[0])),
+        _ => Some(crate::smt2!(term: "(not (and {}))", equalities.join(" "))),
+    }
+}
+
+// The upper and lower bounds of the values of an integer or real expression.
+fn push_bounds(
+    candidates: &mut Vec<Term>,
+    expression: &str,
+    sort: &Sort,
+    values: Vec<BigRational>,
+) {
+    let value = |r: &BigRational| match sort.to_string().as_str() {
+        "Int" => Value::Int(r.to_integer()),
+        _ => Value::Real(r.clone()),
+    };
+    if let (Some(min), Some(max)) = (values.iter().min(), values.iter().max()) {
+        let (min, max) = (value(min).to_term(), value(max).to_term());
+        if let (Some(min), Some(max)) = (min, max) {
+            candidates.push(crate::smt2!(term: "(<= {} {})", expression, max));
+            candidates.push(crate::smt2!(term: "(>= {} {})", expression, min));
+        }
+    }
+}
+
+// Whether `(select array k)` is `element` for all `lower <= k < upper`.
+fn range_holds(array: &Value, lower: &BigInt, upper: &BigInt, element: &Value) -> bool {
+    let (entries, default) = match array {
+        Value::Array { entries, default } => (entries, default),
+        _ => return false,
+    };
+    if upper <= lower {
+        return true;
+    }
+    let range = Value::Int(lower.clone())..Value::Int(upper.clone());
+    let mut covered = BigInt::from(0);
+    for (_, value) in entries.range(range) {
+        if value != element {
+            return false;
+        }
+        covered += 1;
+    }
+    covered == upper - lower || default.as_deref() == Some(element)
+}
+
+fn as_rational(value: &Value) -> Option<BigRational> {
+    match value {
+        Value::Int(n) => Some(BigRational::from_integer(n.clone())),
+        Value::Real(r) => Some(r.clone()),
+        _ => None,
+    }
+}
+
+fn is_numeric(sort: &Sort) -> bool {
+    matches!(sort.to_string().as_str(), "Int" | "Real")
+}
+
+// The index sort of an array sort.
+fn array_index_sort(sort: &Sort) -> Option<&Sort> {
+    match sort {
+        Sort::Parameterized {
+            identifier,
+            parameters,
+        } if identifier.to_string() == "Array" && parameters.len() == 2 => Some(&parameters[0]),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_invariant_miner() {
+    use crate::{concrete::Command, responses::Response};
+
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun n () Int)
+(declare-fun n_next () Int)
+(define-fun .n () Int (! n :next n_next))
+(declare-fun m () Int)
+(declare-fun m_next () Int)
+(define-fun .m () Int (! m :next m_next))
+(declare-fun a () (Array Int Int))
+(declare-fun a_next () (Array Int Int))
+(define-fun .a () (Array Int Int) (! a :next a_next))
+(define-fun init () Bool (! (and (= n 0) (= m 0)) :init true))
+(define-fun trans () Bool (! (and (= n_next (+ n 1)) (= m_next n_next) (= a_next (store a n 7))) :trans true))
+(define-fun property () Bool (! (>= n 0) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let vmt = VMTModel::checked_from(commands).unwrap();
+    let miner = InvariantMiner::new(&vmt, &Template::ALL);
+    let model = Model::parse(
+        r#"(
+(define-fun n@0 () Int 0) (define-fun m@0 () Int 0)
+(define-fun a@0 () (Array Int Int) ((as const (Array Int Int)) 3))
+(define-fun n@1 () Int 1) (define-fun m@1 () Int 1)
+(define-fun a@1 () (Array Int Int) (store ((as const (Array Int Int)) 3) 0 7))
+(define-fun n@2 () Int 2) (define-fun m@2 () Int 2)
+(define-fun a@2 () (Array Int Int) (store (store ((as const (Array Int Int)) 3) 0 7) 1 7))
+)"#,
+    )
+    .unwrap();
+    let states = miner.states_of_model(&model, 2);
+    assert_eq!(states.len(), 3);
+    let candidates = miner
+        .candidates(&states)
+        .iter()
+        .map(|candidate| candidate.to_string())
+        .collect::<Vec<_>>();
+    for candidate in [
+        "(= n m)",
+        "(<= n 2)",
+        "(>= n 0)",
+        "(<= (- n m) 0)",
+        "(>= (+ n m) 0)",
+        "(forall ((k Int)) (=> (and (<= 0 k) (< k n)) (= (select a k) 7)))",
+    ] {
+        assert!(candidates.iter().any(|c| c == candidate), "{}", candidate);
+    }
+    assert_eq!(candidates.len(), 11);
+
+    // A solver answering with the model above until the run is blocked.
+    struct Replay(Model, Vec<bool>);
+
+    impl SmtSolver for Replay {
+        fn execute(&mut self, command: &Command) -> Result<Response, SolverError> {
+            Ok(match command {
+                Command::Push { .. } => {
+                    self.1.push(false);
+                    Response::Success
+                }
+                Command::Pop { .. } => {
+                    self.1.pop();
+                    Response::Success
+                }
+                Command::Assert { term } if term.to_string().starts_with("(not (and (= m@") => {
+                    *self.1.last_mut().unwrap() = true;
+                    Response::Success
+                }
+                Command::CheckSat if self.1.contains(&true) => Response::Unsat,
+                Command::CheckSat => Response::Sat,
+                Command::GetModel => Response::Model(self.0.clone()),
+                _ => Response::Success,
+            })
+        }
+    }
+
+    let mut solver = Replay(model, Vec::new());
+    let sampled = miner.sample(&vmt, &mut solver, 2, 5).unwrap();
+    assert_eq!(sampled, states);
+}
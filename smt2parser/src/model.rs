@@ -0,0 +1,560 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Models returned by solvers for `get-model`, as structured values indexed by symbol.
+
+use crate::{
+    concrete::{Command, Constant, FunctionDec, QualIdentifier, Term},
+    visitors::{Identifier, Index},
+    Numeral,
+};
+use num::{bigint::Sign, BigInt, BigRational, Zero};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+use thiserror::Error;
+
+/// Error reported when parsing a model.
+#[derive(Error, Debug)]
+pub enum ModelError {
+    #[error(transparent)]
+    Syntax(#[from] crate::Error),
+    #[error("unbalanced model: {0}")]
+    Unbalanced(String),
+    #[error("unexpected model entry: {0}")]
+    Unexpected(String),
+}
+
+/// The value of a symbol in a model.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Value {
+    Bool(bool),
+    Int(BigInt),
+    Real(BigRational),
+    BitVec {
+        width: usize,
+        value: Numeral,
+    },
+    String(String),
+    /// An array given by its value at some indices and, when known, its value
+    /// elsewhere.
+    Array {
+        entries: BTreeMap<Value, Value>,
+        default: Option<Box<Value>>,
+    },
+    /// A datatype value, given by its constructor and the values of its fields.
+    Datatype {
+        constructor: String,
+        arguments: Vec<Value>,
+    },
+    /// An element of an uninterpreted sort, such as `U!val!0` or `(as @U_0 U)`.
+    Uninterpreted(String),
+    /// Any other value, kept as a term.
+    Term(Term),
+}
+
+impl Value {
+    /// The value of an array at an index, if known.
+    pub fn select(&self, index: &Value) -> Option<&Value> {
+        match self {
+            Value::Array { entries, default } => entries.get(index).or(default.as_deref()),
+            _ => None,
+        }
+    }
+
+    /// The value as a term, except for arrays, whose sort is not known.
+    pub fn to_term(&self) -> Option<Term> {
+        match self {
+            Value::Array { .. } => None,
+            Value::Term(term) => Some(term.clone()),
+            value => crate::parse_term(&value.to_string()).ok(),
+        }
+    }
+
+    /// The array with one more entry, replacing the previous entry at the same index.
+    pub fn store(self, index: Value, element: Value) -> Value {
+        match self {
+            Value::Array {
+                mut entries,
+                default,
+            } => {
+                entries.insert(index, element);
+                Value::Array { entries, default }
+            }
+            value => value,
+        }
+    }
+}
+
+/// Values are printed in SMT-LIB syntax, except arrays, whose sort is not known, which
+/// are printed as `[i1 -> v1, ..., else d]`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(n) if n.sign() == Sign::Minus => write!(f, "(- {})", -n),
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Real(r) => {
+                let (sign, r) = if r < &BigRational::zero() {
+                    ("(- ", -r)
+                } else {
+                    ("", r.clone())
+                };
+                if r.is_integer() {
+                    write!(f, "{}{}.0", sign, r.numer())?;
+                } else {
+                    write!(f, "{}(/ {} {})", sign, r.numer(), r.denom())?;
+                }
+                if !sign.is_empty() {
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+            Value::BitVec { width, value } => write!(f, "(_ bv{} {})", value, width),
+            Value::String(s) => write!(f, "\"{}\"", s.replace('"', "\"\"")),
+            Value::Array { entries, default } => {
+                write!(f, "[")?;
+                for (i, (index, element)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} -> {}", index, element)?;
+                }
+                if let Some(default) = default {
+                    if !entries.is_empty() {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "else {}", default)?;
+                }
+                write!(f, "]")
+            }
+            Value::Datatype {
+                constructor,
+                arguments,
+            } if arguments.is_empty() => write!(f, "{}", constructor),
+            Value::Datatype {
+                constructor,
+                arguments,
+            } => {
+                write!(f, "({}", constructor)?;
+                for argument in arguments {
+                    write!(f, " {}", argument)?;
+                }
+                write!(f, ")")
+            }
+            Value::Uninterpreted(name) => write!(f, "{}", name),
+            Value::Term(term) => write!(f, "{}", term),
+        }
+    }
+}
+
+/// A model returned by `get-model`: the values of constants, and the definitions of
+/// functions with parameters.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Model {
+    values: BTreeMap<String, Value>,
+    functions: BTreeMap<String, (FunctionDec, Term)>,
+    universe: BTreeSet<String>,
+}
+
+impl Model {
+    /// Parse the output of `get-model`, in the format of Z3 or cvc5, with or without the
+    /// leading `model` symbol. Cardinality constraints of uninterpreted sorts are ignored,
+    /// and their elements are given by the declarations of the model.
+    pub fn parse(input: &str) -> Result<Self, ModelError> {
+        let unbalanced = || ModelError::Unbalanced(input.trim().to_string());
+        let body = match crate::split_s_exprs(input).ok_or_else(unbalanced)?[..] {
+            [body] if body.starts_with('(') => &body[1..body.len() - 1],
+            _ => return Err(unbalanced()),
+        };
+        let mut commands = Vec::new();
+        for (i, item) in crate::split_s_exprs(body)
+            .ok_or_else(unbalanced)?
+            .into_iter()
+            .enumerate()
+        {
+            if (i == 0 && item == "model") || item.starts_with("(forall") {
+                continue;
+            }
+            commands.extend(crate::parse_commands(item)?);
+        }
+        Self::from_commands(&commands)
+    }
+
+    /// Build a model from its definitions, as parsed commands.
+    pub fn from_commands(commands: &[Command]) -> Result<Self, ModelError> {
+        let mut model = Model::default();
+        let mut constants = Vec::new();
+        for command in commands {
+            match command {
+                Command::DefineFun { sig, term } if sig.parameters.is_empty() => {
+                    constants.push((sig.name.0.clone(), term));
+                }
+                Command::DefineFun { sig, term } => {
+                    model
+                        .functions
+                        .insert(sig.name.0.clone(), (sig.clone(), term.clone()));
+                }
+                Command::DeclareFun {
+                    symbol, parameters, ..
+                } if parameters.is_empty() => {
+                    model.universe.insert(symbol.0.clone());
+                }
+                Command::DeclareConst { symbol, .. } => {
+                    model.universe.insert(symbol.0.clone());
+                }
+                Command::DeclareSort { .. } => (),
+                command => return Err(ModelError::Unexpected(command.to_string())),
+            }
+        }
+        // Constants may refer to the functions defined later, as with `as-array`.
+        for (name, term) in constants {
+            let value = model.value_of(term);
+            model.values.insert(name, value);
+        }
+        Ok(model)
+    }
+
+    /// The value of a constant.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.values.get(name)
+    }
+
+    /// The definition of a function with parameters.
+    pub fn function(&self, name: &str) -> Option<(&FunctionDec, &Term)> {
+        self.functions.get(name).map(|(sig, term)| (sig, term))
+    }
+
+    /// Constants and values, ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.values.iter()
+    }
+
+    /// The number of constants.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Compute the value of a term made of literals, as found in models. Symbols that are
+    /// not Booleans nor elements of uninterpreted sorts are taken as datatype
+    /// constructors.
+    pub fn value_of(&self, term: &Term) -> Value {
+        self.try_value_of(term)
+            .unwrap_or_else(|| Value::Term(term.clone()))
+    }
+
+    fn try_value_of(&self, term: &Term) -> Option<Value> {
+        match term {
+            Term::Constant(constant) => Some(constant_value(constant)),
+            Term::QualIdentifier(QualIdentifier::Simple { identifier })
+            | Term::QualIdentifier(QualIdentifier::Sorted { identifier, .. }) => match identifier {
+                Identifier::Simple { symbol } => Some(match symbol.0.as_str() {
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    name if self.is_uninterpreted(name, term) => {
+                        Value::Uninterpreted(name.to_string())
+                    }
+                    name => Value::Datatype {
+                        constructor: name.to_string(),
+                        arguments: Vec::new(),
+                    },
+                }),
+                Identifier::Indexed { symbol, indices } => {
+                    match (symbol.0.as_str(), &indices[..]) {
+                        (name, [Index::Numeral(width)]) if name.starts_with("bv") => {
+                            Some(Value::BitVec {
+                                width: num::ToPrimitive::to_usize(width)?,
+                                value: name[2..].parse().ok()?,
+                            })
+                        }
+                        ("as-array", [Index::Symbol(function)]) => {
+                            self.function_as_array(&function.0)
+                        }
+                        _ => None,
+                    }
+                }
+            },
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } => {
+                let values = arguments
+                    .iter()
+                    .map(|argument| self.try_value_of(argument))
+                    .collect::<Option<Vec<_>>>()?;
+                match (qual_identifier, values.as_slice()) {
+                    (
+                        QualIdentifier::Sorted {
+                            identifier: Identifier::Simple { symbol },
+                            ..
+                        },
+                        [default],
+                    ) if symbol.0 == "const" => Some(Value::Array {
+                        entries: BTreeMap::new(),
+                        default: Some(Box::new(default.clone())),
+                    }),
+                    (_, [Value::Int(n)]) if is_symbol(qual_identifier, "-") => Some(Value::Int(-n)),
+                    (_, [Value::Real(r)]) if is_symbol(qual_identifier, "-") => {
+                        Some(Value::Real(-r))
+                    }
+                    (_, [n, d]) if is_symbol(qual_identifier, "/") => {
+                        let (n, d) = (as_rational(n)?, as_rational(d)?);
+                        if d.is_zero() {
+                            return None;
+                        }
+                        Some(Value::Real(n / d))
+                    }
+                    (_, [array @ Value::Array { .. }, index, element])
+                        if is_symbol(qual_identifier, "store") =>
+                    {
+                        Some(array.clone().store(index.clone(), element.clone()))
+                    }
+                    (
+                        QualIdentifier::Simple {
+                            identifier: Identifier::Simple { symbol },
+                        }
+                        | QualIdentifier::Sorted {
+                            identifier: Identifier::Simple { symbol },
+                            ..
+                        },
+                        _,
+                    ) => Some(Value::Datatype {
+                        constructor: symbol.0.clone(),
+                        arguments: values,
+                    }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn is_uninterpreted(&self, name: &str, term: &Term) -> bool {
+        self.universe.contains(name)
+            || name.contains("!val!")
+            || (name.starts_with('@')
+                && matches!(term, Term::QualIdentifier(QualIdentifier::Sorted { .. })))
+    }
+
+    // The array of a unary function defined by a chain of `ite`s comparing its parameter
+    // with values, as written by Z3 for `(_ as-array f)`.
+    fn function_as_array(&self, name: &str) -> Option<Value> {
+        let (sig, term) = self.functions.get(name)?;
+        let mut term = term;
+        let parameter = match &sig.parameters[..] {
+            [(parameter, _)] => parameter.0.as_str(),
+            _ => return None,
+        };
+        let mut entries = BTreeMap::new();
+        loop {
+            match term {
+                Term::Application {
+                    qual_identifier,
+                    arguments,
+                } if is_symbol(qual_identifier, "ite") && arguments.len() == 3 => {
+                    let index = match &arguments[0] {
+                        Term::Application {
+                            qual_identifier,
+                            arguments,
+                        } if is_symbol(qual_identifier, "=") && arguments.len() == 2 => {
+                            match (&arguments[0], &arguments[1]) {
+                                (Term::QualIdentifier(q), index) if is_symbol(q, parameter) => {
+                                    index
+                                }
+                                (index, Term::QualIdentifier(q)) if is_symbol(q, parameter) => {
+                                    index
+                                }
+                                _ => return None,
+                            }
+                        }
+                        _ => return None,
+                    };
+                    // The first comparisons take precedence.
+                    entries
+                        .entry(self.try_value_of(index)?)
+                        .or_insert(self.try_value_of(&arguments[1])?);
+                    term = &arguments[2];
+                }
+                _ => {
+                    return Some(Value::Array {
+                        entries,
+                        default: Some(Box::new(self.try_value_of(term)?)),
+                    });
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn constant_value(constant: &Constant) -> Value {
+    match constant {
+        Constant::Numeral(n) => Value::Int(BigInt::from(n.clone())),
+        Constant::Decimal(d) => Value::Real(d.clone()),
+        Constant::Hexadecimal(nibbles) => Value::BitVec {
+            width: 4 * nibbles.len(),
+            value: nibbles
+                .iter()
+                .fold(Numeral::zero(), |value, nibble| value * 16u32 + *nibble),
+        },
+        Constant::Binary(bits) => Value::BitVec {
+            width: bits.len(),
+            value: bits
+                .iter()
+                .fold(Numeral::zero(), |value, bit| value * 2u32 + *bit as u32),
+        },
+        Constant::String(s) => Value::String(s.clone()),
+    }
+}
+
+fn as_rational(value: &Value) -> Option<BigRational> {
+    match value {
+        Value::Int(n) => Some(BigRational::from_integer(n.clone())),
+        Value::Real(r) => Some(r.clone()),
+        _ => None,
+    }
+}
+
+fn is_symbol(qual_identifier: &QualIdentifier, name: &str) -> bool {
+    matches!(
+        qual_identifier,
+        QualIdentifier::Simple {
+            identifier: Identifier::Simple { symbol },
+        } if symbol.0 == name
+    )
+}
+
+#[test]
+fn test_parse_model() {
+    let z3 = r#"
+(
+  ;; universe for U:
+  ;;   U!val!0
+  ;; -----------
+  ;; definitions for universe elements:
+  (declare-fun U!val!0 () U)
+  ;; cardinality constraint:
+  (forall ((x U)) (= x U!val!0))
+  ;; -----------
+  (define-fun x () Int
+    (- 3))
+  (define-fun r () Real
+    (/ 1.0 3.0))
+  (define-fun b () (_ BitVec 8)
+    #x1f)
+  (define-fun u () U
+    U!val!0)
+  (define-fun a () (Array Int Int)
+    (_ as-array k!0))
+  (define-fun m () Mode
+    (busy 3 idle))
+  (define-fun k!0 ((x!0 Int)) Int
+    (ite (= x!0 1) 2
+    (ite (= x!0 3) 4
+      0)))
+  (define-fun f ((x!0 Int) (x!1 Int)) Int
+    x!0)
+)
+"#;
+    let model = Model::parse(z3).unwrap();
+    assert_eq!(model.len(), 6);
+    assert_eq!(model.get("x").unwrap().to_string(), "(- 3)");
+    assert_eq!(model.get("r").unwrap().to_string(), "(/ 1 3)");
+    assert_eq!(
+        model.get("b"),
+        Some(&Value::BitVec {
+            width: 8,
+            value: Numeral::from(31u32)
+        })
+    );
+    assert_eq!(
+        model.get("u"),
+        Some(&Value::Uninterpreted("U!val!0".into()))
+    );
+    let a = model.get("a").unwrap();
+    assert_eq!(a.to_string(), "[1 -> 2, 3 -> 4, else 0]");
+    assert_eq!(a.select(&Value::Int(5.into())), Some(&Value::Int(0.into())));
+    assert_eq!(model.get("m").unwrap().to_string(), "(busy 3 idle)");
+    assert!(model.function("f").is_some());
+
+    let cvc5 = r#"(
+(define-fun a () (Array Int Bool) (store (store ((as const (Array Int Bool)) false) 1 true) 2 true))
+(define-fun b () (Array Int Int) (store (store (store ((as const (Array Int Int)) 0) 2 5) 1 3) 2 7))
+(define-fun u () U (as @U_0 U))
+(define-fun s () String "a""b")
+)"#;
+    let model = Model::parse(cvc5).unwrap();
+    assert_eq!(
+        model.get("a").unwrap().to_string(),
+        "[1 -> true, 2 -> true, else false]"
+    );
+    // Later stores take precedence.
+    let b = model.get("b").unwrap();
+    assert_eq!(b.to_string(), "[1 -> 3, 2 -> 7, else 0]");
+    assert!(matches!(b, Value::Array { entries, .. } if entries.len() == 2));
+    assert_eq!(model.get("u"), Some(&Value::Uninterpreted("@U_0".into())));
+    assert_eq!(model.get("s").unwrap().to_string(), "\"a\"\"b\"");
+    assert!(Model::parse("(model (define-fun p () Bool true))")
+        .unwrap()
+        .get("p")
+        .is_some());
+    assert!(matches!(
+        Model::parse("((define-fun p () Bool true)"),
+        Err(ModelError::Unbalanced(_))
+    ));
+}
+
+#[test]
+fn test_array_values() {
+    let z3 = r#"(
+  (define-fun a () (Array Int Int) (_ as-array k!0))
+  (define-fun k!0 ((x!0 Int)) Int
+    (ite (= x!0 3) 4 (ite (= 1 x!0) 2 (ite (= x!0 3) 5 0))))
+  (define-fun f () (Array Int Int) (_ as-array k!1))
+  (define-fun k!1 ((x!0 Int) (x!1 Int)) Int x!0)
+  (define-fun g () (Array Int Int) (_ as-array k!2))
+  (define-fun k!2 ((x!0 Int)) Int (ite (> x!0 1) 2 0))
+)"#;
+    let model = Model::parse(z3).unwrap();
+    // The first comparisons take precedence and entries are ordered by index.
+    let a = model.get("a").unwrap();
+    assert_eq!(a.to_string(), "[1 -> 2, 3 -> 4, else 0]");
+    assert_eq!(a.select(&Value::Int(3.into())), Some(&Value::Int(4.into())));
+    // Functions that are not unary or not given by comparisons are kept as terms.
+    assert!(matches!(model.get("f"), Some(Value::Term(_))));
+    assert!(matches!(model.get("g"), Some(Value::Term(_))));
+
+    let model = Model::default();
+    let value = |input: &str| model.value_of(&crate::parse_term(input).unwrap());
+    // Arrays with the same entries are equal regardless of the order of the stores.
+    assert_eq!(
+        value("(store (store ((as const (Array Int Int)) 0) 2 5) 1 3)"),
+        value("(store (store ((as const (Array Int Int)) 0) 1 3) 2 5)")
+    );
+    assert_eq!(
+        value("(store ((as const (Array (_ BitVec 4) Bool)) false) #x3 true)").to_string(),
+        "[(_ bv3 4) -> true, else false]"
+    );
+    // Nested arrays.
+    let nested = value(
+        "(store ((as const (Array Int (Array Int Int))) ((as const (Array Int Int)) 0)) 1 (store ((as const (Array Int Int)) 0) 2 7))",
+    );
+    assert_eq!(nested.to_string(), "[1 -> [2 -> 7, else 0], else [else 0]]");
+    assert_eq!(
+        nested
+            .select(&Value::Int(1.into()))
+            .and_then(|inner| inner.select(&Value::Int(2.into()))),
+        Some(&Value::Int(7.into()))
+    );
+    // Stores at indices that are not values are kept as terms.
+    assert!(matches!(
+        value("(store ((as const (Array (_ BitVec 2) Int)) 0) ((_ extract 1 0) #x3) 1)"),
+        Value::Term(_)
+    ));
+    // Arrays have no term since their sort is not known.
+    assert_eq!(value("((as const (Array Int Int)) 0)").to_term(), None);
+}
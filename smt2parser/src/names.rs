@@ -0,0 +1,222 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Terms and assertions named by `:named` annotations, as used by unsat cores.
+
+use crate::{
+    concrete::{Command, Error, Keyword, Symbol, SyntaxBuilder, Term},
+    renaming::SymbolFactory,
+    rewriter::Rewriter,
+    visitors::AttributeValue,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+impl Term {
+    /// The name given to the term by a `:named` annotation, if any.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Term::Attributes { attributes, .. } => {
+                attributes.iter().find_map(|(key, value)| match value {
+                    AttributeValue::Symbol(symbol) if key.0 == "named" => Some(symbol.0.as_str()),
+                    _ => None,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    /// Name the term, replacing its previous name if any.
+    pub fn with_name(self, name: &str) -> Term {
+        let (term, mut attributes) = match self.without_name() {
+            Term::Attributes { term, attributes } => (term, attributes),
+            term => (Box::new(term), Vec::new()),
+        };
+        attributes.push((
+            Keyword("named".to_string()),
+            AttributeValue::Symbol(Symbol(name.to_string())),
+        ));
+        Term::Attributes { term, attributes }
+    }
+
+    /// Remove the name of the term, if any, keeping its other annotations.
+    pub fn without_name(self) -> Term {
+        match self {
+            Term::Attributes {
+                term,
+                mut attributes,
+            } => {
+                attributes.retain(|(key, _)| key.0 != "named");
+                if attributes.is_empty() {
+                    *term
+                } else {
+                    Term::Attributes { term, attributes }
+                }
+            }
+            term => term,
+        }
+    }
+}
+
+/// Table of named terms, indexed by name. Terms are stored without their names.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamedTerms(BTreeMap<String, Term>);
+
+impl NamedTerms {
+    /// Collect the terms named in the assertions of a script, at any depth. Later names
+    /// take precedence, and scopes are ignored.
+    pub fn from_commands(commands: &[Command]) -> Self {
+        let mut table = Self::default();
+        for command in commands {
+            if let Command::Assert { term } = command {
+                table.collect(term);
+            }
+        }
+        table
+    }
+
+    fn collect(&mut self, term: &Term) {
+        for (_, named) in term.find(|t| t.name().is_some()) {
+            if let Some(name) = named.name() {
+                self.insert(name, named.clone().without_name());
+            }
+        }
+    }
+
+    /// The term of a name.
+    pub fn get(&self, name: &str) -> Option<&Term> {
+        self.0.get(name)
+    }
+
+    /// Add a named term, returning the previous term of the name if any.
+    pub fn insert(&mut self, name: &str, term: Term) -> Option<Term> {
+        self.0.insert(name.to_string(), term)
+    }
+
+    /// Remove a name, returning its term if any.
+    pub fn remove(&mut self, name: &str) -> Option<Term> {
+        self.0.remove(name)
+    }
+
+    /// Names and terms, ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Term)> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The terms of an unsat core given by the names returned by `get-unsat-core`, or
+    /// the first unknown name.
+    pub fn core<'a, I>(&self, names: I) -> Result<Vec<&Term>, &'a Symbol>
+    where
+        I: IntoIterator<Item = &'a Symbol>,
+    {
+        names
+            .into_iter()
+            .map(|name| self.get(&name.0).ok_or(name))
+            .collect()
+    }
+}
+
+/// Name the unnamed assertions of a script with fresh names based on `prefix`, so that
+/// they can appear in unsat cores. Return the new script and the table of all named
+/// terms. Unsat cores must still be enabled with `(set-option :produce-unsat-cores true)`.
+pub fn name_assertions(commands: Vec<Command>, prefix: &str) -> (Vec<Command>, NamedTerms) {
+    let mut symbols = SymbolFactory::new(&commands);
+    let commands = commands
+        .into_iter()
+        .map(|command| match command {
+            Command::Assert { term } if term.name().is_none() => Command::Assert {
+                term: term.with_name(&symbols.fresh(prefix)),
+            },
+            command => command,
+        })
+        .collect::<Vec<_>>();
+    let table = NamedTerms::from_commands(&commands);
+    (commands, table)
+}
+
+/// Rewriter removing all `:named` annotations, for instance before merging scripts whose
+/// names could clash.
+#[derive(Debug, Default)]
+pub struct NameStripper(SyntaxBuilder);
+
+impl Rewriter for NameStripper {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.0
+    }
+
+    fn process_term(&mut self, value: Term) -> Result<Term, Self::Error> {
+        Ok(value.without_name())
+    }
+}
+
+#[test]
+fn test_named_terms() {
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun x () Int)
+(assert (! (> x 0) :named positive))
+(assert (< x 10))
+(assert (or (! (= x 1) :named one) (! (= x 2) :pattern ((f x)))))
+(assert (! (< x 1) :named a))
+"#,
+    )
+    .unwrap();
+    let (commands, table) = name_assertions(commands, "a");
+    assert_eq!(
+        commands.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+        vec![
+            "(declare-fun x () Int)",
+            "(assert (! (> x 0) :named positive))",
+            "(assert (! (< x 10) :named a!1))",
+            "(assert (! (or (! (= x 1) :named one) (! (= x 2) :pattern ((f x)))) :named a!2))",
+            "(assert (! (< x 1) :named a))",
+        ]
+    );
+    assert_eq!(
+        table
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["a", "a!1", "a!2", "one", "positive"]
+    );
+    assert_eq!(table.get("one").unwrap().to_string(), "(= x 1)");
+    let core = [Symbol("positive".into()), Symbol("a".into())];
+    assert_eq!(
+        table
+            .core(&core)
+            .unwrap()
+            .iter()
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>(),
+        vec!["(> x 0)", "(< x 1)"]
+    );
+    let unknown = [Symbol("b".into())];
+    assert_eq!(table.core(&unknown), Err(&unknown[0]));
+
+    let stripped = commands[3]
+        .clone()
+        .accept(&mut NameStripper::default())
+        .unwrap();
+    assert_eq!(
+        stripped.to_string(),
+        "(assert (or (= x 1) (! (= x 2) :pattern ((f x)))))"
+    );
+    assert_eq!(
+        crate::parse_term("(! p :named q)")
+            .unwrap()
+            .with_name("r")
+            .to_string(),
+        "(! p :named r)"
+    );
+}
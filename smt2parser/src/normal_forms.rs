@@ -0,0 +1,818 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Normal forms of boolean terms.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::{
+    concrete::{Command, Error, QualIdentifier, Sort, Symbol, SyntaxBuilder, Term},
+    inlining::DefinitionInliner,
+    renaming::SymbolFactory,
+    rewriter::{free_symbols, Rewriter, SubstitutionRewriter},
+    simplification::{as_bool, bool_term, symbol_of},
+    visitors::Identifier,
+};
+
+/// Rewriter putting terms in negation normal form: negations are pushed through `and`,
+/// `or`, quantifiers, `let` bindings, and unnamed attributes until they only apply to
+/// atoms, and implications are replaced by disjunctions. Other terms, including
+/// equalities and `ite`, are considered as atoms since their sort is not known.
+#[derive(Debug, Default)]
+pub struct NegationNormalizer(SyntaxBuilder);
+
+impl NegationNormalizer {
+    /// Put a term in negation normal form.
+    pub fn normalize(&mut self, term: Term) -> Result<Term, Error> {
+        term.accept(self)
+    }
+
+    // Negate a term in negation normal form.
+    fn negate(term: Term) -> Term {
+        if let Some(value) = as_bool(&term) {
+            return bool_term(!value);
+        }
+        match term {
+            Term::Application {
+                qual_identifier,
+                mut arguments,
+            } => match symbol_of(&qual_identifier) {
+                Some("not") if arguments.len() == 1 => arguments.pop().unwrap(),
+                Some(symbol @ "and") | Some(symbol @ "or") => application(
+                    if symbol == "and" { "or" } else { "and" },
+                    arguments.into_iter().map(Self::negate).collect(),
+                ),
+                _ => not(Term::Application {
+                    qual_identifier,
+                    arguments,
+                }),
+            },
+            Term::Forall { vars, term } => Term::Exists {
+                vars,
+                term: Box::new(Self::negate(*term)),
+            },
+            Term::Exists { vars, term } => Term::Forall {
+                vars,
+                term: Box::new(Self::negate(*term)),
+            },
+            Term::Let { var_bindings, term } => Term::Let {
+                var_bindings,
+                term: Box::new(Self::negate(*term)),
+            },
+            // Patterns are preserved by negation but names are not.
+            Term::Attributes { term, attributes }
+                if attributes.iter().all(|(key, _)| key.0 != "named") =>
+            {
+                Term::Attributes {
+                    term: Box::new(Self::negate(*term)),
+                    attributes,
+                }
+            }
+            term => not(term),
+        }
+    }
+}
+
+impl Rewriter for NegationNormalizer {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.0
+    }
+
+    // Subterms are already in negation normal form.
+    fn process_term(&mut self, value: Term) -> Result<Term, Self::Error> {
+        match value {
+            Term::Application {
+                qual_identifier,
+                mut arguments,
+            } => match symbol_of(&qual_identifier) {
+                Some("not") if arguments.len() == 1 => Ok(Self::negate(arguments.pop().unwrap())),
+                Some("=>") if !arguments.is_empty() => {
+                    // `(=> a b c)` is `(or (not a) (not b) c)`.
+                    let conclusion = arguments.pop().unwrap();
+                    let mut disjuncts = arguments.into_iter().map(Self::negate).collect::<Vec<_>>();
+                    disjuncts.push(conclusion);
+                    Ok(application("or", disjuncts))
+                }
+                _ => Ok(Term::Application {
+                    qual_identifier,
+                    arguments,
+                }),
+            },
+            value => Ok(value),
+        }
+    }
+}
+
+/// Rewriter putting the applications of associative-commutative operators in a canonical
+/// form, so that terms equal up to associativity and commutativity become structurally
+/// equal: nested applications of `and`, `or`, `xor`, `+`, `*`, `bvadd`, `bvmul`, `bvand`,
+/// `bvor`, and `bvxor` are flattened and their arguments are sorted by their printed
+/// form. Duplicate arguments of `and` and `or` are removed as well.
+#[derive(Debug, Default)]
+pub struct ACNormalizer(SyntaxBuilder);
+
+impl ACNormalizer {
+    /// Put a term in AC normal form.
+    pub fn normalize(&mut self, term: Term) -> Result<Term, Error> {
+        term.accept(self)
+    }
+
+    fn is_ac(symbol: &str) -> bool {
+        matches!(
+            symbol,
+            "and" | "or" | "xor" | "+" | "*" | "bvadd" | "bvmul" | "bvand" | "bvor" | "bvxor"
+        )
+    }
+}
+
+impl Rewriter for ACNormalizer {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.0
+    }
+
+    // Arguments are already in AC normal form, hence flat.
+    fn process_term(&mut self, value: Term) -> Result<Term, Self::Error> {
+        let (qual_identifier, arguments) = match value {
+            Term::Application {
+                qual_identifier: qual_identifier @ QualIdentifier::Simple { .. },
+                arguments,
+            } if symbol_of(&qual_identifier).map_or(false, Self::is_ac) => {
+                (qual_identifier, arguments)
+            }
+            value => return Ok(value),
+        };
+        let symbol = symbol_of(&qual_identifier).unwrap();
+        let mut flat = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            match argument {
+                Term::Application {
+                    qual_identifier: inner,
+                    arguments,
+                } if inner == qual_identifier => flat.extend(arguments),
+                argument => flat.push(argument),
+            }
+        }
+        let mut keyed = flat
+            .into_iter()
+            .map(|argument| (argument.to_string(), argument))
+            .collect::<Vec<_>>();
+        keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+        if symbol == "and" || symbol == "or" {
+            keyed.dedup_by(|(a, _), (b, _)| a == b);
+            if keyed.len() == 1 {
+                return Ok(keyed.pop().unwrap().1);
+            }
+        }
+        Ok(Term::Application {
+            qual_identifier,
+            arguments: keyed.into_iter().map(|(_, argument)| argument).collect(),
+        })
+    }
+}
+
+/// Rewriter pulling the quantifiers of a term in negation normal form to the front,
+/// through `and`, `or`, and `let`. Quantified variables are renamed when they would
+/// capture a symbol or clash with another pulled variable. Quantifiers under other
+/// symbols and under attributes are left in place, so that patterns keep their meaning.
+#[derive(Debug, Default)]
+pub struct PrenexNormalizer {
+    visitor: SyntaxBuilder,
+    symbols: SymbolFactory,
+}
+
+// A block of quantified variables.
+type Quantifiers = (bool, Vec<(Symbol, Sort)>);
+
+impl PrenexNormalizer {
+    /// Create a normalizer whose fresh variables also avoid the given symbols.
+    pub fn new(symbols: SymbolFactory) -> Self {
+        Self {
+            visitor: SyntaxBuilder,
+            symbols,
+        }
+    }
+
+    /// Put a term in negation normal form, then in prenex normal form.
+    pub fn normalize(&mut self, term: Term) -> Result<Term, Error> {
+        self.symbols.avoid_term(&term);
+        let term = NegationNormalizer::default().normalize(term)?;
+        term.accept(self)
+    }
+
+    // Split the leading quantifiers of a term from its matrix, renaming the quantified
+    // variables that are in `avoid`.
+    fn split(
+        &mut self,
+        mut term: Term,
+        avoid: &BTreeSet<String>,
+    ) -> Result<(Vec<Quantifiers>, Term), Error> {
+        let mut prefix = Vec::new();
+        loop {
+            let (universal, mut vars, body) = match term {
+                Term::Forall { vars, term } => (true, vars, term),
+                Term::Exists { vars, term } => (false, vars, term),
+                term => return Ok((prefix, term)),
+            };
+            let mut renaming = BTreeMap::new();
+            for (symbol, _) in &mut vars {
+                if avoid.contains(&symbol.0) {
+                    let name = self.symbols.fresh(&symbol.0);
+                    log::debug!("Renaming quantified variable {} as {}", symbol.0, name);
+                    renaming.insert(symbol.0.clone(), symbol_term(name.clone()));
+                    symbol.0 = name;
+                }
+            }
+            term = SubstitutionRewriter::new(renaming).substitute(*body)?;
+            prefix.push((universal, vars));
+        }
+    }
+
+    fn prefixed(prefix: Vec<Quantifiers>, matrix: Term) -> Term {
+        prefix
+            .into_iter()
+            .rev()
+            .fold(matrix, |term, (universal, vars)| {
+                let term = Box::new(term);
+                if universal {
+                    Term::Forall { vars, term }
+                } else {
+                    Term::Exists { vars, term }
+                }
+            })
+    }
+}
+
+impl Rewriter for PrenexNormalizer {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    // Subterms are already in prenex normal form.
+    fn process_term(&mut self, value: Term) -> Result<Term, Self::Error> {
+        match value {
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } if matches!(symbol_of(&qual_identifier), Some("and") | Some("or")) => {
+                let free = arguments.iter().map(free_symbols).collect::<Vec<_>>();
+                let mut pulled = BTreeSet::new();
+                let mut prefix = Vec::new();
+                let mut matrices = Vec::new();
+                for (i, argument) in arguments.into_iter().enumerate() {
+                    let mut avoid = pulled.clone();
+                    for (j, symbols) in free.iter().enumerate() {
+                        if i != j {
+                            avoid.extend(symbols.iter().cloned());
+                        }
+                    }
+                    let (argument_prefix, matrix) = self.split(argument, &avoid)?;
+                    for (_, vars) in &argument_prefix {
+                        pulled.extend(vars.iter().map(|(symbol, _)| symbol.0.clone()));
+                    }
+                    prefix.extend(argument_prefix);
+                    matrices.push(matrix);
+                }
+                Ok(Self::prefixed(
+                    prefix,
+                    Term::Application {
+                        qual_identifier,
+                        arguments: matrices,
+                    },
+                ))
+            }
+            Term::Let { var_bindings, term } => {
+                let mut avoid = BTreeSet::new();
+                for (symbol, value) in &var_bindings {
+                    avoid.insert(symbol.0.clone());
+                    avoid.extend(free_symbols(value));
+                }
+                let (prefix, matrix) = self.split(*term, &avoid)?;
+                Ok(Self::prefixed(
+                    prefix,
+                    Term::Let {
+                        var_bindings,
+                        term: Box::new(matrix),
+                    },
+                ))
+            }
+            value => Ok(value),
+        }
+    }
+}
+
+/// Literal of a CNF, as in DIMACS: the index of a variable, starting from 1, which is
+/// negative when the variable is negated.
+pub type Literal = i64;
+
+/// Tseitin encoding of the boolean skeleton of quantifier-free terms into clauses.
+///
+/// Each application of `not`, `and`, `or`, `=>`, `xor`, and of `=` and `ite` on
+/// boolean terms is defined by a fresh variable. The other terms are atoms, in
+/// particular quantified terms and applications whose arguments are not known to be
+/// boolean. The clauses are equisatisfiable with the encoded terms.
+#[derive(Debug)]
+pub struct TseitinEncoder {
+    symbols: SymbolFactory,
+    // Constants declared with sort `Bool`.
+    boolean_symbols: BTreeSet<String>,
+    // Term of each variable, and whether the variable is fresh.
+    variables: Vec<(Term, bool)>,
+    // Literals of the encoded terms.
+    literals: HashMap<Term, Literal>,
+    clauses: Vec<Vec<Literal>>,
+    // Whether the clauses were asserted in different scopes.
+    scoped: bool,
+}
+
+impl TseitinEncoder {
+    /// Create an encoder whose fresh variables avoid the symbols of the given commands.
+    pub fn new(commands: &[Command]) -> Self {
+        let mut encoder = Self {
+            symbols: SymbolFactory::new(commands),
+            boolean_symbols: BTreeSet::new(),
+            variables: Vec::new(),
+            literals: HashMap::new(),
+            clauses: Vec::new(),
+            scoped: false,
+        };
+        for command in commands {
+            encoder.declare(command);
+        }
+        encoder
+    }
+
+    /// Inline the definitions of a script and replace each assertion by the declarations
+    /// of its fresh variables followed by one assertion per clause.
+    pub fn encode_commands(commands: Vec<Command>) -> Result<Vec<Command>, Error> {
+        let commands = DefinitionInliner::inline_commands(commands)?;
+        let mut encoder = Self::new(&commands);
+        Ok(commands
+            .into_iter()
+            .flat_map(|command| encoder.encode_command(command))
+            .collect())
+    }
+
+    /// Encode an assertion, or record the effect of another command. Definitions are
+    /// not inlined.
+    pub fn encode_command(&mut self, command: Command) -> Vec<Command> {
+        match command {
+            Command::Assert { term } => {
+                let first_variable = self.variables.len();
+                let first_clause = self.clauses.len();
+                self.assert(&term);
+                let declarations = self.variables[first_variable..]
+                    .iter()
+                    .filter_map(|(term, fresh)| match term {
+                        Term::QualIdentifier(QualIdentifier::Simple {
+                            identifier: Identifier::Simple { symbol },
+                        }) if *fresh => Some(Command::DeclareFun {
+                            symbol: symbol.clone(),
+                            parameters: Vec::new(),
+                            sort: bool_sort(),
+                        }),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                let clauses = self.clauses[first_clause..]
+                    .iter()
+                    .map(|clause| Command::Assert {
+                        term: self.clause_term(clause),
+                    })
+                    .collect::<Vec<_>>();
+                declarations.into_iter().chain(clauses).collect()
+            }
+            command => {
+                match &command {
+                    Command::Push { .. } => self.scoped = true,
+                    // The definitions of the variables may be popped.
+                    Command::Pop { .. } | Command::Reset | Command::ResetAssertions => {
+                        self.literals.clear()
+                    }
+                    _ => self.declare(&command),
+                }
+                vec![command]
+            }
+        }
+    }
+
+    /// Add the clauses of a term and a unit clause asserting it.
+    pub fn assert(&mut self, term: &Term) {
+        match term {
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } if symbol_of(qual_identifier) == Some("and") => {
+                for argument in arguments {
+                    self.assert(argument);
+                }
+            }
+            _ => {
+                let literal = self.encode(term);
+                self.clauses.push(vec![literal]);
+            }
+        }
+    }
+
+    /// Return a literal equivalent to a term, adding the clauses defining its fresh
+    /// variables.
+    pub fn encode(&mut self, term: &Term) -> Literal {
+        if let Some(literal) = self.literals.get(term) {
+            return *literal;
+        }
+        let literal = match term {
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } => match symbol_of(qual_identifier) {
+                Some("not") if arguments.len() == 1 => -self.encode(&arguments[0]),
+                Some(symbol @ "and") | Some(symbol @ "or") => {
+                    let literals = self.encode_all(arguments);
+                    let polarity = if symbol == "and" { 1 } else { -1 };
+                    self.define_and(literals, polarity)
+                }
+                Some("=>") if !arguments.is_empty() => {
+                    let mut literals = self.encode_all(arguments);
+                    let last = literals.len() - 1;
+                    for literal in &mut literals[..last] {
+                        *literal = -*literal;
+                    }
+                    self.define_and(literals, -1)
+                }
+                Some("xor") if !arguments.is_empty() => {
+                    let literals = self.encode_all(arguments);
+                    literals[1..]
+                        .iter()
+                        .fold(literals[0], |a, b| -self.define_iff(a, *b))
+                }
+                Some("=")
+                    if arguments.len() >= 2 && arguments.iter().any(|a| self.is_formula(a)) =>
+                {
+                    let literals = self.encode_all(arguments);
+                    let mut equalities = literals
+                        .windows(2)
+                        .map(|pair| self.define_iff(pair[0], pair[1]))
+                        .collect::<Vec<_>>();
+                    if equalities.len() == 1 {
+                        equalities.pop().unwrap()
+                    } else {
+                        self.define_and(equalities, 1)
+                    }
+                }
+                Some("ite")
+                    if arguments.len() == 3
+                        && (self.is_formula(&arguments[1]) || self.is_formula(&arguments[2])) =>
+                {
+                    let literals = self.encode_all(arguments);
+                    let (c, a, b) = (literals[0], literals[1], literals[2]);
+                    let v = self.fresh_variable();
+                    self.clauses.extend(vec![
+                        vec![-v, -c, a],
+                        vec![-v, c, b],
+                        vec![v, -c, -a],
+                        vec![v, c, -b],
+                    ]);
+                    v
+                }
+                _ => self.atom(term),
+            },
+            _ => self.atom(term),
+        };
+        self.literals.insert(term.clone(), literal);
+        literal
+    }
+
+    /// Clauses added so far.
+    pub fn clauses(&self) -> &[Vec<Literal>] {
+        &self.clauses
+    }
+
+    /// Number of variables used so far.
+    pub fn num_variables(&self) -> usize {
+        self.variables.len()
+    }
+
+    /// Term of the variable of a literal: either an atom or a fresh constant.
+    pub fn variable(&self, literal: Literal) -> &Term {
+        &self.variables[literal.unsigned_abs() as usize - 1].0
+    }
+
+    /// Whether the variable of a literal is an atom rather than a fresh constant.
+    pub fn is_atom(&self, literal: Literal) -> bool {
+        !self.variables[literal.unsigned_abs() as usize - 1].1
+    }
+
+    /// Print the clauses in the DIMACS format, with comments relating variables to
+    /// atoms. Return `None` unless the problem is purely boolean, that is all atoms are
+    /// constants and no assertions were made in different scopes.
+    pub fn to_dimacs(&self) -> Option<String> {
+        if self.scoped {
+            return None;
+        }
+        let mut out = String::new();
+        for (index, (term, fresh)) in self.variables.iter().enumerate() {
+            match term {
+                Term::QualIdentifier(QualIdentifier::Simple {
+                    identifier: Identifier::Simple { .. },
+                }) => {
+                    if !fresh {
+                        out.push_str(&format!("c {} {}\n", index + 1, term));
+                    }
+                }
+                _ => return None,
+            }
+        }
+        out.push_str(&format!(
+            "p cnf {} {}\n",
+            self.variables.len(),
+            self.clauses.len()
+        ));
+        for clause in &self.clauses {
+            for literal in clause {
+                out.push_str(&format!("{} ", literal));
+            }
+            out.push_str("0\n");
+        }
+        Some(out)
+    }
+
+    fn declare(&mut self, command: &Command) {
+        match command {
+            Command::DeclareConst { symbol, sort } if *sort == bool_sort() => {
+                self.boolean_symbols.insert(symbol.0.clone());
+            }
+            Command::DeclareFun {
+                symbol,
+                parameters,
+                sort,
+            } if parameters.is_empty() && *sort == bool_sort() => {
+                self.boolean_symbols.insert(symbol.0.clone());
+            }
+            _ => (),
+        }
+    }
+
+    // Whether a term is known to be boolean without sort information.
+    fn is_formula(&self, term: &Term) -> bool {
+        match term {
+            Term::QualIdentifier(qual_identifier) => match symbol_of(qual_identifier) {
+                Some(symbol) => {
+                    symbol == "true" || symbol == "false" || self.boolean_symbols.contains(symbol)
+                }
+                None => false,
+            },
+            Term::Application {
+                qual_identifier, ..
+            } => matches!(
+                symbol_of(qual_identifier),
+                Some("not")
+                    | Some("and")
+                    | Some("or")
+                    | Some("=>")
+                    | Some("xor")
+                    | Some("=")
+                    | Some("distinct")
+            ),
+            Term::Forall { .. } | Term::Exists { .. } => true,
+            _ => false,
+        }
+    }
+
+    fn encode_all(&mut self, terms: &[Term]) -> Vec<Literal> {
+        terms.iter().map(|term| self.encode(term)).collect()
+    }
+
+    // Define a variable equivalent to the conjunction of the literals, or to the
+    // disjunction when the polarity is negative.
+    fn define_and(&mut self, literals: Vec<Literal>, polarity: Literal) -> Literal {
+        let v = self.fresh_variable();
+        let mut clause = vec![v];
+        for literal in literals {
+            self.clauses.push(vec![-v, polarity * literal]);
+            clause.push(-polarity * literal);
+        }
+        self.clauses.push(clause);
+        polarity * v
+    }
+
+    fn define_iff(&mut self, a: Literal, b: Literal) -> Literal {
+        let v = self.fresh_variable();
+        self.clauses.extend(vec![
+            vec![-v, -a, b],
+            vec![-v, a, -b],
+            vec![v, a, b],
+            vec![v, -a, -b],
+        ]);
+        v
+    }
+
+    fn fresh_variable(&mut self) -> Literal {
+        let name = self.symbols.fresh("tseitin");
+        self.variables.push((symbol_term(name), true));
+        self.variables.len() as Literal
+    }
+
+    fn atom(&mut self, term: &Term) -> Literal {
+        self.variables.push((term.clone(), false));
+        let v = self.variables.len() as Literal;
+        if as_bool(term) == Some(true) {
+            self.clauses.push(vec![v]);
+        } else if as_bool(term) == Some(false) {
+            self.clauses.push(vec![-v]);
+        }
+        v
+    }
+
+    fn clause_term(&self, clause: &[Literal]) -> Term {
+        let mut literals = clause
+            .iter()
+            .map(|literal| {
+                let term = self.variable(*literal).clone();
+                if *literal > 0 {
+                    term
+                } else {
+                    not(term)
+                }
+            })
+            .collect::<Vec<_>>();
+        if literals.len() == 1 {
+            literals.pop().unwrap()
+        } else {
+            application("or", literals)
+        }
+    }
+}
+
+fn bool_sort() -> Sort {
+    Sort::Simple {
+        identifier: Identifier::Simple {
+            symbol: Symbol("Bool".to_string()),
+        },
+    }
+}
+
+fn symbol_term(name: String) -> Term {
+    Term::QualIdentifier(QualIdentifier::Simple {
+        identifier: Identifier::Simple {
+            symbol: Symbol(name),
+        },
+    })
+}
+
+fn application(symbol: &str, arguments: Vec<Term>) -> Term {
+    Term::Application {
+        qual_identifier: QualIdentifier::Simple {
+            identifier: Identifier::Simple {
+                symbol: Symbol(symbol.to_string()),
+            },
+        },
+        arguments,
+    }
+}
+
+fn not(term: Term) -> Term {
+    application("not", vec![term])
+}
+
+#[test]
+fn test_ac_normal_form() {
+    let normalize = |input: &str| {
+        ACNormalizer::default()
+            .normalize(crate::parse_term(input).unwrap())
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(
+        normalize("(and q (and p (or r q)) (and (or q r) p))"),
+        "(and (or q r) p q)"
+    );
+    assert_eq!(
+        normalize("(= (+ x (+ 1 y)) (+ (+ y x) 1))"),
+        "(= (+ 1 x y) (+ 1 x y))"
+    );
+    assert_eq!(normalize("(- b (- a c))"), "(- b (- a c))");
+    assert_eq!(normalize("(or p (or p))"), "p");
+    assert_eq!(
+        normalize("(forall ((x Int)) (! (and (P x) (Q x)) :pattern ((Q x) (P x))))"),
+        "(forall ((x Int)) (! (and (P x) (Q x)) :pattern ((Q x) (P x))))"
+    );
+}
+
+#[test]
+fn test_negation_normal_form() {
+    let input = r#"
+(assert (not (and p (or q (not r)))))
+(assert (=> (and p q) (not (forall ((x Int)) (! (=> (P x) (Q x)) :pattern ((P x)))))))
+(assert (not (exists ((x Int)) (let ((y (+ x 1))) (not (= x y))))))
+(assert (not (! (and p true) :named a)))
+(assert (not (ite p q r)))
+"#;
+    let commands = crate::parse_assertions(input)
+        .into_iter()
+        .map(|term| {
+            NegationNormalizer::default()
+                .normalize(term)
+                .unwrap()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        commands,
+        vec![
+            "(or (not p) (and (not q) r))",
+            "(or (or (not p) (not q)) (exists ((x Int)) (! (and (P x) (not (Q x))) :pattern ((P x)))))",
+            "(forall ((x Int)) (let ((y (+ x 1))) (= x y)))",
+            "(not (! (and p true) :named a))",
+            "(not (ite p q r))",
+        ]
+    );
+}
+
+#[test]
+fn test_tseitin_encoding() {
+    let input = r#"
+(declare-fun p () Bool)
+(declare-fun q () Bool)
+(declare-fun x () Int)
+(define-fun r () Bool (or p (not q)))
+(assert (and p (=> q r)))
+(assert (= p (> x 0)))
+(check-sat)
+"#;
+    let commands = crate::parse_commands(input).unwrap();
+    let encoded = TseitinEncoder::encode_commands(commands.clone()).unwrap();
+    // `r` is inlined as the negation of `tseitin`.
+    assert_eq!(
+        encoded.iter().map(|c| c.to_string()).collect::<Vec<_>>(),
+        vec![
+            "(declare-fun p () Bool)",
+            "(declare-fun q () Bool)",
+            "(declare-fun x () Int)",
+            "(declare-fun tseitin () Bool)",
+            "(declare-fun tseitin!1 () Bool)",
+            "(assert p)",
+            "(assert (or (not tseitin) (not p)))",
+            "(assert (or (not tseitin) q))",
+            "(assert (or tseitin p (not q)))",
+            "(assert (or (not tseitin!1) q))",
+            "(assert (or (not tseitin!1) tseitin))",
+            "(assert (or tseitin!1 (not q) (not tseitin)))",
+            "(assert (not tseitin!1))",
+            "(declare-fun tseitin!2 () Bool)",
+            "(assert (or (not tseitin!2) (not p) (> x 0)))",
+            "(assert (or (not tseitin!2) p (not (> x 0))))",
+            "(assert (or tseitin!2 p (> x 0)))",
+            "(assert (or tseitin!2 (not p) (not (> x 0))))",
+            "(assert tseitin!2)",
+            "(check-sat)",
+        ]
+    );
+
+    let commands = DefinitionInliner::inline_commands(commands).unwrap();
+    let mut encoder = TseitinEncoder::new(&commands);
+    for command in &commands[..4] {
+        encoder.encode_command(command.clone());
+    }
+    assert_eq!(
+        encoder.to_dimacs().unwrap(),
+        "c 1 p\nc 2 q\np cnf 4 8\n1 0\n-3 -1 0\n-3 2 0\n3 1 -2 0\n-4 2 0\n-4 3 0\n4 -2 -3 0\n-4 0\n"
+    );
+    encoder.encode_command(commands[4].clone());
+    assert_eq!(encoder.to_dimacs(), None);
+}
+
+#[test]
+fn test_prenex_normal_form() {
+    let input = r#"
+(assert (and (forall ((x Int)) (P x)) (not (forall ((x Int)) (Q x)))))
+(assert (or (exists ((y Int)) (R x y)) (forall ((x Int)) (exists ((y Int)) (R y x)))))
+(assert (let ((z (f x))) (forall ((x Int) (z Int)) (! (P z) :pattern ((P z))))))
+(assert (=> (forall ((x Int)) (P x)) (= p (forall ((x Int)) (Q x)))))
+"#;
+    let commands = crate::parse_assertions(input)
+        .into_iter()
+        .map(|term| {
+            PrenexNormalizer::default()
+                .normalize(term)
+                .unwrap()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        commands,
+        vec![
+            "(forall ((x Int)) (exists ((x!1 Int)) (and (P x) (not (Q x!1)))))",
+            "(exists ((y Int)) (forall ((x!1 Int)) (exists ((y!1 Int)) (or (R x y) (R y!1 x!1)))))",
+            "(forall ((x!1 Int) (z!1 Int)) (let ((z (f x))) (! (P z!1) :pattern ((P z!1)))))",
+            "(exists ((x Int)) (or (not (P x)) (= p (forall ((x Int)) (Q x)))))",
+        ]
+    );
+}
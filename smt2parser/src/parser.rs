@@ -5,6 +5,8 @@
 #![allow(clippy::type_complexity)]
 #![allow(clippy::upper_case_acronyms)]
 #![allow(clippy::needless_lifetimes)]
+#![allow(clippy::diverging_sub_expression)]
+#![allow(clippy::let_unit_value)]
 
 pub use internal::{Parser, Token};
 
@@ -241,8 +243,8 @@ pomelo! {
         }
     }
 
-    function_decs ::= function_dec(x) { vec![x] }
-    function_decs ::= function_decs(mut xs) function_dec(x) { xs.push(x); xs }
+    function_decs ::= LeftParen function_dec(x) RightParen { vec![x] }
+    function_decs ::= function_decs(mut xs) LeftParen function_dec(x) RightParen { xs.push(x); xs }
 
     // sort_dec ::= ( ⟨symbol⟩ ⟨numeral⟩ )
     sort_dec ::= LeftParen fresh_symbol(x) Numeral(num) RightParen { (x, num) }
@@ -365,7 +367,7 @@ pub(crate) mod tests {
     use crate::{concrete::*, lexer::Lexer};
 
     pub(crate) fn parse_tokens<I: IntoIterator<Item = Token>>(tokens: I) -> Result<Command, Error> {
-        let mut builder = SyntaxBuilder::default();
+        let mut builder = SyntaxBuilder;
         let mut position = crate::Position::default();
         let mut p = Parser::new((&mut builder, &mut position));
         for token in tokens.into_iter() {
@@ -508,35 +510,53 @@ pub(crate) mod tests {
 
         assert!(matches!(value, Command::DeclareDatatypes { .. }));
         // Test syntax visiting while we're at it.
-        let mut builder = crate::concrete::SyntaxBuilder::default();
+        let mut builder = crate::concrete::SyntaxBuilder;
         assert_eq!(value, value.clone().accept(&mut builder).unwrap());
     }
 
+    #[test]
+    fn test_recursive_definitions() {
+        for input in [
+            "(define-fun-rec f ((x Int)) Int (f x))",
+            "(define-funs-rec ((f ((x Int)) Int) (g () Bool)) ((f x) g))",
+        ] {
+            let value = parse_tokens(Lexer::new(input.as_bytes())).unwrap();
+            assert_eq!(value.to_string(), input);
+        }
+        let result = parse_tokens(Lexer::new(
+            &b"(define-funs-rec ((f ((x Int)) Int)) ((f x) (f x)))"[..],
+        ));
+        assert!(result.is_err());
+    }
+
     #[test]
     fn test_attributes() {
-        let value = parse_tokens(Lexer::new(&b"(define-fun property () Bool (! (> Z 0) :invar-property 0))"[..])).unwrap();
+        let value = parse_tokens(Lexer::new(
+            &b"(define-fun property () Bool (! (> Z 0) :invar-property 0))"[..],
+        ))
+        .unwrap();
 
         match value {
-              Command::DefineFun { sig: _ , term } => {
-                match term {
-                    Term::Attributes { term: _, attributes } => {
-                        assert!(attributes.len() == 1);
-                        let keyword = &attributes[0].0.0;
-                        assert!(keyword == "invar-property");
-                        let value = &attributes[0].1;
-                        match value {
-                            AttributeValue::Constant(c) => match c {
-                                Constant::Numeral(big_uint) => assert!(big_uint.eq(&BigUint::from(0 as u8))),
-                                _ => assert!(false)
-                            }
-                            _ => assert!(false)
-                        }
+            Command::DefineFun {
+                sig: _,
+                term:
+                    Term::Attributes {
+                        term: _,
+                        attributes,
+                    },
+            } => {
+                assert!(attributes.len() == 1);
+                let keyword = &attributes[0].0 .0;
+                assert!(keyword == "invar-property");
+                let value = &attributes[0].1;
+                match value {
+                    AttributeValue::Constant(Constant::Numeral(big_uint)) => {
+                        assert!(big_uint.eq(&BigUint::from(0u8)))
                     }
-                    _ => assert!(false)
+                    _ => panic!(),
                 }
-              }
-              _ => assert!(false)
-  
+            }
+            _ => panic!(),
         };
     }
 }
@@ -0,0 +1,173 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Concurrent runs of several solvers on the same problem, keeping the first conclusive
+//! answer.
+
+use crate::{
+    concrete::Command,
+    solver::{self, SatResult, SolverConfig, SolverError},
+    vmt::SMTProblem,
+};
+use std::time::Duration;
+
+/// Solvers run concurrently on the same problem. Each solver is a configuration with a
+/// name used in reports. Z3 tactics can be compared by giving the same solver different
+/// `tactic.default_tactic` options.
+#[derive(Debug, Clone, Default)]
+pub struct Portfolio {
+    solvers: Vec<(String, SolverConfig)>,
+    budget: Option<Duration>,
+}
+
+/// Outcome of one solver of a [`Portfolio`].
+#[derive(Debug)]
+pub struct SolverRun {
+    pub name: String,
+    /// The answer of the solver, or why it has none, e.g. [`SolverError::Cancelled`] when
+    /// another solver answered first.
+    pub answer: Result<SatResult, SolverError>,
+    /// Time from the start of the portfolio to the answer or the failure.
+    pub elapsed: Duration,
+}
+
+/// Outcome of a [`Portfolio`].
+#[derive(Debug)]
+pub struct PortfolioResult {
+    /// The first `sat` or `unsat` answer, or `unknown` if no solver concluded.
+    pub answer: SatResult,
+    /// Index of the solver giving the answer, if conclusive.
+    pub winner: Option<usize>,
+    /// Outcomes of all the solvers, in the order of the portfolio.
+    pub runs: Vec<SolverRun>,
+}
+
+impl PortfolioResult {
+    /// The run of the solver giving the answer, if conclusive.
+    pub fn winner(&self) -> Option<&SolverRun> {
+        self.winner.map(|index| &self.runs[index])
+    }
+}
+
+impl Portfolio {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a solver to the portfolio.
+    pub fn with_solver<S: Into<String>>(mut self, name: S, config: SolverConfig) -> Self {
+        self.solvers.push((name.into(), config));
+        self
+    }
+
+    /// Cancel the solvers that have not answered after the given time.
+    pub fn with_budget(mut self, budget: Duration) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.solvers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.solvers.is_empty()
+    }
+
+    /// Check the satisfiability of an unrolled VMT model.
+    pub fn check_problem(&self, problem: &SMTProblem) -> PortfolioResult {
+        self.check(&problem.to_commands())
+    }
+
+    /// Execute the given commands with all the solvers, then `check-sat`. As soon as a
+    /// solver answers `sat` or `unsat`, the others are cancelled.
+    pub fn check(&self, commands: &[Command]) -> PortfolioResult {
+        let configs = self
+            .solvers
+            .iter()
+            .map(|(_, config)| config.clone())
+            .collect::<Vec<_>>();
+        let runs = solver::race_runs(&configs, commands, self.budget)
+            .into_iter()
+            .zip(&self.solvers)
+            .map(|((answer, elapsed), (name, _))| {
+                log::debug!("Solver {} answered {:?} after {:?}", name, answer, elapsed);
+                SolverRun {
+                    name: name.clone(),
+                    answer,
+                    elapsed,
+                }
+            })
+            .collect::<Vec<_>>();
+        let winner = runs
+            .iter()
+            .enumerate()
+            .filter(|(_, run)| matches!(run.answer, Ok(SatResult::Sat | SatResult::Unsat)))
+            .min_by_key(|(_, run)| run.elapsed)
+            .map(|(index, _)| index);
+        let answer = match winner {
+            Some(index) => *runs[index].answer.as_ref().expect("conclusive answer"),
+            None => SatResult::Unknown,
+        };
+        PortfolioResult {
+            answer,
+            winner,
+            runs,
+        }
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_portfolio() {
+    // Fake solvers answering `check-sat` after some time.
+    let solver = |answer: &str, delay: &str| {
+        let script = format!(
+            "#!/bin/sh\nwhile read line; do case \"$line\" in '(check-sat)') sleep {} > /dev/null; echo {};; *) echo success;; esac; done\n",
+            delay, answer
+        );
+        let path = std::env::temp_dir().join(format!(
+            "smt2parser-portfolio-{}-{}-{}",
+            std::process::id(),
+            answer,
+            delay
+        ));
+        std::fs::write(&path, script).unwrap();
+        std::process::Command::new("chmod")
+            .arg("+x")
+            .arg(&path)
+            .status()
+            .unwrap();
+        SolverConfig {
+            path: Some(path),
+            options: Vec::new(),
+            ..SolverConfig::new(crate::solver::SolverKind::Z3)
+        }
+    };
+    let commands = crate::parse_commands("(declare-fun x () Int) (assert (> x 0))").unwrap();
+
+    let portfolio = Portfolio::new()
+        .with_solver("slow", solver("unsat", "5"))
+        .with_solver("unknown", solver("unknown", "0"))
+        .with_solver("fast", solver("sat", "0.2"));
+    let result = portfolio.check(&commands);
+    assert_eq!(result.answer, SatResult::Sat);
+    assert_eq!(result.winner().unwrap().name, "fast");
+    assert!(matches!(result.runs[0].answer, Err(SolverError::Cancelled)));
+    assert!(matches!(result.runs[1].answer, Ok(SatResult::Unknown)));
+    assert!(result.runs[0].elapsed < Duration::from_secs(5));
+
+    let result = Portfolio::new()
+        .with_solver("slow", solver("unsat", "5"))
+        .with_budget(Duration::from_millis(100))
+        .check(&commands);
+    assert_eq!(result.answer, SatResult::Unknown);
+    assert!(matches!(
+        result.runs[0].answer,
+        Err(SolverError::Timeout(_))
+    ));
+
+    for (_, config) in &portfolio.solvers {
+        std::fs::remove_file(config.path.as_ref().unwrap()).ok();
+    }
+}
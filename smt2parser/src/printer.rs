@@ -0,0 +1,308 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Pretty-printing of commands and terms on several lines.
+
+use crate::concrete::{AttributeValue, Command, FunctionDec, Sort, Symbol, Term};
+use itertools::Itertools;
+
+/// Configuration of the pretty-printer. Subterms that fit in the remaining width are
+/// printed on one line, as by `to_string()`. Larger applications put each argument on
+/// a separate line, indented relative to the opening parenthesis. The bodies of `let`
+/// bindings and quantifiers start on a new line and the bindings are aligned.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PrettyPrinter {
+    /// Number of spaces added at each level of nesting.
+    pub indent: usize,
+    /// Preferred maximal length of a line. Symbols and constants that are too long are
+    /// not broken.
+    pub width: usize,
+}
+
+impl Default for PrettyPrinter {
+    fn default() -> Self {
+        Self {
+            indent: 2,
+            width: 80,
+        }
+    }
+}
+
+impl PrettyPrinter {
+    pub fn new(indent: usize, width: usize) -> Self {
+        Self { indent, width }
+    }
+
+    /// Print a term starting at the first column.
+    pub fn term_to_string(&self, term: &Term) -> String {
+        let mut out = String::new();
+        self.write_term(term, 0, &mut out);
+        out
+    }
+
+    /// Print a command starting at the first column.
+    pub fn command_to_string(&self, command: &Command) -> String {
+        let flat = command.to_string();
+        if flat.len() <= self.width {
+            return flat;
+        }
+        let mut out = String::new();
+        match command {
+            Command::Assert { term } => {
+                out.push_str("(assert ");
+                self.write_term(term, out.len(), &mut out);
+                out.push(')');
+            }
+            Command::DefineFun { sig, term } => {
+                self.write_definition("define-fun", sig, term, &mut out)
+            }
+            Command::DefineFunRec { sig, term } => {
+                self.write_definition("define-fun-rec", sig, term, &mut out)
+            }
+            Command::GetValue { terms } => {
+                out.push_str("(get-value (");
+                let column = out.len();
+                for (i, term) in terms.iter().enumerate() {
+                    if i > 0 {
+                        self.newline(column, &mut out);
+                    }
+                    self.write_term(term, column, &mut out);
+                }
+                out.push_str("))");
+            }
+            _ => return flat,
+        }
+        out
+    }
+
+    fn write_definition(&self, keyword: &str, sig: &FunctionDec, term: &Term, out: &mut String) {
+        out.push_str(&format!("({} {}", keyword, sig));
+        self.newline(self.indent, out);
+        self.write_term(term, self.indent, out);
+        out.push(')');
+    }
+
+    // Print a term whose first character is at the given column.
+    fn write_term(&self, term: &Term, column: usize, out: &mut String) {
+        let flat = term.to_string();
+        if column + flat.len() <= self.width {
+            out.push_str(&flat);
+            return;
+        }
+        let inner = column + self.indent;
+        match term {
+            Term::Constant(_) | Term::QualIdentifier(_) => out.push_str(&flat),
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } => {
+                out.push_str(&format!("({}", qual_identifier));
+                for argument in arguments {
+                    self.newline(inner, out);
+                    self.write_term(argument, inner, out);
+                }
+                out.push(')');
+            }
+            Term::Let { var_bindings, term } => {
+                out.push_str("(let (");
+                let binding_column = column + "(let (".len();
+                for (i, (symbol, value)) in var_bindings.iter().enumerate() {
+                    if i > 0 {
+                        self.newline(binding_column, out);
+                    }
+                    let prefix = format!("({} ", symbol);
+                    out.push_str(&prefix);
+                    self.write_term(value, binding_column + prefix.len(), out);
+                    out.push(')');
+                }
+                out.push(')');
+                self.newline(inner, out);
+                self.write_term(term, inner, out);
+                out.push(')');
+            }
+            Term::Forall { vars, term } => self.write_quantifier("forall", vars, term, column, out),
+            Term::Exists { vars, term } => self.write_quantifier("exists", vars, term, column, out),
+            Term::Match { term, cases } => {
+                out.push_str("(match ");
+                self.write_term(term, column + "(match ".len(), out);
+                self.newline(inner, out);
+                out.push('(');
+                for (i, (pattern, body)) in cases.iter().enumerate() {
+                    if i > 0 {
+                        self.newline(inner + 1, out);
+                    }
+                    let prefix = if pattern.len() == 1 {
+                        format!("({} ", pattern[0])
+                    } else {
+                        format!("(({}) ", pattern.iter().format(" "))
+                    };
+                    out.push_str(&prefix);
+                    self.write_term(body, inner + 1 + prefix.len(), out);
+                    out.push(')');
+                }
+                out.push_str("))");
+            }
+            Term::Attributes { term, attributes } => {
+                out.push_str("(! ");
+                let term_column = column + "(! ".len();
+                self.write_term(term, term_column, out);
+                for (key, value) in attributes {
+                    self.newline(term_column, out);
+                    match value {
+                        AttributeValue::None => out.push_str(&key.to_string()),
+                        _ => out.push_str(&format!("{} {}", key, value)),
+                    }
+                }
+                out.push(')');
+            }
+        }
+    }
+
+    fn write_quantifier(
+        &self,
+        quantifier: &str,
+        vars: &[(Symbol, Sort)],
+        term: &Term,
+        column: usize,
+        out: &mut String,
+    ) {
+        out.push_str(&format!(
+            "({} ({})",
+            quantifier,
+            vars.iter()
+                .format_with(" ", |(v, s), f| f(&format_args!("({} {})", v, s)))
+        ));
+        self.newline(column + self.indent, out);
+        self.write_term(term, column + self.indent, out);
+        out.push(')');
+    }
+
+    fn newline(&self, column: usize, out: &mut String) {
+        out.push('\n');
+        out.extend(std::iter::repeat(' ').take(column));
+    }
+}
+
+impl Term {
+    /// Print the term on several lines with the given printer.
+    pub fn to_pretty_string(&self, printer: &PrettyPrinter) -> String {
+        printer.term_to_string(self)
+    }
+}
+
+impl Command {
+    /// Print the command on several lines with the given printer.
+    pub fn to_pretty_string(&self, printer: &PrettyPrinter) -> String {
+        printer.command_to_string(self)
+    }
+}
+
+/// Canonical text of a script, so that generated files can be compared across runs: one
+/// command per line as by `to_string()`, each followed by a newline, where every run of
+/// consecutive `declare-sort`, `declare-fun`, and `declare-const` commands is sorted,
+/// sorts first, by symbol. Numbers in symbols are compared by value, so that `x@2` comes
+/// before `x@10`.
+pub fn to_canonical_string(commands: &[Command]) -> String {
+    let mut commands = commands.iter().collect::<Vec<_>>();
+    for run in commands.split_mut(|command| declaration_key(command).is_none()) {
+        run.sort_by(|c1, c2| {
+            let (rank1, name1) = declaration_key(c1).expect("declarations have keys");
+            let (rank2, name2) = declaration_key(c2).expect("declarations have keys");
+            rank1
+                .cmp(&rank2)
+                .then_with(|| natural_chunks(name1).cmp(&natural_chunks(name2)))
+        });
+    }
+    commands
+        .iter()
+        .map(|command| command.to_string() + "\n")
+        .collect()
+}
+
+// The rank and the symbol of a sort or function declaration.
+fn declaration_key(command: &Command) -> Option<(u8, &str)> {
+    match command {
+        Command::DeclareSort { symbol, .. } => Some((0, &symbol.0)),
+        Command::DeclareFun { symbol, .. } | Command::DeclareConst { symbol, .. } => {
+            Some((1, &symbol.0))
+        }
+        _ => None,
+    }
+}
+
+// The maximal runs of digits and of other characters of a symbol, with the value of
+// the digits, so that symbols compare as in a natural ordering.
+pub(crate) fn natural_chunks(symbol: &str) -> Vec<(Option<num::BigUint>, &str)> {
+    let mut chunks = Vec::new();
+    let mut rest = symbol;
+    while let Some(c) = rest.chars().next() {
+        let digit = c.is_ascii_digit();
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != digit)
+            .unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at(end);
+        let value = digit.then(|| chunk.parse().expect("digits are a number"));
+        chunks.push((value, chunk));
+        rest = tail;
+    }
+    chunks
+}
+
+#[test]
+fn test_pretty_printer() {
+    let input = r#"
+(assert (and (= (select a i) (select (store a j v) i)) (forall ((k Int)) (let ((x (+ k 1)) (y (* k 2))) (or (p x) (q y))))))
+(define-fun f ((x Int)) Bool (match l ((nil false) ((cons h t) (! (> (+ h x) 100000) :named positive)))))
+(assert (p 0))
+"#;
+    let printer = PrettyPrinter::new(2, 30);
+    let commands = crate::parse_commands(input)
+        .unwrap()
+        .iter()
+        .map(|command| command.to_pretty_string(&printer))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        commands,
+        vec![
+            r#"(assert (and
+          (=
+            (select a i)
+            (select
+              (store a j v)
+              i))
+          (forall ((k Int))
+            (let ((x (+ k 1))
+                  (y (* k 2)))
+              (or (p x) (q y))))))"#,
+            r#"(define-fun f ((x Int)) Bool
+  (match l
+    ((nil false)
+     ((cons h t) (! (>
+                      (+ h x)
+                      100000)
+                    :named positive)))))"#,
+            "(assert (p 0))",
+        ]
+    );
+}
+
+#[test]
+fn test_canonical_string() {
+    let commands = crate::parse_commands(
+        r#"
+(set-logic ALL)
+(declare-fun x@10 () Int)   (declare-fun x@2 () Int)
+(declare-sort S 0)
+(declare-const b S)
+(assert (> x@10   x@2))
+(declare-fun a () Int)
+(check-sat)
+"#,
+    )
+    .unwrap();
+    assert_eq!(
+        to_canonical_string(&commands),
+        "(set-logic ALL)\n(declare-sort S 0)\n(declare-const b S)\n(declare-fun x@2 () Int)\n\
+         (declare-fun x@10 () Int)\n(assert (> x@10 x@2))\n(declare-fun a () Int)\n(check-sat)\n"
+    );
+}
@@ -0,0 +1,261 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Bounded proofs: which frames of an unrolled problem appear in its unsat core.
+//!
+//! The assertions of a problem given by [`VMTModel::unroll`](crate::vmt::VMTModel::unroll)
+//! and of extra lemmas are named after their frames, e.g. `trans@2` for the transition
+//! from step 2 to step 3, so that the unsat core of the problem tells which steps and
+//! which lemmas were needed to prove the property up to the bound.
+
+use crate::{
+    concrete::{Command, Symbol, Term},
+    renaming::SymbolFactory,
+    solver::{SatResult, SmtSolver, SolverError},
+    vmt::SMTProblem,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// Role of an assertion of an unrolled problem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "index", rename_all = "snake_case")]
+pub enum Frame {
+    /// The initial condition, at step 0.
+    Initial,
+    /// The transition from the given step to the next one.
+    Transition(usize),
+    /// The negation of the property at the last step.
+    Property,
+    /// The lemma of the given index.
+    Lemma(usize),
+}
+
+/// An unrolled problem, with lemmas, whose assertions are named after their frames.
+/// Unsat cores must still be enabled with `(set-option :produce-unsat-cores true)`.
+#[derive(Debug, Clone)]
+pub struct NamedProblem {
+    pub commands: Vec<Command>,
+    frames: BTreeMap<String, Frame>,
+    lemmas: usize,
+    transitions: usize,
+}
+
+/// Frames of an unsat core.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoreReport {
+    pub initial: bool,
+    /// Steps of the transitions in the core.
+    pub transitions: Vec<usize>,
+    /// Number of transitions of the problem.
+    pub total_transitions: usize,
+    pub property: bool,
+    /// Indices of the lemmas in the core.
+    pub lemmas: Vec<usize>,
+    /// Indices of the lemmas not in the core.
+    pub unused_lemmas: Vec<usize>,
+}
+
+impl NamedProblem {
+    /// Name the assertions of a problem given by `VMTModel::unroll` and add the lemmas,
+    /// asserted after the transitions. Assertions repeated at several steps are only
+    /// asserted, and named, at the first one.
+    pub fn new(problem: &SMTProblem, lemmas: &[Term]) -> Self {
+        let mut commands = problem
+            .to_commands()
+            .into_iter()
+            .filter(|command| !matches!(command, Command::Assert { .. }))
+            .collect::<Vec<_>>();
+        let mut symbols = SymbolFactory::new(&commands);
+        let (assertions, property) = problem.assertions();
+        let mut framed = Vec::new();
+        let mut seen = HashSet::new();
+        for (index, term) in assertions.iter().enumerate() {
+            if seen.insert(term) {
+                let frame = match index {
+                    0 => Frame::Initial,
+                    index => Frame::Transition(index - 1),
+                };
+                framed.push((frame, term.clone()));
+            }
+        }
+        framed.extend(
+            lemmas
+                .iter()
+                .enumerate()
+                .map(|(index, lemma)| (Frame::Lemma(index), lemma.clone())),
+        );
+        if let Some(property) = property {
+            framed.push((Frame::Property, crate::smt2!(term: "(not {})", property)));
+        }
+        let mut frames = BTreeMap::new();
+        for (frame, term) in framed {
+            let name = symbols.fresh(&frame.to_string());
+            commands.push(Command::Assert {
+                term: term.with_name(&name),
+            });
+            frames.insert(name, frame);
+        }
+        Self {
+            commands,
+            frames,
+            lemmas: lemmas.len(),
+            transitions: assertions.len().saturating_sub(1),
+        }
+    }
+
+    /// The frame of an assertion name.
+    pub fn frame(&self, name: &str) -> Option<Frame> {
+        self.frames.get(name).copied()
+    }
+
+    /// The frames of an unsat core given by the names returned by `get-unsat-core`.
+    /// Unknown names are ignored.
+    pub fn report(&self, core: &[Symbol]) -> CoreReport {
+        let frames = core
+            .iter()
+            .filter_map(|name| self.frame(&name.0))
+            .collect::<HashSet<_>>();
+        let mut report = CoreReport {
+            initial: frames.contains(&Frame::Initial),
+            total_transitions: self.transitions,
+            property: frames.contains(&Frame::Property),
+            ..CoreReport::default()
+        };
+        report.transitions = (0..self.transitions)
+            .filter(|step| frames.contains(&Frame::Transition(*step)))
+            .collect();
+        let (used, unused) =
+            (0..self.lemmas).partition(|index| frames.contains(&Frame::Lemma(*index)));
+        report.lemmas = used;
+        report.unused_lemmas = unused;
+        report
+    }
+
+    /// Check the problem in a new scope of the solver. Return the frames of its unsat
+    /// core if it is unsatisfiable.
+    pub fn check<S: SmtSolver>(&self, solver: &mut S) -> Result<Option<CoreReport>, SolverError> {
+        solver.push(1)?;
+        for command in &self.commands {
+            solver.run(command)?;
+        }
+        let report = match solver.check_sat()? {
+            SatResult::Unsat => solver.get_unsat_core().map(|core| Some(self.report(&core))),
+            _ => Ok(None),
+        };
+        solver.pop(1)?;
+        report
+    }
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Frame::Initial => write!(f, "init"),
+            Frame::Transition(step) => write!(f, "trans@{}", step),
+            Frame::Property => write!(f, "property"),
+            Frame::Lemma(index) => write!(f, "lemma@{}", index),
+        }
+    }
+}
+
+impl std::fmt::Display for CoreReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let list = |indices: &[usize]| {
+            indices
+                .iter()
+                .map(|index| index.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let used = |used| if used { "used" } else { "unused" };
+        writeln!(f, "initial condition: {}", used(self.initial))?;
+        writeln!(
+            f,
+            "transitions used: {} of {} [{}]",
+            self.transitions.len(),
+            self.total_transitions,
+            list(&self.transitions)
+        )?;
+        writeln!(f, "property: {}", used(self.property))?;
+        writeln!(f, "lemmas used: [{}]", list(&self.lemmas))?;
+        write!(f, "lemmas unused: [{}]", list(&self.unused_lemmas))
+    }
+}
+
+#[test]
+fn test_core_report() {
+    use crate::{responses::Response, vmt::VMTModel};
+
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun n () Int)
+(declare-fun n_next () Int)
+(define-fun .n () Int (! n :next n_next))
+(declare-fun m () Int)
+(declare-fun m_next () Int)
+(define-fun .m () Int (! m :next m_next))
+(define-fun init () Bool (! (and (= n 0) (= m 0)) :init true))
+(define-fun trans () Bool (! (and (= n_next (+ n 1)) (= m_next m)) :trans true))
+(define-fun property () Bool (! (>= n 0) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let vmt = VMTModel::checked_from(commands).unwrap();
+    let lemmas = [
+        crate::smt2!(term: "(>= n@2 0)"),
+        crate::smt2!(term: "(= m@2 0)"),
+    ];
+    let problem = NamedProblem::new(&vmt.unroll(2), &lemmas);
+    let assertions = problem
+        .commands
+        .iter()
+        .filter(|command| matches!(command, Command::Assert { .. }))
+        .map(|command| command.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        assertions,
+        vec![
+            "(assert (! (and (= n@0 0) (= m@0 0)) :named init))",
+            "(assert (! (and (= n@1 (+ n@0 1)) (= m@1 m@0)) :named trans@0))",
+            "(assert (! (and (= n@2 (+ n@1 1)) (= m@2 m@1)) :named trans@1))",
+            "(assert (! (>= n@2 0) :named lemma@0))",
+            "(assert (! (= m@2 0) :named lemma@1))",
+            "(assert (! (not (>= n@2 0)) :named property))",
+        ]
+    );
+    assert_eq!(problem.frame("trans@1"), Some(Frame::Transition(1)));
+
+    // A solver answering with an unsat core made of the first lemma and the property.
+    struct Core;
+
+    impl SmtSolver for Core {
+        fn execute(&mut self, command: &Command) -> Result<Response, SolverError> {
+            Ok(match command {
+                Command::CheckSat => Response::Unsat,
+                Command::GetUnsatCore => {
+                    Response::UnsatCore(vec![Symbol("property".into()), Symbol("lemma@0".into())])
+                }
+                _ => Response::Success,
+            })
+        }
+    }
+
+    let report = problem.check(&mut Core).unwrap().unwrap();
+    assert_eq!(
+        report,
+        CoreReport {
+            initial: false,
+            transitions: vec![],
+            total_transitions: 2,
+            property: true,
+            lemmas: vec![0],
+            unused_lemmas: vec![1],
+        }
+    );
+    assert_eq!(
+        report.to_string(),
+        "initial condition: unused\ntransitions used: 0 of 2 []\nproperty: used\n\
+         lemmas used: [0]\nlemmas unused: [1]"
+    );
+}
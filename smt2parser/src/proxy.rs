@@ -0,0 +1,229 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Interception of the commands sent by a client to a solver, to debug integrations:
+//! commands and responses are logged with their timings, and commands can be rewritten
+//! on the way.
+
+use crate::{
+    concrete::{Command, Error, SyntaxBuilder, Term},
+    renaming::{SymbolNormalizer, SymbolNormalizerConfig},
+    rewriter::Rewriter,
+    solver::{ProcessSolver, SolverError},
+    visitors::AttributeValue,
+};
+use std::{
+    io::Write,
+    time::{Duration, Instant},
+};
+
+/// A [`Rewriter`] removing the annotations of terms, such as patterns, except `:named`
+/// annotations, which unsat cores depend on.
+#[derive(Debug, Default)]
+pub struct AnnotationStripper(SyntaxBuilder);
+
+impl Rewriter for AnnotationStripper {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut SyntaxBuilder {
+        &mut self.0
+    }
+
+    fn process_term(&mut self, term: Term) -> Result<Term, Error> {
+        match term {
+            Term::Attributes {
+                term,
+                mut attributes,
+            } => {
+                attributes.retain(|(key, _)| key.0 == "named");
+                if attributes.is_empty() {
+                    Ok(*term)
+                } else {
+                    Ok(Term::Attributes { term, attributes })
+                }
+            }
+            term => Ok(term),
+        }
+    }
+}
+
+/// Time taken by the solver to answer a command.
+#[derive(Debug, Clone)]
+pub struct CommandTiming {
+    /// The command, as sent to the solver.
+    pub command: Command,
+    pub elapsed: Duration,
+}
+
+/// A solver session forwarding the commands of a client. Every command and response is
+/// written to an optional transcript, which is a valid SMT-LIB script where responses
+/// and timings are comments.
+///
+/// The proxy keeps the solver in `:print-success` mode and answers the `:print-success`
+/// options of the client itself, so that `success` is only returned when the client asked
+/// for it. When symbols are normalized, the responses of the solver, e.g. models, refer to
+/// the normalized symbols.
+pub struct Proxy {
+    solver: ProcessSolver,
+    transcript: Option<Box<dyn Write>>,
+    stripper: Option<AnnotationStripper>,
+    normalizer: Option<SymbolNormalizer<SyntaxBuilder>>,
+    print_success: bool,
+    timings: Vec<CommandTiming>,
+}
+
+impl std::fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.debug_struct("Proxy")
+            .field("solver", &self.solver)
+            .field("print_success", &self.print_success)
+            .field("timings", &self.timings)
+            .finish()
+    }
+}
+
+impl Proxy {
+    pub fn new(solver: ProcessSolver) -> Self {
+        Self {
+            solver,
+            transcript: None,
+            stripper: None,
+            normalizer: None,
+            print_success: false,
+            timings: Vec::new(),
+        }
+    }
+
+    /// Write the commands, responses and timings of the session.
+    pub fn with_transcript<W: Write + 'static>(mut self, transcript: W) -> Self {
+        self.transcript = Some(Box::new(transcript));
+        self
+    }
+
+    /// Remove the annotations of terms, except names, before sending commands.
+    pub fn with_stripped_annotations(mut self) -> Self {
+        self.stripper = Some(AnnotationStripper::default());
+        self
+    }
+
+    /// Normalize the symbols of commands before sending them, as by
+    /// [`SymbolNormalizer`].
+    pub fn with_normalized_symbols(mut self, config: SymbolNormalizerConfig) -> Self {
+        self.normalizer = Some(SymbolNormalizer::new(SyntaxBuilder, config));
+        self
+    }
+
+    /// The timings of the commands sent so far.
+    pub fn timings(&self) -> &[CommandTiming] {
+        &self.timings
+    }
+
+    /// Rewrite a command and send it to the solver. Return the response for the client,
+    /// if any. Errors of the solver are returned as responses, like other responses.
+    pub fn forward(&mut self, command: Command) -> Result<Option<String>, SolverError> {
+        let command = self.rewrite(command)?;
+        if let Command::SetOption { keyword, value } = &command {
+            if keyword.0 == "print-success" {
+                self.print_success =
+                    matches!(value, AttributeValue::Symbol(symbol) if symbol.0 == "true");
+                self.log(format_args!("; {}", command))?;
+                return Ok(Some("success".to_string()).filter(|_| self.print_success));
+            }
+        }
+        self.log(format_args!("{}", command))?;
+        if command == Command::Exit {
+            // The solver is stopped when the session is dropped.
+            return Ok(None);
+        }
+        let start = Instant::now();
+        let response = self.solver.execute_raw(&command);
+        let elapsed = start.elapsed();
+        if let Ok(response) = &response {
+            for line in response.lines() {
+                self.log(format_args!("; {}", line))?;
+            }
+        }
+        self.log(format_args!("; {:.6}s", elapsed.as_secs_f64()))?;
+        self.timings.push(CommandTiming { command, elapsed });
+        let response = response?;
+        match response.trim() {
+            "success" if !self.print_success => Ok(None),
+            response => Ok(Some(response.to_string())),
+        }
+    }
+
+    fn rewrite(&mut self, command: Command) -> Result<Command, Error> {
+        let command = match &mut self.stripper {
+            Some(stripper) => command.accept(stripper)?,
+            None => command,
+        };
+        match &mut self.normalizer {
+            Some(normalizer) => command.accept(normalizer),
+            None => Ok(command),
+        }
+    }
+
+    fn log(&mut self, line: std::fmt::Arguments) -> Result<(), SolverError> {
+        log::debug!("{}", line);
+        if let Some(transcript) = &mut self.transcript {
+            writeln!(transcript, "{}", line)?;
+            transcript.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_proxy() {
+    let script = "while read line; do case \"$line\" in '(check-sat)') echo sat;; *) echo success;; esac; done";
+    let solver = ProcessSolver::spawn_program(std::path::Path::new("sh"), &["-c", script]).unwrap();
+    let path = std::env::temp_dir().join(format!("smt2parser-proxy-{}.smt2", std::process::id()));
+    let mut proxy = Proxy::new(solver)
+        .with_transcript(std::fs::File::create(&path).unwrap())
+        .with_stripped_annotations();
+
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun f (Int) Int)
+(assert (! (forall ((x Int)) (! (> (f x) 0) :pattern ((f x)))) :named positive))
+(check-sat)
+(set-option :print-success true)
+(declare-const a Int)
+"#,
+    )
+    .unwrap();
+    let responses = commands
+        .into_iter()
+        .map(|command| proxy.forward(command).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        responses,
+        vec![
+            None,
+            None,
+            Some("sat".to_string()),
+            Some("success".to_string()),
+            Some("success".to_string())
+        ]
+    );
+    assert_eq!(
+        proxy
+            .timings()
+            .iter()
+            .map(|timing| timing.command.name())
+            .collect::<Vec<_>>(),
+        vec!["declare-fun", "assert", "check-sat", "declare-const"]
+    );
+
+    drop(proxy);
+    let transcript = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    assert!(transcript
+        .contains("(assert (! (forall ((x Int)) (> (f x) 0)) :named positive))\n; success\n"));
+    assert!(transcript.contains("(check-sat)\n; sat\n"));
+    assert!(transcript.contains("; (set-option :print-success true)\n(declare-const a Int)\n"));
+    // Responses and timings are comments.
+    assert_eq!(crate::parse_commands(&transcript).unwrap().len(), 4);
+}
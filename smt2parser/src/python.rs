@@ -0,0 +1,119 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Python bindings for VMT models, built as the extension module `smt2parser`:
+//!
+//! ```python
+//! from smt2parser import VmtModel
+//!
+//! model = VmtModel.from_file("german.vmt")
+//! print(model.stats())
+//! script = model.unroll(10)
+//! abstraction, axioms = model.abstract_arrays()
+//! ```
+//!
+//! JSON documents of [`crate::schema`] are returned as Python dictionaries.
+
+use crate::{
+    abstraction::{array_axioms, ArrayAbstractor},
+    concrete::Command,
+    schema::{SMTProblemJson, VMTModelJson},
+    vmt::VMTModel,
+};
+use pyo3::{
+    exceptions::{PyIOError, PyValueError},
+    prelude::*,
+};
+use std::collections::BTreeMap;
+
+/// A transition system in VMT format.
+#[pyclass(name = "VmtModel", module = "smt2parser", frozen)]
+pub struct PyVMTModel {
+    model: VMTModel,
+}
+
+impl PyVMTModel {
+    fn from_commands(commands: Vec<Command>) -> PyResult<Self> {
+        let model = VMTModel::checked_from(commands)
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+        Ok(Self { model })
+    }
+}
+
+#[pymethods]
+impl PyVMTModel {
+    /// Parse a VMT model from SMT-LIB text.
+    #[staticmethod]
+    fn parse(text: &str) -> PyResult<Self> {
+        let commands = crate::parse_commands(text)
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+        Self::from_commands(commands)
+    }
+
+    /// Read a VMT model from a file.
+    #[staticmethod]
+    fn from_file(path: std::path::PathBuf) -> PyResult<Self> {
+        let text = std::fs::read_to_string(&path)
+            .map_err(|error| PyIOError::new_err(error.to_string()))?;
+        Self::parse(&text)
+    }
+
+    /// Number of state variables, actions, and sorts.
+    fn stats(&self) -> BTreeMap<&'static str, usize> {
+        self.model.stats().into_iter().collect()
+    }
+
+    /// Name of the fragment of the model, e.g. `ArrayProperty`.
+    fn fragment(&self) -> String {
+        format!("{:?}", self.model.fragment())
+    }
+
+    /// SMT-LIB script checking the property up to the given bound, without `check-sat`.
+    fn unroll(&self, bound: u8) -> String {
+        self.model.unroll(bound).to_smtlib2()
+    }
+
+    /// The unrolled problem, as a JSON document.
+    fn unroll_json<'py>(&self, py: Python<'py>, bound: u8) -> PyResult<Bound<'py, PyAny>> {
+        to_python(py, &SMTProblemJson::from(&self.model.unroll(bound)))
+    }
+
+    /// The model, as a JSON document.
+    fn to_json<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_python(py, &VMTModelJson::from(&self.model))
+    }
+
+    /// Abstract the arrays of the model with uninterpreted functions. Return the abstract
+    /// model and the array axioms, as SMT-LIB assertions.
+    fn abstract_arrays(&self) -> PyResult<(Self, Vec<String>)> {
+        let syntax_error = |error: crate::concrete::Error| PyValueError::new_err(error.to_string());
+        let commands =
+            ArrayAbstractor::abstract_commands(self.model.to_commands()).map_err(syntax_error)?;
+        let axioms = array_axioms(&commands);
+        Ok((
+            Self::from_commands(commands)?,
+            axioms.iter().map(Command::to_string).collect(),
+        ))
+    }
+
+    fn __str__(&self) -> String {
+        self.model
+            .to_commands()
+            .iter()
+            .map(|command| command.to_string() + "\n")
+            .collect()
+    }
+}
+
+// Convert a JSON document into Python values with the `json` module.
+fn to_python<'py, T: serde::Serialize>(py: Python<'py>, value: &T) -> PyResult<Bound<'py, PyAny>> {
+    let text =
+        serde_json::to_string(value).map_err(|error| PyValueError::new_err(error.to_string()))?;
+    py.import("json")?.call_method1("loads", (text,))
+}
+
+#[pymodule]
+fn smt2parser(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyVMTModel>()?;
+    Ok(())
+}
@@ -0,0 +1,255 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Search of the subterms of a term, by predicate or by pattern.
+
+use crate::concrete::{QualIdentifier, Term};
+use crate::visitors::Identifier;
+use std::collections::BTreeMap;
+
+/// Position of a subterm, given by the indices of the children followed from the root.
+/// See [`Term::children`].
+pub type Path = Vec<usize>;
+
+impl Term {
+    /// The immediate subterms: the arguments of an application, the bound values then the
+    /// body of a `let`, the body of a quantifier, the matched term then the bodies of the
+    /// cases of a `match`, and the annotated term.
+    pub fn children(&self) -> Vec<&Term> {
+        match self {
+            Term::Constant(_) | Term::QualIdentifier(_) => Vec::new(),
+            Term::Application { arguments, .. } => arguments.iter().collect(),
+            Term::Let { var_bindings, term } => var_bindings
+                .iter()
+                .map(|(_, value)| value)
+                .chain(std::iter::once(&**term))
+                .collect(),
+            Term::Forall { term, .. } | Term::Exists { term, .. } => vec![term],
+            Term::Match { term, cases } => std::iter::once(&**term)
+                .chain(cases.iter().map(|(_, body)| body))
+                .collect(),
+            Term::Attributes { term, .. } => vec![term],
+        }
+    }
+
+    /// The subterm at a path, if any.
+    pub fn subterm(&self, path: &[usize]) -> Option<&Term> {
+        path.iter()
+            .try_fold(self, |term, i| term.children().get(*i).copied())
+    }
+
+    /// The subterms satisfying the predicate, with their paths, in pre-order. Subterms
+    /// under binders are included, whether they mention bound variables or not.
+    pub fn find<F>(&self, mut predicate: F) -> Vec<(Path, &Term)>
+    where
+        F: FnMut(&Term) -> bool,
+    {
+        let mut found = Vec::new();
+        self.find_in(&mut Vec::new(), &mut predicate, &mut found);
+        found
+    }
+
+    fn find_in<'a, F>(
+        &'a self,
+        path: &mut Path,
+        predicate: &mut F,
+        found: &mut Vec<(Path, &'a Term)>,
+    ) where
+        F: FnMut(&Term) -> bool,
+    {
+        if predicate(self) {
+            found.push((path.clone(), self));
+        }
+        for (i, child) in self.children().into_iter().enumerate() {
+            path.push(i);
+            child.find_in(path, predicate, found);
+            path.pop();
+        }
+    }
+
+    /// The subterms matching a pattern, in pre-order.
+    pub fn find_matches(&self, pattern: &Pattern) -> Vec<Match<'_>> {
+        self.find(|term| pattern.matches(term).is_some())
+            .into_iter()
+            .map(|(path, term)| Match {
+                path,
+                term,
+                captures: pattern.matches(term).expect("the term matches"),
+            })
+            .collect()
+    }
+
+    /// The symbol of a constant or of the function of an application, if any.
+    pub fn head_symbol(&self) -> Option<&str> {
+        match self {
+            Term::QualIdentifier(qual_identifier)
+            | Term::Application {
+                qual_identifier, ..
+            } => Some(qual_identifier_symbol(qual_identifier)),
+            _ => None,
+        }
+    }
+}
+
+/// A subterm matching a [`Pattern`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<'a> {
+    pub path: Path,
+    pub term: &'a Term,
+    /// The subterms matching the named wildcards.
+    pub captures: BTreeMap<String, &'a Term>,
+}
+
+/// A term pattern, made of head symbols and wildcards.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pattern {
+    /// Any term, captured under the given name if any. Terms captured twice under the
+    /// same name must be equal.
+    Wildcard(Option<String>),
+    /// Constants or applications of the given symbol, whatever their sort annotations and
+    /// indices. With `rest`, there may be more arguments than patterns.
+    Application {
+        head: String,
+        arguments: Vec<Pattern>,
+        rest: bool,
+    },
+    /// Exactly the given term.
+    Term(Term),
+}
+
+impl Pattern {
+    /// Parse a pattern written as a term, where the symbol `?` is an anonymous wildcard,
+    /// symbols `?name` are named wildcards, and a final `...` argument matches any
+    /// remaining arguments. For instance, `(select ? ?i)` matches the reads of arrays and
+    /// captures their indices.
+    pub fn parse(input: &str) -> Result<Self, crate::Error> {
+        Ok(Self::from_term(&crate::parse_term(input)?))
+    }
+
+    /// Interpret a term as a pattern. See [`Pattern::parse`].
+    pub fn from_term(term: &Term) -> Self {
+        match term {
+            Term::QualIdentifier(QualIdentifier::Simple {
+                identifier: Identifier::Simple { symbol },
+            }) if symbol.0.starts_with('?') => {
+                let name = &symbol.0[1..];
+                Pattern::Wildcard(if name.is_empty() {
+                    None
+                } else {
+                    Some(name.to_string())
+                })
+            }
+            Term::QualIdentifier(qual_identifier) => Pattern::Application {
+                head: qual_identifier_symbol(qual_identifier).to_string(),
+                arguments: Vec::new(),
+                rest: false,
+            },
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } => {
+                let rest = arguments.last().map_or(false, |a| {
+                    a.head_symbol() == Some("...") && a.children().is_empty()
+                });
+                let arguments = &arguments[..arguments.len() - rest as usize];
+                Pattern::Application {
+                    head: qual_identifier_symbol(qual_identifier).to_string(),
+                    arguments: arguments.iter().map(Self::from_term).collect(),
+                    rest,
+                }
+            }
+            term => Pattern::Term(term.clone()),
+        }
+    }
+
+    /// Match a term against the pattern, returning the captured subterms on success.
+    pub fn matches<'a>(&self, term: &'a Term) -> Option<BTreeMap<String, &'a Term>> {
+        let mut captures = BTreeMap::new();
+        if self.match_into(term, &mut captures) {
+            Some(captures)
+        } else {
+            None
+        }
+    }
+
+    fn match_into<'a>(&self, term: &'a Term, captures: &mut BTreeMap<String, &'a Term>) -> bool {
+        match self {
+            Pattern::Wildcard(None) => true,
+            Pattern::Wildcard(Some(name)) => match captures.get(name) {
+                Some(captured) => *captured == term,
+                None => {
+                    captures.insert(name.clone(), term);
+                    true
+                }
+            },
+            Pattern::Application {
+                head,
+                arguments,
+                rest,
+            } => {
+                let actual = match term {
+                    Term::QualIdentifier(_) => &[][..],
+                    Term::Application { arguments, .. } => arguments.as_slice(),
+                    _ => return false,
+                };
+                term.head_symbol() == Some(head.as_str())
+                    && (actual.len() == arguments.len()
+                        || (*rest && actual.len() >= arguments.len()))
+                    && arguments
+                        .iter()
+                        .zip(actual)
+                        .all(|(pattern, argument)| pattern.match_into(argument, captures))
+            }
+            Pattern::Term(expected) => expected == term,
+        }
+    }
+}
+
+fn qual_identifier_symbol(qual_identifier: &QualIdentifier) -> &str {
+    match qual_identifier {
+        QualIdentifier::Simple { identifier } | QualIdentifier::Sorted { identifier, .. } => {
+            match identifier {
+                Identifier::Simple { symbol } | Identifier::Indexed { symbol, .. } => &symbol.0,
+            }
+        }
+    }
+}
+
+#[test]
+fn test_find_matches() {
+    let term = crate::parse_term(
+        "(and (= (select a i) (select a (+ i 1))) (forall ((j Int)) (! (> (select b j) 0) :pattern ((select b j)))))",
+    )
+    .unwrap();
+    let reads = term.find(|t| t.head_symbol() == Some("select"));
+    assert_eq!(
+        reads
+            .iter()
+            .map(|(path, t)| format!("{:?} {}", path, t))
+            .collect::<Vec<_>>(),
+        vec![
+            "[0, 0] (select a i)",
+            "[0, 1] (select a (+ i 1))",
+            "[1, 0, 0, 0] (select b j)",
+        ]
+    );
+    assert_eq!(term.subterm(&[0, 1, 1]).unwrap().to_string(), "(+ i 1)");
+    assert_eq!(term.subterm(&[2]), None);
+
+    let pattern = Pattern::parse("(select a ?i)").unwrap();
+    let indices = term
+        .find_matches(&pattern)
+        .into_iter()
+        .map(|m| m.captures["i"].to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(indices, vec!["i", "(+ i 1)"]);
+
+    let same = Pattern::parse("(= (select ?a ?) (select ?a ?))").unwrap();
+    assert_eq!(term.find_matches(&same).len(), 1);
+    let conjunction = Pattern::parse("(and (= ...) ...)").unwrap();
+    assert_eq!(term.find_matches(&conjunction)[0].path, Vec::<usize>::new());
+    assert!(Pattern::parse("(select ? 0)")
+        .unwrap()
+        .matches(&term)
+        .is_none());
+}
@@ -6,7 +6,7 @@
 use crate::{
     concrete::*,
     rewriter::Rewriter,
-    visitors::{Identifier, Index, Smt2Visitor, SymbolKind},
+    visitors::{Identifier, Index, SExprVisitor, Smt2Visitor, SymbolKind, SymbolVisitor},
 };
 use num::ToPrimitive;
 use std::collections::{BTreeMap, BTreeSet};
@@ -51,19 +51,203 @@ where
     }
 }
 
+/// Generator of fresh symbols that differ from the symbols of some commands and terms,
+/// and from each other. A fresh symbol is the requested name if it is not taken, or the
+/// name followed by `!1`, `!2`, etc.
+#[derive(Debug, Default, Clone)]
+pub struct SymbolFactory {
+    /// Symbols that are taken.
+    symbols: BTreeSet<String>,
+}
+
+impl SymbolFactory {
+    /// Build a factory avoiding all the symbols of the given commands.
+    pub fn new<'a, I>(commands: I) -> Self
+    where
+        I: IntoIterator<Item = &'a Command>,
+    {
+        let mut factory = Self::default();
+        let mut collector = SymbolCollector {
+            visitor: SyntaxBuilder,
+            symbols: &mut factory.symbols,
+        };
+        for command in commands {
+            command
+                .clone()
+                .accept(&mut collector)
+                .expect("Rebuilding a command does not fail");
+        }
+        factory
+    }
+
+    /// Avoid a symbol.
+    pub fn avoid(&mut self, symbol: &str) {
+        self.symbols.insert(symbol.to_string());
+    }
+
+    /// Avoid all the symbols of a term.
+    pub fn avoid_term(&mut self, term: &Term) {
+        let mut collector = SymbolCollector {
+            visitor: SyntaxBuilder,
+            symbols: &mut self.symbols,
+        };
+        term.clone()
+            .accept(&mut collector)
+            .expect("Rebuilding a term does not fail");
+    }
+
+    /// Generate a fresh symbol based on `name`. The result is avoided from now on.
+    pub fn fresh(&mut self, name: &str) -> String {
+        let mut fresh = name.to_string();
+        let mut index = 0;
+        while self.symbols.contains(&fresh) {
+            index += 1;
+            fresh = format!("{}!{}", name, index);
+        }
+        self.symbols.insert(fresh.clone());
+        fresh
+    }
+}
+
+/// Rewriter collecting all the symbols of the visited values, bound or not.
+struct SymbolCollector<'a> {
+    visitor: SyntaxBuilder,
+    symbols: &'a mut BTreeSet<String>,
+}
+
+impl<'a> Rewriter for SymbolCollector<'a> {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn process_symbol(&mut self, s: Symbol) -> Result<Symbol, Self::Error> {
+        self.symbols.insert(s.0.clone());
+        Ok(s)
+    }
+}
+
+/// A [`Rewriter`] implementation that gives distinct names to all the variables bound in
+/// the visited values, also distinct from their free symbols (Barendregt's convention).
+/// * The first binder of a name keeps it unless it is taken. Others are renamed into
+///   `x!1`, `x!2`, etc.
+/// * Names stay distinct across all the values visited by the same renamer.
+/// * Symbols of `:pattern` annotations follow the renaming.
+#[derive(Debug, Default)]
+pub struct BoundVariableRenamer {
+    /// The underlying syntax visitor.
+    visitor: SyntaxBuilder,
+    /// Generator of the new names.
+    symbols: SymbolFactory,
+    /// Original and new names of the variables introduced but not bound yet.
+    fresh_variables: Vec<(String, String)>,
+    /// Original and new names of the currently bound variables, innermost last.
+    bound_variables: Vec<(String, String)>,
+}
+
+impl BoundVariableRenamer {
+    /// Build a renamer whose new names are also distinct from the symbols avoided by the
+    /// given factory, e.g. the symbols declared by a script.
+    pub fn new(symbols: SymbolFactory) -> Self {
+        Self {
+            symbols,
+            ..Self::default()
+        }
+    }
+
+    /// Rename the bound variables of a term.
+    pub fn rename(&mut self, term: Term) -> Result<Term, Error> {
+        for symbol in crate::rewriter::free_symbols(&term) {
+            self.symbols.avoid(&symbol);
+        }
+        term.accept(self)
+    }
+
+    fn renamed(&self, symbol: &str) -> Option<&String> {
+        self.bound_variables
+            .iter()
+            .rev()
+            .find(|(original, _)| original == symbol)
+            .map(|(_, name)| name)
+    }
+}
+
+impl Rewriter for BoundVariableRenamer {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn visit_fresh_symbol(
+        &mut self,
+        value: String,
+        kind: SymbolKind,
+    ) -> Result<Symbol, Self::Error> {
+        let value = if kind == SymbolKind::Variable {
+            let name = self.symbols.fresh(&value);
+            self.fresh_variables.push((value, name.clone()));
+            name
+        } else {
+            value
+        };
+        let value = self.visitor().visit_fresh_symbol(value, kind)?;
+        self.process_symbol(value)
+    }
+
+    fn visit_bound_symbol(&mut self, value: String) -> Result<Symbol, Self::Error> {
+        let value = self.renamed(&value).cloned().unwrap_or(value);
+        let value = self.visitor().visit_bound_symbol(value)?;
+        self.process_symbol(value)
+    }
+
+    fn visit_symbol_s_expr(&mut self, value: Symbol) -> Result<SExpr, Self::Error> {
+        let value = match self.renamed(&value.0) {
+            Some(name) => Symbol(name.clone()),
+            None => value,
+        };
+        let value = self.visitor().visit_symbol_s_expr(value)?;
+        self.process_s_expr(value)
+    }
+
+    fn bind_symbol(&mut self, symbol: &Symbol) {
+        if let Some(index) = self
+            .fresh_variables
+            .iter()
+            .rposition(|(_, name)| *name == symbol.0)
+        {
+            let variable = self.fresh_variables.remove(index);
+            self.bound_variables.push(variable);
+        }
+        self.visitor().bind_symbol(symbol);
+    }
+
+    fn unbind_symbol(&mut self, symbol: &Symbol) {
+        if let Some(index) = self
+            .bound_variables
+            .iter()
+            .rposition(|(_, name)| *name == symbol.0)
+        {
+            self.bound_variables.remove(index);
+        }
+        self.visitor().unbind_symbol(symbol);
+    }
+}
+
 /// A [`Rewriter`] implementation that normalizes local symbols into `x0`, `x1`, etc.
 /// * Normalization applies to all locally resolved symbols.
 /// * A different prefix is applied depending on the symbol kind (datatype, sorts,
-/// functions, variables, etc).
+///   functions, variables, etc).
 /// * "Global" symbols (those which don't resolve locally) are ignored.
 /// * Symbol names may be re-used after a `reset` or a `pop` command, but are otherwise
-/// unique (disregarding the more limited lexical scoping of variables).
+///   unique (disregarding the more limited lexical scoping of variables).
 #[derive(Debug, Default)]
 pub struct SymbolNormalizer<V> {
     /// The underlying syntax visitor.
     visitor: V,
-    /// Configuration.
-    config: SymbolNormalizerConfig,
     /// Original names of current local symbols, indexed by kind.
     current_local_symbols: BTreeMap<SymbolKind, Vec<String>>,
     /// Currently bound symbols.
@@ -164,7 +348,6 @@ impl<V> SymbolNormalizer<V> {
         }
         Self {
             visitor,
-            config,
             current_local_symbols: BTreeMap::new(),
             current_bound_symbols: BTreeMap::new(),
             encoding_tables,
@@ -371,6 +554,194 @@ where
     }
 }
 
+impl Term {
+    /// Whether two terms are equal up to the names of their bound variables, that is the
+    /// variables of quantifiers, `let` bindings, and `match` patterns with arguments.
+    pub fn alpha_eq(&self, other: &Term) -> bool {
+        AlphaEquivalence::default().terms(self, other)
+    }
+}
+
+/// Bound variables of the two compared terms, innermost last.
+#[derive(Debug, Default)]
+struct AlphaEquivalence {
+    left: Vec<String>,
+    right: Vec<String>,
+}
+
+impl AlphaEquivalence {
+    fn terms(&mut self, t1: &Term, t2: &Term) -> bool {
+        match (t1, t2) {
+            (Term::Constant(c1), Term::Constant(c2)) => c1 == c2,
+            (Term::QualIdentifier(q1), Term::QualIdentifier(q2)) => self.qual_identifiers(q1, q2),
+            (
+                Term::Application {
+                    qual_identifier: q1,
+                    arguments: a1,
+                },
+                Term::Application {
+                    qual_identifier: q2,
+                    arguments: a2,
+                },
+            ) => {
+                self.qual_identifiers(q1, q2)
+                    && a1.len() == a2.len()
+                    && a1.iter().zip(a2).all(|(t1, t2)| self.terms(t1, t2))
+            }
+            (
+                Term::Let {
+                    var_bindings: b1,
+                    term: t1,
+                },
+                Term::Let {
+                    var_bindings: b2,
+                    term: t2,
+                },
+            ) => {
+                // Bindings are parallel: the values are compared outside of the scope.
+                b1.len() == b2.len()
+                    && b1
+                        .iter()
+                        .zip(b2)
+                        .all(|((_, v1), (_, v2))| self.terms(v1, v2))
+                    && self.bound(
+                        b1.iter().map(|(s, _)| s),
+                        b2.iter().map(|(s, _)| s),
+                        |this| this.terms(t1, t2),
+                    )
+            }
+            (Term::Forall { vars: v1, term: t1 }, Term::Forall { vars: v2, term: t2 })
+            | (Term::Exists { vars: v1, term: t1 }, Term::Exists { vars: v2, term: t2 }) => {
+                v1.len() == v2.len()
+                    && v1.iter().zip(v2).all(|((_, s1), (_, s2))| s1 == s2)
+                    && self.bound(
+                        v1.iter().map(|(s, _)| s),
+                        v2.iter().map(|(s, _)| s),
+                        |this| this.terms(t1, t2),
+                    )
+            }
+            (
+                Term::Match {
+                    term: t1,
+                    cases: c1,
+                },
+                Term::Match {
+                    term: t2,
+                    cases: c2,
+                },
+            ) => {
+                // Patterns made of a single symbol are compared as constructors.
+                self.terms(t1, t2)
+                    && c1.len() == c2.len()
+                    && c1.iter().zip(c2).all(|((p1, b1), (p2, b2))| {
+                        p1.len() == p2.len()
+                            && p1[0] == p2[0]
+                            && self.bound(p1[1..].iter(), p2[1..].iter(), |this| this.terms(b1, b2))
+                    })
+            }
+            (
+                Term::Attributes {
+                    term: t1,
+                    attributes: a1,
+                },
+                Term::Attributes {
+                    term: t2,
+                    attributes: a2,
+                },
+            ) => {
+                self.terms(t1, t2)
+                    && a1.len() == a2.len()
+                    && a1.iter().zip(a2).all(|((k1, v1), (k2, v2))| {
+                        k1 == k2
+                            && match (v1, v2) {
+                                (AttributeValue::SExpr(e1), AttributeValue::SExpr(e2)) => {
+                                    e1.len() == e2.len()
+                                        && e1.iter().zip(e2).all(|(e1, e2)| self.s_exprs(e1, e2))
+                                }
+                                _ => v1 == v2,
+                            }
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    fn qual_identifiers(&self, q1: &QualIdentifier, q2: &QualIdentifier) -> bool {
+        match (q1, q2) {
+            (
+                QualIdentifier::Simple {
+                    identifier: Identifier::Simple { symbol: s1 },
+                },
+                QualIdentifier::Simple {
+                    identifier: Identifier::Simple { symbol: s2 },
+                },
+            ) => self.symbols(s1, s2),
+            (
+                QualIdentifier::Sorted {
+                    identifier: Identifier::Simple { symbol: s1 },
+                    sort: sort1,
+                },
+                QualIdentifier::Sorted {
+                    identifier: Identifier::Simple { symbol: s2 },
+                    sort: sort2,
+                },
+            ) => sort1 == sort2 && self.symbols(s1, s2),
+            _ => q1 == q2,
+        }
+    }
+
+    fn s_exprs(&self, e1: &SExpr, e2: &SExpr) -> bool {
+        match (e1, e2) {
+            (SExpr::Symbol(s1), SExpr::Symbol(s2)) => self.symbols(s1, s2),
+            (SExpr::Application(a1), SExpr::Application(a2)) => {
+                a1.len() == a2.len() && a1.iter().zip(a2).all(|(e1, e2)| self.s_exprs(e1, e2))
+            }
+            _ => e1 == e2,
+        }
+    }
+
+    // Bound symbols must be bound by the same binder, free symbols must be equal.
+    fn symbols(&self, s1: &Symbol, s2: &Symbol) -> bool {
+        match (
+            self.left.iter().rposition(|name| *name == s1.0),
+            self.right.iter().rposition(|name| *name == s2.0),
+        ) {
+            (None, None) => s1 == s2,
+            (i, j) => i == j,
+        }
+    }
+
+    fn bound<'a, I1, I2, F>(&mut self, s1: I1, s2: I2, f: F) -> bool
+    where
+        I1: Iterator<Item = &'a Symbol>,
+        I2: Iterator<Item = &'a Symbol>,
+        F: FnOnce(&mut Self) -> bool,
+    {
+        let (left, right) = (self.left.len(), self.right.len());
+        self.left.extend(s1.map(|s| s.0.clone()));
+        self.right.extend(s2.map(|s| s.0.clone()));
+        let result = f(self);
+        self.left.truncate(left);
+        self.right.truncate(right);
+        result
+    }
+}
+
+#[test]
+fn test_symbol_factory() {
+    let input = r#"
+(declare-fun x () Int)
+(assert (forall ((x!1 Int)) (> x!1 x)))
+"#;
+    let commands = crate::parse_commands(input).unwrap();
+    let mut factory = SymbolFactory::new(&commands);
+    assert_eq!(factory.fresh("y"), "y");
+    assert_eq!(factory.fresh("y"), "y!1");
+    assert_eq!(factory.fresh("x"), "x!2");
+    factory.avoid("z");
+    assert_eq!(factory.fresh("z"), "z!1");
+}
+
 #[test]
 fn test_testers() {
     use crate::{concrete, lexer::Lexer, parser::tests::parse_tokens};
@@ -387,7 +758,7 @@ fn test_testers() {
             }
         }
     ));
-    let mut builder = concrete::SyntaxBuilder::default();
+    let mut builder = concrete::SyntaxBuilder;
     assert_eq!(value, value.clone().accept(&mut builder).unwrap());
     // Visit with the TesterModernizer this time.
     let mut builder = TesterModernizer::<SyntaxBuilder>::default();
@@ -430,7 +801,7 @@ fn test_declare_datatypes_renaming() {
     ))
     .unwrap();
     assert!(matches!(value2, Command::DeclareDatatypes { .. }));
-    let mut builder = SyntaxBuilder::default();
+    let mut builder = SyntaxBuilder;
     assert_eq!(value, value.clone().accept(&mut builder).unwrap());
 
     let mut builder = SymbolNormalizer::<SyntaxBuilder>::default();
@@ -596,3 +967,47 @@ fn test_random_renaming() {
         5
     );
 }
+
+#[test]
+fn test_alpha_equivalence() {
+    let term = |input: &str| crate::parse_term(input).unwrap();
+    let t = term("(forall ((x Int) (y Int)) (! (= (f x) y) :pattern ((f x))))");
+    assert!(t.alpha_eq(&term(
+        "(forall ((a Int) (b Int)) (! (= (f a) b) :pattern ((f a))))"
+    )));
+    assert!(!t.alpha_eq(&term(
+        "(forall ((a Int) (b Int)) (! (= (f b) a) :pattern ((f b))))"
+    )));
+    assert!(!t.alpha_eq(&term(
+        "(forall ((a Int) (b Int)) (! (= (f a) b) :pattern ((f b))))"
+    )));
+    assert!(!t.alpha_eq(&term(
+        "(exists ((x Int) (y Int)) (! (= (f x) y) :pattern ((f x))))"
+    )));
+    // Free symbols must be equal, and shadowing is respected.
+    assert!(term("(let ((x y)) (forall ((x Int)) (P x)))")
+        .alpha_eq(&term("(let ((z y)) (forall ((w Int)) (P w)))")));
+    assert!(!term("(let ((x y)) (P x y))").alpha_eq(&term("(let ((y y)) (P y y))")));
+    assert!(term("(match l ((nil 0) ((cons h t) h)))")
+        .alpha_eq(&term("(match l ((nil 0) ((cons a b) a)))")));
+}
+
+#[test]
+fn test_bound_variable_renaming() {
+    let term = crate::parse_term(
+        "(forall ((x Int)) (! (and (exists ((x Int)) (> x y!1)) (let ((y x)) (forall ((y Int)) (= x y)))) :pattern ((f x))))",
+    )
+    .unwrap();
+    let mut renamer = BoundVariableRenamer::default();
+    let renamed = renamer.rename(term.clone()).unwrap();
+    assert_eq!(
+        renamed.to_string(),
+        "(forall ((x Int)) (! (and (exists ((x!1 Int)) (> x!1 y!1)) (let ((y x)) (forall ((y!2 Int)) (= x y!2)))) :pattern ((f x))))"
+    );
+    assert!(renamed.alpha_eq(&term));
+    // Names stay distinct across terms.
+    let renamed = renamer
+        .rename(crate::parse_term("(exists ((x Int)) (P x))").unwrap())
+        .unwrap();
+    assert_eq!(renamed.to_string(), "(exists ((x!2 Int)) (P x!2))");
+}
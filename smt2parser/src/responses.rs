@@ -0,0 +1,243 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Responses printed by solvers for the commands of a script, such as `check-sat`,
+//! `get-value`, `get-unsat-core`, `get-model`, and errors.
+
+use crate::{
+    concrete::{Command, Symbol, Term},
+    model::{Model, ModelError, Value},
+    visitors::Identifier,
+};
+use thiserror::Error;
+
+/// Error reported when parsing solver responses.
+#[derive(Error, Debug)]
+pub enum ResponseError {
+    #[error(transparent)]
+    Syntax(#[from] crate::Error),
+    #[error(transparent)]
+    Model(#[from] ModelError),
+    #[error("unbalanced response: {0}")]
+    Unbalanced(String),
+    #[error("unexpected response to `{command}`: {response}")]
+    Unexpected { command: String, response: String },
+}
+
+/// A response to a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Response {
+    Success,
+    Unsupported,
+    /// The message of an `(error "...")` response.
+    Error(String),
+    Sat,
+    Unsat,
+    Unknown,
+    /// The terms of `get-value` with their values.
+    Values(Vec<(Term, Value)>),
+    /// The names of the assertions of an unsat core.
+    UnsatCore(Vec<Symbol>),
+    Model(Model),
+    /// The keywords of `get-info`, without colons, and their values as printed.
+    Info(Vec<(String, String)>),
+    /// The response to any other command, as printed.
+    Other(String),
+}
+
+impl Response {
+    /// Parse the response to a command.
+    pub fn parse(input: &str, command: &Command) -> Result<Self, ResponseError> {
+        let unexpected = || ResponseError::Unexpected {
+            command: command.to_string(),
+            response: input.trim().to_string(),
+        };
+        let items = split_items(input)?;
+        let input = match items[..] {
+            [item] => item,
+            _ => return Err(unexpected()),
+        };
+        match input {
+            "success" => return Ok(Response::Success),
+            "unsupported" => return Ok(Response::Unsupported),
+            _ => (),
+        }
+        if let Some(message) = error_message(input) {
+            return Ok(Response::Error(message));
+        }
+        match command {
+            Command::CheckSat | Command::CheckSatAssuming { .. } => match input {
+                "sat" => Ok(Response::Sat),
+                "unsat" => Ok(Response::Unsat),
+                "unknown" => Ok(Response::Unknown),
+                _ => Err(unexpected()),
+            },
+            Command::GetValue { .. } => {
+                let model = Model::default();
+                let values = split_items(list_body(input).ok_or_else(unexpected)?)?
+                    .into_iter()
+                    .map(
+                        |pair| match split_items(list_body(pair).ok_or_else(unexpected)?)?[..] {
+                            [term, value] => Ok((
+                                crate::parse_term(term)?,
+                                model.value_of(&crate::parse_term(value)?),
+                            )),
+                            _ => Err(unexpected()),
+                        },
+                    )
+                    .collect::<Result<_, ResponseError>>()?;
+                Ok(Response::Values(values))
+            }
+            Command::GetUnsatCore | Command::GetUnsatAssumptions => {
+                let names = split_items(list_body(input).ok_or_else(unexpected)?)?
+                    .into_iter()
+                    .map(|name| match crate::parse_term(name)? {
+                        Term::QualIdentifier(crate::concrete::QualIdentifier::Simple {
+                            identifier: Identifier::Simple { symbol },
+                        }) => Ok(symbol),
+                        _ => Err(unexpected()),
+                    })
+                    .collect::<Result<_, ResponseError>>()?;
+                Ok(Response::UnsatCore(names))
+            }
+            Command::GetModel => Ok(Response::Model(Model::parse(input)?)),
+            Command::GetInfo { .. } => {
+                let items = split_items(list_body(input).unwrap_or(input))?;
+                let mut info = Vec::new();
+                let mut items = items.into_iter().peekable();
+                while let Some(key) = items.next() {
+                    let key = key.strip_prefix(':').ok_or_else(unexpected)?;
+                    let value = items.next_if(|item| !item.starts_with(':'));
+                    info.push((key.to_string(), value.unwrap_or_default().to_string()));
+                }
+                Ok(Response::Info(info))
+            }
+            _ => Ok(Response::Other(input.to_string())),
+        }
+    }
+}
+
+/// Whether a command prints a response when `:print-success` is disabled, as by
+/// default.
+pub fn has_response(command: &Command) -> bool {
+    matches!(
+        command,
+        Command::CheckSat
+            | Command::CheckSatAssuming { .. }
+            | Command::Echo { .. }
+            | Command::GetAssertions
+            | Command::GetAssignment
+            | Command::GetInfo { .. }
+            | Command::GetModel
+            | Command::GetOption { .. }
+            | Command::GetProof
+            | Command::GetUnsatAssumptions
+            | Command::GetUnsatCore
+            | Command::GetValue { .. }
+    )
+}
+
+/// Split the output of a solver into its top-level responses, skipping comments.
+pub fn split_responses(output: &str) -> Result<Vec<&str>, ResponseError> {
+    split_items(output)
+}
+
+/// Parse the output of a solver for a script. Each command with a response, in the sense
+/// of [`has_response`], is matched with the next response of the output, which may be an
+/// error. `success` responses are skipped. Parsing stops at the end of the output, so that
+/// fewer responses than commands may be returned, e.g. after a timeout.
+pub fn parse_responses(output: &str, commands: &[Command]) -> Result<Vec<Response>, ResponseError> {
+    let items = split_items(output)?
+        .into_iter()
+        .filter(|item| *item != "success");
+    commands
+        .iter()
+        .filter(|command| has_response(command))
+        .zip(items)
+        .map(|(command, item)| Response::parse(item, command))
+        .collect()
+}
+
+fn split_items(input: &str) -> Result<Vec<&str>, ResponseError> {
+    crate::split_s_exprs(input).ok_or_else(|| ResponseError::Unbalanced(input.trim().to_string()))
+}
+
+// The contents of a parenthesized list.
+fn list_body(input: &str) -> Option<&str> {
+    input.strip_prefix('(')?.strip_suffix(')')
+}
+
+fn error_message(input: &str) -> Option<String> {
+    let body = list_body(input)?.trim_start().strip_prefix("error")?;
+    let message = body.trim().strip_prefix('"')?.strip_suffix('"')?;
+    Some(message.replace("\"\"", "\""))
+}
+
+#[test]
+fn test_parse_responses() {
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun x () Int)
+(assert (! (> x 0) :named pos))
+(check-sat)
+(get-value (x (+ x 1)))
+(get-unsat-core)
+(get-info :name)
+(get-model)
+(echo "done")
+"#,
+    )
+    .unwrap();
+    let output = r#"sat
+((x 1)
+ ((+ x 1) 2))
+(error "line 5 column 15: unsat core is not available")
+(:name "Z3")
+(
+  ;; model
+  (define-fun x () Int
+    1)
+)
+done
+"#;
+    let responses = parse_responses(output, &commands).unwrap();
+    assert_eq!(responses.len(), 6);
+    assert_eq!(responses[0], Response::Sat);
+    match &responses[1] {
+        Response::Values(values) => {
+            assert_eq!(values[1].0.to_string(), "(+ x 1)");
+            assert_eq!(values[1].1, Value::Int(2.into()));
+        }
+        response => panic!("unexpected response {:?}", response),
+    }
+    assert_eq!(
+        responses[2],
+        Response::Error("line 5 column 15: unsat core is not available".to_string())
+    );
+    assert_eq!(
+        responses[3],
+        Response::Info(vec![("name".to_string(), "\"Z3\"".to_string())])
+    );
+    match &responses[4] {
+        Response::Model(model) => assert_eq!(model.get("x"), Some(&Value::Int(1.into()))),
+        response => panic!("unexpected response {:?}", response),
+    }
+    assert_eq!(responses[5], Response::Other("done".to_string()));
+
+    assert_eq!(
+        Response::parse("(a |b c|)", &Command::GetUnsatCore).unwrap(),
+        Response::UnsatCore(vec![Symbol("a".into()), Symbol("b c".into())])
+    );
+    assert!(matches!(
+        Response::parse("maybe", &Command::CheckSat),
+        Err(ResponseError::Unexpected { .. })
+    ));
+    assert_eq!(
+        Response::parse("(error \"a \"\"quoted\"\" word\")", &Command::CheckSat).unwrap(),
+        Response::Error("a \"quoted\" word".to_string())
+    );
+    assert_eq!(
+        parse_responses("success\nunknown\n", &commands).unwrap(),
+        vec![Response::Unknown]
+    );
+}
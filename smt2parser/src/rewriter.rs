@@ -6,6 +6,10 @@
 //! Rewriting of Smt2 values
 
 use crate::{
+    concrete::{Error as ConcreteError, QualIdentifier, SExpr, Sort, Symbol, SyntaxBuilder, Term},
+    renaming::SymbolFactory,
+    stats::TermMetrics,
+    typing::{Signature, SmtLibTheories},
     visitors::{
         AttributeValue, CommandVisitor, ConstantVisitor, DatatypeDec, FunctionDec, Identifier,
         KeywordVisitor, QualIdentifierVisitor, SExprVisitor, Smt2Visitor, SortVisitor, SymbolKind,
@@ -13,6 +17,7 @@ use crate::{
     },
     Binary, Decimal, Hexadecimal, Numeral, Position,
 };
+use std::collections::{BTreeMap, BTreeSet};
 
 /// Helper trait to create variants of an existing Smt2Visitor.
 pub trait Rewriter {
@@ -72,6 +77,13 @@ pub trait Rewriter {
         Ok(value)
     }
 
+    /// Whether to rewrite a term and its subterms. Other terms are rebuilt by the delegate
+    /// visitor without calling the post-processing hooks, e.g. to skip the subterms that
+    /// contain no symbol to rewrite.
+    fn should_descend(&mut self, _term: &Term) -> bool {
+        true
+    }
+
     // ConstantVisitor
     fn visit_numeral_constant(
         &mut self,
@@ -718,6 +730,13 @@ where
     ) -> Result<Self::T, Self::E> {
         self.visit_attributes(term, attributes)
     }
+
+    fn skip_term(&mut self, term: &Term) -> Option<Result<Self::T, Self::E>> {
+        if self.should_descend(term) {
+            return None;
+        }
+        Some(term.accept_ref(self.visitor()).map_err(Into::into))
+    }
 }
 
 impl<R, V> CommandVisitor<V::Term, V::Symbol, V::Sort, V::Keyword, V::Constant, V::SExpr> for R
@@ -920,6 +939,454 @@ where
     }
 }
 
+/// A [`Rewriter`] implementation that replaces the free occurrences of symbols by terms.
+/// * Occurrences bound by a quantifier, a `let`, a `match`, or a function definition are
+///   not replaced.
+/// * Bound variables that would capture a symbol of a replacement term are renamed into
+///   `x!1`, `x!2`, etc.
+#[derive(Debug, Default)]
+pub struct SubstitutionRewriter {
+    /// The underlying syntax visitor.
+    visitor: SyntaxBuilder,
+    /// Replacement terms, indexed by symbol.
+    substitution: BTreeMap<String, Term>,
+    /// Symbols of the replacement terms.
+    captured_symbols: BTreeSet<String>,
+    /// Generator of the new names.
+    symbols: SymbolFactory,
+    /// Original and new names of the variables introduced but not bound yet.
+    fresh_variables: Vec<(String, String)>,
+    /// Original and new names of the currently bound variables, innermost last.
+    bound_variables: Vec<(String, String)>,
+}
+
+impl SubstitutionRewriter {
+    pub fn new(substitution: BTreeMap<String, Term>) -> Self {
+        let mut captured_symbols = BTreeSet::new();
+        for term in substitution.values() {
+            captured_symbols.extend(free_symbols(term));
+        }
+        let mut symbols = SymbolFactory::default();
+        for symbol in captured_symbols.iter().chain(substitution.keys()) {
+            symbols.avoid(symbol);
+        }
+        Self {
+            substitution,
+            captured_symbols,
+            symbols,
+            ..Self::default()
+        }
+    }
+
+    /// Apply the substitution to a term.
+    pub fn substitute(&mut self, term: Term) -> Result<Term, ConcreteError> {
+        // New names must not capture the free symbols of the term either.
+        self.symbols.avoid_term(&term);
+        term.accept(self)
+    }
+
+    fn is_bound(&self, symbol: &Symbol) -> bool {
+        self.bound_variables
+            .iter()
+            .any(|(_, name)| *name == symbol.0)
+    }
+}
+
+impl Rewriter for SubstitutionRewriter {
+    type V = SyntaxBuilder;
+    type Error = ConcreteError;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn visit_fresh_symbol(
+        &mut self,
+        value: String,
+        kind: SymbolKind,
+    ) -> Result<Symbol, Self::Error> {
+        let value = if kind == SymbolKind::Variable {
+            let name = if self.captured_symbols.contains(&value) {
+                self.symbols.fresh(&value)
+            } else {
+                value.clone()
+            };
+            self.fresh_variables.push((value, name.clone()));
+            name
+        } else {
+            value
+        };
+        let value = self.visitor().visit_fresh_symbol(value, kind)?;
+        self.process_symbol(value)
+    }
+
+    fn visit_bound_symbol(&mut self, value: String) -> Result<Symbol, Self::Error> {
+        let value = match self
+            .bound_variables
+            .iter()
+            .rev()
+            .find(|(original, _)| *original == value)
+        {
+            Some((_, name)) => name.clone(),
+            None => value,
+        };
+        let value = self.visitor().visit_bound_symbol(value)?;
+        self.process_symbol(value)
+    }
+
+    // The symbols of patterns follow the renaming of bound variables and the replacement
+    // of free symbols by symbols.
+    fn visit_symbol_s_expr(&mut self, value: Symbol) -> Result<SExpr, Self::Error> {
+        let value = match self
+            .bound_variables
+            .iter()
+            .rev()
+            .find(|(original, _)| *original == value.0)
+        {
+            Some((_, name)) => Symbol(name.clone()),
+            None => match self.substitution.get(&value.0) {
+                Some(Term::QualIdentifier(QualIdentifier::Simple {
+                    identifier: Identifier::Simple { symbol },
+                })) => symbol.clone(),
+                _ => value,
+            },
+        };
+        let value = self.visitor().visit_symbol_s_expr(value)?;
+        self.process_s_expr(value)
+    }
+
+    fn bind_symbol(&mut self, symbol: &Symbol) {
+        if let Some(index) = self
+            .fresh_variables
+            .iter()
+            .rposition(|(_, name)| *name == symbol.0)
+        {
+            let variable = self.fresh_variables.remove(index);
+            self.bound_variables.push(variable);
+        }
+        self.visitor().bind_symbol(symbol);
+    }
+
+    fn unbind_symbol(&mut self, symbol: &Symbol) {
+        if let Some(index) = self
+            .bound_variables
+            .iter()
+            .rposition(|(_, name)| *name == symbol.0)
+        {
+            self.bound_variables.remove(index);
+        }
+        self.visitor().unbind_symbol(symbol);
+    }
+
+    fn visit_qual_identifier(
+        &mut self,
+        qual_identifier: QualIdentifier,
+    ) -> Result<Term, Self::Error> {
+        if let QualIdentifier::Simple {
+            identifier: Identifier::Simple { symbol },
+        } = &qual_identifier
+        {
+            if !self.is_bound(symbol) {
+                if let Some(term) = self.substitution.get(&symbol.0) {
+                    return Ok(term.clone());
+                }
+            }
+        }
+        let value = self.visitor().visit_qual_identifier(qual_identifier)?;
+        self.process_term(value)
+    }
+}
+
+/// A [`Rewriter`] implementation that collects the free symbols of the visited terms,
+/// that is, the symbols of the uninterpreted constants and functions that are not bound by
+/// a quantifier, a `let`, a `match`, or a function definition. The symbols of the SMT-LIB
+/// theories (such as `and`, `false`, or `select`), bit-vector operations, and indexed
+/// identifiers (such as `(_ extract 0 0)`) are not collected. Sorts and attributes are
+/// ignored.
+#[derive(Debug, Default)]
+pub struct FreeSymbolCollector {
+    /// The underlying syntax visitor.
+    visitor: SyntaxBuilder,
+    /// Free symbols found so far.
+    symbols: BTreeSet<String>,
+    /// Number of free occurrences of each symbol.
+    occurrences: BTreeMap<String, usize>,
+    /// Variables introduced but not bound yet.
+    fresh_variables: Vec<String>,
+    /// Currently bound variables, innermost last.
+    bound_variables: Vec<String>,
+}
+
+impl FreeSymbolCollector {
+    pub fn symbols(&self) -> &BTreeSet<String> {
+        &self.symbols
+    }
+
+    pub fn occurrences(&self) -> &BTreeMap<String, usize> {
+        &self.occurrences
+    }
+
+    fn collect(&mut self, qual_identifier: &QualIdentifier) {
+        let symbol = match qual_identifier {
+            QualIdentifier::Simple { identifier } | QualIdentifier::Sorted { identifier, .. } => {
+                match identifier {
+                    Identifier::Simple { symbol } | Identifier::Indexed { symbol, .. } => symbol,
+                }
+            }
+        };
+        if !self.bound_variables.contains(&symbol.0) && !is_theory_symbol(qual_identifier) {
+            self.symbols.insert(symbol.0.clone());
+            *self.occurrences.entry(symbol.0.clone()).or_default() += 1;
+        }
+    }
+}
+
+impl Rewriter for FreeSymbolCollector {
+    type V = SyntaxBuilder;
+    type Error = ConcreteError;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn visit_fresh_symbol(
+        &mut self,
+        value: String,
+        kind: SymbolKind,
+    ) -> Result<Symbol, Self::Error> {
+        if kind == SymbolKind::Variable {
+            self.fresh_variables.push(value.clone());
+        }
+        let value = self.visitor().visit_fresh_symbol(value, kind)?;
+        self.process_symbol(value)
+    }
+
+    fn bind_symbol(&mut self, symbol: &Symbol) {
+        if let Some(index) = self
+            .fresh_variables
+            .iter()
+            .rposition(|name| *name == symbol.0)
+        {
+            let variable = self.fresh_variables.remove(index);
+            self.bound_variables.push(variable);
+        }
+        self.visitor().bind_symbol(symbol);
+    }
+
+    fn unbind_symbol(&mut self, symbol: &Symbol) {
+        if let Some(index) = self
+            .bound_variables
+            .iter()
+            .rposition(|name| *name == symbol.0)
+        {
+            self.bound_variables.remove(index);
+        }
+        self.visitor().unbind_symbol(symbol);
+    }
+
+    fn visit_simple_identifier(
+        &mut self,
+        identifier: Identifier<Symbol>,
+    ) -> Result<QualIdentifier, Self::Error> {
+        let value = self.visitor().visit_simple_identifier(identifier)?;
+        self.collect(&value);
+        self.process_qual_identifier(value)
+    }
+
+    fn visit_sorted_identifier(
+        &mut self,
+        identifier: Identifier<Symbol>,
+        sort: Sort,
+    ) -> Result<QualIdentifier, Self::Error> {
+        let value = self.visitor().visit_sorted_identifier(identifier, sort)?;
+        self.collect(&value);
+        self.process_qual_identifier(value)
+    }
+}
+
+// Whether an identifier is interpreted by a theory. Indexed identifiers are reserved to
+// theories in SMT-LIB.
+fn is_theory_symbol(qual_identifier: &QualIdentifier) -> bool {
+    let identifier = match qual_identifier {
+        QualIdentifier::Simple { identifier } | QualIdentifier::Sorted { identifier, .. } => {
+            identifier
+        }
+    };
+    match identifier {
+        Identifier::Simple { symbol } => {
+            BIT_VECTOR_SYMBOLS.contains(&symbol.0.as_str())
+                || SmtLibTheories
+                    .function_sort(identifier, &[], None)
+                    .is_some()
+        }
+        Identifier::Indexed { .. } => true,
+    }
+}
+
+// The non-indexed function symbols of the theory `FixedSizeBitVectors` and of the logic
+// `QF_BV`.
+const BIT_VECTOR_SYMBOLS: &[&str] = &[
+    "concat", "bvnot", "bvand", "bvor", "bvneg", "bvadd", "bvmul", "bvudiv", "bvurem", "bvshl",
+    "bvlshr", "bvult", "bvnand", "bvnor", "bvxor", "bvxnor", "bvcomp", "bvsub", "bvsdiv", "bvsrem",
+    "bvsmod", "bvashr", "bvule", "bvugt", "bvuge", "bvslt", "bvsle", "bvsgt", "bvsge",
+];
+
+/// Free symbols of a term. See [`FreeSymbolCollector`].
+pub fn free_symbols(term: &Term) -> BTreeSet<String> {
+    let mut collector = FreeSymbolCollector::default();
+    term.clone()
+        .accept(&mut collector)
+        .expect("Rebuilding a term does not fail");
+    collector.symbols
+}
+
+/// A [`Rewriter`] implementation that eliminates `let` bindings by substituting the bound
+/// terms in the body of each `let`.
+/// * With a sharing threshold `n`, the bindings of terms of more than `n` nodes that occur
+///   more than once in the body are kept, so that the result does not blow up.
+/// * The bindings of other `let`s are still eliminated, inside the kept bound terms too.
+#[derive(Debug, Default)]
+pub struct LetExpander {
+    /// The underlying syntax visitor.
+    visitor: SyntaxBuilder,
+    /// Size above which shared bound terms are kept.
+    sharing_threshold: Option<usize>,
+}
+
+impl LetExpander {
+    pub fn new(sharing_threshold: Option<usize>) -> Self {
+        Self {
+            visitor: SyntaxBuilder,
+            sharing_threshold,
+        }
+    }
+
+    /// Whether to keep a binding, given its term and the body of the `let`.
+    fn is_shared(&self, term: &Term, occurrences: usize) -> bool {
+        match self.sharing_threshold {
+            Some(threshold) => occurrences > 1 && TermMetrics::of(term).size > threshold,
+            None => false,
+        }
+    }
+}
+
+impl Rewriter for LetExpander {
+    type V = SyntaxBuilder;
+    type Error = ConcreteError;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn visit_let(
+        &mut self,
+        var_bindings: Vec<(Symbol, Term)>,
+        term: Term,
+    ) -> Result<Term, Self::Error> {
+        let mut collector = FreeSymbolCollector::default();
+        term.accept_ref(&mut collector)?;
+        let (mut kept, mut expanded): (Vec<_>, Vec<_>) =
+            var_bindings.into_iter().partition(|(symbol, value)| {
+                let occurrences = collector.occurrences().get(&symbol.0).copied();
+                self.is_shared(value, occurrences.unwrap_or(0))
+            });
+        // The kept bindings must not capture the symbols of the expanded terms.
+        if expanded
+            .iter()
+            .any(|(_, value)| kept.iter().any(|(s, _)| free_symbols(value).contains(&s.0)))
+        {
+            expanded.append(&mut kept);
+        }
+        let substitution = expanded
+            .into_iter()
+            .map(|(symbol, value)| (symbol.0, value))
+            .collect();
+        let term = SubstitutionRewriter::new(substitution).substitute(term)?;
+        if kept.is_empty() {
+            return Ok(term);
+        }
+        let value = self.visitor().visit_let(kept, term)?;
+        self.process_term(value)
+    }
+}
+
+#[test]
+fn test_let_expander() {
+    let term = crate::parse_term(
+        "(let ((x (f a b)) (y a)) (let ((a x)) (g a x y (exists ((x Int)) (p x)))))",
+    )
+    .unwrap();
+    assert_eq!(
+        term.clone()
+            .accept(&mut LetExpander::default())
+            .unwrap()
+            .to_string(),
+        "(g (f a b) (f a b) a (exists ((x!1 Int)) (p x!1)))"
+    );
+    assert_eq!(
+        term.accept(&mut LetExpander::new(Some(2)))
+            .unwrap()
+            .to_string(),
+        "(let ((x (f a b))) (g x x a (exists ((x!1 Int)) (p x!1))))"
+    );
+}
+
+#[test]
+fn test_substitution_rewriter() {
+    let input = r#"
+(assert (and (f x y) (forall ((y Int)) (g x y)) (let ((x y)) (g x z))))
+(assert (exists ((z Int) (z!1 Int)) (g x (h z z!1))))
+"#;
+    let substitution = vec![("x".to_string(), crate::parse_term("(+ y z)").unwrap())]
+        .into_iter()
+        .collect();
+    let mut rewriter = SubstitutionRewriter::new(substitution);
+    let results = crate::parse_assertions(input)
+        .into_iter()
+        .map(|term| rewriter.substitute(term).unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        results,
+        vec![
+            "(and (f (+ y z) y) (forall ((y!1 Int)) (g (+ y z) y!1)) (let ((x y)) (g x z)))",
+            "(exists ((z!2 Int) (z!1 Int)) (g (+ y z) (h z!2 z!1)))",
+        ]
+    );
+}
+
+#[test]
+fn test_free_symbols() {
+    let input = r#"
+(assert (and (f x) (forall ((y Int)) (! (g x y) :pattern ((g x y)))) (let ((z y)) (= z 0))))
+(assert (= ((as const (Array Int Bool)) false) ((_ extract 0 0) b)))
+(assert (bvult (bvadd u v) (concat w #b0)))
+"#;
+    let symbols = crate::parse_assertions(input)
+        .iter()
+        .map(free_symbols)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        symbols[0].iter().collect::<Vec<_>>(),
+        vec!["f", "g", "x", "y"]
+    );
+    assert_eq!(symbols[1].iter().collect::<Vec<_>>(), vec!["b"]);
+    assert_eq!(symbols[2].iter().collect::<Vec<_>>(), vec!["u", "v", "w"]);
+
+    // Symbols are free when used outside of the scope of a variable of the same name,
+    // including in the bound terms of `let`.
+    let term =
+        crate::parse_term("(let ((x x)) (forall ((y Int)) (and (let ((y x)) y) (p y))))").unwrap();
+    assert_eq!(
+        free_symbols(&term).into_iter().collect::<Vec<_>>(),
+        vec!["p", "x"]
+    );
+    let term = crate::parse_term(&format!("{}x{}", "(f ".repeat(100), ")".repeat(100))).unwrap();
+    assert_eq!(
+        free_symbols(&term).into_iter().collect::<Vec<_>>(),
+        vec!["f", "x"]
+    );
+}
+
 #[test]
 fn test_term_rewriter() {
     use crate::concrete::*;
@@ -1042,3 +1509,60 @@ fn test_term_rewriter() {
     let command3 = Command::Exit;
     assert_eq!(command2, command3);
 }
+
+#[test]
+fn test_should_descend() {
+    // Rename `x` into `y`, skipping the subterms without `x`.
+    #[derive(Default)]
+    struct Renamer {
+        visitor: SyntaxBuilder,
+        processed: Vec<String>,
+    }
+
+    impl Rewriter for Renamer {
+        type V = SyntaxBuilder;
+        type Error = ConcreteError;
+
+        fn visitor(&mut self) -> &mut Self::V {
+            &mut self.visitor
+        }
+
+        fn should_descend(&mut self, term: &Term) -> bool {
+            free_symbols(term).contains("x")
+        }
+
+        fn process_symbol(&mut self, value: Symbol) -> Result<Symbol, Self::Error> {
+            match value.0.as_str() {
+                "x" => Ok(Symbol("y".to_string())),
+                _ => Ok(value),
+            }
+        }
+
+        fn process_term(&mut self, value: Term) -> Result<Term, Self::Error> {
+            self.processed.push(value.to_string());
+            Ok(value)
+        }
+    }
+
+    let term = crate::parse_term("(and (f x) (g (h z)))").unwrap();
+    let mut renamer = Renamer::default();
+    let term = term.accept(&mut renamer).unwrap();
+    assert_eq!(term.to_string(), "(and (f y) (g (h z)))");
+    assert_eq!(
+        renamer.processed,
+        vec!["y", "(f y)", "(and (f y) (g (h z)))"]
+    );
+
+    // Visiting by reference makes the same calls as visiting a clone.
+    let term = crate::parse_term(
+        "(let ((a (f x))) (forall ((b Int)) (! (match a ((nil (g z)) ((cons h t) (h x)))) :pattern ((f x)))))",
+    )
+    .unwrap();
+    let mut owned = Renamer::default();
+    let mut borrowed = Renamer::default();
+    assert_eq!(
+        term.accept_ref(&mut borrowed).unwrap(),
+        term.clone().accept(&mut owned).unwrap()
+    );
+    assert_eq!(borrowed.processed, owned.processed);
+}
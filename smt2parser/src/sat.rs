@@ -0,0 +1,213 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Purely boolean problems checked by SAT solvers reading DIMACS CNF on their standard
+//! input, such as kissat, CaDiCaL, or MiniSat, run as subprocesses.
+
+use crate::{
+    model::Model,
+    normal_forms::Literal,
+    solver::{SatResult, SolverError},
+    vmt::SMTProblem,
+};
+use std::{
+    io::{Read, Write},
+    path::PathBuf,
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+/// Configuration of a SAT solver following the conventions of the SAT competitions: the
+/// exit code is 10 for satisfiable problems and 20 for unsatisfiable ones, and the output
+/// has a status line `s ...` and values lines `v ...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SatSolver {
+    path: PathBuf,
+    args: Vec<String>,
+    timeout: Option<Duration>,
+}
+
+impl SatSolver {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            args: Vec::new(),
+            timeout: None,
+        }
+    }
+
+    /// Pass command-line arguments to the solver.
+    pub fn with_args(mut self, args: Vec<String>) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Stop the solver after some time, failing with [`SolverError::Timeout`].
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Check a CNF in the DIMACS format. Return the answer of the solver and, for a
+    /// satisfiable CNF, the literals of the assignment that it printed, if any.
+    pub fn check_dimacs(&self, dimacs: &str) -> Result<(SatResult, Vec<Literal>), SolverError> {
+        log::debug!("Starting {} {}", self.path.display(), self.args.join(" "));
+        let mut child = std::process::Command::new(&self.path)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|error| match error.kind() {
+                std::io::ErrorKind::NotFound => {
+                    SolverError::NotFound(self.path.display().to_string())
+                }
+                _ => SolverError::Io(error),
+            })?;
+        // Write and read in the background, so that the pipes do not fill up.
+        let mut stdin = child.stdin.take().expect("piped standard input");
+        let dimacs = dimacs.to_string();
+        std::thread::spawn(move || stdin.write_all(dimacs.as_bytes()));
+        let mut stdout = child.stdout.take().expect("piped standard output");
+        let output = std::thread::spawn(move || {
+            let mut output = String::new();
+            stdout.read_to_string(&mut output).map(|_| output)
+        });
+        let start = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if let Some(timeout) = self.timeout {
+                if start.elapsed() >= timeout {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(SolverError::Timeout(timeout));
+                }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        let output = output.join().expect("reader thread")?;
+        let mut answer = match status.code() {
+            Some(10) => Some(SatResult::Sat),
+            Some(20) => Some(SatResult::Unsat),
+            _ => None,
+        };
+        let mut assignment = Vec::new();
+        for line in output.lines() {
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                ["s", "SATISFIABLE"] => answer = Some(SatResult::Sat),
+                ["s", "UNSATISFIABLE"] => answer = Some(SatResult::Unsat),
+                ["s", "UNKNOWN"] => answer = answer.or(Some(SatResult::Unknown)),
+                ["v", ref values @ ..] => {
+                    for value in values {
+                        match value.parse::<Literal>() {
+                            Ok(0) => (),
+                            Ok(literal) => assignment.push(literal),
+                            Err(_) => return Err(SolverError::Solver(line.to_string())),
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        match answer {
+            Some(answer) => Ok((answer, assignment)),
+            None => Err(SolverError::Solver(format!(
+                "no answer ({}): {}",
+                status,
+                output.trim()
+            ))),
+        }
+    }
+
+    /// Check an unrolled problem, which must be purely boolean, after its Tseitin
+    /// encoding. For a satisfiable problem, also return the values of its boolean
+    /// constants printed by the solver.
+    pub fn check_problem(
+        &self,
+        problem: &SMTProblem,
+    ) -> Result<(SatResult, Option<Model>), SolverError> {
+        let encoder = problem.to_cnf();
+        let dimacs = encoder.to_dimacs().ok_or(SolverError::NotBoolean)?;
+        let (answer, assignment) = self.check_dimacs(&dimacs)?;
+        if answer != SatResult::Sat {
+            return Ok((answer, None));
+        }
+        let mut definitions = Vec::new();
+        for literal in assignment {
+            if encoder.is_atom(literal) {
+                // Atoms of purely boolean problems are constants.
+                let constant = encoder.variable(literal);
+                definitions.extend(crate::smt2!(
+                    "(define-fun {} () Bool {})",
+                    constant,
+                    literal > 0
+                ));
+            }
+        }
+        let model = Model::from_commands(&definitions)
+            .map_err(|error| SolverError::Response(error.into()))?;
+        Ok((answer, Some(model)))
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_sat_solver() {
+    // Fake SAT solvers running a shell script after reading their input.
+    let solver = |name: &str, script: &str| {
+        let path =
+            std::env::temp_dir().join(format!("smt2parser-sat-{}-{}", std::process::id(), name));
+        std::fs::write(&path, format!("#!/bin/sh\ncat > /dev/null\n{}\n", script)).unwrap();
+        std::process::Command::new("chmod")
+            .arg("+x")
+            .arg(&path)
+            .status()
+            .unwrap();
+        SatSolver::new(path)
+    };
+    let commands =
+        crate::parse_commands("(declare-fun a () Bool) (declare-fun b () Bool)").unwrap();
+    let problem = SMTProblem::from_parts(
+        Vec::new(),
+        commands,
+        Vec::new(),
+        Vec::new(),
+        crate::smt2!(term: "(=> a b)"),
+    );
+
+    let sat = solver("sat", "printf 's SATISFIABLE\\nv -1 2 0\\n'; exit 10");
+    let (answer, model) = sat.check_problem(&problem).unwrap();
+    assert_eq!(answer, SatResult::Sat);
+    let model = model.unwrap();
+    assert_eq!(model.get("a"), Some(&crate::model::Value::Bool(false)));
+    assert_eq!(model.get("b"), Some(&crate::model::Value::Bool(true)));
+
+    let unsat = solver("unsat", "echo 's UNSATISFIABLE'; exit 20");
+    assert_eq!(
+        unsat.check_problem(&problem).unwrap(),
+        (SatResult::Unsat, None)
+    );
+
+    let slow = solver("slow", "sleep 5").with_timeout(Some(Duration::from_millis(100)));
+    assert!(matches!(
+        slow.check_dimacs("p cnf 0 0\n"),
+        Err(SolverError::Timeout(_))
+    ));
+
+    let problem = SMTProblem::from_parts(
+        Vec::new(),
+        crate::parse_commands("(declare-fun x () Int)").unwrap(),
+        Vec::new(),
+        Vec::new(),
+        crate::smt2!(term: "(> x 0)"),
+    );
+    assert!(matches!(
+        sat.check_problem(&problem),
+        Err(SolverError::NotBoolean)
+    ));
+
+    for solver in [sat, unsat, slow] {
+        std::fs::remove_file(solver.path).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Stable JSON representations of VMT models and unrolled problems, for web viewers and
+//! tools that are not written in Rust.
+//!
+//! Unlike the serde representations of the syntax, which follow the Rust definitions,
+//! terms, sorts and commands are given as SMT-LIB strings, and field names only change
+//! with [`SCHEMA_VERSION`]. Every document carries its version in a `version` field.
+
+use serde::{Deserialize, Serialize};
+
+/// Version of the JSON documents of this module and of the documents of `z3tracer`.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// A VMT model:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "sorts": ["(declare-sort S 0)"],
+///   "functions": ["(declare-fun f (S) Int)"],
+///   "state_variables": [{ "name": "x", "next": "x_next", "sort": "Int" }],
+///   "actions": [{ "name": "a", "sort": "Bool" }],
+///   "init": "(= x 0)",
+///   "trans": "(= x_next (+ x 1))",
+///   "property": "(>= x 0)"
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VMTModelJson {
+    pub version: u32,
+    /// Sort declarations and definitions.
+    pub sorts: Vec<String>,
+    /// Declarations of uninterpreted functions.
+    pub functions: Vec<String>,
+    pub state_variables: Vec<StateVariableJson>,
+    pub actions: Vec<ActionJson>,
+    /// Initial condition, over the current state variables.
+    pub init: String,
+    /// Transition relation, over the current and next state variables and the actions.
+    pub trans: String,
+    /// Property that should hold in all reachable states.
+    pub property: String,
+}
+
+/// A state variable of a VMT model, with the name of its value in the next state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateVariableJson {
+    pub name: String,
+    pub next: String,
+    pub sort: String,
+}
+
+/// An action of a VMT model, i.e. an input of the transition relation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionJson {
+    pub name: String,
+    pub sort: String,
+}
+
+/// A VMT model unrolled for bounded model checking:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "sorts": [],
+///   "functions": [],
+///   "definitions": ["(declare-fun x@0 () Int)", "(declare-fun x@1 () Int)"],
+///   "assertions": ["(= x@0 0)", "(= x@1 (+ x@0 1))"],
+///   "property": "(>= x@1 0)"
+/// }
+/// ```
+///
+/// The problem asserts the negation of the property, if any.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SMTProblemJson {
+    pub version: u32,
+    /// Sort declarations and definitions.
+    pub sorts: Vec<String>,
+    /// Declarations of uninterpreted functions.
+    pub functions: Vec<String>,
+    /// Declarations of the state variables and actions at each step.
+    pub definitions: Vec<String>,
+    /// Initial condition and transition relation at each step.
+    pub assertions: Vec<String>,
+    /// Property at the last step.
+    pub property: Option<String>,
+}
+
+#[test]
+fn test_vmt_json() {
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun x () Int)
+(declare-fun x_next () Int)
+(define-fun .x () Int (! x :next x_next))
+(declare-fun a () Bool)
+(define-fun .a () Bool (! a :action 0))
+(define-fun init () Bool (! (= x 0) :init true))
+(define-fun trans () Bool (! (= x_next (ite a (+ x 1) x)) :trans true))
+(define-fun property () Bool (! (>= x 0) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let vmt = crate::vmt::VMTModel::checked_from(commands).unwrap();
+    let value = serde_json::to_value(VMTModelJson::from(&vmt)).unwrap();
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "version": 1,
+            "sorts": [],
+            "functions": [],
+            "state_variables": [{ "name": "x", "next": "x_next", "sort": "Int" }],
+            "actions": [{ "name": "a", "sort": "Bool" }],
+            "init": "(= x 0)",
+            "trans": "(= x_next (ite a (+ x 1) x))",
+            "property": "(>= x 0)",
+        })
+    );
+
+    let problem = SMTProblemJson::from(&vmt.unroll(1));
+    assert_eq!(problem.version, SCHEMA_VERSION);
+    assert_eq!(problem.assertions.len(), 2);
+    assert!(problem.property.is_some());
+    let json = serde_json::to_string(&problem).unwrap();
+    assert_eq!(
+        serde_json::from_str::<SMTProblemJson>(&json).unwrap(),
+        problem
+    );
+}
@@ -0,0 +1,193 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Simplification of terms by constant folding.
+
+use crate::{
+    concrete::{Constant, Error, QualIdentifier, Symbol, SyntaxBuilder, Term},
+    rewriter::Rewriter,
+    visitors::Identifier,
+    Numeral,
+};
+
+/// Rewriter folding constants bottom-up:
+/// * ground arithmetic on numerals (`+`, `*`, `-` with a non-negative result, and
+///   comparisons),
+/// * `and`, `or`, `=>`, and `not` applied to `true` or `false`, and double negations,
+/// * `ite` with a constant condition or equal branches,
+/// * equalities between syntactically equal terms or between distinct numerals.
+#[derive(Debug, Default)]
+pub struct Simplifier(SyntaxBuilder);
+
+impl Simplifier {
+    fn simplify(symbol: &str, mut arguments: Vec<Term>) -> Result<Term, Vec<Term>> {
+        match symbol {
+            "not" if arguments.len() == 1 => match arguments.pop().unwrap() {
+                Term::Application {
+                    qual_identifier,
+                    mut arguments,
+                } if symbol_of(&qual_identifier) == Some("not") && arguments.len() == 1 => {
+                    Ok(arguments.pop().unwrap())
+                }
+                argument => match as_bool(&argument) {
+                    Some(value) => Ok(bool_term(!value)),
+                    None => Err(vec![argument]),
+                },
+            },
+            "and" | "or" => {
+                // The neutral element is dropped and the absorbing element absorbs.
+                let absorbing = symbol == "or";
+                if arguments.iter().any(|a| as_bool(a) == Some(absorbing)) {
+                    return Ok(bool_term(absorbing));
+                }
+                arguments.retain(|a| as_bool(a) != Some(!absorbing));
+                match arguments.len() {
+                    0 => Ok(bool_term(!absorbing)),
+                    1 => Ok(arguments.pop().unwrap()),
+                    _ => Err(arguments),
+                }
+            }
+            "=>" if arguments.len() == 2 => {
+                match (as_bool(&arguments[0]), as_bool(&arguments[1])) {
+                    (Some(false), _) | (_, Some(true)) => Ok(bool_term(true)),
+                    (Some(true), _) => Ok(arguments.pop().unwrap()),
+                    _ => Err(arguments),
+                }
+            }
+            "ite" if arguments.len() == 3 => {
+                let else_branch = arguments.pop().unwrap();
+                let then_branch = arguments.pop().unwrap();
+                match as_bool(&arguments[0]) {
+                    Some(true) => Ok(then_branch),
+                    Some(false) => Ok(else_branch),
+                    None if then_branch == else_branch => Ok(then_branch),
+                    None => Err(vec![arguments.pop().unwrap(), then_branch, else_branch]),
+                }
+            }
+            "=" if arguments.len() == 2 => {
+                if arguments[0] == arguments[1] {
+                    return Ok(bool_term(true));
+                }
+                match (as_numeral(&arguments[0]), as_numeral(&arguments[1])) {
+                    (Some(_), Some(_)) => Ok(bool_term(false)),
+                    _ => Err(arguments),
+                }
+            }
+            "+" | "*" | "-" | "<" | "<=" | ">" | ">=" => {
+                let numerals = match arguments.iter().map(as_numeral).collect::<Option<Vec<_>>>() {
+                    Some(numerals) if !numerals.is_empty() => numerals,
+                    _ => return Err(arguments),
+                };
+                let holds = |f: fn(&Numeral, &Numeral) -> bool| {
+                    bool_term(numerals.windows(2).all(|pair| f(&pair[0], &pair[1])))
+                };
+                match symbol {
+                    "+" => Ok(numeral_term(numerals.iter().sum())),
+                    "*" => Ok(numeral_term(numerals.iter().product())),
+                    "-" if numerals.len() == 2 && numerals[0] >= numerals[1] => {
+                        Ok(numeral_term(&numerals[0] - &numerals[1]))
+                    }
+                    "<" if numerals.len() >= 2 => Ok(holds(|a, b| a < b)),
+                    "<=" if numerals.len() >= 2 => Ok(holds(|a, b| a <= b)),
+                    ">" if numerals.len() >= 2 => Ok(holds(|a, b| a > b)),
+                    ">=" if numerals.len() >= 2 => Ok(holds(|a, b| a >= b)),
+                    _ => Err(arguments),
+                }
+            }
+            _ => Err(arguments),
+        }
+    }
+}
+
+impl Rewriter for Simplifier {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.0
+    }
+
+    fn process_term(&mut self, value: Term) -> Result<Term, Self::Error> {
+        match value {
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } => match symbol_of(&qual_identifier) {
+                Some(symbol) => match Self::simplify(symbol, arguments) {
+                    Ok(term) => Ok(term),
+                    Err(arguments) => Ok(Term::Application {
+                        qual_identifier,
+                        arguments,
+                    }),
+                },
+                None => Ok(Term::Application {
+                    qual_identifier,
+                    arguments,
+                }),
+            },
+            value => Ok(value),
+        }
+    }
+}
+
+pub(crate) fn symbol_of(qual_identifier: &QualIdentifier) -> Option<&str> {
+    match qual_identifier {
+        QualIdentifier::Simple {
+            identifier: Identifier::Simple { symbol },
+        } => Some(&symbol.0),
+        _ => None,
+    }
+}
+
+pub(crate) fn as_bool(term: &Term) -> Option<bool> {
+    match term {
+        Term::QualIdentifier(qual_identifier) => match symbol_of(qual_identifier) {
+            Some("true") => Some(true),
+            Some("false") => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn as_numeral(term: &Term) -> Option<Numeral> {
+    match term {
+        Term::Constant(Constant::Numeral(value)) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+pub(crate) fn bool_term(value: bool) -> Term {
+    Term::QualIdentifier(QualIdentifier::Simple {
+        identifier: Identifier::Simple {
+            symbol: Symbol(value.to_string()),
+        },
+    })
+}
+
+fn numeral_term(value: Numeral) -> Term {
+    Term::Constant(Constant::Numeral(value))
+}
+
+#[test]
+fn test_simplifier() {
+    let input = r#"
+(assert (and (> x (+ 1 2)) true (not (not (= y y)))))
+(assert (or (ite (< 1 2 3) p q) (=> false r) s))
+(assert (ite c (- 2 3) (- 2 3)))
+(assert (and (= 1 2) p))
+"#;
+    let commands = crate::parse_assertions(input)
+        .into_iter()
+        .map(|term| term.accept(&mut Simplifier::default()).unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(commands, vec!["(> x 3)", "true", "(- 2 3)", "false"]);
+
+    // Simplifications apply bottom-up through nested terms.
+    let term =
+        crate::parse_term(&format!("{}p{}", "(not (not ".repeat(50), "))".repeat(50))).unwrap();
+    assert_eq!(
+        term.accept(&mut Simplifier::default()).unwrap().to_string(),
+        "p"
+    );
+}
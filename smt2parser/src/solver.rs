@@ -0,0 +1,628 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Interactive sessions with SMT solvers, run as subprocesses that read SMT-LIB commands
+//! on their standard input.
+
+use crate::{
+    concrete::{Command, Constant, Keyword, Symbol, Term},
+    model::Model,
+    responses::{Response, ResponseError},
+    visitors::AttributeValue,
+    Numeral,
+};
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// Error reported by a solver session.
+#[derive(Error, Debug)]
+pub enum SolverError {
+    #[error("solver not found: {0}")]
+    NotFound(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Response(#[from] ResponseError),
+    /// A command could not be rewritten before being sent.
+    #[error(transparent)]
+    Syntax(#[from] crate::concrete::Error),
+    /// The message of an `(error "...")` response.
+    #[error("solver error: {0}")]
+    Solver(String),
+    #[error("unexpected response to `{command}`: {response:?}")]
+    Unexpected { command: String, response: Response },
+    #[error("no response after {0:?}")]
+    Timeout(Duration),
+    #[error("the solver was cancelled")]
+    Cancelled,
+    /// A problem given to a SAT solver is not purely boolean.
+    #[error("the problem is not purely boolean")]
+    NotBoolean,
+}
+
+/// Answer of a solver to `check-sat`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SatResult {
+    Sat,
+    Unsat,
+    Unknown,
+}
+
+/// An incremental SMT solver. Implementations only execute commands: the other methods
+/// are derived from [`SmtSolver::execute`].
+pub trait SmtSolver {
+    /// Execute a command and return the response of the solver. Commands without a
+    /// response return [`Response::Success`].
+    fn execute(&mut self, command: &Command) -> Result<Response, SolverError>;
+
+    /// Execute a command that does not return a value, such as a declaration.
+    fn run(&mut self, command: &Command) -> Result<(), SolverError> {
+        match self.execute(command)? {
+            Response::Success => Ok(()),
+            response => Err(unexpected(command, response)),
+        }
+    }
+
+    fn assert(&mut self, term: &Term) -> Result<(), SolverError> {
+        self.run(&Command::Assert { term: term.clone() })
+    }
+
+    fn check_sat(&mut self) -> Result<SatResult, SolverError> {
+        let command = Command::CheckSat;
+        let response = self.execute(&command)?;
+        sat_result(&command, response)
+    }
+
+    /// Check satisfiability assuming Boolean constants, or their negations when paired
+    /// with `false`.
+    fn check_sat_assuming(
+        &mut self,
+        literals: &[(Symbol, bool)],
+    ) -> Result<SatResult, SolverError> {
+        let command = Command::CheckSatAssuming {
+            literals: literals.to_vec(),
+        };
+        let response = self.execute(&command)?;
+        sat_result(&command, response)
+    }
+
+    fn get_model(&mut self) -> Result<Model, SolverError> {
+        let command = Command::GetModel;
+        match self.execute(&command)? {
+            Response::Model(model) => Ok(model),
+            response => Err(unexpected(&command, response)),
+        }
+    }
+
+    /// The names of the assertions of an unsat core, given with `:named` attributes.
+    fn get_unsat_core(&mut self) -> Result<Vec<Symbol>, SolverError> {
+        let command = Command::GetUnsatCore;
+        match self.execute(&command)? {
+            Response::UnsatCore(names) => Ok(names),
+            response => Err(unexpected(&command, response)),
+        }
+    }
+
+    fn push(&mut self, levels: u32) -> Result<(), SolverError> {
+        self.run(&Command::Push {
+            level: Numeral::from(levels),
+        })
+    }
+
+    fn pop(&mut self, levels: u32) -> Result<(), SolverError> {
+        self.run(&Command::Pop {
+            level: Numeral::from(levels),
+        })
+    }
+}
+
+fn unexpected(command: &Command, response: Response) -> SolverError {
+    match response {
+        Response::Error(message) => SolverError::Solver(message),
+        response => SolverError::Unexpected {
+            command: command.to_string(),
+            response,
+        },
+    }
+}
+
+fn sat_result(command: &Command, response: Response) -> Result<SatResult, SolverError> {
+    match response {
+        Response::Sat => Ok(SatResult::Sat),
+        Response::Unsat => Ok(SatResult::Unsat),
+        Response::Unknown => Ok(SatResult::Unknown),
+        response => Err(unexpected(command, response)),
+    }
+}
+
+/// The solvers with a subprocess backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SolverKind {
+    Z3,
+    Cvc5,
+    Yices,
+}
+
+impl SolverKind {
+    /// Name of the executable looked up in `PATH`.
+    pub fn program(self) -> &'static str {
+        match self {
+            SolverKind::Z3 => "z3",
+            SolverKind::Cvc5 => "cvc5",
+            SolverKind::Yices => "yices-smt2",
+        }
+    }
+
+    /// Environment variable overriding the path of the executable.
+    pub fn env_var(self) -> &'static str {
+        match self {
+            SolverKind::Z3 => "SMT2_Z3",
+            SolverKind::Cvc5 => "SMT2_CVC5",
+            SolverKind::Yices => "SMT2_YICES",
+        }
+    }
+
+    /// Command-line arguments to read commands interactively on the standard input.
+    fn args(self) -> &'static [&'static str] {
+        match self {
+            SolverKind::Z3 => &["-in", "-smt2"],
+            SolverKind::Cvc5 => &["--lang=smt2", "--incremental"],
+            SolverKind::Yices => &["--incremental"],
+        }
+    }
+
+    /// Path of the executable given by the environment variable of the solver, or found in
+    /// `PATH`.
+    pub fn locate(self) -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(self.env_var()) {
+            return Some(PathBuf::from(path));
+        }
+        std::env::split_paths(&std::env::var_os("PATH")?)
+            .map(|directory| directory.join(self.program()))
+            .find(|path| path.is_file())
+    }
+}
+
+impl std::str::FromStr for SolverKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "z3" => Ok(SolverKind::Z3),
+            "cvc5" => Ok(SolverKind::Cvc5),
+            "yices" | "yices2" | "yices-smt2" => Ok(SolverKind::Yices),
+            _ => Err(format!("unknown solver: {}", s)),
+        }
+    }
+}
+
+/// Configuration of a subprocess solver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolverConfig {
+    pub kind: SolverKind,
+    /// Path of the executable. By default, see [`SolverKind::locate`].
+    pub path: Option<PathBuf>,
+    /// Options set with `set-option` when the solver starts, as keywords without colons
+    /// and values in SMT-LIB syntax, e.g. `("random-seed", "1")`.
+    pub options: Vec<(String, String)>,
+}
+
+impl SolverConfig {
+    /// Default configuration of a solver, producing models and unsat cores.
+    pub fn new(kind: SolverKind) -> Self {
+        Self {
+            kind,
+            path: None,
+            options: vec![
+                ("produce-models".to_string(), "true".to_string()),
+                ("produce-unsat-cores".to_string(), "true".to_string()),
+            ],
+        }
+    }
+}
+
+/// A solver run as a subprocess. Responses are synchronized with commands by
+/// `:print-success`, and read by a background thread, so that waiting for a response can
+/// be bounded by a timeout or cancelled from another thread. The solver is stopped when
+/// the session is dropped.
+#[derive(Debug)]
+pub struct ProcessSolver {
+    child: Arc<Mutex<Child>>,
+    stdin: ChildStdin,
+    responses: Receiver<Result<String, SolverError>>,
+    cancelled: Arc<AtomicBool>,
+    timeout: Option<Duration>,
+}
+
+/// Handle stopping a [`ProcessSolver`] from another thread. The pending command then fails
+/// with [`SolverError::Cancelled`], and so do later ones.
+#[derive(Debug, Clone)]
+pub struct CancelHandle {
+    child: Arc<Mutex<Child>>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        let mut child = self.child.lock().unwrap_or_else(|error| error.into_inner());
+        let _ = child.kill();
+    }
+}
+
+impl ProcessSolver {
+    /// Start a solver and set its options.
+    pub fn spawn(config: &SolverConfig) -> Result<Self, SolverError> {
+        let path = match &config.path {
+            Some(path) => path.clone(),
+            None => config
+                .kind
+                .locate()
+                .ok_or_else(|| SolverError::NotFound(config.kind.program().to_string()))?,
+        };
+        let mut solver = Self::spawn_program(&path, config.kind.args())?;
+        solver.set_option("print-success", "true")?;
+        for (key, value) in &config.options {
+            solver.set_option(key, value)?;
+        }
+        Ok(solver)
+    }
+
+    pub(crate) fn spawn_program(path: &Path, args: &[&str]) -> Result<Self, SolverError> {
+        log::debug!("Starting {} {}", path.display(), args.join(" "));
+        let mut child = std::process::Command::new(path)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|error| match error.kind() {
+                std::io::ErrorKind::NotFound => SolverError::NotFound(path.display().to_string()),
+                _ => SolverError::Io(error),
+            })?;
+        let stdin = child.stdin.take().expect("piped standard input");
+        let mut stdout = BufReader::new(child.stdout.take().expect("piped standard output"));
+        let (sender, responses) = mpsc::channel();
+        std::thread::spawn(move || loop {
+            let response = read_response(&mut stdout);
+            let stop = response.is_err();
+            if sender.send(response).is_err() || stop {
+                break;
+            }
+        });
+        Ok(Self {
+            child: Arc::new(Mutex::new(child)),
+            stdin,
+            responses,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            timeout: None,
+        })
+    }
+
+    /// Bound the time to wait for each response, e.g. to `check-sat`. When the time is
+    /// up, the solver is stopped and the command fails with [`SolverError::Timeout`].
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// A handle to cancel the session from another thread.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        CancelHandle {
+            child: self.child.clone(),
+            cancelled: self.cancelled.clone(),
+        }
+    }
+
+    /// Set an option, given as a keyword without colon and a value in SMT-LIB syntax.
+    pub fn set_option(&mut self, key: &str, value: &str) -> Result<(), SolverError> {
+        let value = match value {
+            "true" | "false" => AttributeValue::Symbol(Symbol(value.to_string())),
+            _ => match value.parse::<Numeral>() {
+                Ok(n) => AttributeValue::Constant(Constant::Numeral(n)),
+                Err(_) => AttributeValue::Symbol(Symbol(value.to_string())),
+            },
+        };
+        self.run(&Command::SetOption {
+            keyword: Keyword(key.to_string()),
+            value,
+        })
+    }
+
+    /// Execute a command and return the response as written by the solver.
+    pub fn execute_raw(&mut self, command: &Command) -> Result<String, SolverError> {
+        if self.cancelled.load(Ordering::SeqCst) {
+            return Err(SolverError::Cancelled);
+        }
+        log::trace!("> {}", command);
+        if let Err(error) = writeln!(self.stdin, "{}", command).and_then(|()| self.stdin.flush()) {
+            if self.cancelled.load(Ordering::SeqCst) {
+                return Err(SolverError::Cancelled);
+            }
+            return Err(error.into());
+        }
+        let response = self.wait_response()?;
+        log::trace!("< {}", response.trim_end());
+        Ok(response)
+    }
+
+    fn wait_response(&mut self) -> Result<String, SolverError> {
+        let response = match self.timeout {
+            Some(timeout) => match self.responses.recv_timeout(timeout) {
+                Err(RecvTimeoutError::Timeout) => {
+                    self.cancel_handle().cancel();
+                    return Err(SolverError::Timeout(timeout));
+                }
+                response => response.ok(),
+            },
+            None => self.responses.recv().ok(),
+        };
+        match response {
+            _ if self.cancelled.load(Ordering::SeqCst) => Err(SolverError::Cancelled),
+            Some(response) => response,
+            None => Err(SolverError::Io(std::io::ErrorKind::UnexpectedEof.into())),
+        }
+    }
+}
+
+impl SmtSolver for ProcessSolver {
+    fn execute(&mut self, command: &Command) -> Result<Response, SolverError> {
+        let response = self.execute_raw(command)?;
+        Ok(Response::parse(&response, command)?)
+    }
+}
+
+impl Drop for ProcessSolver {
+    fn drop(&mut self) {
+        let _ = writeln!(self.stdin, "(exit)").and_then(|()| self.stdin.flush());
+        let mut child = self.child.lock().unwrap_or_else(|error| error.into_inner());
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Run the same commands with several solvers in parallel, then `check-sat`, and return
+/// the index of the configuration of the first solver answering `sat` or `unsat` with its
+/// answer. The other solvers are cancelled. When no solver is conclusive, return
+/// [`SatResult::Unknown`] if some solver answered, and the last error otherwise. With a
+/// time budget, all the solvers are cancelled when it is exhausted. Solvers that fail to
+/// start, e.g. because they are not installed, are skipped, unless none starts.
+pub fn race(
+    configs: &[SolverConfig],
+    commands: &[Command],
+    budget: Option<Duration>,
+) -> Result<(usize, SatResult), SolverError> {
+    let mut runs = race_runs(configs, commands, budget)
+        .into_iter()
+        .enumerate()
+        .collect::<Vec<_>>();
+    runs.sort_by_key(|(_, (_, elapsed))| *elapsed);
+    let answered = |conclusive: bool| {
+        runs.iter().find_map(|(index, (answer, _))| match answer {
+            Ok(SatResult::Unknown) if !conclusive => Some((*index, SatResult::Unknown)),
+            Ok(answer @ (SatResult::Sat | SatResult::Unsat)) if conclusive => {
+                Some((*index, *answer))
+            }
+            _ => None,
+        })
+    };
+    if let Some(result) = answered(true).or_else(|| answered(false)) {
+        return Ok(result);
+    }
+    // Solvers failing to start fail first.
+    match runs.pop() {
+        Some((_, (answer, _))) => Err(answer.expect_err("no answer")),
+        None => Err(SolverError::NotFound("no solver".to_string())),
+    }
+}
+
+/// Same as [`race`], but return the outcome of every solver, in the order of the
+/// configurations, with the time from the start of the race to its answer or its failure.
+/// Solvers cancelled because the budget is exhausted fail with [`SolverError::Timeout`],
+/// and those cancelled because another solver concluded with [`SolverError::Cancelled`].
+pub fn race_runs(
+    configs: &[SolverConfig],
+    commands: &[Command],
+    budget: Option<Duration>,
+) -> Vec<(Result<SatResult, SolverError>, Duration)> {
+    let start = Instant::now();
+    let deadline = budget.map(|budget| start + budget);
+    let mut outcomes = Vec::new();
+    let mut solvers = Vec::new();
+    for (index, config) in configs.iter().enumerate() {
+        match ProcessSolver::spawn(config) {
+            Ok(solver) => solvers.push((index, solver)),
+            Err(error) => {
+                log::warn!("Skipping {}: {}", config.kind.program(), error);
+                outcomes.push((index, Err(error), start.elapsed()));
+            }
+        }
+    }
+    let handles = solvers
+        .iter()
+        .map(|(_, solver)| solver.cancel_handle())
+        .collect::<Vec<_>>();
+    let (sender, answers) = mpsc::channel();
+    std::thread::scope(|scope| {
+        for (index, mut solver) in solvers {
+            let sender = sender.clone();
+            scope.spawn(move || {
+                let answer = commands
+                    .iter()
+                    .try_for_each(|command| solver.run(command))
+                    .and_then(|()| solver.check_sat());
+                let _ = sender.send((index, answer, start.elapsed()));
+            });
+        }
+        drop(sender);
+        let mut cancelled = false;
+        loop {
+            let outcome = match deadline {
+                Some(deadline) if !cancelled => answers
+                    .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    .map_err(|error| error == RecvTimeoutError::Timeout),
+                _ => answers.recv().map_err(|_| false),
+            };
+            match outcome {
+                Ok(outcome) => {
+                    let conclusive = matches!(outcome.1, Ok(SatResult::Sat | SatResult::Unsat));
+                    outcomes.push(outcome);
+                    if conclusive && !cancelled {
+                        handles.iter().for_each(CancelHandle::cancel);
+                        cancelled = true;
+                    }
+                }
+                // The budget is exhausted.
+                Err(true) => {
+                    log::debug!("Race budget exhausted after {:?}", start.elapsed());
+                    handles.iter().for_each(CancelHandle::cancel);
+                    cancelled = true;
+                }
+                // All the solvers are done.
+                Err(false) => break,
+            }
+        }
+    });
+    outcomes.sort_by_key(|(index, _, _)| *index);
+    outcomes
+        .into_iter()
+        .map(|(_, answer, elapsed)| {
+            let answer = match (answer, budget) {
+                (Err(SolverError::Cancelled), Some(budget)) if elapsed >= budget => {
+                    Err(SolverError::Timeout(budget))
+                }
+                (answer, _) => answer,
+            };
+            (answer, elapsed)
+        })
+        .collect()
+}
+
+// Read lines until they form exactly one complete response.
+fn read_response<R: BufRead>(reader: &mut R) -> Result<String, SolverError> {
+    let mut response = String::new();
+    loop {
+        if reader.read_line(&mut response)? == 0 {
+            return Err(SolverError::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "the solver stopped",
+            )));
+        }
+        if let Some(items) = crate::split_s_exprs(&response) {
+            if !items.is_empty() {
+                return Ok(response);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_smt_solver() {
+    // A solver replaying the responses of a transcript.
+    struct Replay(std::vec::IntoIter<&'static str>);
+
+    impl SmtSolver for Replay {
+        fn execute(&mut self, command: &Command) -> Result<Response, SolverError> {
+            let mut output = self.0.next().unwrap().as_bytes();
+            Ok(Response::parse(&read_response(&mut output)?, command)?)
+        }
+    }
+
+    let mut solver = Replay(
+        vec![
+            "success\n",
+            "success\n",
+            "sat\n",
+            "(\n  (define-fun x () Int\n    1)\n)\n",
+            "success\n",
+            "unsat\n",
+            "(a)\n",
+            "(error \"unsat core is not available\")\n",
+        ]
+        .into_iter(),
+    );
+    let term = |input| crate::parse_term(input).unwrap();
+    solver.push(1).unwrap();
+    solver.assert(&term("(! (> x 0) :named a)")).unwrap();
+    assert_eq!(solver.check_sat().unwrap(), SatResult::Sat);
+    let model = solver.get_model().unwrap();
+    assert_eq!(model.get("x").unwrap().to_string(), "1");
+    solver.pop(1).unwrap();
+    assert_eq!(
+        solver
+            .check_sat_assuming(&[(Symbol("b".into()), false)])
+            .unwrap(),
+        SatResult::Unsat
+    );
+    assert_eq!(solver.get_unsat_core().unwrap(), vec![Symbol("a".into())]);
+    assert!(matches!(
+        solver.get_unsat_core(),
+        Err(SolverError::Solver(message)) if message == "unsat core is not available"
+    ));
+
+    let mut output = "(model\n  (define-fun y () Bool\n   true))\nsat\n".as_bytes();
+    assert_eq!(read_response(&mut output).unwrap().lines().count(), 3);
+    assert_eq!(read_response(&mut output).unwrap(), "sat\n");
+    assert!(read_response(&mut output).is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_race() {
+    // A fake solver answering `unsat`, and a missing one.
+    let path = std::env::temp_dir().join(format!("smt2parser-race-{}", std::process::id()));
+    std::fs::write(
+        &path,
+        "#!/bin/sh\nwhile read line; do case \"$line\" in '(check-sat)') echo unsat;; *) echo success;; esac; done\n",
+    )
+    .unwrap();
+    std::process::Command::new("chmod")
+        .arg("+x")
+        .arg(&path)
+        .status()
+        .unwrap();
+    let config = |path: &Path| SolverConfig {
+        path: Some(path.to_path_buf()),
+        ..SolverConfig::new(SolverKind::Z3)
+    };
+    let missing = Path::new("/nonexistent/solver");
+    let commands = crate::parse_commands("(declare-fun p () Bool) (assert p)").unwrap();
+
+    let result = race(&[config(missing), config(&path)], &commands, None);
+    assert_eq!(result.unwrap(), (1, SatResult::Unsat));
+    // An error is only reported when no solver starts.
+    let result = race(&[config(missing), config(missing)], &commands, None);
+    assert!(matches!(result, Err(SolverError::NotFound(_))));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_process_solver() {
+    let script = "while read line; do case \"$line\" in '(check-sat)') sleep 5 > /dev/null; echo sat;; *) echo success;; esac; done";
+    let spawn = || ProcessSolver::spawn_program(Path::new("sh"), &["-c", script]).unwrap();
+
+    let mut solver = spawn();
+    solver.push(1).unwrap();
+    solver.set_timeout(Some(Duration::from_millis(100)));
+    assert!(matches!(solver.check_sat(), Err(SolverError::Timeout(_))));
+    assert!(matches!(solver.pop(1), Err(SolverError::Cancelled)));
+
+    let mut solver = spawn();
+    let handle = solver.cancel_handle();
+    let start = Instant::now();
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        handle.cancel();
+    });
+    assert!(matches!(solver.check_sat(), Err(SolverError::Cancelled)));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
@@ -12,6 +12,7 @@ use crate::{
     Binary, Decimal, Hexadecimal, Numeral, Position,
 };
 
+use num::ToPrimitive;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
@@ -572,3 +573,320 @@ impl Smt2Visitor for Smt2Counters {
         Error::ParsingError(position, s)
     }
 }
+
+/// Complexity of a term, e.g. an initial condition or a transition relation.
+#[derive(Clone, Eq, PartialEq, Default, Debug, Serialize, Deserialize)]
+pub struct TermMetrics {
+    /// Number of nodes: constants, identifiers, applications, binders, and attributes.
+    pub size: usize,
+    /// Number of nodes on the longest path from the root to a leaf.
+    pub depth: usize,
+    /// Number of quantifiers.
+    pub quantifiers: usize,
+    /// Number of applications of each function symbol, e.g. `select` and `store`.
+    pub operators: BTreeMap<String, usize>,
+}
+
+impl TermMetrics {
+    /// Compute the metrics of a term.
+    pub fn of(term: &crate::concrete::Term) -> Self {
+        let mut builder = TermMetricsBuilder::default();
+        term.clone()
+            .accept(&mut builder)
+            .expect("Rebuilding a term does not fail");
+        builder.stack.pop().unwrap_or_default()
+    }
+
+    /// Add the metrics of another term, as if both were children of a common root.
+    pub fn add(&mut self, other: &TermMetrics) {
+        self.size += other.size;
+        self.depth = std::cmp::max(self.depth, other.depth);
+        self.quantifiers += other.quantifiers;
+        for (operator, count) in &other.operators {
+            *self.operators.entry(operator.clone()).or_default() += count;
+        }
+    }
+
+    fn node(children: Vec<TermMetrics>) -> Self {
+        let mut result = TermMetrics::default();
+        for child in &children {
+            result.add(child);
+        }
+        result.size += 1;
+        result.depth += 1;
+        result
+    }
+}
+
+/// Rewriter computing the metrics of the visited terms. Terms are rebuilt bottom-up,
+/// so the metrics of the subterms of a node are on top of the stack when the node is
+/// visited.
+#[derive(Default)]
+struct TermMetricsBuilder {
+    visitor: crate::concrete::SyntaxBuilder,
+    stack: Vec<TermMetrics>,
+}
+
+impl crate::rewriter::Rewriter for TermMetricsBuilder {
+    type V = crate::concrete::SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn process_term(
+        &mut self,
+        value: crate::concrete::Term,
+    ) -> Result<crate::concrete::Term, Self::Error> {
+        use crate::{
+            concrete::{QualIdentifier, Term},
+            visitors::Identifier,
+        };
+
+        let children = match &value {
+            Term::Constant(_) | Term::QualIdentifier(_) => 0,
+            Term::Application { arguments, .. } => arguments.len(),
+            Term::Let { var_bindings, .. } => var_bindings.len() + 1,
+            Term::Forall { .. } | Term::Exists { .. } | Term::Attributes { .. } => 1,
+            Term::Match { cases, .. } => cases.len() + 1,
+        };
+        let mut node = TermMetrics::node(self.stack.split_off(self.stack.len() - children));
+        match &value {
+            Term::Forall { .. } | Term::Exists { .. } => node.quantifiers += 1,
+            Term::Application {
+                qual_identifier:
+                    QualIdentifier::Simple { identifier } | QualIdentifier::Sorted { identifier, .. },
+                ..
+            } => {
+                let symbol = match identifier {
+                    Identifier::Simple { symbol } | Identifier::Indexed { symbol, .. } => symbol,
+                };
+                *node.operators.entry(symbol.0.clone()).or_default() += 1;
+            }
+            _ => (),
+        }
+        self.stack.push(node);
+        Ok(value)
+    }
+}
+
+#[test]
+fn test_term_metrics() {
+    let term = crate::parse_term(
+        "(and (= (select a i) (select (store a j v) i)) (forall ((k Int)) (! (p k) :pattern ((p k)))))",
+    )
+    .unwrap();
+    let metrics = TermMetrics::of(&term);
+    assert_eq!(metrics.size, 15);
+    assert_eq!(metrics.depth, 5);
+    assert_eq!(metrics.quantifiers, 1);
+    assert_eq!(
+        metrics.operators.into_iter().collect::<Vec<_>>(),
+        vec![
+            ("=".to_string(), 1),
+            ("and".to_string(), 1),
+            ("p".to_string(), 1),
+            ("select".to_string(), 2),
+            ("store".to_string(), 1),
+        ]
+    );
+}
+
+/// Statistics of a whole script, e.g. to sort benchmarks before running them.
+#[derive(Clone, Eq, PartialEq, Default, Debug, Serialize, Deserialize)]
+pub struct ScriptStats {
+    /// Number of commands of each kind, e.g. `assert`.
+    pub commands: BTreeMap<String, usize>,
+    /// Number of declared and defined functions, parameters, and bound variables of each
+    /// sort. Functions are counted with their result sort.
+    pub sorts: BTreeMap<String, usize>,
+    /// Maximal depth of the asserted terms and of the bodies of definitions.
+    pub max_term_depth: usize,
+    /// Number of occurrences of each symbol in terms, whether applied or not.
+    pub symbols: BTreeMap<String, usize>,
+    /// Maximal number of `push` levels.
+    pub max_scope_level: usize,
+    // Current number of `push` levels.
+    #[serde(skip)]
+    scope_level: usize,
+}
+
+impl ScriptStats {
+    /// Compute the statistics of a script.
+    pub fn of(commands: &[crate::concrete::Command]) -> Self {
+        let mut stats = Self::default();
+        for command in commands {
+            stats.add(command);
+        }
+        stats
+    }
+
+    /// Add a command to the statistics.
+    pub fn add(&mut self, command: &crate::concrete::Command) {
+        let mut builder = ScriptStatsBuilder {
+            visitor: crate::concrete::SyntaxBuilder,
+            stats: std::mem::take(self),
+        };
+        command
+            .clone()
+            .accept(&mut builder)
+            .expect("Rebuilding a command does not fail");
+        *self = builder.stats;
+    }
+
+    fn add_sort(&mut self, sort: &crate::concrete::Sort) {
+        *self.sorts.entry(sort.to_string()).or_default() += 1;
+    }
+
+    fn add_term(&mut self, term: &crate::concrete::Term) {
+        self.max_term_depth = std::cmp::max(self.max_term_depth, TermMetrics::of(term).depth);
+    }
+}
+
+/// Rewriter updating script statistics with the visited commands.
+struct ScriptStatsBuilder {
+    visitor: crate::concrete::SyntaxBuilder,
+    stats: ScriptStats,
+}
+
+impl crate::rewriter::Rewriter for ScriptStatsBuilder {
+    type V = crate::concrete::SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn process_term(
+        &mut self,
+        value: crate::concrete::Term,
+    ) -> Result<crate::concrete::Term, Self::Error> {
+        use crate::{
+            concrete::{QualIdentifier, Term},
+            visitors::Identifier,
+        };
+
+        match &value {
+            Term::QualIdentifier(
+                QualIdentifier::Simple { identifier } | QualIdentifier::Sorted { identifier, .. },
+            )
+            | Term::Application {
+                qual_identifier:
+                    QualIdentifier::Simple { identifier } | QualIdentifier::Sorted { identifier, .. },
+                ..
+            } => {
+                let symbol = match identifier {
+                    Identifier::Simple { symbol } | Identifier::Indexed { symbol, .. } => symbol,
+                };
+                *self.stats.symbols.entry(symbol.0.clone()).or_default() += 1;
+            }
+            Term::Forall { vars, .. } | Term::Exists { vars, .. } => {
+                for (_, sort) in vars {
+                    self.stats.add_sort(sort);
+                }
+            }
+            _ => (),
+        }
+        Ok(value)
+    }
+
+    fn process_command(
+        &mut self,
+        value: crate::concrete::Command,
+    ) -> Result<crate::concrete::Command, Self::Error> {
+        use crate::concrete::Command::*;
+
+        *self
+            .stats
+            .commands
+            .entry(value.name().to_string())
+            .or_default() += 1;
+        match &value {
+            Assert { term } => self.stats.add_term(term),
+            Push { level } => {
+                self.stats.scope_level += level.to_usize().expect("too many levels");
+                self.stats.max_scope_level =
+                    std::cmp::max(self.stats.max_scope_level, self.stats.scope_level);
+            }
+            Pop { level } => {
+                let level = level.to_usize().expect("too many levels");
+                self.stats.scope_level = self.stats.scope_level.saturating_sub(level);
+            }
+            Reset | ResetAssertions => self.stats.scope_level = 0,
+            DeclareConst { sort, .. } => self.stats.add_sort(sort),
+            DeclareFun {
+                parameters, sort, ..
+            } => {
+                for parameter in parameters {
+                    self.stats.add_sort(parameter);
+                }
+                self.stats.add_sort(sort);
+            }
+            DefineFun { sig, term } | DefineFunRec { sig, term } => {
+                for (_, sort) in &sig.parameters {
+                    self.stats.add_sort(sort);
+                }
+                self.stats.add_sort(&sig.result);
+                self.stats.add_term(term);
+            }
+            DefineFunsRec { funs } => {
+                for (sig, term) in funs {
+                    for (_, sort) in &sig.parameters {
+                        self.stats.add_sort(sort);
+                    }
+                    self.stats.add_sort(&sig.result);
+                    self.stats.add_term(term);
+                }
+            }
+            _ => (),
+        }
+        Ok(value)
+    }
+}
+
+#[test]
+fn test_script_stats() {
+    let commands = crate::parse_commands(
+        r#"
+(declare-sort U 0)
+(declare-fun f (U) Int)
+(declare-const u U)
+(define-fun g ((x Int)) Int (+ x 1))
+(assert (forall ((v U)) (> (g (f v)) (f u))))
+(push 1)
+(assert (= (f u) 0))
+(check-sat)
+(pop 1)
+(push 2)
+(pop 2)
+"#,
+    )
+    .unwrap();
+    let stats = ScriptStats::of(&commands);
+    let entries = |map: &BTreeMap<String, usize>| {
+        map.iter()
+            .map(|(key, count)| format!("{}:{}", key, count))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        entries(&stats.commands),
+        vec![
+            "assert:2",
+            "check-sat:1",
+            "declare-const:1",
+            "declare-fun:1",
+            "declare-sort:1",
+            "define-fun:1",
+            "pop:2",
+            "push:2"
+        ]
+    );
+    assert_eq!(entries(&stats.sorts), vec!["Int:3", "U:3"]);
+    assert_eq!(stats.max_term_depth, 5);
+    assert_eq!(stats.max_scope_level, 2);
+    assert_eq!(
+        entries(&stats.symbols),
+        vec!["+:1", "=:1", ">:1", "f:3", "g:1", "u:2", "v:1", "x:1"]
+    );
+}
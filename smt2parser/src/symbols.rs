@@ -0,0 +1,350 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Symbol tables recording the sorts and functions declared or defined by a script.
+
+use crate::{
+    concrete::{Command, DatatypeDec, Error, FunctionDec, Sort, Symbol, SyntaxBuilder},
+    environment::Environment,
+    rewriter::Rewriter,
+    visitors::Identifier,
+};
+use num::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How a symbol was introduced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum SymbolKind {
+    /// A sort declared by `declare-sort` or by a datatype declaration.
+    Sort,
+    /// A sort defined by `define-sort`.
+    SortAlias,
+    /// A function without parameters declared by `declare-const` or `declare-fun`.
+    Constant,
+    /// A function with parameters declared by `declare-fun`.
+    Function,
+    /// A function defined by `define-fun`, `define-fun-rec`, or `define-funs-rec`.
+    Definition,
+    /// A datatype constructor.
+    Constructor,
+    /// A datatype selector.
+    Selector,
+    /// A datatype tester `is-C`.
+    Tester,
+}
+
+/// What is known of a symbol: its kind, the sorts of its parameters and result for
+/// functions, and its number of parameters.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolInfo {
+    pub kind: SymbolKind,
+    /// The sorts of the parameters of a function. Empty for sorts.
+    pub parameters: Vec<Sort>,
+    /// The result sort of a function, or the definition of a sort alias. `None` for
+    /// declared sorts.
+    pub sort: Option<Sort>,
+    /// The number of parameters of a function or a sort.
+    pub arity: usize,
+}
+
+impl SymbolInfo {
+    fn function(kind: SymbolKind, parameters: Vec<Sort>, sort: Sort) -> Self {
+        Self {
+            kind,
+            arity: parameters.len(),
+            parameters,
+            sort: Some(sort),
+        }
+    }
+
+    /// Whether the symbol is a sort rather than a function.
+    pub fn is_sort(&self) -> bool {
+        matches!(self.kind, SymbolKind::Sort | SymbolKind::SortAlias)
+    }
+}
+
+/// Table of the symbols declared or defined by a script, indexed by name. Sorts and
+/// functions share the table, and later declarations replace earlier ones.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolTable(BTreeMap<String, SymbolInfo>);
+
+impl SymbolTable {
+    /// The symbols in scope at the end of a script: the declarations of the levels
+    /// removed by `pop` and `reset` are forgotten.
+    pub fn from_commands(commands: &[Command]) -> Self {
+        let mut environment = Environment::new();
+        for command in commands {
+            environment.add_command(command);
+        }
+        environment.into_symbols()
+    }
+
+    /// Record the symbols introduced by a command. Scoping commands such as `pop` are
+    /// ignored: see [`Environment`] to follow them.
+    pub fn add(&mut self, command: &Command) {
+        match command {
+            Command::DeclareConst { symbol, sort } => {
+                self.insert(
+                    symbol,
+                    SymbolInfo::function(SymbolKind::Constant, Vec::new(), sort.clone()),
+                );
+            }
+            Command::DeclareFun {
+                symbol,
+                parameters,
+                sort,
+            } => {
+                let kind = if parameters.is_empty() {
+                    SymbolKind::Constant
+                } else {
+                    SymbolKind::Function
+                };
+                self.insert(
+                    symbol,
+                    SymbolInfo::function(kind, parameters.clone(), sort.clone()),
+                );
+            }
+            Command::DefineFun { sig, .. } | Command::DefineFunRec { sig, .. } => {
+                self.add_definition(sig)
+            }
+            Command::DefineFunsRec { funs } => {
+                for (sig, _) in funs {
+                    self.add_definition(sig);
+                }
+            }
+            Command::DeclareSort { symbol, arity } => {
+                self.insert(
+                    symbol,
+                    SymbolInfo {
+                        kind: SymbolKind::Sort,
+                        parameters: Vec::new(),
+                        sort: None,
+                        arity: arity.to_usize().expect("too many parameters"),
+                    },
+                );
+            }
+            Command::DefineSort {
+                symbol,
+                parameters,
+                sort,
+            } => {
+                self.insert(
+                    symbol,
+                    SymbolInfo {
+                        kind: SymbolKind::SortAlias,
+                        parameters: Vec::new(),
+                        sort: Some(sort.clone()),
+                        arity: parameters.len(),
+                    },
+                );
+            }
+            Command::DeclareDatatype { symbol, datatype } => self.add_datatype(symbol, datatype),
+            Command::DeclareDatatypes { datatypes } => {
+                for (symbol, _, datatype) in datatypes {
+                    self.add_datatype(symbol, datatype);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn add_definition(&mut self, sig: &FunctionDec) {
+        let parameters = sig
+            .parameters
+            .iter()
+            .map(|(_, sort)| sort.clone())
+            .collect();
+        self.insert(
+            &sig.name,
+            SymbolInfo::function(SymbolKind::Definition, parameters, sig.result.clone()),
+        );
+    }
+
+    fn add_datatype(&mut self, symbol: &Symbol, datatype: &DatatypeDec) {
+        self.insert(
+            symbol,
+            SymbolInfo {
+                kind: SymbolKind::Sort,
+                parameters: Vec::new(),
+                sort: None,
+                arity: datatype.parameters.len(),
+            },
+        );
+        let identifier = Identifier::Simple {
+            symbol: symbol.clone(),
+        };
+        let sort = if datatype.parameters.is_empty() {
+            Sort::Simple { identifier }
+        } else {
+            Sort::Parameterized {
+                identifier,
+                parameters: datatype
+                    .parameters
+                    .iter()
+                    .map(|parameter| Sort::Simple {
+                        identifier: Identifier::Simple {
+                            symbol: parameter.clone(),
+                        },
+                    })
+                    .collect(),
+            }
+        };
+        let bool_sort = Sort::Simple {
+            identifier: Identifier::Simple {
+                symbol: Symbol("Bool".to_string()),
+            },
+        };
+        for constructor in &datatype.constructors {
+            for (selector, selector_sort) in &constructor.selectors {
+                self.insert(
+                    selector,
+                    SymbolInfo::function(
+                        SymbolKind::Selector,
+                        vec![sort.clone()],
+                        selector_sort.clone(),
+                    ),
+                );
+            }
+            self.insert(
+                &constructor.symbol,
+                SymbolInfo::function(
+                    SymbolKind::Constructor,
+                    constructor
+                        .selectors
+                        .iter()
+                        .map(|(_, sort)| sort.clone())
+                        .collect(),
+                    sort.clone(),
+                ),
+            );
+            self.insert(
+                &Symbol(format!("is-{}", constructor.symbol.0)),
+                SymbolInfo::function(SymbolKind::Tester, vec![sort.clone()], bool_sort.clone()),
+            );
+        }
+    }
+
+    fn insert(&mut self, symbol: &Symbol, info: SymbolInfo) {
+        self.0.insert(symbol.0.clone(), info);
+    }
+
+    /// What is known of a symbol.
+    pub fn get(&self, name: &str) -> Option<&SymbolInfo> {
+        self.0.get(name)
+    }
+
+    /// The result sort of a function symbol.
+    pub fn sort_of(&self, name: &str) -> Option<&Sort> {
+        self.get(name)
+            .filter(|info| !info.is_sort())
+            .and_then(|info| info.sort.as_ref())
+    }
+
+    /// The symbols of the given kind, ordered by name.
+    pub fn of_kind(&self, kind: SymbolKind) -> impl Iterator<Item = (&String, &SymbolInfo)> {
+        self.0.iter().filter(move |(_, info)| info.kind == kind)
+    }
+
+    /// Names and symbol information, ordered by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &SymbolInfo)> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Rewriter recording the symbols of the visited commands, e.g. to build a symbol table
+/// while parsing a [`crate::CommandStream`]. Scopes are followed as in
+/// [`SymbolTable::from_commands`].
+#[derive(Debug, Default)]
+pub struct SymbolTableBuilder {
+    visitor: SyntaxBuilder,
+    environment: Environment,
+}
+
+impl SymbolTableBuilder {
+    pub fn table(&self) -> &SymbolTable {
+        self.environment.symbols()
+    }
+
+    pub fn into_table(self) -> SymbolTable {
+        self.environment.into_symbols()
+    }
+}
+
+impl Rewriter for SymbolTableBuilder {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn process_command(&mut self, value: Command) -> Result<Command, Self::Error> {
+        self.environment.add_command(&value);
+        Ok(value)
+    }
+}
+
+#[test]
+fn test_symbol_table() {
+    let input = r#"
+(declare-sort U 0)
+(define-sort Set (T) (Array T Bool))
+(declare-const c U)
+(declare-fun f (U Int) (Set U))
+(define-fun g ((x Int)) Int (+ x 1))
+(declare-datatypes ((List 1)) ((par (T) ((nil) (cons (head T) (tail (List T)))))))
+(assert (= (g 1) 2))
+"#;
+    let table = SymbolTable::from_commands(&crate::parse_commands(input).unwrap());
+    assert_eq!(table.get("U").unwrap().kind, SymbolKind::Sort);
+    assert!(table.get("U").unwrap().is_sort());
+    assert_eq!(table.get("Set").unwrap().arity, 1);
+    assert_eq!(table.get("c").unwrap().kind, SymbolKind::Constant);
+    assert_eq!(table.sort_of("c").unwrap().to_string(), "U");
+    let f = table.get("f").unwrap();
+    assert_eq!((f.kind, f.arity), (SymbolKind::Function, 2));
+    assert_eq!(f.sort.as_ref().unwrap().to_string(), "(Set U)");
+    assert_eq!(table.get("g").unwrap().kind, SymbolKind::Definition);
+    assert_eq!(table.sort_of("cons").unwrap().to_string(), "(List T)");
+    assert_eq!(table.get("tail").unwrap().kind, SymbolKind::Selector);
+    assert_eq!(table.sort_of("is-nil").unwrap().to_string(), "Bool");
+    assert_eq!(table.sort_of("Set"), None);
+    assert_eq!(
+        table
+            .of_kind(SymbolKind::Constructor)
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["cons", "nil"]
+    );
+
+    let mut stream =
+        crate::CommandStream::new(input.as_bytes(), SymbolTableBuilder::default(), None);
+    assert_eq!(stream.by_ref().count(), 7);
+    assert_eq!(stream.into_visitor().into_table(), table);
+
+    let input = r#"
+(declare-const c Int)
+(push 2)
+(declare-const c Bool)
+(declare-const d Bool)
+(pop 1)
+(declare-const e Bool)
+(pop 1)
+"#;
+    let table = SymbolTable::from_commands(&crate::parse_commands(input).unwrap());
+    assert_eq!(table.sort_of("c").unwrap().to_string(), "Int");
+    assert_eq!(table.get("d"), None);
+    assert_eq!(table.get("e"), None);
+    let table =
+        SymbolTable::from_commands(&crate::parse_commands("(declare-const c Int)(reset)").unwrap());
+    assert!(table.is_empty());
+}
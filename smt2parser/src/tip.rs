@@ -0,0 +1,226 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Import of [TIP](https://tip-org.github.io) benchmarks (Tons of Inductive Problems).
+//!
+//! TIP extends SMT-LIB 2.6 with the commands `(prove φ)` and `(assert-not φ)`, which
+//! state the goals of a problem. Datatypes, including polymorphic ones, and recursive
+//! functions are standard SMT-LIB. Polymorphic functions and goals (`par`) and
+//! higher-order functions (`lambda`, `@`) are not supported.
+
+use crate::{
+    concrete::{Command, Term},
+    vmt::SMTProblem,
+};
+use thiserror::Error;
+
+/// Error reported when importing a TIP benchmark.
+#[derive(Error, Debug)]
+pub enum TipError {
+    #[error(transparent)]
+    Syntax(#[from] crate::Error),
+    #[error("unbalanced input")]
+    Unbalanced,
+    #[error("unsupported TIP feature `{feature}` in: {command}")]
+    Unsupported {
+        feature: &'static str,
+        command: String,
+    },
+    #[error("the benchmark has no goal")]
+    NoGoal,
+}
+
+/// A TIP benchmark: declarations, definitions, and assertions, with the goals to prove.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TipProblem {
+    sorts: Vec<Command>,
+    functions: Vec<Command>,
+    definitions: Vec<Command>,
+    assertions: Vec<Term>,
+    goals: Vec<Term>,
+}
+
+impl TipProblem {
+    /// Parse a TIP benchmark. Commands other than declarations, definitions, assertions,
+    /// and goals, such as `set-logic` or `check-sat`, are ignored.
+    pub fn parse(input: &str) -> Result<Self, TipError> {
+        // Goals are blanked out of the input, so that positions in syntax errors are
+        // still those of the original input.
+        let mut script = input.to_string();
+        let mut goal_inputs = Vec::new();
+        for item in crate::split_s_exprs(input).ok_or(TipError::Unbalanced)? {
+            let tokens = tokens(item);
+            let unsupported = |feature| TipError::Unsupported {
+                feature,
+                command: item.to_string(),
+            };
+            if tokens.contains(&"lambda") {
+                return Err(unsupported("lambda"));
+            }
+            if tokens.contains(&"@") {
+                return Err(unsupported("@"));
+            }
+            match tokens[..] {
+                ["(", "declare-datatype" | "declare-datatypes", ..] => (),
+                ["(", _, "(", "par", ..] => return Err(unsupported("par")),
+                ["(", "prove" | "assert-not", ..] => {
+                    let body = item[1..item.len() - 1].trim_start();
+                    let body = body.trim_start_matches(|c: char| !c.is_whitespace() && c != '(');
+                    goal_inputs.push(body.to_string());
+                    // Items are subslices of the input.
+                    let offset = item.as_ptr() as usize - input.as_ptr() as usize;
+                    script.replace_range(offset..offset + item.len(), &blank(item));
+                }
+                _ => (),
+            }
+        }
+
+        let mut problem = Self::default();
+        for command in crate::parse_commands(&script)? {
+            match command {
+                Command::DeclareSort { .. }
+                | Command::DefineSort { .. }
+                | Command::DeclareDatatype { .. }
+                | Command::DeclareDatatypes { .. } => problem.sorts.push(command),
+                Command::DeclareFun { .. } | Command::DeclareConst { .. } => {
+                    problem.functions.push(command)
+                }
+                Command::DefineFun { .. }
+                | Command::DefineFunRec { .. }
+                | Command::DefineFunsRec { .. } => problem.definitions.push(command),
+                Command::Assert { term } => problem.assertions.push(term),
+                _ => (),
+            }
+        }
+        for goal in goal_inputs {
+            problem.goals.push(crate::parse_term(&goal)?);
+        }
+        Ok(problem)
+    }
+
+    /// The goals of the benchmark, in order.
+    pub fn goals(&self) -> &[Term] {
+        &self.goals
+    }
+
+    /// One problem per goal, whose property is the goal. Each problem asserts the negation
+    /// of its goal, so that the goal holds if and only if the problem is unsatisfiable.
+    pub fn problems(&self) -> Result<Vec<SMTProblem>, TipError> {
+        if self.goals.is_empty() {
+            return Err(TipError::NoGoal);
+        }
+        Ok(self
+            .goals
+            .iter()
+            .map(|goal| {
+                SMTProblem::from_parts(
+                    self.sorts.clone(),
+                    self.functions.clone(),
+                    self.definitions.clone(),
+                    self.assertions.clone(),
+                    goal.clone(),
+                )
+            })
+            .collect())
+    }
+
+    /// The declarations, definitions, and assertions of the benchmark, without the goals.
+    pub fn to_commands(&self) -> Vec<Command> {
+        let mut commands = self.sorts.clone();
+        commands.extend(self.functions.iter().cloned());
+        commands.extend(self.definitions.iter().cloned());
+        commands.extend(
+            self.assertions
+                .iter()
+                .map(|term| Command::Assert { term: term.clone() }),
+        );
+        commands
+    }
+}
+
+// Parentheses and atoms of an s-expression, ignoring strings, quoted symbols and comments.
+fn tokens(input: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut rest = input;
+    while let Some(c) = rest.chars().next() {
+        let len = match c {
+            '(' | ')' => {
+                tokens.push(&rest[..1]);
+                1
+            }
+            ';' => rest.find('\n').unwrap_or(rest.len()),
+            '"' | '|' => rest[1..].find(c).map_or(rest.len(), |end| end + 2),
+            c if c.is_whitespace() => c.len_utf8(),
+            _ => {
+                let len = rest
+                    .find(|c: char| c.is_whitespace() || "();\"|".contains(c))
+                    .unwrap_or(rest.len());
+                tokens.push(&rest[..len]);
+                len
+            }
+        };
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+// Replace the characters of a command by as many spaces as their UTF-8 length, keeping
+// line breaks, so that the offsets of the next commands do not change.
+fn blank(input: &str) -> String {
+    input
+        .chars()
+        .map(|c| match c {
+            '\n' => "\n".to_string(),
+            c => " ".repeat(c.len_utf8()),
+        })
+        .collect()
+}
+
+#[test]
+fn test_tip_problem() {
+    let problem = TipProblem::parse(
+        r#"
+(declare-datatype Nat ((Z) (S (p Nat))))
+(declare-datatype list (par (a) ((nil) (cons (head a) (tail (list a))))))
+(define-fun-rec plus ((x Nat) (y Nat)) Nat
+  (match x ((Z y) ((S z) (S (plus z y))))))
+(prove (forall ((x Nat)) (= (plus x Z) x)))
+(assert-not (forall ((x Nat)) (= (plus Z x) x)))
+(check-sat)
+"#,
+    )
+    .unwrap();
+    assert_eq!(problem.to_commands().len(), 3);
+    assert_eq!(
+        problem.goals()[1].to_string(),
+        "(forall ((x Nat)) (= (plus Z x) x))"
+    );
+    let problems = problem.problems().unwrap();
+    assert_eq!(problems.len(), 2);
+    let commands = problems[0].to_commands();
+    assert_eq!(
+        commands.last().unwrap().to_string(),
+        "(assert (not (forall ((x Nat)) (= (plus x Z) x))))"
+    );
+    assert!(!problems[1].to_smtlib2().contains("set-logic"));
+    let problems = problem
+        .problems()
+        .unwrap()
+        .into_iter()
+        .map(SMTProblem::with_logic);
+    assert!(problems
+        .map(|problem| problem.to_smtlib2())
+        .all(|script| script.starts_with("(set-logic UFDT)\n")));
+
+    let error = TipProblem::parse("(define-fun-rec (par (a) (id ((x a)) a x)))").unwrap_err();
+    assert!(matches!(
+        error,
+        TipError::Unsupported { feature: "par", .. }
+    ));
+    let error = TipProblem::parse("(declare-sort S 0)\n(prove (= 1 (f 2))").unwrap_err();
+    assert!(matches!(error, TipError::Unbalanced));
+    assert!(matches!(
+        TipProblem::parse("(declare-sort S 0)").unwrap().problems(),
+        Err(TipError::NoGoal)
+    ));
+}
@@ -0,0 +1,202 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Inspection and rewriting of the triggers of quantifiers, given by `:pattern`
+//! annotations.
+
+use crate::{
+    concrete::{Error, Keyword, SExpr, SyntaxBuilder, Term},
+    rewriter::Rewriter,
+    visitors::AttributeValue,
+};
+use std::collections::BTreeMap;
+
+impl Term {
+    /// The name of a quantifier given by its `:qid` annotation, if any.
+    pub fn qid(&self) -> Option<&str> {
+        quantifier_attributes(self)?
+            .iter()
+            .find_map(|(key, value)| match value {
+                AttributeValue::Symbol(symbol) if key.0 == "qid" => Some(symbol.0.as_str()),
+                _ => None,
+            })
+    }
+
+    /// The triggers of a quantifier. Each trigger is a list of terms, which has several
+    /// elements for multi-patterns. Terms that are not quantifiers have no triggers, and
+    /// elements of patterns that are not terms are ignored.
+    pub fn triggers(&self) -> Vec<Vec<Term>> {
+        let attributes = match quantifier_attributes(self) {
+            Some(attributes) => attributes,
+            None => return Vec::new(),
+        };
+        attributes
+            .iter()
+            .filter_map(|(key, value)| match value {
+                AttributeValue::SExpr(values) if key.0 == "pattern" => Some(
+                    values
+                        .iter()
+                        .filter_map(|value| crate::parse_term(&value.to_string()).ok())
+                        .collect(),
+                ),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Replace the triggers of a quantifier, keeping its other annotations. Triggers are
+    /// expressed with the names of the variables of the quantifier. Terms that are not
+    /// quantifiers are returned unchanged.
+    pub fn with_triggers(self, triggers: Vec<Vec<Term>>) -> Term {
+        let (is_forall, vars, body) = match self {
+            Term::Forall { vars, term } => (true, vars, *term),
+            Term::Exists { vars, term } => (false, vars, *term),
+            term => return term,
+        };
+        let (body, mut attributes) = match body {
+            Term::Attributes { term, attributes } => (*term, attributes),
+            body => (body, Vec::new()),
+        };
+        attributes.retain(|(key, _)| key.0 != "pattern" && key.0 != "no-pattern");
+        let patterns = triggers.into_iter().map(|trigger| {
+            (
+                Keyword("pattern".to_string()),
+                AttributeValue::SExpr(trigger.iter().map(to_s_expr).collect()),
+            )
+        });
+        // Patterns come first, as usually printed.
+        let attributes = patterns.chain(attributes).collect::<Vec<_>>();
+        let term = if attributes.is_empty() {
+            Box::new(body)
+        } else {
+            Box::new(Term::Attributes {
+                term: Box::new(body),
+                attributes,
+            })
+        };
+        if is_forall {
+            Term::Forall { vars, term }
+        } else {
+            Term::Exists { vars, term }
+        }
+    }
+}
+
+fn quantifier_attributes(term: &Term) -> Option<&[(Keyword, AttributeValue)]> {
+    match term {
+        Term::Forall { term, .. } | Term::Exists { term, .. } => match &**term {
+            Term::Attributes { attributes, .. } => Some(attributes.as_slice()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Convert a term into an s-expression by printing and parsing it as a pattern.
+fn to_s_expr(term: &Term) -> SExpr {
+    match crate::parse_term(&format!("(! true :pattern ({}))", term)) {
+        Ok(Term::Attributes { mut attributes, .. }) => match attributes.pop() {
+            Some((_, AttributeValue::SExpr(mut values))) if values.len() == 1 => {
+                values.pop().unwrap()
+            }
+            _ => unreachable!(),
+        },
+        _ => unreachable!("Printed terms are parsed back"),
+    }
+}
+
+/// Rewriter removing the `:pattern` and `:no-pattern` annotations of all quantifiers, so
+/// that the solver chooses triggers itself.
+#[derive(Debug, Default)]
+pub struct TriggerStripper(SyntaxBuilder);
+
+impl Rewriter for TriggerStripper {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.0
+    }
+
+    fn process_term(&mut self, value: Term) -> Result<Term, Self::Error> {
+        Ok(value.with_triggers(Vec::new()))
+    }
+}
+
+/// Rewriter replacing the triggers of the quantifiers named by `:qid` annotations, such
+/// as the quantifiers reported in Z3 traces. Other quantifiers are unchanged.
+#[derive(Debug, Default)]
+pub struct TriggerReplacer {
+    visitor: SyntaxBuilder,
+    triggers: BTreeMap<String, Vec<Vec<Term>>>,
+}
+
+impl TriggerReplacer {
+    pub fn new(triggers: BTreeMap<String, Vec<Vec<Term>>>) -> Self {
+        Self {
+            visitor: SyntaxBuilder,
+            triggers,
+        }
+    }
+}
+
+impl Rewriter for TriggerReplacer {
+    type V = SyntaxBuilder;
+    type Error = Error;
+
+    fn visitor(&mut self) -> &mut Self::V {
+        &mut self.visitor
+    }
+
+    fn process_term(&mut self, value: Term) -> Result<Term, Self::Error> {
+        match value.qid().and_then(|qid| self.triggers.get(qid)) {
+            Some(triggers) => {
+                let triggers = triggers.clone();
+                Ok(value.with_triggers(triggers))
+            }
+            None => Ok(value),
+        }
+    }
+}
+
+#[test]
+fn test_triggers() {
+    let term = |input: &str| crate::parse_term(input).unwrap();
+    let t = term(
+        "(forall ((x Int) (y Int)) (! (= (f x y) (g y)) :pattern ((f x y)) :pattern ((h x) (g y)) :qid ax))",
+    );
+    assert_eq!(t.qid(), Some("ax"));
+    assert_eq!(
+        t.triggers(),
+        vec![vec![term("(f x y)")], vec![term("(h x)"), term("(g y)")]]
+    );
+    assert_eq!(
+        t.clone()
+            .with_triggers(vec![vec![term("(g y)"), term("(h x)")]])
+            .to_string(),
+        "(forall ((x Int) (y Int)) (! (= (f x y) (g y)) :pattern ((g y) (h x)) :qid ax))"
+    );
+    assert_eq!(
+        term("(forall ((x Int)) (! (P x) :pattern ((P x))))")
+            .with_triggers(Vec::new())
+            .to_string(),
+        "(forall ((x Int)) (P x))"
+    );
+    assert!(term("(P x)").triggers().is_empty());
+
+    let nested = term("(and (forall ((x Int)) (! (exists ((y Int)) (! (R x y) :pattern ((R x y)) :qid inner)) :pattern ((P x)) :qid outer)) q)");
+    assert_eq!(
+        nested
+            .clone()
+            .accept(&mut TriggerStripper::default())
+            .unwrap()
+            .to_string(),
+        "(and (forall ((x Int)) (! (exists ((y Int)) (! (R x y) :qid inner)) :qid outer)) q)"
+    );
+    let mut triggers = BTreeMap::new();
+    triggers.insert("inner".to_string(), vec![vec![term("(S y)")]]);
+    assert_eq!(
+        nested.accept(&mut TriggerReplacer::new(triggers)).unwrap().to_string(),
+        "(and (forall ((x Int)) (! (exists ((y Int)) (! (R x y) :pattern ((S y)) :qid inner)) :pattern ((P x)) :qid outer)) q)"
+    );
+}
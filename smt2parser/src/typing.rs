@@ -0,0 +1,965 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Sort checking of commands and terms.
+
+use crate::{
+    concrete::{Command, Constant, DatatypeDec, FunctionDec, QualIdentifier, Sort, Symbol, Term},
+    visitors::{Identifier, Index},
+    Numeral,
+};
+use num::ToPrimitive;
+use std::collections::{BTreeMap, BTreeSet};
+use thiserror::Error;
+
+/// Error reported by a [`SortChecker`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SortError {
+    #[error("unknown sort `{0}`")]
+    UnknownSort(Sort),
+    #[error("unknown symbol `{symbol}` in term `{term}`")]
+    UnknownSymbol { symbol: String, term: Term },
+    #[error("symbol `{0}` is already declared")]
+    AlreadyDeclared(String),
+    #[error("ill-sorted term `{term}`: expected sort `{expected}`, found `{actual}`")]
+    Mismatch {
+        term: Term,
+        expected: Sort,
+        actual: Sort,
+    },
+    #[error("ill-sorted term `{term}`: expected {expected} arguments, found {actual}")]
+    Arity {
+        term: Term,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("ill-sorted term `{term}`: {message}")]
+    Invalid { term: Term, message: String },
+}
+
+/// Reason why a function symbol of a [`Signature`] does not apply to some arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApplicationError {
+    /// The symbol expects the given number of arguments.
+    Arity(usize),
+    /// The argument at the given position should have the given sort.
+    Argument(usize, Sort),
+    /// Any other reason.
+    Other(String),
+}
+
+/// The sorts and function symbols of the theories known to a [`SortChecker`], in
+/// addition to the symbols declared by the checked commands.
+pub trait Signature {
+    /// Return the number of parameters of a theory sort, or `None` if the sort is
+    /// unknown.
+    fn sort_arity(&self, identifier: &Identifier) -> Option<usize>;
+
+    /// Return the sort of the application of a theory function symbol to arguments of
+    /// the given sorts, or `None` if the symbol is unknown. Constants are applied to no
+    /// arguments. `result` is the sort given by an `as` annotation, if any.
+    fn function_sort(
+        &self,
+        identifier: &Identifier,
+        arguments: &[Sort],
+        result: Option<&Sort>,
+    ) -> Option<Result<Sort, ApplicationError>>;
+}
+
+/// The signature of the SMT-LIB theories `Core`, `Ints`, `Reals`, `Reals_Ints`, and
+/// `ArraysEx`. Bit-vector sorts are known but their operations are not.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SmtLibTheories;
+
+impl Signature for SmtLibTheories {
+    fn sort_arity(&self, identifier: &Identifier) -> Option<usize> {
+        match identifier {
+            Identifier::Simple { symbol } => match symbol.0.as_str() {
+                "Bool" | "Int" | "Real" => Some(0),
+                "Array" => Some(2),
+                _ => None,
+            },
+            Identifier::Indexed { symbol, indices }
+                if symbol.0 == "BitVec" && matches!(indices[..], [Index::Numeral(_)]) =>
+            {
+                Some(0)
+            }
+            Identifier::Indexed { .. } => None,
+        }
+    }
+
+    fn function_sort(
+        &self,
+        identifier: &Identifier,
+        arguments: &[Sort],
+        result: Option<&Sort>,
+    ) -> Option<Result<Sort, ApplicationError>> {
+        let symbol = match identifier {
+            Identifier::Simple { symbol } => symbol.0.as_str(),
+            Identifier::Indexed { .. } => return None,
+        };
+        let (bool_sort, int_sort, real_sort) =
+            (simple_sort("Bool"), simple_sort("Int"), simple_sort("Real"));
+        let sort = match symbol {
+            "true" | "false" => expect_sorts(arguments, &[]).map(|()| bool_sort),
+            "not" => expect_sorts(arguments, &[bool_sort.clone()]).map(|()| bool_sort),
+            "=>" | "and" | "or" | "xor" => same_sorts(arguments, 1, Some(&bool_sort)),
+            "=" | "distinct" => same_sorts(arguments, 2, None).map(|_| bool_sort),
+            "ite" => match arguments {
+                [condition, ..] if *condition != bool_sort => {
+                    Err(ApplicationError::Argument(0, bool_sort))
+                }
+                [_, then_sort, else_sort] if then_sort != else_sort => {
+                    Err(ApplicationError::Argument(2, then_sort.clone()))
+                }
+                [_, then_sort, _] => Ok(then_sort.clone()),
+                _ => Err(ApplicationError::Arity(3)),
+            },
+            "+" | "-" | "*" => numeric_sorts(arguments, 1),
+            "<" | "<=" | ">" | ">=" => numeric_sorts(arguments, 2).map(|_| bool_sort),
+            "/" => same_sorts(arguments, 2, Some(&real_sort)),
+            "div" => same_sorts(arguments, 2, Some(&int_sort)),
+            "mod" => {
+                expect_sorts(arguments, &[int_sort.clone(), int_sort.clone()]).map(|()| int_sort)
+            }
+            "abs" => expect_sorts(arguments, &[int_sort.clone()]).map(|()| int_sort),
+            "to_real" => expect_sorts(arguments, &[int_sort]).map(|()| real_sort),
+            "to_int" => expect_sorts(arguments, &[real_sort]).map(|()| int_sort),
+            "is_int" => expect_sorts(arguments, &[real_sort]).map(|()| bool_sort),
+            "select" => match arguments {
+                [array, index] => match array_sorts(array) {
+                    Some((index_sort, _)) if index_sort != index => {
+                        Err(ApplicationError::Argument(1, index_sort.clone()))
+                    }
+                    Some((_, element_sort)) => Ok(element_sort.clone()),
+                    None => Err(not_an_array(array)),
+                },
+                _ => Err(ApplicationError::Arity(2)),
+            },
+            "store" => match arguments {
+                [array, index, element] => match array_sorts(array) {
+                    Some((index_sort, _)) if index_sort != index => {
+                        Err(ApplicationError::Argument(1, index_sort.clone()))
+                    }
+                    Some((_, element_sort)) if element_sort != element => {
+                        Err(ApplicationError::Argument(2, element_sort.clone()))
+                    }
+                    Some(_) => Ok(array.clone()),
+                    None => Err(not_an_array(array)),
+                },
+                _ => Err(ApplicationError::Arity(3)),
+            },
+            "const" => match (result.map(|sort| (sort, array_sorts(sort))), arguments) {
+                (Some((sort, Some((_, element_sort)))), [element]) => {
+                    if element_sort == element {
+                        Ok(sort.clone())
+                    } else {
+                        Err(ApplicationError::Argument(0, element_sort.clone()))
+                    }
+                }
+                (Some((_, Some(_))), _) => Err(ApplicationError::Arity(1)),
+                _ => Err(ApplicationError::Other(
+                    "`const` requires an array sort annotation".to_string(),
+                )),
+            },
+            _ => return None,
+        };
+        Some(sort)
+    }
+}
+
+// Check that the arguments have exactly the given sorts.
+fn expect_sorts(arguments: &[Sort], expected: &[Sort]) -> Result<(), ApplicationError> {
+    if arguments.len() != expected.len() {
+        return Err(ApplicationError::Arity(expected.len()));
+    }
+    match arguments.iter().zip(expected).position(|(a, e)| a != e) {
+        Some(i) => Err(ApplicationError::Argument(i, expected[i].clone())),
+        None => Ok(()),
+    }
+}
+
+// Check that there are at least `min` arguments, all of the same sort, which is
+// `expected` if given. Return the common sort.
+fn same_sorts(
+    arguments: &[Sort],
+    min: usize,
+    expected: Option<&Sort>,
+) -> Result<Sort, ApplicationError> {
+    if arguments.len() < min {
+        return Err(ApplicationError::Other(format!(
+            "expected at least {} arguments, found {}",
+            min,
+            arguments.len()
+        )));
+    }
+    let sort = expected.unwrap_or(&arguments[0]);
+    match arguments.iter().position(|a| a != sort) {
+        Some(i) => Err(ApplicationError::Argument(i, sort.clone())),
+        None => Ok(sort.clone()),
+    }
+}
+
+// Same as `same_sorts` for arguments of sort `Int` or `Real`.
+fn numeric_sorts(arguments: &[Sort], min: usize) -> Result<Sort, ApplicationError> {
+    let sort = same_sorts(arguments, min, None)?;
+    if sort == simple_sort("Int") || sort == simple_sort("Real") {
+        Ok(sort)
+    } else {
+        Err(ApplicationError::Argument(0, simple_sort("Int")))
+    }
+}
+
+fn array_sorts(sort: &Sort) -> Option<(&Sort, &Sort)> {
+    match sort {
+        Sort::Parameterized {
+            identifier: Identifier::Simple { symbol },
+            parameters,
+        } if symbol.0 == "Array" && parameters.len() == 2 => Some((&parameters[0], &parameters[1])),
+        _ => None,
+    }
+}
+
+fn not_an_array(sort: &Sort) -> ApplicationError {
+    ApplicationError::Other(format!("`{}` is not an array sort", sort))
+}
+
+/// Checker of the sorts of the terms of a command stream, following the SMT-LIB sort
+/// rules. Declarations and definitions are recorded as the commands are checked, and
+/// the theory symbols are given by a [`Signature`].
+#[derive(Debug, Default, Clone)]
+pub struct SortChecker<S = SmtLibTheories> {
+    signature: S,
+    scope: Scope,
+    // Declarations saved by `push` commands.
+    scopes: Vec<Scope>,
+}
+
+#[derive(Debug, Default, Clone)]
+struct Scope {
+    // Arity of the declared sorts.
+    sorts: BTreeMap<String, usize>,
+    // Parameters and resolved definitions of the sorts defined by `define-sort`.
+    aliases: BTreeMap<String, (Vec<String>, Sort)>,
+    // Declared and defined functions, including datatype constructors, selectors, and
+    // testers.
+    functions: BTreeMap<String, FunctionSort>,
+    // Datatype constructors.
+    constructors: BTreeSet<String>,
+}
+
+// The sort of a function, possibly with sort parameters in the case of datatypes.
+#[derive(Debug, Clone)]
+struct FunctionSort {
+    parameters: Vec<String>,
+    arguments: Vec<Sort>,
+    result: Sort,
+}
+
+impl<S: Signature> SortChecker<S> {
+    pub fn new(signature: S) -> Self {
+        Self {
+            signature,
+            scope: Scope::default(),
+            scopes: Vec::new(),
+        }
+    }
+
+    /// Check a sequence of commands, stopping at the first error.
+    pub fn check_commands<'a, I>(&mut self, commands: I) -> Result<(), SortError>
+    where
+        I: IntoIterator<Item = &'a Command>,
+    {
+        commands
+            .into_iter()
+            .try_for_each(|command| self.check_command(command))
+    }
+
+    /// Check a command and record its declarations.
+    pub fn check_command(&mut self, command: &Command) -> Result<(), SortError> {
+        match command {
+            Command::Assert { term } => self.check_bool(term, &mut Vec::new()),
+            Command::CheckSatAssuming { literals } => {
+                literals.iter().try_for_each(|(symbol, _)| {
+                    self.check_bool(&symbol_term(&symbol.0), &mut Vec::new())
+                })
+            }
+            Command::DeclareConst { symbol, sort } => {
+                let result = self.resolve_sort(sort, &[])?;
+                self.declare(&symbol.0, Vec::new(), Vec::new(), result)
+            }
+            Command::DeclareDatatype { symbol, datatype } => {
+                self.declare_datatypes(&[(symbol, datatype)])
+            }
+            Command::DeclareDatatypes { datatypes } => self.declare_datatypes(
+                &datatypes
+                    .iter()
+                    .map(|(symbol, _, datatype)| (symbol, datatype))
+                    .collect::<Vec<_>>(),
+            ),
+            Command::DeclareFun {
+                symbol,
+                parameters,
+                sort,
+            } => {
+                let arguments = parameters
+                    .iter()
+                    .map(|sort| self.resolve_sort(sort, &[]))
+                    .collect::<Result<_, _>>()?;
+                let result = self.resolve_sort(sort, &[])?;
+                self.declare(&symbol.0, Vec::new(), arguments, result)
+            }
+            Command::DeclareSort { symbol, arity } => {
+                self.check_fresh_sort(&symbol.0)?;
+                let arity = arity.to_usize().expect("too many parameters");
+                self.scope.sorts.insert(symbol.0.clone(), arity);
+                Ok(())
+            }
+            Command::DefineFun { sig, term } => {
+                let (arguments, result) = self.check_definition(sig, term)?;
+                self.declare(&sig.name.0, Vec::new(), arguments, result)
+            }
+            Command::DefineFunRec { sig, term } => {
+                self.define_funs_rec(&[(sig.clone(), term.clone())])
+            }
+            Command::DefineFunsRec { funs } => self.define_funs_rec(funs),
+            Command::DefineSort {
+                symbol,
+                parameters,
+                sort,
+            } => {
+                self.check_fresh_sort(&symbol.0)?;
+                let parameters = parameters.iter().map(|p| p.0.clone()).collect::<Vec<_>>();
+                let sort = self.resolve_sort(sort, &parameters)?;
+                self.scope
+                    .aliases
+                    .insert(symbol.0.clone(), (parameters, sort));
+                Ok(())
+            }
+            Command::GetValue { terms } => terms
+                .iter()
+                .try_for_each(|term| self.term_sort(term, &mut Vec::new()).map(|_| ())),
+            Command::Push { level } => {
+                for _ in 0..level.to_usize().expect("too many levels") {
+                    self.scopes.push(self.scope.clone());
+                }
+                Ok(())
+            }
+            Command::Pop { level } => {
+                for _ in 0..level.to_usize().expect("too many levels") {
+                    if let Some(scope) = self.scopes.pop() {
+                        self.scope = scope;
+                    }
+                }
+                Ok(())
+            }
+            Command::Reset => {
+                self.scope = Scope::default();
+                self.scopes.clear();
+                Ok(())
+            }
+            Command::ResetAssertions => {
+                if !self.scopes.is_empty() {
+                    self.scope = self.scopes.swap_remove(0);
+                    self.scopes.clear();
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Compute the sort of a closed term.
+    pub fn sort_of(&self, term: &Term) -> Result<Sort, SortError> {
+        self.term_sort(term, &mut Vec::new())
+    }
+
+    fn declare(
+        &mut self,
+        symbol: &str,
+        parameters: Vec<String>,
+        arguments: Vec<Sort>,
+        result: Sort,
+    ) -> Result<(), SortError> {
+        if self.scope.functions.contains_key(symbol) {
+            return Err(SortError::AlreadyDeclared(symbol.to_string()));
+        }
+        self.scope.functions.insert(
+            symbol.to_string(),
+            FunctionSort {
+                parameters,
+                arguments,
+                result,
+            },
+        );
+        Ok(())
+    }
+
+    fn check_fresh_sort(&self, symbol: &str) -> Result<(), SortError> {
+        if self.scope.sorts.contains_key(symbol) || self.scope.aliases.contains_key(symbol) {
+            return Err(SortError::AlreadyDeclared(symbol.to_string()));
+        }
+        Ok(())
+    }
+
+    fn declare_datatypes(
+        &mut self,
+        datatypes: &[(&Symbol, &DatatypeDec)],
+    ) -> Result<(), SortError> {
+        // Datatypes may be mutually recursive so all the sorts are declared first.
+        for (symbol, datatype) in datatypes {
+            self.check_fresh_sort(&symbol.0)?;
+            self.scope
+                .sorts
+                .insert(symbol.0.clone(), datatype.parameters.len());
+        }
+        for (symbol, datatype) in datatypes {
+            let parameters = datatype
+                .parameters
+                .iter()
+                .map(|p| p.0.clone())
+                .collect::<Vec<_>>();
+            let sort = if parameters.is_empty() {
+                simple_sort(&symbol.0)
+            } else {
+                Sort::Parameterized {
+                    identifier: Identifier::Simple {
+                        symbol: (*symbol).clone(),
+                    },
+                    parameters: parameters.iter().map(|p| simple_sort(p)).collect(),
+                }
+            };
+            for constructor in &datatype.constructors {
+                let mut arguments = Vec::new();
+                for (selector, selector_sort) in &constructor.selectors {
+                    let selector_sort = self.resolve_sort(selector_sort, &parameters)?;
+                    self.declare(
+                        &selector.0,
+                        parameters.clone(),
+                        vec![sort.clone()],
+                        selector_sort.clone(),
+                    )?;
+                    arguments.push(selector_sort);
+                }
+                self.declare(
+                    &constructor.symbol.0,
+                    parameters.clone(),
+                    arguments,
+                    sort.clone(),
+                )?;
+                self.declare(
+                    &format!("is-{}", constructor.symbol.0),
+                    parameters.clone(),
+                    vec![sort.clone()],
+                    simple_sort("Bool"),
+                )?;
+                self.scope.constructors.insert(constructor.symbol.0.clone());
+            }
+        }
+        Ok(())
+    }
+
+    fn define_funs_rec(&mut self, funs: &[(FunctionDec, Term)]) -> Result<(), SortError> {
+        for (sig, _) in funs {
+            let arguments = sig
+                .parameters
+                .iter()
+                .map(|(_, sort)| self.resolve_sort(sort, &[]))
+                .collect::<Result<_, _>>()?;
+            let result = self.resolve_sort(&sig.result, &[])?;
+            self.declare(&sig.name.0, Vec::new(), arguments, result)?;
+        }
+        for (sig, term) in funs {
+            self.check_definition(sig, term)?;
+        }
+        Ok(())
+    }
+
+    // Check the body of a function definition and return the sorts of the function.
+    fn check_definition(
+        &self,
+        sig: &FunctionDec,
+        term: &Term,
+    ) -> Result<(Vec<Sort>, Sort), SortError> {
+        let mut locals = sig
+            .parameters
+            .iter()
+            .map(|(symbol, sort)| Ok((symbol.0.clone(), self.resolve_sort(sort, &[])?)))
+            .collect::<Result<Vec<_>, SortError>>()?;
+        let result = self.resolve_sort(&sig.result, &[])?;
+        self.check_sort(term, &result, &mut locals)?;
+        let arguments = locals.into_iter().map(|(_, sort)| sort).collect();
+        Ok((arguments, result))
+    }
+
+    // Expand the sort definitions in a sort and check that it is known. The given
+    // parameters are accepted as sorts.
+    fn resolve_sort(&self, sort: &Sort, parameters: &[String]) -> Result<Sort, SortError> {
+        let (identifier, arguments) = match sort {
+            Sort::Simple { identifier } => (identifier, Vec::new()),
+            Sort::Parameterized {
+                identifier,
+                parameters: arguments,
+            } => (
+                identifier,
+                arguments
+                    .iter()
+                    .map(|s| self.resolve_sort(s, parameters))
+                    .collect::<Result<_, _>>()?,
+            ),
+        };
+        if let Identifier::Simple { symbol } = identifier {
+            if arguments.is_empty() && parameters.contains(&symbol.0) {
+                return Ok(sort.clone());
+            }
+            if let Some((alias_parameters, definition)) = self.scope.aliases.get(&symbol.0) {
+                if alias_parameters.len() != arguments.len() {
+                    return Err(SortError::UnknownSort(sort.clone()));
+                }
+                let bindings = alias_parameters.iter().cloned().zip(arguments).collect();
+                return Ok(substitute_sort(definition, &bindings));
+            }
+        }
+        let arity = match identifier {
+            Identifier::Simple { symbol } => self.scope.sorts.get(&symbol.0).copied(),
+            Identifier::Indexed { .. } => None,
+        };
+        if arity.or_else(|| self.signature.sort_arity(identifier)) != Some(arguments.len()) {
+            return Err(SortError::UnknownSort(sort.clone()));
+        }
+        if arguments.is_empty() {
+            Ok(Sort::Simple {
+                identifier: identifier.clone(),
+            })
+        } else {
+            Ok(Sort::Parameterized {
+                identifier: identifier.clone(),
+                parameters: arguments,
+            })
+        }
+    }
+
+    fn check_bool(&self, term: &Term, locals: &mut Vec<(String, Sort)>) -> Result<(), SortError> {
+        self.check_sort(term, &simple_sort("Bool"), locals)
+    }
+
+    fn check_sort(
+        &self,
+        term: &Term,
+        expected: &Sort,
+        locals: &mut Vec<(String, Sort)>,
+    ) -> Result<(), SortError> {
+        let actual = self.term_sort(term, locals)?;
+        if actual != *expected {
+            return Err(SortError::Mismatch {
+                term: term.clone(),
+                expected: expected.clone(),
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    // Compute the sort of a term where the given local symbols are bound. Later
+    // bindings shadow earlier ones.
+    fn term_sort(&self, term: &Term, locals: &mut Vec<(String, Sort)>) -> Result<Sort, SortError> {
+        match term {
+            Term::Constant(constant) => Ok(constant_sort(constant)),
+            Term::QualIdentifier(qual_identifier) => {
+                self.application_sort(term, qual_identifier, &[], Vec::new(), locals)
+            }
+            Term::Application {
+                qual_identifier,
+                arguments,
+            } => {
+                let sorts = arguments
+                    .iter()
+                    .map(|a| self.term_sort(a, locals))
+                    .collect::<Result<_, _>>()?;
+                self.application_sort(term, qual_identifier, arguments, sorts, locals)
+            }
+            Term::Let { var_bindings, term } => {
+                // Bindings are parallel: their terms are sorted in the outer scope.
+                let bindings = var_bindings
+                    .iter()
+                    .map(|(symbol, t)| Ok((symbol.0.clone(), self.term_sort(t, locals)?)))
+                    .collect::<Result<Vec<_>, SortError>>()?;
+                let depth = locals.len();
+                locals.extend(bindings);
+                let sort = self.term_sort(term, locals);
+                locals.truncate(depth);
+                sort
+            }
+            Term::Forall { vars, term } | Term::Exists { vars, term } => {
+                let bindings = vars
+                    .iter()
+                    .map(|(symbol, sort)| Ok((symbol.0.clone(), self.resolve_sort(sort, &[])?)))
+                    .collect::<Result<Vec<_>, SortError>>()?;
+                let depth = locals.len();
+                locals.extend(bindings);
+                let result = self.check_bool(term, locals);
+                locals.truncate(depth);
+                result.map(|()| simple_sort("Bool"))
+            }
+            Term::Match {
+                term: scrutinee,
+                cases,
+            } => {
+                let scrutinee_sort = self.term_sort(scrutinee, locals)?;
+                let mut result: Option<Sort> = None;
+                for (pattern, body) in cases {
+                    let bindings = self.pattern_bindings(term, pattern, &scrutinee_sort)?;
+                    let depth = locals.len();
+                    locals.extend(bindings);
+                    let sort = match &result {
+                        Some(expected) => self.check_sort(body, expected, locals).map(|()| None),
+                        None => self.term_sort(body, locals).map(Some),
+                    };
+                    locals.truncate(depth);
+                    if let Some(sort) = sort? {
+                        result = Some(sort);
+                    }
+                }
+                result.ok_or_else(|| SortError::Invalid {
+                    term: term.clone(),
+                    message: "`match` without cases".to_string(),
+                })
+            }
+            Term::Attributes { term, .. } => self.term_sort(term, locals),
+        }
+    }
+
+    // Compute the local bindings introduced by a `match` pattern.
+    fn pattern_bindings(
+        &self,
+        term: &Term,
+        pattern: &[Symbol],
+        scrutinee_sort: &Sort,
+    ) -> Result<Vec<(String, Sort)>, SortError> {
+        let (constructor, variables) = match pattern.split_first() {
+            Some(split) => split,
+            None => {
+                return Err(SortError::Invalid {
+                    term: term.clone(),
+                    message: "empty pattern".to_string(),
+                })
+            }
+        };
+        if variables.is_empty() && !self.scope.constructors.contains(&constructor.0) {
+            // A variable pattern.
+            return Ok(vec![(constructor.0.clone(), scrutinee_sort.clone())]);
+        }
+        let function = match self.scope.functions.get(&constructor.0) {
+            Some(function) if self.scope.constructors.contains(&constructor.0) => function,
+            _ => {
+                return Err(SortError::UnknownSymbol {
+                    symbol: constructor.0.clone(),
+                    term: term.clone(),
+                })
+            }
+        };
+        let mut bindings = BTreeMap::new();
+        if !match_sort(
+            &function.result,
+            scrutinee_sort,
+            &function.parameters,
+            &mut bindings,
+        ) {
+            return Err(SortError::Mismatch {
+                term: term.clone(),
+                expected: function.result.clone(),
+                actual: scrutinee_sort.clone(),
+            });
+        }
+        if variables.len() != function.arguments.len() {
+            return Err(SortError::Arity {
+                term: term.clone(),
+                expected: function.arguments.len(),
+                actual: variables.len(),
+            });
+        }
+        Ok(variables
+            .iter()
+            .zip(&function.arguments)
+            .map(|(variable, sort)| (variable.0.clone(), substitute_sort(sort, &bindings)))
+            .collect())
+    }
+
+    // Compute the sort of the application of a qualified identifier to arguments of the
+    // given sorts.
+    fn application_sort(
+        &self,
+        term: &Term,
+        qual_identifier: &QualIdentifier,
+        arguments: &[Term],
+        sorts: Vec<Sort>,
+        locals: &[(String, Sort)],
+    ) -> Result<Sort, SortError> {
+        let (identifier, annotation) = match qual_identifier {
+            QualIdentifier::Simple { identifier } => (identifier, None),
+            QualIdentifier::Sorted { identifier, sort } => {
+                (identifier, Some(self.resolve_sort(sort, &[])?))
+            }
+        };
+        let check_annotation = |sort: Sort| match &annotation {
+            Some(expected) if *expected != sort => Err(SortError::Mismatch {
+                term: term.clone(),
+                expected: expected.clone(),
+                actual: sort,
+            }),
+            _ => Ok(sort),
+        };
+        let name = match identifier {
+            Identifier::Simple { symbol } => Some(symbol.0.clone()),
+            Identifier::Indexed { symbol, indices } if symbol.0 == "is" => match &indices[..] {
+                [Index::Symbol(constructor)] => Some(format!("is-{}", constructor.0)),
+                _ => None,
+            },
+            Identifier::Indexed { .. } => None,
+        };
+        if let Some(name) = &name {
+            if arguments.is_empty() {
+                if let Some((_, sort)) = locals.iter().rev().find(|(symbol, _)| symbol == name) {
+                    return check_annotation(sort.clone());
+                }
+            }
+            if let Some(function) = self.scope.functions.get(name) {
+                return self.instantiate(term, function, arguments, sorts, annotation.as_ref());
+            }
+        }
+        match self
+            .signature
+            .function_sort(identifier, &sorts, annotation.as_ref())
+        {
+            Some(Ok(sort)) => check_annotation(sort),
+            Some(Err(ApplicationError::Arity(expected))) => Err(SortError::Arity {
+                term: term.clone(),
+                expected,
+                actual: sorts.len(),
+            }),
+            Some(Err(ApplicationError::Argument(i, expected))) => Err(SortError::Mismatch {
+                term: arguments[i].clone(),
+                expected,
+                actual: sorts[i].clone(),
+            }),
+            Some(Err(ApplicationError::Other(message))) => Err(SortError::Invalid {
+                term: term.clone(),
+                message,
+            }),
+            None => Err(SortError::UnknownSymbol {
+                symbol: identifier.to_string(),
+                term: term.clone(),
+            }),
+        }
+    }
+
+    // Compute the sort of the application of a declared function, instantiating its sort
+    // parameters if needed.
+    fn instantiate(
+        &self,
+        term: &Term,
+        function: &FunctionSort,
+        arguments: &[Term],
+        sorts: Vec<Sort>,
+        annotation: Option<&Sort>,
+    ) -> Result<Sort, SortError> {
+        if sorts.len() != function.arguments.len() {
+            return Err(SortError::Arity {
+                term: term.clone(),
+                expected: function.arguments.len(),
+                actual: sorts.len(),
+            });
+        }
+        let mut bindings = BTreeMap::new();
+        for ((argument, sort), expected) in arguments.iter().zip(sorts).zip(&function.arguments) {
+            if !match_sort(expected, &sort, &function.parameters, &mut bindings) {
+                return Err(SortError::Mismatch {
+                    term: argument.clone(),
+                    expected: substitute_sort(expected, &bindings),
+                    actual: sort,
+                });
+            }
+        }
+        if let Some(annotation) = annotation {
+            if !match_sort(
+                &function.result,
+                annotation,
+                &function.parameters,
+                &mut bindings,
+            ) {
+                return Err(SortError::Mismatch {
+                    term: term.clone(),
+                    expected: annotation.clone(),
+                    actual: substitute_sort(&function.result, &bindings),
+                });
+            }
+        }
+        if function
+            .parameters
+            .iter()
+            .any(|p| !bindings.contains_key(p))
+        {
+            return Err(SortError::Invalid {
+                term: term.clone(),
+                message: "ambiguous sort, an `as` annotation is required".to_string(),
+            });
+        }
+        Ok(substitute_sort(&function.result, &bindings))
+    }
+}
+
+// Match a sort against a pattern whose parameters are bound in `bindings`.
+fn match_sort(
+    pattern: &Sort,
+    sort: &Sort,
+    parameters: &[String],
+    bindings: &mut BTreeMap<String, Sort>,
+) -> bool {
+    match (pattern, sort) {
+        (
+            Sort::Simple {
+                identifier: Identifier::Simple { symbol },
+            },
+            _,
+        ) if parameters.contains(&symbol.0) => match bindings.get(&symbol.0) {
+            Some(bound) => bound == sort,
+            None => {
+                bindings.insert(symbol.0.clone(), sort.clone());
+                true
+            }
+        },
+        (
+            Sort::Parameterized {
+                identifier: i1,
+                parameters: p1,
+            },
+            Sort::Parameterized {
+                identifier: i2,
+                parameters: p2,
+            },
+        ) => {
+            i1 == i2
+                && p1.len() == p2.len()
+                && p1
+                    .iter()
+                    .zip(p2)
+                    .all(|(s1, s2)| match_sort(s1, s2, parameters, bindings))
+        }
+        _ => pattern == sort,
+    }
+}
+
+// Replace the sort parameters in a sort.
+pub(crate) fn substitute_sort(sort: &Sort, bindings: &BTreeMap<String, Sort>) -> Sort {
+    match sort {
+        Sort::Simple {
+            identifier: Identifier::Simple { symbol },
+        } if bindings.contains_key(&symbol.0) => bindings[&symbol.0].clone(),
+        Sort::Simple { .. } => sort.clone(),
+        Sort::Parameterized {
+            identifier,
+            parameters,
+        } => Sort::Parameterized {
+            identifier: identifier.clone(),
+            parameters: parameters
+                .iter()
+                .map(|s| substitute_sort(s, bindings))
+                .collect(),
+        },
+    }
+}
+
+fn constant_sort(constant: &Constant) -> Sort {
+    let bit_vector = |width: usize| Sort::Simple {
+        identifier: Identifier::Indexed {
+            symbol: Symbol("BitVec".to_string()),
+            indices: vec![Index::Numeral(Numeral::from(width))],
+        },
+    };
+    match constant {
+        Constant::Numeral(_) => simple_sort("Int"),
+        Constant::Decimal(_) => simple_sort("Real"),
+        Constant::Hexadecimal(digits) => bit_vector(4 * digits.len()),
+        Constant::Binary(digits) => bit_vector(digits.len()),
+        Constant::String(_) => simple_sort("String"),
+    }
+}
+
+fn simple_sort(name: &str) -> Sort {
+    Sort::Simple {
+        identifier: Identifier::Simple {
+            symbol: Symbol(name.to_string()),
+        },
+    }
+}
+
+fn symbol_term(name: &str) -> Term {
+    Term::QualIdentifier(QualIdentifier::Simple {
+        identifier: Identifier::Simple {
+            symbol: Symbol(name.to_string()),
+        },
+    })
+}
+
+#[test]
+fn test_sort_checker() {
+    let input = r#"
+(declare-sort U 0)
+(define-sort Map (T) (Array Int T))
+(declare-datatypes ((List 1)) ((par (T) ((nil) (cons (head T) (tail (List T)))))))
+(declare-fun m () (Map U))
+(declare-fun l () (List Int))
+(define-fun f ((x Int) (u U)) Bool (= (select (store m x u) 0) u))
+(push 1)
+(declare-const y Real)
+(assert (> (* 2.0 y) (to_real (head l))))
+(pop 1)
+(assert (forall ((u U)) (let ((b (f 1 u))) (=> b (not (= l (as nil (List Int))))))))
+(assert (match l ((nil true) ((cons h t) (and ((_ is cons) t) (> h 0))))))
+(assert (= ((as const (Array Int Int)) 0) (store ((as const (Array Int Int)) 1) 0 0)))
+(check-sat)
+"#;
+    let commands = crate::parse_commands(input).unwrap();
+    let mut checker = SortChecker::new(SmtLibTheories);
+    checker.check_commands(&commands).unwrap();
+    assert_eq!(
+        checker.sort_of(&symbol_term("m")).unwrap().to_string(),
+        "(Array Int U)"
+    );
+    assert!(matches!(
+        checker.sort_of(&symbol_term("y")),
+        Err(SortError::UnknownSymbol { .. })
+    ));
+}
+
+#[test]
+fn test_sort_errors() {
+    let check = |input: &str| {
+        let commands = crate::parse_commands(input).unwrap();
+        SortChecker::new(SmtLibTheories)
+            .check_commands(&commands)
+            .unwrap_err()
+            .to_string()
+    };
+    assert_eq!(
+        check("(declare-fun x () Int) (assert (and (> x 0) (+ x 1)))"),
+        "ill-sorted term `(+ x 1)`: expected sort `Bool`, found `Int`"
+    );
+    assert_eq!(
+        check("(declare-fun f (Int) Bool) (assert (f true))"),
+        "ill-sorted term `true`: expected sort `Int`, found `Bool`"
+    );
+    assert_eq!(
+        check("(declare-fun f (Int) Bool) (assert (f 1 2))"),
+        "ill-sorted term `(f 1 2)`: expected 1 arguments, found 2"
+    );
+    assert_eq!(check("(assert (> x 0))"), "unknown symbol `x` in term `x`");
+    assert_eq!(
+        check("(declare-const a (Array Int))"),
+        "unknown sort `(Array Int)`"
+    );
+    assert_eq!(
+        check("(define-fun g ((x Int)) Int (ite (> x 0) x 0.5))"),
+        "ill-sorted term `0.5`: expected sort `Int`, found `Real`"
+    );
+}
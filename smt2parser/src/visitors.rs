@@ -70,7 +70,7 @@ pub trait SExprVisitor<Constant, Symbol, Keyword> {
     fn visit_application_s_expr(&mut self, values: Vec<Self::T>) -> Result<Self::T, Self::E>;
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Index<Symbol = crate::concrete::Symbol> {
     Numeral(Numeral),
     Symbol(Symbol),
@@ -91,7 +91,7 @@ impl<S> Index<S> {
 }
 
 /// Concrete identifier.
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Identifier<Symbol = crate::concrete::Symbol> {
     Simple {
         symbol: Symbol,
@@ -148,7 +148,7 @@ pub trait QualIdentifierVisitor<Identifier, Sort> {
     ) -> Result<Self::T, Self::E>;
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum AttributeValue<
     Constant = crate::concrete::Constant,
     Symbol = crate::concrete::Symbol,
@@ -236,9 +236,16 @@ pub trait TermVisitor<Constant, QualIdentifier, Keyword, SExpr, Symbol, Sort> {
         term: Self::T,
         attributes: Vec<(Keyword, AttributeValue<Constant, Symbol, SExpr>)>,
     ) -> Result<Self::T, Self::E>;
+
+    /// Called before visiting a concrete term and its subterms. Returning a value skips
+    /// the visit of the term, which produces this value instead. By default, all terms
+    /// are visited.
+    fn skip_term(&mut self, _term: &crate::concrete::Term) -> Option<Result<Self::T, Self::E>> {
+        None
+    }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct ConstructorDec<Symbol, Sort> {
     pub symbol: Symbol,
     pub selectors: Vec<(Symbol, Sort)>,
@@ -270,7 +277,7 @@ impl<T1, T2> ConstructorDec<T1, T2> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct DatatypeDec<Symbol = crate::concrete::Symbol, Sort = crate::concrete::Sort> {
     pub parameters: Vec<Symbol>,
     pub constructors: Vec<ConstructorDec<Symbol, Sort>>,
@@ -307,7 +314,7 @@ impl<T1, T2> DatatypeDec<T1, T2> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct FunctionDec<Symbol = crate::concrete::Symbol, Sort = crate::concrete::Sort> {
     pub name: Symbol,
     pub parameters: Vec<(Symbol, Sort)>,
@@ -1,18 +1,72 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use std::sync::{Arc, OnceLock};
 
-use crate::concrete::{Command, Symbol, SyntaxBuilder, Term};
+use crate::concrete::{
+    AttributeValue, Command, Constant, FunctionDec, Identifier, Keyword, QualIdentifier, SExpr,
+    Sort, Symbol, SyntaxBuilder, Term,
+};
+use crate::dependencies::{conjuncts, DependencyGraph};
+use crate::inlining::DefinitionInliner;
+use crate::logic::{detect_fragment, detect_logic, Fragment};
+use crate::model::{Model, Value};
+use crate::normal_forms::TseitinEncoder;
+use crate::printer::{natural_chunks, to_canonical_string};
+use crate::renaming::SymbolFactory;
+use crate::rewriter::free_symbols;
+use crate::schema::{ActionJson, SMTProblemJson, StateVariableJson, VMTModelJson, SCHEMA_VERSION};
+use crate::simplification::Simplifier;
+use crate::solver::{SatResult, SmtSolver, SolverError};
+use crate::stats::TermMetrics;
+use crate::symbols::SymbolTable;
+use crate::visitors::{SymbolKind, SymbolVisitor};
+use crate::Numeral;
+use num::ToPrimitive;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 static PROPERTY_ATTRIBUTE: &str = "invar-property";
 static TRANSITION_ATTRIBUTE: &str = "trans";
 static INITIAL_ATTRIBUTE: &str = "init";
 
-/// VMTModel represents a transition system given in VMT format. 
+/// Error reported by [`VMTModel::checked_from`] on ill-formed models, and by the
+/// transformations of an [`SMTProblem`] that require a property.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum VMTError {
+    #[error("not enough commands for a VMT model")]
+    NotEnoughCommands,
+    #[error("expected a definition with attribute `:{attribute}`, found `{command}`")]
+    MissingComponent { attribute: String, command: Command },
+    #[error("unsupported command in a VMT model: `{0}`")]
+    UnsupportedCommand(Command),
+    #[error("ill-formed variable relationship: `{0}`")]
+    InvalidRelationship(Command),
+    #[error("undeclared variable `{0}`")]
+    UndeclaredVariable(String),
+    #[error("invalid scope level: `{0}`")]
+    InvalidLevel(Command),
+    #[error("no property assertion")]
+    MissingProperty,
+}
+
+/// Declarations of a VMT model in scope, while reading its commands.
+#[derive(Clone, Default)]
+struct Declarations<'a> {
+    variable_commands: HashMap<String, Command>,
+    sorts: Vec<Command>,
+    functions: Vec<Command>,
+    variable_relationships: Vec<&'a Command>,
+}
+
+/// VMTModel represents a transition system given in VMT format.
 /// The VMT specification is no longer available but there is an example here:
 /// https://es-static.fbk.eu/people/griggio/ic3ia/
 /// The property is specifie
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct VMTModel {
     sorts: Vec<Command>,
+    functions: Vec<Command>,
     state_variables: Vec<Variable>,
     actions: Vec<Action>,
     initial_condition: Term,
@@ -21,51 +75,101 @@ pub struct VMTModel {
 }
 
 impl VMTModel {
-    pub fn checked_from(commands: Vec<Command>) -> Result<Self, ()> {
+    /// Read a VMT model from its commands, the last three of which define the initial
+    /// condition, the transition relation, and the property.
+    pub fn checked_from(commands: Vec<Command>) -> Result<Self, VMTError> {
         let number_of_commands = commands.len();
-        assert!(number_of_commands > 3, "Not enough commands for VMT model!");
+        if number_of_commands <= 3 {
+            return Err(VMTError::NotEnoughCommands);
+        }
         let property_condition: Term =
-            get_transition_system_component(&commands[number_of_commands - 1], PROPERTY_ATTRIBUTE);
+            get_transition_system_component(&commands[number_of_commands - 1], PROPERTY_ATTRIBUTE)?;
         let transition_condition: Term = get_transition_system_component(
             &commands[number_of_commands - 2],
             TRANSITION_ATTRIBUTE,
-        );
+        )?;
         let initial_condition: Term =
-            get_transition_system_component(&commands[number_of_commands - 3], INITIAL_ATTRIBUTE);
-        let mut variable_commands: HashMap<String, Command> = HashMap::new();
-        let mut sorts: Vec<Command> = vec![];
-        let mut variable_relationships = vec![];
-        for (i, command) in commands.iter().enumerate() {
-            if i < number_of_commands - 3 {
-                // Check whether a variable should be action, state, or local
-                match command {
-                    Command::DeclareFun {
-                        symbol,
-                        parameters: _,
-                        sort: _,
-                    } => {
-                        variable_commands.insert(symbol.0.clone(), command.clone());
-                    }
-                    Command::DefineFun { sig: _, term: _ } => {
-                        variable_relationships.push(command);
+            get_transition_system_component(&commands[number_of_commands - 3], INITIAL_ATTRIBUTE)?;
+        let mut declarations = Declarations::default();
+        // Declarations saved by `push` commands.
+        let mut scopes: Vec<Declarations> = vec![];
+        for command in &commands[..number_of_commands - 3] {
+            // Check whether a variable should be action, state, or local
+            match command {
+                Command::DeclareFun {
+                    symbol: _,
+                    parameters,
+                    sort: _,
+                } if !parameters.is_empty() => {
+                    declarations.functions.push(command.clone());
+                }
+                Command::DeclareFun {
+                    symbol,
+                    parameters: _,
+                    sort: _,
+                } => {
+                    declarations
+                        .variable_commands
+                        .insert(symbol.0.clone(), command.clone());
+                }
+                Command::DefineFun { sig: _, term: _ } => {
+                    declarations.variable_relationships.push(command);
+                }
+                Command::DeclareSort { .. }
+                | Command::DeclareDatatype { .. }
+                | Command::DeclareDatatypes { .. } => {
+                    // State variables and actions may range over datatypes.
+                    declarations.sorts.push(command.clone());
+                }
+                Command::Push { level } => {
+                    let level = level
+                        .to_usize()
+                        .ok_or_else(|| VMTError::InvalidLevel(command.clone()))?;
+                    for _ in 0..level {
+                        scopes.push(declarations.clone());
                     }
-                    Command::DeclareSort {
-                        symbol: _,
-                        arity: _,
-                    } => {
-                        sorts.push(command.clone());
+                }
+                Command::Pop { level } => {
+                    let level = level
+                        .to_usize()
+                        .filter(|level| *level <= scopes.len())
+                        .ok_or_else(|| VMTError::InvalidLevel(command.clone()))?;
+                    for _ in 0..level {
+                        declarations = scopes.pop().unwrap();
                     }
-                    _ => {
-                        panic!("Unknown VMT command: {:?}", command);
+                }
+                Command::Reset => {
+                    declarations = Declarations::default();
+                    scopes.clear();
+                }
+                Command::ResetAssertions => {
+                    if !scopes.is_empty() {
+                        declarations = scopes.swap_remove(0);
+                        scopes.clear();
                     }
                 }
+                _ => return Err(VMTError::UnsupportedCommand(command.clone())),
             }
         }
+        let Declarations {
+            variable_commands,
+            sorts,
+            functions,
+            variable_relationships,
+        } = declarations;
         let (state_variables, actions) =
-            get_variables_and_actions(variable_relationships, variable_commands);
+            get_variables_and_actions(variable_relationships, variable_commands)?;
+        log::debug!(
+            "VMT model with {} sorts, {} functions, {} state variables, and {} actions",
+            sorts.len(),
+            functions.len(),
+            state_variables.len(),
+            actions.len()
+        );
 
         Ok(VMTModel {
             sorts,
+            functions,
             state_variables,
             actions,
             initial_condition,
@@ -75,9 +179,47 @@ impl VMTModel {
     }
 
     pub fn print_stats(&self) {
-        println!("Number of Variables: {}", self.state_variables.len());
-        println!("Number of Actions: {}", self.actions.len());
-        println!("Number of Sorts: {}", self.sorts.len());
+        for (name, count) in self.stats() {
+            println!("Number of {}: {}", name, count);
+        }
+        let mut total = TermMetrics::default();
+        for (name, metrics) in self.metrics() {
+            println!(
+                "{}: {} nodes, depth {}, {} quantifiers",
+                name, metrics.size, metrics.depth, metrics.quantifiers
+            );
+            total.add(&metrics);
+        }
+        let operators = total
+            .operators
+            .iter()
+            .map(|(operator, count)| format!("{} {}", operator, count))
+            .collect::<Vec<_>>();
+        println!("Operators: {}", operators.join(", "));
+    }
+
+    /// Number of state variables, actions, and sorts of the model.
+    pub fn stats(&self) -> Vec<(&'static str, usize)> {
+        vec![
+            ("Variables", self.state_variables.len()),
+            ("Actions", self.actions.len()),
+            ("Sorts", self.sorts.len()),
+        ]
+    }
+
+    /// Complexity of the initial condition, the transition relation, and the property.
+    pub fn metrics(&self) -> Vec<(&'static str, TermMetrics)> {
+        vec![
+            (
+                "Initial condition",
+                TermMetrics::of(&self.initial_condition),
+            ),
+            (
+                "Transition relation",
+                TermMetrics::of(&self.transition_condition),
+            ),
+            ("Property", TermMetrics::of(&self.property_condition)),
+        ]
     }
 
     pub fn print_raw_smtlib2(&self) {
@@ -123,27 +265,201 @@ impl VMTModel {
         );
     }
 
+    /// The fragment of the model, including its definitions. See `Fragment`.
+    pub fn fragment(&self) -> Fragment {
+        detect_fragment(&self.to_commands())
+    }
+
+    /// The sorts, functions, state variables, and actions of the model.
+    pub fn symbol_table(&self) -> SymbolTable {
+        SymbolTable::from_commands(&self.to_commands())
+    }
+
+    /// Names and sorts of the state variables, in order of declaration.
+    pub fn state_variables(&self) -> Vec<(String, Sort)> {
+        self.state_variables
+            .iter()
+            .map(|var| {
+                (
+                    var.get_current_variable_name().clone(),
+                    sort_of(&var.current),
+                )
+            })
+            .collect()
+    }
+
+    /// Dependencies of the next values of the state variables on the current state
+    /// variables and actions. The next value of a variable depends on the current
+    /// variables and actions that occur in the same conjunct of the transition condition.
+    pub fn dependency_graph(&self) -> DependencyGraph {
+        let next_variables = self.get_all_next_variable_names();
+        let current_variables = self.get_all_current_variable_names();
+        let mut graph = DependencyGraph::new(
+            self.state_variables
+                .iter()
+                .map(|var| var.get_current_variable_name().clone())
+                .collect(),
+            self.actions
+                .iter()
+                .map(|action| action.get_current_action_name().clone())
+                .collect(),
+        );
+        for conjunct in conjuncts(&self.transition_condition) {
+            let symbols = free_symbols(conjunct);
+            let sources = symbols
+                .iter()
+                .filter(|symbol| current_variables.contains(*symbol))
+                .collect::<Vec<_>>();
+            for variable in symbols
+                .iter()
+                .filter_map(|symbol| next_variables.get(symbol))
+            {
+                for source in &sources {
+                    graph.add_dependency(variable, source);
+                }
+            }
+        }
+        graph
+    }
+
+    pub fn transition(&self) -> &Term {
+        &self.transition_condition
+    }
+
+    pub fn property(&self) -> &Term {
+        &self.property_condition
+    }
+
+    /// Same model with another property.
+    pub fn with_property(&self, property: Term) -> Self {
+        VMTModel {
+            property_condition: property,
+            ..self.clone()
+        }
+    }
+
+    /// The model in VMT format.
+    pub fn to_commands(&self) -> Vec<Command> {
+        let mut commands = self.sorts.clone();
+        commands.extend(self.functions.iter().cloned());
+        for variable in &self.state_variables {
+            let next = AttributeValue::Symbol(Symbol(variable.get_next_variable_name().clone()));
+            commands.push(variable.current.clone());
+            commands.push(variable.next.clone());
+            commands.push(define_fun(
+                &format!(".{}", variable.get_current_variable_name()),
+                sort_of(&variable.current),
+                variable_term(variable.get_current_variable_name()),
+                "next",
+                next,
+            ));
+        }
+        for action in &self.actions {
+            let index = AttributeValue::Constant(Constant::Numeral(Numeral::from(0u32)));
+            commands.push(action.action_command.clone());
+            commands.push(define_fun(
+                &format!(".{}", action.get_current_action_name()),
+                sort_of(&action.action_command),
+                variable_term(action.get_current_action_name()),
+                "action",
+                index,
+            ));
+        }
+        let bool_sort = Sort::Simple {
+            identifier: Identifier::Simple {
+                symbol: Symbol("Bool".to_string()),
+            },
+        };
+        commands.push(define_fun(
+            "init",
+            bool_sort.clone(),
+            self.initial_condition.clone(),
+            INITIAL_ATTRIBUTE,
+            AttributeValue::Symbol(Symbol("true".to_string())),
+        ));
+        commands.push(define_fun(
+            "trans",
+            bool_sort.clone(),
+            self.transition_condition.clone(),
+            TRANSITION_ATTRIBUTE,
+            AttributeValue::Symbol(Symbol("true".to_string())),
+        ));
+        let index = AttributeValue::Constant(Constant::Numeral(Numeral::from(0u32)));
+        commands.push(define_fun(
+            "property",
+            bool_sort,
+            self.property_condition.clone(),
+            PROPERTY_ATTRIBUTE,
+            index,
+        ));
+        commands
+    }
+
+    /// The model in VMT format, one command per line.
+    pub fn to_vmt_string(&self) -> String {
+        self.to_commands()
+            .iter()
+            .map(|command| command.to_string() + "\n")
+            .collect()
+    }
+
+    /// The model in VMT format, with the state variables and the actions sorted by name.
+    /// See [`to_canonical_string`].
+    pub fn to_canonical_vmt_string(&self) -> String {
+        let mut model = self.clone();
+        model.state_variables.sort_by(|var1, var2| {
+            natural_chunks(var1.get_current_variable_name())
+                .cmp(&natural_chunks(var2.get_current_variable_name()))
+        });
+        model.actions.sort_by(|action1, action2| {
+            natural_chunks(action1.get_current_action_name())
+                .cmp(&natural_chunks(action2.get_current_action_name()))
+        });
+        to_canonical_string(&model.to_commands())
+    }
+
     pub fn unroll(&self, length: u8) -> SMTProblem {
-        let mut builder = VMTBuilder {
-            visitor: SyntaxBuilder,
-            current_variables: self.get_all_current_variable_names(),
-            next_variables: self.get_all_next_variable_names(),
-            step: 0,
+        let builder = self.builder();
+        let mut smt_problem = SMTProblem::new(&self.sorts, &self.functions);
+        log::debug!("Unrolling the transition relation {} times", length);
+
+        // The renaming of each step only depends on its index, so steps are rewritten in
+        // parallel, then added in order.
+        let mentions = Arc::new(builder.mentioning_subterms(&self.transition_condition));
+        let rewrite_steps = || {
+            (0..=length)
+                .into_par_iter()
+                .map(|step| {
+                    let mut builder = builder.at_step(step);
+                    // Must add variable definitions for each variable at each time step,
+                    // including time `length`.
+                    let definitions =
+                        builder.rewrite_definitions(&self.state_variables, &self.actions);
+                    let transition = (step < length).then(|| {
+                        builder.rewrite_term_with(&self.transition_condition, mentions.clone())
+                    });
+                    (definitions, transition)
+                })
+                .collect::<Vec<_>>()
         };
-        let mut smt_problem = SMTProblem::new(&self.sorts);
-
-        smt_problem.add_assertion(&self.initial_condition, builder.clone());
-        for _ in 0..length {
-            // Must add variable definitions for each variable at each time step.
-            smt_problem.add_definitions(&self.state_variables, &self.actions, builder.clone());
-            smt_problem.add_assertion(&self.transition_condition, builder.clone());
-            builder.add_step();
-        }
-        // Don't forget the variable definitions at time `length`.
-        smt_problem.add_definitions(&self.state_variables, &self.actions, builder.clone());
-        smt_problem.add_property_assertion(&self.property_condition, builder.clone());
+        let steps = match unroll_pool() {
+            Some(pool) => pool.install(rewrite_steps),
+            None => rewrite_steps(),
+        };
+        smt_problem.add_assertion(builder.at_step(0).rewrite_term(&self.initial_condition));
+        for (definitions, transition) in steps {
+            smt_problem.add_definitions(definitions);
+            if let Some(transition) = transition {
+                smt_problem.add_assertion(transition);
+            }
+        }
+        smt_problem.add_property_assertion(
+            builder
+                .at_step(length)
+                .rewrite_term(&self.property_condition),
+        );
         assert!(
-            smt_problem.init_and_trans_assertions.len() == (length + 1).into(),
+            smt_problem.init_and_trans_assertions.len() == usize::from(length) + 1,
             "Unrolling gives incorrect number of steps {} for length {}.",
             smt_problem.init_and_trans_assertions.len(),
             length
@@ -151,19 +467,136 @@ impl VMTModel {
         smt_problem
     }
 
-    fn get_all_current_variable_names(&self) -> Vec<String> {
-        let mut state_variable_names: Vec<String> = self
-            .state_variables
+    /// Induction step: the assumption holds in some state, and the property does not hold
+    /// after one transition. The property is inductive relative to the assumption if this
+    /// problem is unsatisfiable.
+    pub fn induction_step(&self, assumption: &Term) -> SMTProblem {
+        let mut builder = self.builder();
+        let mut smt_problem = SMTProblem::new(&self.sorts, &self.functions);
+        smt_problem
+            .add_definitions(builder.rewrite_definitions(&self.state_variables, &self.actions));
+        smt_problem.add_assertion(builder.rewrite_term(assumption));
+        smt_problem.add_assertion(builder.rewrite_term(&self.transition_condition));
+        builder.add_step();
+        smt_problem
+            .add_definitions(builder.rewrite_definitions(&self.state_variables, &self.actions));
+        smt_problem.add_property_assertion(builder.rewrite_term(&self.property_condition));
+        smt_problem
+    }
+
+    /// k-induction step: the property holds in `k` consecutive states linked by
+    /// transitions, and does not hold after one more transition. If this problem is
+    /// unsatisfiable and no counterexample exists within `k - 1` steps, the property holds
+    /// in all reachable states. States are not required to be distinct, so some
+    /// properties are not k-inductive for any `k`.
+    pub fn k_induction_step(&self, k: u8) -> SMTProblem {
+        let builder = self.builder();
+        let mut smt_problem = SMTProblem::new(&self.sorts, &self.functions);
+        for step in 0..k {
+            let mut builder = builder.at_step(step);
+            smt_problem
+                .add_definitions(builder.rewrite_definitions(&self.state_variables, &self.actions));
+            smt_problem.add_assertion(builder.rewrite_term(&self.property_condition));
+            smt_problem.add_assertion(builder.rewrite_term(&self.transition_condition));
+        }
+        let mut builder = builder.at_step(k);
+        smt_problem
+            .add_definitions(builder.rewrite_definitions(&self.state_variables, &self.actions));
+        smt_problem.add_property_assertion(builder.rewrite_term(&self.property_condition));
+        smt_problem
+    }
+
+    /// Houdini: the largest subset of the candidates whose conjunction holds in the initial
+    /// states and is inductive. Candidates are dropped when they are falsified by a model
+    /// of the solver, either an initial state or the target of a counterexample to
+    /// induction, until a fixpoint. The solver must produce models.
+    ///
+    /// Candidates that cannot be evaluated in a model, e.g. quantified ones, are dropped
+    /// only when no other candidate is falsified by the model.
+    pub fn houdini<S: SmtSolver>(
+        &self,
+        candidates: &[Term],
+        solver: &mut S,
+    ) -> Result<Vec<Term>, SolverError> {
+        let mut invariants = candidates.to_vec();
+        while !invariants.is_empty() {
+            let initial = self.with_property(conjunction(&invariants)).unroll(0);
+            match self.drop_falsified(solver, &initial, 0, &invariants)? {
+                Some(kept) => invariants = kept,
+                None => break,
+            }
+        }
+        while !invariants.is_empty() {
+            let assumption = conjunction(&invariants);
+            let step = self
+                .with_property(assumption.clone())
+                .induction_step(&assumption);
+            match self.drop_falsified(solver, &step, 1, &invariants)? {
+                Some(kept) => invariants = kept,
+                None => break,
+            }
+        }
+        Ok(invariants)
+    }
+
+    // Check a problem asserting that some candidate does not hold at the given step. If it
+    // is satisfiable, return the candidates that are not falsified by the model.
+    fn drop_falsified<S: SmtSolver>(
+        &self,
+        solver: &mut S,
+        problem: &SMTProblem,
+        step: u8,
+        candidates: &[Term],
+    ) -> Result<Option<Vec<Term>>, SolverError> {
+        let model = match solve(solver, problem)? {
+            Some(model) => model,
+            None => return Ok(None),
+        };
+        let mut builder = self.builder().at_step(step);
+        let values = candidates
+            .iter()
+            .map(|candidate| model.evaluate(&builder.rewrite_term(candidate)).ok())
+            .collect::<Vec<_>>();
+        let falsified = values.contains(&Some(Value::Bool(false)));
+        let kept = candidates
+            .iter()
+            .zip(values)
+            .filter(|(_, value)| match value {
+                Some(value) => *value != Value::Bool(false),
+                None => falsified,
+            })
+            .map(|(candidate, _)| candidate.clone())
+            .collect::<Vec<_>>();
+        if kept.len() == candidates.len() {
+            return Err(SolverError::Solver(
+                "the model does not falsify any candidate".to_string(),
+            ));
+        }
+        Ok(Some(kept))
+    }
+
+    fn builder(&self) -> VMTBuilder {
+        VMTBuilder {
+            visitor: SyntaxBuilder,
+            current_variables: self.get_all_current_variable_names(),
+            next_variables: self.get_all_next_variable_names(),
+            step: 0,
+            renamed: HashMap::new(),
+            mentions: None,
+            bound: HashMap::new(),
+        }
+    }
+
+    fn get_all_current_variable_names(&self) -> HashSet<String> {
+        self.state_variables
             .iter()
             .map(|var| var.get_current_variable_name().clone())
-            .collect();
-        let mut action_names: Vec<String> = self
-            .actions
-            .iter()
-            .map(|action| action.get_current_action_name().clone())
-            .collect();
-        state_variable_names.append(&mut action_names);
-        state_variable_names
+            .chain(
+                self.actions
+                    .iter()
+                    .map(|action| action.get_current_action_name().clone()),
+            )
+            .collect()
     }
 
     fn get_all_next_variable_names(&self) -> HashMap<String, String> {
@@ -179,62 +612,316 @@ impl VMTModel {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Serialize, Deserialize)]
 pub struct SMTProblem {
     sorts: Vec<Command>,
+    functions: Vec<Command>,
     definitions: Vec<Command>,
     init_and_trans_assertions: Vec<Term>,
     property_assertion: Option<Term>,
+    /// Whether `to_smtlib2` starts with a `set-logic` command.
+    #[serde(skip)]
+    logic: bool,
 }
 
 impl SMTProblem {
-    fn new(sorts: &Vec<Command>) -> Self {
+    fn new(sorts: &[Command], functions: &[Command]) -> Self {
         Self {
-            sorts: sorts.clone(),
+            sorts: sorts.to_vec(),
+            functions: functions.to_vec(),
             definitions: vec![],
             init_and_trans_assertions: vec![],
-            property_assertion: None
+            property_assertion: None,
+            logic: false,
         }
     }
 
-    fn add_assertion(&mut self, condition: &Term, mut builder: VMTBuilder) {
-        let rewritten_condition = condition.clone().accept(&mut builder).unwrap();
-        self.init_and_trans_assertions.push(rewritten_condition);
+    /// A problem made of the given declarations, definitions, and assertions, checking
+    /// the property by asserting its negation.
+    pub(crate) fn from_parts(
+        sorts: Vec<Command>,
+        functions: Vec<Command>,
+        definitions: Vec<Command>,
+        assertions: Vec<Term>,
+        property: Term,
+    ) -> Self {
+        Self {
+            sorts,
+            functions,
+            definitions,
+            init_and_trans_assertions: assertions,
+            property_assertion: Some(property),
+            logic: false,
+        }
+    }
+
+    /// Start the SMT-LIB output of the problem with a `set-logic` command, naming the
+    /// smallest standard logic of the problem as given by [`detect_logic`], or `ALL`.
+    pub fn with_logic(mut self) -> Self {
+        self.logic = true;
+        self
+    }
+
+    // The `set-logic` command to print, if any.
+    fn logic_command(&self) -> Option<Command> {
+        if self.logic {
+            Some(detect_logic(&self.to_commands()).to_command())
+        } else {
+            None
+        }
+    }
+
+    fn add_assertion(&mut self, condition: Term) {
+        self.init_and_trans_assertions.push(condition);
     }
 
     /// Need to assert the negation of the property given in the VMTModel for BMC.
-    fn add_property_assertion(&mut self, condition: &Term, mut builder: VMTBuilder) {
-        let rewritten_property = condition.clone().accept(&mut builder).unwrap();
-        self.property_assertion = Some(rewritten_property);
+    fn add_property_assertion(&mut self, condition: Term) {
+        self.property_assertion = Some(condition);
     }
 
-    fn add_definitions(
-        &mut self,
-        state_variables: &Vec<Variable>,
-        actions: &Vec<Action>,
-        mut builder: VMTBuilder,
-    ) {
-        for state_variable in state_variables {
-            let definition_at_time = state_variable.current.clone().accept(&mut builder).unwrap();
-            self.definitions.push(definition_at_time);
+    fn add_definitions(&mut self, definitions: Vec<Command>) {
+        self.definitions.extend(definitions);
+    }
+
+    // Definitions and assertions are printed once, since those that do not mention state
+    // variables are the same at every step, and solvers reject repeated definitions.
+    fn unique_definitions(&self) -> impl Iterator<Item = &Command> {
+        unique(&self.definitions)
+    }
+
+    fn unique_assertions(&self) -> impl Iterator<Item = &Term> {
+        unique(&self.init_and_trans_assertions)
+    }
+
+    /// Fold the constants of the definitions and assertions. See `Simplifier`.
+    pub fn simplify(&mut self) {
+        let simplify = |term: Term| term.accept(&mut Simplifier::default()).unwrap();
+        self.definitions = self
+            .definitions
+            .drain(..)
+            .map(|command| command.accept(&mut Simplifier::default()).unwrap())
+            .collect();
+        self.init_and_trans_assertions = self
+            .init_and_trans_assertions
+            .drain(..)
+            .map(simplify)
+            .collect();
+        self.property_assertion = self.property_assertion.take().map(simplify);
+    }
+
+    /// Replace the universally quantified variables of the property that are not under a
+    /// negation by fresh constants, so that the negation of the property is ground.
+    pub fn skolemize_property(&mut self) -> Result<(), VMTError> {
+        let property = self
+            .property_assertion
+            .take()
+            .ok_or(VMTError::MissingProperty)?;
+        let mut symbols = SymbolFactory::new(
+            self.sorts
+                .iter()
+                .chain(&self.functions)
+                .chain(&self.definitions),
+        );
+        for assertion in &self.init_and_trans_assertions {
+            symbols.avoid_term(assertion);
         }
-        for action in actions {
-            let action_at_time = action.action_command.clone().accept(&mut builder).unwrap();
-            self.definitions.push(action_at_time);
+        symbols.avoid_term(&property);
+        self.property_assertion = Some(self.skolemize(property, &mut symbols));
+        Ok(())
+    }
+
+    fn skolemize(&mut self, term: Term, symbols: &mut SymbolFactory) -> Term {
+        match term {
+            // Bind each variable to its fresh constant with a `let`, which respects shadowing.
+            Term::Forall { vars, term } => {
+                let mut var_bindings = vec![];
+                for (symbol, sort) in vars {
+                    let name = symbols.fresh(&format!("{}_sk", symbol.0));
+                    log::debug!("Skolemizing {} as {}", symbol.0, name);
+                    self.functions.push(Command::DeclareFun {
+                        symbol: Symbol(name.clone()),
+                        parameters: vec![],
+                        sort,
+                    });
+                    let constant = Term::QualIdentifier(QualIdentifier::Simple {
+                        identifier: Identifier::Simple {
+                            symbol: Symbol(name),
+                        },
+                    });
+                    var_bindings.push((symbol, constant));
+                }
+                Term::Let {
+                    var_bindings,
+                    term: Box::new(self.skolemize(*term, symbols)),
+                }
+            }
+            Term::Application {
+                qual_identifier:
+                    QualIdentifier::Simple {
+                        identifier: Identifier::Simple { symbol },
+                    },
+                arguments,
+            } if symbol.0 == "and" || symbol.0 == "or" => Term::Application {
+                qual_identifier: QualIdentifier::Simple {
+                    identifier: Identifier::Simple { symbol },
+                },
+                arguments: arguments
+                    .into_iter()
+                    .map(|argument| self.skolemize(argument, symbols))
+                    .collect(),
+            },
+            term => term,
         }
     }
+
+    // The initial and transition assertions, in order, and the property.
+    pub(crate) fn assertions(&self) -> (&[Term], Option<&Term>) {
+        (
+            &self.init_and_trans_assertions,
+            self.property_assertion.as_ref(),
+        )
+    }
+
     pub fn to_smtlib2(&self) -> String {
-        assert!(self.property_assertion.is_some(), "No property assertion for SMTProblem!");
-        let sort_names = self.sorts.iter().map(|sort| sort.to_string()).collect::<Vec<String>>().join("\n");
-        let defs = self.definitions.iter().map(|def| def.to_string()).collect::<Vec<String>>().join("\n");
-        let init_and_trans_asserts = self.init_and_trans_assertions.iter().map(|assert_term| assert(assert_term)).collect::<Vec<String>>().join("\n");
+        assert!(
+            self.property_assertion.is_some(),
+            "No property assertion for SMTProblem!"
+        );
+        let sort_names = self
+            .sorts
+            .iter()
+            .map(|sort| sort.to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+        let functions = self
+            .functions
+            .iter()
+            .map(|function| function.to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+        let defs = self
+            .unique_definitions()
+            .map(|def| def.to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+        let init_and_trans_asserts = self
+            .unique_assertions()
+            .map(assert)
+            .collect::<Vec<String>>()
+            .join("\n");
         let prop = self.property_assertion.clone().unwrap();
         let property_assert = assert_negation(&prop);
-        format!("{}\n{}\n{}\n{}", sort_names, defs, init_and_trans_asserts, property_assert)
+        let logic = self
+            .logic_command()
+            .map_or_else(String::new, |command| format!("{}\n", command));
+        format!(
+            "{}{}\n{}\n{}\n{}\n{}",
+            logic, sort_names, functions, defs, init_and_trans_asserts, property_assert
+        )
+    }
+
+    /// Same problem as `to_smtlib2`, in canonical form. See [`to_canonical_string`].
+    pub fn to_canonical_smtlib2(&self) -> String {
+        let mut commands = self.logic_command().into_iter().collect::<Vec<_>>();
+        commands.extend(self.to_commands());
+        to_canonical_string(&commands)
+    }
+
+    /// Tseitin encoding of the problem, as by `to_commands`, after inlining its
+    /// definitions. Purely boolean problems can be printed in the DIMACS format with
+    /// `TseitinEncoder::to_dimacs`.
+    pub fn to_cnf(&self) -> TseitinEncoder {
+        let commands = DefinitionInliner::inline_commands(self.to_commands())
+            .expect("Inlining definitions does not fail");
+        let mut encoder = TseitinEncoder::new(&commands);
+        for command in commands {
+            encoder.encode_command(command);
+        }
+        encoder
+    }
+
+    /// Same problem as `to_smtlib2`, as commands.
+    pub fn to_commands(&self) -> Vec<Command> {
+        let prop = self
+            .property_assertion
+            .clone()
+            .expect("No property assertion for SMTProblem!");
+        let property_negation = Term::Application {
+            qual_identifier: QualIdentifier::Simple {
+                identifier: Identifier::Simple {
+                    symbol: Symbol("not".to_string()),
+                },
+            },
+            arguments: vec![prop],
+        };
+        let mut commands = self.sorts.clone();
+        commands.extend(self.functions.iter().cloned());
+        commands.extend(self.unique_definitions().cloned());
+        commands.extend(
+            self.unique_assertions()
+                .map(|term| Command::Assert { term: term.clone() }),
+        );
+        commands.push(Command::Assert {
+            term: property_negation,
+        });
+        commands
     }
 }
 
-#[derive(Clone, Debug)]
+impl From<&VMTModel> for VMTModelJson {
+    fn from(vmt: &VMTModel) -> Self {
+        VMTModelJson {
+            version: SCHEMA_VERSION,
+            sorts: vmt.sorts.iter().map(Command::to_string).collect(),
+            functions: vmt.functions.iter().map(Command::to_string).collect(),
+            state_variables: vmt
+                .state_variables
+                .iter()
+                .map(|variable| StateVariableJson {
+                    name: variable.get_current_variable_name().clone(),
+                    next: variable.get_next_variable_name().clone(),
+                    sort: sort_of(&variable.current).to_string(),
+                })
+                .collect(),
+            actions: vmt
+                .actions
+                .iter()
+                .map(|action| ActionJson {
+                    name: action.get_current_action_name().clone(),
+                    sort: sort_of(&action.action_command).to_string(),
+                })
+                .collect(),
+            init: vmt.initial_condition.to_string(),
+            trans: vmt.transition_condition.to_string(),
+            property: vmt.property_condition.to_string(),
+        }
+    }
+}
+
+// Values without their repetitions, in order of first occurrence.
+fn unique<T: Hash + Eq>(values: &[T]) -> impl Iterator<Item = &T> {
+    let mut seen = HashSet::new();
+    values.iter().filter(move |value| seen.insert(*value))
+}
+
+impl From<&SMTProblem> for SMTProblemJson {
+    fn from(problem: &SMTProblem) -> Self {
+        SMTProblemJson {
+            version: SCHEMA_VERSION,
+            sorts: problem.sorts.iter().map(Command::to_string).collect(),
+            functions: problem.functions.iter().map(Command::to_string).collect(),
+            definitions: problem
+                .unique_definitions()
+                .map(Command::to_string)
+                .collect(),
+            assertions: problem.unique_assertions().map(Term::to_string).collect(),
+            property: problem.property_assertion.as_ref().map(Term::to_string),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Variable {
     current: Command,
     next: Command,
@@ -247,9 +934,7 @@ impl Variable {
                 symbol,
                 parameters: _,
                 sort: _,
-            } => {
-                return &symbol.0;
-            }
+            } => &symbol.0,
             _ => panic!("Variable's current Command must be DeclareFun."),
         }
     }
@@ -260,15 +945,13 @@ impl Variable {
                 symbol,
                 parameters: _,
                 sort: _,
-            } => {
-                return &symbol.0;
-            }
+            } => &symbol.0,
             _ => panic!("Variable's next Command must be DeclareFun."),
         }
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Action {
     action_command: Command,
 }
@@ -280,14 +963,41 @@ impl Action {
                 symbol,
                 parameters: _,
                 sort: _,
-            } => {
-                return &symbol.0;
-            }
+            } => &symbol.0,
             _ => panic!("Actions's Command must be DeclareFun."),
         }
     }
 }
 
+fn sort_of(command: &Command) -> Sort {
+    match command {
+        Command::DeclareFun { sort, .. } => sort.clone(),
+        _ => panic!("Variables must be declared with declare-fun."),
+    }
+}
+
+fn variable_term(name: &str) -> Term {
+    Term::QualIdentifier(QualIdentifier::Simple {
+        identifier: Identifier::Simple {
+            symbol: Symbol(name.to_string()),
+        },
+    })
+}
+
+fn define_fun(name: &str, sort: Sort, term: Term, keyword: &str, value: AttributeValue) -> Command {
+    Command::DefineFun {
+        sig: FunctionDec {
+            name: Symbol(name.to_string()),
+            parameters: vec![],
+            result: sort,
+        },
+        term: Term::Attributes {
+            term: Box::new(term),
+            attributes: vec![(Keyword(keyword.to_string()), value)],
+        },
+    }
+}
+
 fn assert(term: &Term) -> String {
     format!("(assert {})", term)
 }
@@ -299,53 +1009,44 @@ fn assert_negation(term: &Term) -> String {
 fn get_variables_and_actions(
     variable_relationships: Vec<&Command>,
     variable_commands: HashMap<String, Command>,
-) -> (Vec<Variable>, Vec<Action>) {
+) -> Result<(Vec<Variable>, Vec<Action>), VMTError> {
     let mut state_variables: Vec<Variable> = vec![];
     let mut actions: Vec<Action> = vec![];
     for variable_relationship in variable_relationships {
-        match variable_relationship {
-            Command::DefineFun { sig: _, term } => match term {
-                Term::Attributes { term, attributes } => {
-                    assert!(attributes.len() == 1);
-                    let (keyword, value) = &attributes[0];
-                    let keyword_string = keyword.to_string();
-                    if keyword_string == ":next" {
-                        let variable_command = get_variable_command(
-                            scrub_variable_name(term.to_string()),
-                            &variable_commands,
-                        );
-                        let new_variable_command = get_variable_command(
-                            scrub_variable_name(value.to_string()),
-                            &variable_commands,
-                        );
-                        state_variables.push(Variable {
-                            current: variable_command,
-                            next: new_variable_command,
-                        });
-                    } else if keyword_string == ":action" {
-                        let action_variable_name = scrub_variable_name(term.to_string());
-                        if variable_commands.contains_key(&action_variable_name) {
-                            for (variable_name, action_command) in &variable_commands {
-                                if action_variable_name == *variable_name {
-                                    actions.push(Action {
-                                        action_command: action_command.clone(),
-                                    });
-                                    break;
-                                }
-                            }
-                        } else {
-                            panic!("Proposed action variable {} not previously defined.", term);
-                        }
-                    } else {
-                        panic!("Only `next` and `action` keyword attributes are allowed in variable relationships found: {}", keyword_string);
-                    }
-                }
-                _ => panic!("Only Attribute terms can define variable relationships."),
-            },
-            _ => panic!("Variable Relationship is not a (define-fun)."),
+        let invalid = || VMTError::InvalidRelationship(variable_relationship.clone());
+        let (term, keyword, value) = match variable_relationship {
+            Command::DefineFun {
+                sig: _,
+                term: Term::Attributes { term, attributes },
+            } if attributes.len() == 1 => (term, &attributes[0].0, &attributes[0].1),
+            _ => return Err(invalid()),
+        };
+        match keyword.0.as_str() {
+            "next" => {
+                let variable_command = get_variable_command(
+                    scrub_variable_name(term.to_string()),
+                    &variable_commands,
+                )?;
+                let new_variable_command = get_variable_command(
+                    scrub_variable_name(value.to_string()),
+                    &variable_commands,
+                )?;
+                state_variables.push(Variable {
+                    current: variable_command,
+                    next: new_variable_command,
+                });
+            }
+            "action" => {
+                let action_command = get_variable_command(
+                    scrub_variable_name(term.to_string()),
+                    &variable_commands,
+                )?;
+                actions.push(Action { action_command });
+            }
+            _ => return Err(invalid()),
         }
     }
-    (state_variables, actions)
+    Ok((state_variables, actions))
 }
 
 fn scrub_variable_name(variable_name: String) -> String {
@@ -362,67 +1063,180 @@ fn scrub_variable_name(variable_name: String) -> String {
 fn get_variable_command(
     variable_name: String,
     variable_commands: &HashMap<String, Command>,
-) -> Command {
-    match variable_commands.get(&variable_name) {
-        Some(command) => command.clone(),
-        None => panic!(
-            "First term in define-fun must be a variable name: {}",
-            variable_name
-        ),
-    }
-}
-
-fn get_transition_system_component(command: &Command, attribute: &str) -> Term {
-    if command_has_attribute_string(command, attribute) {
-        match command {
-            Command::DefineFun { sig: _, term } => match term {
-                Term::Attributes {
-                    term,
-                    attributes: _,
-                } => {
-                    return *term.clone();
-                }
-                _ => panic!("{}: Must have attribute.", attribute),
-            },
-            _ => panic!("{}: Command must be define-fun", attribute),
-        }
-    } else {
-        panic!(
-            "Ill-formed system component: {}.\nShould have {} as attribute.",
-            command, attribute
-        );
-    }
+) -> Result<Command, VMTError> {
+    variable_commands
+        .get(&variable_name)
+        .cloned()
+        .ok_or(VMTError::UndeclaredVariable(variable_name))
 }
 
-fn command_has_attribute_string(command: &Command, attribute: &str) -> bool {
+fn get_transition_system_component(command: &Command, attribute: &str) -> Result<Term, VMTError> {
     match command {
-        Command::DefineFun { sig: _, term } => match term {
-            Term::Attributes {
-                term: _,
-                attributes,
-            } => {
-                assert!(attributes.len() == 1);
-                let keyword = &attributes[0].0 .0;
-                keyword == attribute
-            }
-            _ => false,
+        Command::DefineFun {
+            sig: _,
+            term: Term::Attributes { term, attributes },
+        } if attributes.len() == 1 && attributes[0].0 .0 == attribute => Ok(*term.clone()),
+        _ => Err(VMTError::MissingComponent {
+            attribute: attribute.to_string(),
+            command: command.clone(),
+        }),
+    }
+}
+
+// Check a problem in a new scope of the solver. Return a model if it is satisfiable.
+pub(crate) fn solve<S: SmtSolver>(
+    solver: &mut S,
+    problem: &SMTProblem,
+) -> Result<Option<Model>, SolverError> {
+    solver.push(1)?;
+    for command in problem.to_commands() {
+        solver.run(&command)?;
+    }
+    let model = match solver.check_sat()? {
+        SatResult::Sat => solver.get_model().map(Some),
+        SatResult::Unsat => Ok(None),
+        SatResult::Unknown => Err(SolverError::Solver("unknown".to_string())),
+    };
+    solver.pop(1)?;
+    model
+}
+
+// The conjunction of the terms, or the term itself if there is only one.
+pub(crate) fn conjunction(terms: &[Term]) -> Term {
+    match terms {
+        [term] => term.clone(),
+        _ => Term::Application {
+            qual_identifier: QualIdentifier::Simple {
+                identifier: Identifier::Simple {
+                    symbol: Symbol("and".to_string()),
+                },
+            },
+            arguments: terms.to_vec(),
         },
-        _ => false,
     }
 }
 
+// Stack size of the threads unrolling transition relations. Terms are rewritten
+// recursively, and the default stack of rayon threads overflows on deep terms.
+const UNROLL_STACK_SIZE: usize = 256 << 20;
+
+// Threads unrolling transition relations, if they can be started.
+fn unroll_pool() -> Option<&'static rayon::ThreadPool> {
+    static POOL: OnceLock<Option<rayon::ThreadPool>> = OnceLock::new();
+    POOL.get_or_init(|| {
+        rayon::ThreadPoolBuilder::new()
+            .thread_name(|index| format!("unroll-{}", index))
+            .stack_size(UNROLL_STACK_SIZE)
+            .build()
+            .ok()
+    })
+    .as_ref()
+}
+
 #[derive(Clone)]
 struct VMTBuilder {
     visitor: SyntaxBuilder,
-    current_variables: Vec<String>,
+    current_variables: HashSet<String>,
     next_variables: HashMap<String, String>,
     step: u8,
+    // Renamed state variables, by step.
+    renamed: HashMap<u8, HashMap<String, Symbol>>,
+    // Addresses of the subterms of the term being rewritten that mention state variables or
+    // actions, if known. The other subterms are the same at every step.
+    mentions: Option<Arc<HashSet<usize>>>,
+    // Number of binders in scope for each bound variable. Bound variables shadow the state
+    // variables of the same name and are not renamed.
+    bound: HashMap<String, usize>,
 }
 
 impl VMTBuilder {
     pub fn add_step(&mut self) {
         self.step += 1;
     }
+
+    fn at_step(&self, step: u8) -> Self {
+        Self {
+            step,
+            ..self.clone()
+        }
+    }
+
+    fn rewrite_term(&mut self, term: &Term) -> Term {
+        let mentions = Arc::new(self.mentioning_subterms(term));
+        self.rewrite_term_with(term, mentions)
+    }
+
+    // Rewrite a term given the addresses of its subterms that mention state variables or
+    // actions, e.g. to rewrite the same term at several steps.
+    fn rewrite_term_with(&mut self, term: &Term, mentions: Arc<HashSet<usize>>) -> Term {
+        self.mentions = Some(mentions);
+        let term = term.accept_ref(self).unwrap();
+        self.mentions = None;
+        term
+    }
+
+    // The addresses of the subterms of a term that mention state variables or actions,
+    // computed bottom-up in one pass. Bound variables of the same names count as mentions:
+    // they are left unchanged by `process_symbol` anyway.
+    fn mentioning_subterms(&self, term: &Term) -> HashSet<usize> {
+        let is_variable = |symbol: &str| {
+            self.current_variables.contains(symbol) || self.next_variables.contains_key(symbol)
+        };
+        let mut mentions = HashSet::new();
+        // Terms to visit, with their number of children once these are pushed, and whether
+        // the visited terms mention variables.
+        let mut stack = vec![(term, None)];
+        let mut mentioned = Vec::new();
+        while let Some((term, children)) = stack.pop() {
+            let children = match children {
+                Some(children) => children,
+                None => {
+                    let children = term.children();
+                    stack.push((term, Some(children.len())));
+                    stack.extend(children.into_iter().map(|child| (child, None)));
+                    continue;
+                }
+            };
+            let mut found = match term {
+                Term::QualIdentifier(qual_identifier)
+                | Term::Application {
+                    qual_identifier, ..
+                } => qual_identifier_mentions(qual_identifier, &is_variable),
+                Term::Attributes { attributes, .. } => {
+                    attributes.iter().any(|(_, value)| match value {
+                        AttributeValue::None | AttributeValue::Constant(_) => false,
+                        AttributeValue::Symbol(symbol) => is_variable(&symbol.0),
+                        AttributeValue::SExpr(values) => values
+                            .iter()
+                            .any(|value| s_expr_mentions(value, &is_variable)),
+                    })
+                }
+                _ => false,
+            };
+            for _ in 0..children {
+                found |= mentioned.pop().unwrap_or(false);
+            }
+            if found {
+                mentions.insert(address(term));
+            }
+            mentioned.push(found);
+        }
+        mentions
+    }
+
+    // Definitions of the state variables and actions at the current step.
+    fn rewrite_definitions(
+        &mut self,
+        state_variables: &[Variable],
+        actions: &[Action],
+    ) -> Vec<Command> {
+        state_variables
+            .iter()
+            .map(|state_variable| &state_variable.current)
+            .chain(actions.iter().map(|action| &action.action_command))
+            .map(|command| command.clone().accept(self).unwrap())
+            .collect()
+    }
 }
 
 impl crate::rewriter::Rewriter for VMTBuilder {
@@ -433,19 +1247,522 @@ impl crate::rewriter::Rewriter for VMTBuilder {
         &mut self.visitor
     }
 
+    fn should_descend(&mut self, term: &Term) -> bool {
+        match &self.mentions {
+            Some(mentions) => mentions.contains(&address(term)),
+            None => true,
+        }
+    }
+
+    fn visit_fresh_symbol(
+        &mut self,
+        value: String,
+        kind: SymbolKind,
+    ) -> Result<Symbol, Self::Error> {
+        let symbol = self.visitor.visit_fresh_symbol(value, kind)?;
+        match kind {
+            SymbolKind::Variable => Ok(symbol),
+            _ => self.process_symbol(symbol),
+        }
+    }
+
+    fn bind_symbol(&mut self, symbol: &Symbol) {
+        *self.bound.entry(symbol.0.clone()).or_insert(0) += 1;
+        self.visitor.bind_symbol(symbol);
+    }
+
+    fn unbind_symbol(&mut self, symbol: &Symbol) {
+        if let Some(count) = self.bound.get_mut(&symbol.0) {
+            *count -= 1;
+            if *count == 0 {
+                self.bound.remove(&symbol.0);
+            }
+        }
+        self.visitor.unbind_symbol(symbol);
+    }
+
     fn process_symbol(&mut self, s: Symbol) -> Result<Symbol, Self::Error> {
-        if self.current_variables.contains(&s.0) {
-            Ok(Symbol(format!("{}@{}", s.0, &self.step.to_string())))
-        } else if self.next_variables.contains_key(&s.0) {
-            let next = self.step + 1;
-            let current_variable_name = self.next_variables.get(&s.0).unwrap();
-            Ok(Symbol(format!(
-                "{}@{}",
-                current_variable_name,
-                &next.to_string()
-            )))
+        if !self.bound.is_empty() && self.bound.contains_key(&s.0) {
+            return Ok(s);
+        }
+        let (name, step) = if self.current_variables.contains(&s.0) {
+            (&s.0, self.step)
+        } else if let Some(current_variable_name) = self.next_variables.get(&s.0) {
+            (current_variable_name, self.step + 1)
         } else {
-            Ok(s)
+            return Ok(s);
+        };
+        let names = self.renamed.entry(self.step).or_default();
+        if let Some(renamed) = names.get(&s.0) {
+            return Ok(renamed.clone());
+        }
+        let renamed = Symbol(format!("{}@{}", name, step));
+        names.insert(s.0, renamed.clone());
+        Ok(renamed)
+    }
+}
+
+// Address of a term, to identify the subterms of a term while it is borrowed.
+fn address(term: &Term) -> usize {
+    term as *const Term as usize
+}
+
+// Whether a symbol of the s-expression satisfies the predicate.
+fn s_expr_mentions(s_expr: &SExpr, predicate: &impl Fn(&str) -> bool) -> bool {
+    match s_expr {
+        SExpr::Constant(_) | SExpr::Keyword(_) => false,
+        SExpr::Symbol(symbol) => predicate(&symbol.0),
+        SExpr::Application(values) => values.iter().any(|v| s_expr_mentions(v, predicate)),
+    }
+}
+
+fn qual_identifier_mentions(
+    qual_identifier: &QualIdentifier,
+    predicate: &impl Fn(&str) -> bool,
+) -> bool {
+    match qual_identifier {
+        QualIdentifier::Simple { identifier } | QualIdentifier::Sorted { identifier, .. } => {
+            match identifier {
+                Identifier::Simple { symbol } | Identifier::Indexed { symbol, .. } => {
+                    predicate(&symbol.0)
+                }
+            }
         }
     }
 }
+
+#[test]
+fn test_vmt_datatypes() {
+    let input = r#"
+(declare-datatypes ((Mode 0) (Pair 1)) (((idle) (busy (owner Int))) (par (T) ((mk (fst T) (snd T))))))
+(declare-fun m () Mode)
+(declare-fun m_next () Mode)
+(define-fun .m () Mode (! m :next m_next))
+(declare-fun p () (Pair Int))
+(declare-fun p_next () (Pair Int))
+(define-fun .p () (Pair Int) (! p :next p_next))
+(define-fun init () Bool (! (and (= m idle) (= p (mk 0 0))) :init true))
+(define-fun trans () Bool (! (and (= m_next (match m ((idle (busy (fst p))) ((busy o) idle)))) (= p_next (mk (snd p) (fst p)))) :trans true))
+(define-fun property () Bool (! (or ((_ is idle) m) (= (owner m) 0)) :invar-property 0))
+"#;
+    let commands = crate::parse_commands(input).unwrap();
+    let vmt = VMTModel::checked_from(commands).unwrap();
+    assert_eq!(vmt.stats()[0], ("Variables", 2));
+    assert_eq!(vmt.stats()[2], ("Sorts", 1));
+    let commands = vmt.unroll(1).to_commands();
+    assert!(matches!(commands[0], Command::DeclareDatatypes { .. }));
+    let mut checker = crate::typing::SortChecker::new(crate::typing::SmtLibTheories);
+    checker.check_commands(&commands).unwrap();
+    let smtlib2 = commands
+        .iter()
+        .map(|command| command.to_string())
+        .collect::<Vec<_>>()
+        .join("\n");
+    assert!(smtlib2.contains("(declare-fun p@1 () (Pair Int))"));
+    assert!(smtlib2.contains("(= m@1 (match m@0 ((idle (busy (fst p@0))) ((busy o) idle))))"));
+}
+
+#[test]
+fn test_vmt_shadowing() {
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun x () Int)
+(declare-fun x_next () Int)
+(define-fun .x () Int (! x :next x_next))
+(define-fun init () Bool (! (= x 0) :init true))
+(define-fun trans () Bool (! (and (= x_next (+ x 1)) (forall ((x Int)) (>= (* x x) 0))) :trans true))
+(define-fun property () Bool (! (>= x 0) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let vmt = VMTModel::checked_from(commands).unwrap();
+    let smtlib2 = vmt.unroll(1).to_smtlib2();
+    assert!(smtlib2.contains("(= x@1 (+ x@0 1))"));
+    assert!(smtlib2.contains("(forall ((x Int)) (>= (* x x) 0))"));
+    assert!(!smtlib2.contains("set-logic"));
+    let smtlib2 = vmt.unroll(1).with_logic().to_smtlib2();
+    assert!(smtlib2.starts_with("(set-logic NIA)\n"));
+
+    // Bound variables shadow state variables in terms that mention other state variables.
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun x () Int)
+(declare-fun x_next () Int)
+(define-fun .x () Int (! x :next x_next))
+(declare-fun y () Int)
+(declare-fun y_next () Int)
+(define-fun .y () Int (! y :next y_next))
+(define-fun init () Bool (! (= x y) :init true))
+(define-fun trans () Bool (! (and (let ((y x)) (> y_next y)) (forall ((x Int)) (! (>= (* x x) y) :pattern ((* x x))))) :trans true))
+(define-fun property () Bool (! (exists ((y Int)) (and (= y x) (let ((x y)) (>= x y)))) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let vmt = VMTModel::checked_from(commands).unwrap();
+    let smtlib2 = vmt.unroll(1).to_smtlib2();
+    assert!(smtlib2.contains("(let ((y x@0)) (> y@1 y))"));
+    assert!(smtlib2.contains("(forall ((x Int)) (! (>= (* x x) y@0) :pattern ((* x x))))"));
+    assert!(smtlib2.contains("(exists ((y Int)) (and (= y x@1) (let ((x y)) (>= x y))))"));
+}
+
+#[test]
+fn test_vmt_scopes() {
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun x () Int)
+(declare-fun x_next () Int)
+(define-fun .x () Int (! x :next x_next))
+(push 1)
+(declare-fun y () Int)
+(declare-fun y_next () Int)
+(define-fun .y () Int (! y :next y_next))
+(pop 1)
+(define-fun init () Bool (! (= x 0) :init true))
+(define-fun trans () Bool (! (= x_next (+ x 1)) :trans true))
+(define-fun property () Bool (! (>= x 0) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let vmt = VMTModel::checked_from(commands.clone()).unwrap();
+    assert_eq!(vmt.stats()[0], ("Variables", 1));
+    assert!(!vmt.unroll(1).to_smtlib2().contains("y@"));
+
+    let mut unbalanced = commands;
+    unbalanced.insert(0, Command::Pop { level: 1u32.into() });
+    assert!(matches!(
+        VMTModel::checked_from(unbalanced),
+        Err(VMTError::InvalidLevel(Command::Pop { .. }))
+    ));
+}
+
+#[test]
+fn test_vmt_errors() {
+    let check = |text: &str| VMTModel::checked_from(crate::parse_commands(text).unwrap());
+    let system = r#"
+(define-fun init () Bool (! (= x 0) :init true))
+(define-fun trans () Bool (! (= x_next (+ x 1)) :trans true))
+(define-fun property () Bool (! (>= x 0) :invar-property 0))
+"#;
+    assert_eq!(check(system).unwrap_err(), VMTError::NotEnoughCommands);
+    let error = check(&format!("(declare-fun x () Int)\n(check-sat){}", system)).unwrap_err();
+    assert_eq!(error, VMTError::UnsupportedCommand(Command::CheckSat));
+    let error = check(&format!(
+        "(declare-fun x () Int)\n(define-fun .x () Int (! x :next x_next)){}",
+        system
+    ))
+    .unwrap_err();
+    assert_eq!(error, VMTError::UndeclaredVariable("x_next".to_string()));
+    let error = check(&format!(
+        "(declare-fun x () Int)\n(define-fun .x () Int (! x :prev x)){}",
+        system
+    ))
+    .unwrap_err();
+    assert!(matches!(error, VMTError::InvalidRelationship(_)));
+    let error = check(&format!(
+        "(declare-fun x () Int)\n(declare-fun x_next () Int){}",
+        system.replace(":trans", ":transition")
+    ))
+    .unwrap_err();
+    assert!(matches!(error, VMTError::MissingComponent { attribute, .. } if attribute == "trans"));
+}
+
+#[test]
+fn test_skolemize_property() {
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun x () Int)
+(declare-fun x_next () Int)
+(define-fun .x () Int (! x :next x_next))
+(define-fun init () Bool (! (= x 0) :init true))
+(define-fun trans () Bool (! (= x_next (+ x 1)) :trans true))
+(define-fun property () Bool (! (forall ((y Int)) (>= (+ x (* y y)) 0)) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let vmt = VMTModel::checked_from(commands).unwrap();
+    let mut smt = vmt.unroll(1);
+    smt.skolemize_property().unwrap();
+    let smtlib2 = smt.to_smtlib2();
+    assert!(smtlib2.contains("(declare-fun y_sk () Int)"));
+    assert!(smtlib2.contains("(assert (not (let ((y y_sk)) (>= (+ x@1 (* y y)) 0))))"));
+
+    let mut smt = SMTProblem::default();
+    assert_eq!(smt.skolemize_property(), Err(VMTError::MissingProperty));
+}
+
+#[test]
+fn test_unroll_deep_term() {
+    // `(= x_next (+ (+ ... x 1) 1))`, deeper than the default stack of rayon threads allows.
+    let depth = 2000;
+    let transition = format!("(= x_next {}x{})", "(+ ".repeat(depth), " 1)".repeat(depth));
+    let input = format!(
+        r#"
+(declare-fun x () Int)
+(declare-fun x_next () Int)
+(define-fun .x () Int (! x :next x_next))
+(define-fun init () Bool (! (= x 0) :init true))
+(define-fun trans () Bool (! {} :trans true))
+(define-fun property () Bool (! (>= x 0) :invar-property 0))
+"#,
+        transition
+    );
+    // Parsing and dropping the term are recursive as well.
+    std::thread::Builder::new()
+        .stack_size(UNROLL_STACK_SIZE)
+        .spawn(move || {
+            let vmt = VMTModel::checked_from(crate::parse_commands(&input).unwrap()).unwrap();
+            let script = vmt.unroll(3).to_smtlib2();
+            assert!(script.contains(&format!("(= x@3 {}x@2 1)", "(+ ".repeat(depth))));
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn test_unroll_renaming() {
+    // Renamed symbols are memoized by step: each step must still get its own names.
+    let mut input = String::new();
+    for i in 0..50 {
+        input += &format!(
+            "(declare-fun v{0} () Int)\n\
+             (declare-fun v{0}_next () Int)\n\
+             (define-fun .v{0} () Int (! v{0} :next v{0}_next))\n",
+            i
+        );
+    }
+    let conjuncts = (0..50)
+        .map(|i| format!("(= v{0}_next (+ v{0} v{1}))", i, (i + 1) % 50))
+        .collect::<Vec<_>>();
+    input += &format!(
+        "(define-fun init () Bool (! (= v0 0) :init true))\n\
+         (define-fun trans () Bool (! (and {}) :trans true))\n\
+         (define-fun property () Bool (! (>= v0 0) :invar-property 0))\n",
+        conjuncts.join(" ")
+    );
+    let vmt = VMTModel::checked_from(crate::parse_commands(&input).unwrap()).unwrap();
+    let script = vmt.unroll(2).to_smtlib2();
+    for i in 0..50 {
+        let j = (i + 1) % 50;
+        assert!(script.contains(&format!("(declare-fun v{}@2 () Int)", i)));
+        assert!(script.contains(&format!("(= v{0}@1 (+ v{0}@0 v{1}@0))", i, j)));
+        assert!(script.contains(&format!("(= v{0}@2 (+ v{0}@1 v{1}@1))", i, j)));
+    }
+    assert!(!script.contains("_next"));
+}
+
+#[test]
+fn test_unroll_max_length() {
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun x () Int)
+(declare-fun x_next () Int)
+(define-fun .x () Int (! x :next x_next))
+(define-fun init () Bool (! (= x 0) :init true))
+(define-fun trans () Bool (! (= x_next (+ x 1)) :trans true))
+(define-fun property () Bool (! (>= x 0) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let vmt = VMTModel::checked_from(commands).unwrap();
+    let problem = vmt.unroll(u8::MAX);
+    assert_eq!(problem.init_and_trans_assertions.len(), 256);
+    assert!(problem.to_smtlib2().contains("(>= x@255 0)"));
+}
+
+#[test]
+fn test_k_induction_step() {
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun x () Int)
+(declare-fun x_next () Int)
+(define-fun .x () Int (! x :next x_next))
+(define-fun init () Bool (! (= x 0) :init true))
+(define-fun trans () Bool (! (= x_next (- 1 x)) :trans true))
+(define-fun property () Bool (! (<= x 1) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let vmt = VMTModel::checked_from(commands).unwrap();
+    let script = vmt.k_induction_step(2).to_smtlib2();
+    for step in 0..=2 {
+        assert!(script.contains(&format!("(declare-fun x@{} () Int)", step)));
+    }
+    assert!(script.contains("(assert (<= x@0 1))"));
+    assert!(script.contains("(assert (= x@1 (- 1 x@0)))"));
+    assert!(script.contains("(assert (<= x@1 1))"));
+    assert!(script.contains("(assert (= x@2 (- 1 x@1)))"));
+    assert!(script.contains("(assert (not (<= x@2 1)))"));
+    // The initial condition is not assumed.
+    assert!(!script.contains("(= x@0 0)"));
+    // With one step, this is the induction step assuming the property.
+    assert_eq!(
+        vmt.k_induction_step(1).to_smtlib2(),
+        vmt.induction_step(vmt.property()).to_smtlib2()
+    );
+}
+
+#[test]
+fn test_unique_assertions() {
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun x () Int)
+(declare-fun x_next () Int)
+(define-fun .x () Int (! x :next x_next))
+(define-fun init () Bool (! (= x 0) :init true))
+(define-fun trans () Bool (! (> 2 1) :trans true))
+(define-fun property () Bool (! (>= x 0) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let vmt = VMTModel::checked_from(commands).unwrap();
+    // The transition condition does not mention state variables.
+    let script = vmt.unroll(3).to_smtlib2();
+    assert_eq!(script.matches("(assert (> 2 1))").count(), 1);
+    assert_eq!(script.matches("(declare-fun x@3 () Int)").count(), 1);
+}
+
+#[test]
+fn test_houdini() {
+    use crate::responses::Response;
+    use std::collections::VecDeque;
+
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun n () Int)
+(declare-fun n_next () Int)
+(define-fun .n () Int (! n :next n_next))
+(declare-fun m () Int)
+(declare-fun m_next () Int)
+(define-fun .m () Int (! m :next m_next))
+(define-fun init () Bool (! (and (= n 0) (= m 0)) :init true))
+(define-fun trans () Bool (! (and (= n_next (+ n 1)) (= m_next m)) :trans true))
+(define-fun property () Bool (! (>= n 0) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let vmt = VMTModel::checked_from(commands).unwrap();
+
+    // A solver answering `check-sat` with the given models, or unsat.
+    struct Scripted(VecDeque<Option<&'static str>>, usize);
+
+    impl SmtSolver for Scripted {
+        fn execute(&mut self, command: &Command) -> Result<Response, SolverError> {
+            Ok(match command {
+                Command::Push { .. } => {
+                    self.1 += 1;
+                    Response::Success
+                }
+                Command::Pop { .. } => {
+                    self.1 -= 1;
+                    Response::Success
+                }
+                Command::CheckSat => match self.0.front().unwrap() {
+                    Some(_) => Response::Sat,
+                    None => {
+                        self.0.pop_front();
+                        Response::Unsat
+                    }
+                },
+                Command::GetModel => {
+                    let model = self.0.pop_front().unwrap().unwrap();
+                    Response::Model(crate::model::Model::parse(model).unwrap())
+                }
+                _ => Response::Success,
+            })
+        }
+    }
+
+    let candidates = ["(>= n 0)", "(= m 0)", "(<= n 1)", "(= n 1)"]
+        .iter()
+        .map(|candidate| crate::smt2!(term: "{}", candidate))
+        .collect::<Vec<_>>();
+    let mut solver = Scripted(
+        vec![
+            // An initial state.
+            Some("((define-fun n@0 () Int 0) (define-fun m@0 () Int 0))"),
+            None,
+            // A counterexample to the induction of `(<= n 1)`.
+            Some(
+                "((define-fun n@0 () Int 1) (define-fun m@0 () Int 0) \
+                  (define-fun n@1 () Int 2) (define-fun m@1 () Int 0))",
+            ),
+            None,
+        ]
+        .into(),
+        0,
+    );
+    let invariants = vmt.houdini(&candidates, &mut solver).unwrap();
+    assert_eq!(invariants, candidates[..2]);
+    assert!(solver.0.is_empty());
+    assert_eq!(solver.1, 0);
+}
+
+#[test]
+fn test_dependency_graph() {
+    let commands = crate::parse_commands(
+        r#"
+(declare-fun n () Int)
+(declare-fun n_next () Int)
+(define-fun .n () Int (! n :next n_next))
+(declare-fun m () Int)
+(declare-fun m_next () Int)
+(define-fun .m () Int (! m :next m_next))
+(declare-fun done () Bool)
+(declare-fun done_next () Bool)
+(define-fun .done () Bool (! done :next done_next))
+(declare-fun input () Int)
+(define-fun .input () Int (! input :action 0))
+(define-fun init () Bool (! (and (= n 0) (= m 0) (not done)) :init true))
+(define-fun trans () Bool (! (and (= n_next (+ n input)) (and (= m_next m) (= done_next (> n m)))) :trans true))
+(define-fun property () Bool (! (>= n 0) :invar-property 0))
+"#,
+    )
+    .unwrap();
+    let vmt = VMTModel::checked_from(commands).unwrap();
+    let graph = vmt.dependency_graph();
+    assert_eq!(
+        graph.to_dot(),
+        r#"digraph dependencies {
+  "n";
+  "m";
+  "done";
+  "input" [shape=box];
+  "input" -> "n";
+  "n" -> "n";
+  "m" -> "m";
+  "m" -> "done";
+  "n" -> "done";
+}
+"#
+    );
+}
+
+#[test]
+fn test_canonical_vmt_string() {
+    let model = r#"
+(declare-fun y () Int)
+(declare-fun y_next () Int)
+(define-fun .y () Int (! y :next y_next))
+(declare-fun x () Int)
+(declare-fun x_next () Int)
+(define-fun .x () Int (! x :next x_next))
+(define-fun init () Bool (! (and (= x 0) (= y 0)) :init true))
+(define-fun trans () Bool (! (and (= x_next (+ x 1)) (= y_next x)) :trans true))
+(define-fun property () Bool (! (>= x y) :invar-property 0))
+"#;
+    let vmt = VMTModel::checked_from(crate::parse_commands(model).unwrap()).unwrap();
+    let canonical = vmt.to_canonical_vmt_string();
+    assert!(canonical.starts_with(
+        "(declare-fun x () Int)\n(declare-fun x_next () Int)\n(define-fun .x () Int (! x :next x_next))\n\
+         (declare-fun y () Int)\n"
+    ));
+    // The canonical form is a fixpoint.
+    let reparsed = VMTModel::checked_from(crate::parse_commands(&canonical).unwrap()).unwrap();
+    assert_eq!(reparsed.to_canonical_vmt_string(), canonical);
+    assert_eq!(reparsed.to_vmt_string(), canonical);
+
+    let smtlib2 = vmt.unroll(2).to_canonical_smtlib2();
+    assert!(smtlib2.contains("(declare-fun x@0 () Int)\n(declare-fun x@1 () Int)\n"));
+    assert!(!smtlib2.contains("\n\n"));
+}
@@ -0,0 +1,97 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! JavaScript bindings for parsing and printing SMT-LIB scripts and VMT models, e.g. built
+//! with `wasm-pack build --target web -- --features wasm`:
+//!
+//! ```js
+//! import init, { formatScript, VmtModel } from "./pkg/smt2parser.js";
+//!
+//! await init();
+//! console.log(formatScript("(assert   (> x 0))"));
+//! const model = new VmtModel(text);
+//! console.log(model.stats(), model.unroll(10));
+//! ```
+//!
+//! JSON documents of [`crate::schema`] are returned as JavaScript objects.
+
+use crate::{
+    concrete::Command,
+    schema::{SMTProblemJson, VMTModelJson},
+    vmt::VMTModel,
+};
+use wasm_bindgen::prelude::*;
+
+/// Parse an SMT-LIB script and print it back, one command per line.
+#[wasm_bindgen(js_name = formatScript)]
+pub fn format_script(text: &str) -> Result<String, JsError> {
+    let commands = crate::parse_commands(text)?;
+    Ok(print_commands(&commands))
+}
+
+/// A transition system in VMT format.
+#[wasm_bindgen(js_name = VmtModel)]
+pub struct WasmVMTModel {
+    model: VMTModel,
+}
+
+#[wasm_bindgen(js_class = VmtModel)]
+impl WasmVMTModel {
+    /// Parse a VMT model from SMT-LIB text.
+    #[wasm_bindgen(constructor)]
+    pub fn new(text: &str) -> Result<WasmVMTModel, JsError> {
+        let commands = crate::parse_commands(text)?;
+        let model = VMTModel::checked_from(commands)?;
+        Ok(Self { model })
+    }
+
+    /// Number of state variables, actions, and sorts.
+    pub fn stats(&self) -> Result<JsValue, JsError> {
+        let stats = self
+            .model
+            .stats()
+            .into_iter()
+            .collect::<std::collections::BTreeMap<_, _>>();
+        to_js(&stats)
+    }
+
+    /// Name of the fragment of the model, e.g. `ArrayProperty`.
+    pub fn fragment(&self) -> String {
+        format!("{:?}", self.model.fragment())
+    }
+
+    /// SMT-LIB script checking the property up to the given bound, without `check-sat`.
+    pub fn unroll(&self, bound: u8) -> String {
+        self.model.unroll(bound).to_smtlib2()
+    }
+
+    /// The unrolled problem, as a JSON document.
+    #[wasm_bindgen(js_name = unrollJson)]
+    pub fn unroll_json(&self, bound: u8) -> Result<JsValue, JsError> {
+        to_js(&SMTProblemJson::from(&self.model.unroll(bound)))
+    }
+
+    /// The model, as a JSON document.
+    #[wasm_bindgen(js_name = toJson)]
+    pub fn to_json(&self) -> Result<JsValue, JsError> {
+        to_js(&VMTModelJson::from(&self.model))
+    }
+
+    #[wasm_bindgen(js_name = toString)]
+    pub fn to_string_js(&self) -> String {
+        print_commands(&self.model.to_commands())
+    }
+}
+
+fn print_commands(commands: &[Command]) -> String {
+    commands
+        .iter()
+        .map(|command| command.to_string() + "\n")
+        .collect()
+}
+
+// Convert a JSON document into JavaScript values with `JSON.parse`.
+fn to_js<T: serde::Serialize>(value: &T) -> Result<JsValue, JsError> {
+    let text = serde_json::to_string(value)?;
+    js_sys::JSON::parse(&text).map_err(|_| JsError::new("invalid JSON document"))
+}
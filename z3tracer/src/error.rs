@@ -51,6 +51,14 @@ pub enum RawError {
     InvalidPush(u64),
     #[error("Invalid 'pop' command {0} {1}")]
     InvalidPop(u64, u64),
+    #[error("Cannot produce a ground instance of quantifier {0:?}")]
+    CannotInstantiateQuantifier(Ident),
+    #[error("Cannot convert term {0:?}")]
+    CannotConvertTerm(Ident),
+    #[error("Invalid term pattern {0}")]
+    InvalidTermPattern(String),
+    #[error("Processing was cancelled")]
+    Cancelled,
 }
 
 /// Record a position in the input stream.
@@ -90,7 +98,12 @@ impl std::fmt::Debug for Position {
 }
 
 const SUPPORTED_TOOL: &str = "Z3";
-const SUPPORTED_VERSIONS: &[&str] = &["4.8.9"];
+const SUPPORTED_VERSIONS: &[&str] = &[
+    "4.8.9", "4.8.10", "4.8.11", "4.8.12", "4.8.13", "4.8.14", "4.8.15", "4.8.16", "4.8.17",
+    "4.9.0", "4.9.1", "4.10.0", "4.10.1", "4.10.2", "4.11.0", "4.11.2", "4.12.0", "4.12.1",
+    "4.12.2", "4.12.3", "4.12.4", "4.12.5", "4.12.6", "4.13.0", "4.13.1", "4.13.2", "4.13.3",
+    "4.13.4",
+];
 
 impl RawError {
     pub fn check_that_tool_version_is_supported(s1: &str, s2: &str) -> RawResult<()> {
@@ -111,5 +124,10 @@ impl From<Error> for RawError {
 fn test_version_check() {
     assert!(RawError::check_that_tool_version_is_supported("Z3", "4.8.9").is_ok());
     assert!(RawError::check_that_tool_version_is_supported("Z4", "4.8.9").is_err());
-    assert!(RawError::check_that_tool_version_is_supported("Z3", "4.8.10").is_err());
+    assert!(RawError::check_that_tool_version_is_supported("Z3", "4.8.12").is_ok());
+    assert!(RawError::check_that_tool_version_is_supported("Z3", "4.9.1").is_ok());
+    assert!(RawError::check_that_tool_version_is_supported("Z3", "4.10.2").is_ok());
+    assert!(RawError::check_that_tool_version_is_supported("Z3", "4.12.2").is_ok());
+    assert!(RawError::check_that_tool_version_is_supported("Z3", "4.8.8").is_err());
+    assert!(RawError::check_that_tool_version_is_supported("Z3", "4.8.1").is_err());
 }
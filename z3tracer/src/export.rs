@@ -0,0 +1,297 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::{collections::BTreeMap, io::Write};
+
+use crate::{
+    model::Model,
+    syntax::{Equality, Ident, Literal, MatchedTerm, QiFrame, QiKey, Term},
+};
+
+/// Write the content of a model in the format of Z3 logs, as consumed by the
+/// [Axiom Profiler](https://github.com/viperproject/axiom-profiler).
+///
+/// Events are written in the order in which they were logged. The justifications of
+/// assignments and conflicts are not exported.
+pub fn write_log<W: Write>(model: &Model, out: &mut W) -> std::io::Result<()> {
+    let mut events = Vec::new();
+    for (id, data) in model.terms() {
+        if !matches!(data.term, Term::Builtin { .. }) {
+            events.push((data.timestamp, Event::Term(id)));
+        }
+    }
+    for (key, inst) in model.instantiations() {
+        events.push((inst.timestamp, Event::Instantiation(key)));
+        for (i, (start, end)) in inst.instance_timestamps.iter().enumerate() {
+            events.push((*start, Event::StartInstance(key, i)));
+            events.push((*end, Event::EndInstance(key, i)));
+        }
+    }
+    for (id, eqs) in model.equality_explanations() {
+        for (timestamp, eq) in eqs {
+            events.push((*timestamp, Event::Equality(id, eq)));
+        }
+    }
+    let mut assignments = BTreeMap::new();
+    let mut previous_level = 0;
+    for scope in model
+        .scopes()
+        .iter()
+        .chain(std::iter::once(model.current_scope()))
+    {
+        if scope.timestamp > 0 {
+            events.push((scope.timestamp, Event::Scope(previous_level, scope.level)));
+        }
+        previous_level = scope.level;
+        if let Some(conflict) = &scope.conflict {
+            events.push((conflict.timestamp, Event::Conflict(&conflict.lits)));
+        }
+        for (id, data) in &scope.terms {
+            if let Some(assignment) = &data.assignment {
+                assignments.insert(assignment.timestamp, (id, assignment.sign));
+            }
+        }
+    }
+    for (timestamp, (id, sign)) in assignments {
+        events.push((timestamp, Event::Assignment(id, sign)));
+    }
+    events.sort_by_key(|(timestamp, _)| *timestamp);
+
+    if let Some((tool, version)) = model.tool_version() {
+        writeln!(out, "[tool-version] {} {}", tool, version)?;
+    }
+    for (_, event) in events {
+        match event {
+            Event::Term(id) => write_term(model, out, id)?,
+            Event::Instantiation(key) => {
+                let inst = &model.instantiations()[key];
+                match &inst.frame {
+                    QiFrame::Discovered {
+                        method,
+                        quantifier,
+                        terms,
+                        blame,
+                    } => writeln!(
+                        out,
+                        "[inst-discovered] {} {} {}{} ;{}",
+                        method,
+                        fmt_key(key),
+                        fmt_ident(quantifier),
+                        fmt_idents(terms),
+                        fmt_idents(blame)
+                    )?,
+                    QiFrame::NewMatch {
+                        quantifier,
+                        trigger,
+                        terms,
+                        used,
+                    } => {
+                        let used = used
+                            .iter()
+                            .map(|u| match u {
+                                MatchedTerm::Trigger(id) => format!(" {}", fmt_ident(id)),
+                                MatchedTerm::Equality(id1, id2) => {
+                                    format!(" ({} {})", fmt_ident(id1), fmt_ident(id2))
+                                }
+                            })
+                            .collect::<String>();
+                        writeln!(
+                            out,
+                            "[new-match] {} {} {}{} ;{}",
+                            fmt_key(key),
+                            fmt_ident(quantifier),
+                            fmt_ident(trigger),
+                            fmt_idents(terms),
+                            used
+                        )?
+                    }
+                }
+            }
+            Event::StartInstance(key, i) => {
+                let instance = &model.instantiations()[key].instances[i];
+                write!(out, "[instance] {}", fmt_key(key))?;
+                if let Some(term) = &instance.term {
+                    write!(out, " {}", fmt_ident(term))?;
+                }
+                if let Some(generation) = instance.generation {
+                    write!(out, " ; {}", generation)?;
+                }
+                writeln!(out)?;
+            }
+            Event::EndInstance(key, i) => {
+                let instance = &model.instantiations()[key].instances[i];
+                for id in &instance.enodes {
+                    writeln!(
+                        out,
+                        "[attach-enode] {} {}",
+                        fmt_ident(id),
+                        instance.generation.unwrap_or(0)
+                    )?;
+                }
+                writeln!(out, "[end-of-instance]")?;
+            }
+            Event::Assignment(id, sign) => writeln!(out, "[assign] {}", fmt_literal(id, sign))?,
+            Event::Conflict(lits) => {
+                let lits = lits
+                    .iter()
+                    .map(|lit| format!(" {}", fmt_literal(&lit.id, lit.sign)))
+                    .collect::<Vec<_>>()
+                    .concat();
+                writeln!(out, "[conflict]{}", lits)?
+            }
+            Event::Scope(previous_level, level) if level > previous_level => {
+                writeln!(out, "[push] {}", previous_level)?
+            }
+            Event::Scope(previous_level, level) => {
+                writeln!(out, "[pop] {} {}", previous_level - level, previous_level)?
+            }
+            Event::Equality(id, eq) => {
+                writeln!(out, "[eq-expl] {} {}", fmt_ident(id), fmt_equality(eq))?
+            }
+        }
+    }
+    writeln!(out, "[eof]")
+}
+
+enum Event<'a> {
+    Term(&'a Ident),
+    Instantiation(&'a QiKey),
+    StartInstance(&'a QiKey, usize),
+    EndInstance(&'a QiKey, usize),
+    Equality(&'a Ident, &'a Equality),
+    Assignment(&'a Ident, bool),
+    Conflict(&'a [Literal]),
+    Scope(u64, u64),
+}
+
+fn write_term<W: Write>(model: &Model, out: &mut W, id: &Ident) -> std::io::Result<()> {
+    use Term::*;
+    let term = &model.terms()[id].term;
+    match term {
+        App {
+            name,
+            args,
+            meaning,
+        } => {
+            writeln!(
+                out,
+                "[mk-app] {} {}{}",
+                fmt_ident(id),
+                name,
+                fmt_idents(args)
+            )?;
+            if let Some(meaning) = meaning {
+                writeln!(
+                    out,
+                    "[attach-meaning] {} {} {}",
+                    fmt_ident(id),
+                    meaning.theory,
+                    meaning.sexp
+                )?;
+            }
+        }
+        Var { index } => writeln!(out, "[mk-var] {} {}", fmt_ident(id), index)?,
+        Quant {
+            name,
+            params,
+            triggers,
+            body,
+            var_names,
+        } => {
+            writeln!(
+                out,
+                "[mk-quant] {} {} {}{} {}",
+                fmt_ident(id),
+                name,
+                params,
+                fmt_idents(triggers),
+                fmt_ident(body)
+            )?;
+            if let Some(var_names) = var_names {
+                let names = var_names
+                    .iter()
+                    .map(|v| format!(" (|{}| ; |{}|)", v.name.0, v.sort.0))
+                    .collect::<Vec<_>>()
+                    .concat();
+                writeln!(out, "[attach-var-names] {}{}", fmt_ident(id), names)?;
+            }
+        }
+        Lambda {
+            name,
+            params,
+            triggers,
+            body,
+        } => writeln!(
+            out,
+            "[mk-lambda] {} {} {}{} {}",
+            fmt_ident(id),
+            name,
+            params,
+            fmt_idents(triggers),
+            fmt_ident(body)
+        )?,
+        Proof {
+            name,
+            args,
+            property,
+        } => writeln!(
+            out,
+            "[mk-proof] {} {}{} {}",
+            fmt_ident(id),
+            name,
+            fmt_idents(args),
+            fmt_ident(property)
+        )?,
+        Builtin { .. } => (),
+    }
+    Ok(())
+}
+
+// Identifiers are written without their version: versions are re-created when the
+// output is parsed again.
+fn fmt_ident(id: &Ident) -> String {
+    format!(
+        "{}#{}",
+        id.namespace.as_deref().unwrap_or(""),
+        id.id.map(|i| i.to_string()).unwrap_or_default()
+    )
+}
+
+fn fmt_literal(id: &Ident, sign: bool) -> String {
+    match (id.is_builtin() && id.namespace.is_none(), sign) {
+        (true, true) => "true".to_string(),
+        (true, false) => "false".to_string(),
+        (false, true) => fmt_ident(id),
+        (false, false) => format!("(not {})", fmt_ident(id)),
+    }
+}
+
+fn fmt_idents(ids: &[Ident]) -> String {
+    ids.iter()
+        .map(|id| format!(" {}", fmt_ident(id)))
+        .collect::<Vec<_>>()
+        .concat()
+}
+
+fn fmt_key(key: &QiKey) -> String {
+    format!("{:#x}", key.key)
+}
+
+fn fmt_equality(eq: &Equality) -> String {
+    use Equality::*;
+    match eq {
+        Root => "root".to_string(),
+        Literal(id1, id2) => format!("lit {} ; {}", fmt_ident(id1), fmt_ident(id2)),
+        Congruence(eqs, id) => {
+            let eqs = eqs
+                .iter()
+                .map(|(id1, id2)| format!("({} {}) ", fmt_ident(id1), fmt_ident(id2)))
+                .collect::<Vec<_>>()
+                .concat();
+            format!("cg {}; {}", eqs, fmt_ident(id))
+        }
+        Theory(theory, id) => format!("th {} ; {}", theory, fmt_ident(id)),
+        Axiom(id) => format!("ax ; {}", fmt_ident(id)),
+        Unknown(id) => format!("unknown ; {}", fmt_ident(id)),
+    }
+}
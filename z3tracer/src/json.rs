@@ -0,0 +1,176 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::collections::BTreeMap;
+
+use serde_json::{json, Value};
+use smt2parser::schema::SCHEMA_VERSION;
+
+use crate::{
+    error::RawResult,
+    model::Model,
+    syntax::{Ident, Term},
+};
+
+impl Model {
+    /// Summary of the trace in JSON, with the versioned schema of
+    /// [`smt2parser::schema`]:
+    ///
+    /// ```json
+    /// {
+    ///   "version": 1,
+    ///   "tool": { "name": "Z3", "version": "4.8.12" },
+    ///   "processed_logs": 1068,
+    ///   "terms": 841,
+    ///   "instantiations": 28,
+    ///   "conflicts": 1,
+    ///   "ematching_rounds": 2,
+    ///   "max_scope_level": 1,
+    ///   "theories": { "arith": { "instantiations": 15, "equalities": 0, "meanings": 11 } },
+    ///   "quantifiers": [
+    ///     { "id": "arith#", "name": "arith", "formula": null,
+    ///       "instantiations": 15, "first_instantiation": 58 },
+    ///     { "id": "#68", "name": "pf", "formula": "(QUANT ((x Int)) ..)",
+    ///       "instantiations": 2, "first_instantiation": 998 }
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// Quantifiers, including the theories whose axioms were instantiated, are sorted by
+    /// decreasing number of instantiations. The formula of a theory is `null`. Timestamps
+    /// are line numbers in the log.
+    pub fn to_json(&self) -> RawResult<Value> {
+        let mut quantifiers = BTreeMap::<&Ident, (usize, usize)>::new();
+        for inst in self.instantiations().values() {
+            let entry = quantifiers
+                .entry(inst.frame.quantifier())
+                .or_insert((0, inst.timestamp));
+            entry.0 += 1;
+            entry.1 = entry.1.min(inst.timestamp);
+        }
+        let mut quantifiers = quantifiers.into_iter().collect::<Vec<_>>();
+        quantifiers.sort_by(|(_, (count1, _)), (_, (count2, _))| count2.cmp(count1));
+        let venv = BTreeMap::new();
+        let quantifiers = quantifiers
+            .into_iter()
+            .map(|(id, (instantiations, first_instantiation))| {
+                Ok(json!({
+                    "id": format!("{:?}", id),
+                    "name": self.quantifier_name(id),
+                    "formula": match self.term(id) {
+                        Ok(Term::Quant { .. }) => Some(self.id_to_sexp(&venv, id)?),
+                        _ => None,
+                    },
+                    "instantiations": instantiations,
+                    "first_instantiation": first_instantiation,
+                }))
+            })
+            .collect::<RawResult<Vec<_>>>()?;
+        let theories = self
+            .theory_stats()
+            .iter()
+            .map(|(name, stats)| {
+                let stats = json!({
+                    "instantiations": stats.instantiations,
+                    "equalities": stats.equalities,
+                    "meanings": stats.meanings,
+                });
+                (name.clone(), stats)
+            })
+            .collect::<serde_json::Map<_, _>>();
+        let max_scope_level = self
+            .scopes()
+            .iter()
+            .chain(std::iter::once(self.current_scope()))
+            .map(|scope| scope.level)
+            .max();
+        Ok(json!({
+            "version": SCHEMA_VERSION,
+            "tool": self
+                .tool_version()
+                .map(|(name, version)| json!({ "name": name, "version": version })),
+            "processed_logs": self.processed_logs(),
+            "terms": self.terms().len(),
+            "instantiations": self.instantiations().len(),
+            "conflicts": self.conflicts().count(),
+            "ematching_rounds": self.ematching_rounds(),
+            "max_scope_level": max_scope_level,
+            "theories": theories,
+            "quantifiers": quantifiers,
+        }))
+    }
+
+    /// Instantiation profile of the trace in JSON, with the versioned schema of
+    /// [`smt2parser::schema`]:
+    ///
+    /// ```json
+    /// {
+    ///   "version": 1,
+    ///   "quantifiers": [
+    ///     { "id": "#68", "name": "pf", "useful_instantiations": 1,
+    ///       "useless_instantiations": 1, "conflicts": 1 }
+    ///   ],
+    ///   "triggers": [
+    ///     { "quantifier": "#68", "trigger": "#10", "pattern": "(pattern (p _0))",
+    ///       "matches": 2, "instances": 2, "distinct_bindings": 2 }
+    ///   ],
+    ///   "instantiations": [
+    ///     { "key": "0x0", "quantifier": "arith#", "timestamp": 58, "terms": 1,
+    ///       "caused_instantiations": 0, "caused_terms": 1 }
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// The entries are sorted as by [`Model::conflict_participation`],
+    /// [`Model::trigger_stats`], and [`Model::instantiation_costs`].
+    pub fn instantiation_profile_json(&self) -> RawResult<Value> {
+        let venv = BTreeMap::new();
+        let quantifiers = self
+            .conflict_participation()
+            .into_iter()
+            .map(|participation| {
+                json!({
+                    "id": format!("{:?}", participation.quantifier),
+                    "name": self.quantifier_name(&participation.quantifier),
+                    "useful_instantiations": participation.useful_instantiations,
+                    "useless_instantiations": participation.useless_instantiations,
+                    "conflicts": participation.conflicts,
+                })
+            })
+            .collect::<Vec<_>>();
+        let triggers = self
+            .trigger_stats()
+            .into_iter()
+            .map(|stats| {
+                Ok(json!({
+                    "quantifier": format!("{:?}", stats.quantifier),
+                    "trigger": format!("{:?}", stats.trigger),
+                    "pattern": self.id_to_sexp(&venv, &stats.trigger)?,
+                    "matches": stats.matches,
+                    "instances": stats.instances,
+                    "distinct_bindings": stats.distinct_bindings,
+                }))
+            })
+            .collect::<RawResult<Vec<_>>>()?;
+        let instantiations = self
+            .instantiation_costs()
+            .into_iter()
+            .map(|cost| {
+                json!({
+                    "key": format!("{:?}", cost.key),
+                    "quantifier": format!("{:?}", cost.quantifier),
+                    "timestamp": self.instantiations()[&cost.key].timestamp,
+                    "terms": cost.terms,
+                    "caused_instantiations": cost.caused_instantiations,
+                    "caused_terms": cost.caused_terms,
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(json!({
+            "version": SCHEMA_VERSION,
+            "quantifiers": quantifiers,
+            "triggers": triggers,
+            "instantiations": instantiations,
+        }))
+    }
+}
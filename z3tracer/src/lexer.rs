@@ -3,6 +3,7 @@
 
 use crate::{
     error::{Error, Position, RawError, RawResult},
+    parser::Progress,
     syntax::{Equality, Ident, Literal, MatchedTerm, QiKey, VarName},
 };
 use smt2parser::concrete::Symbol;
@@ -43,6 +44,14 @@ where
         }
     }
 
+    /// Number of bytes and lines consumed so far.
+    pub fn progress(&self) -> Progress {
+        Progress {
+            bytes: self.current_offset,
+            lines: self.current_line,
+        }
+    }
+
     pub fn make_error(&self, error: RawError) -> Error {
         Error {
             position: self.current_position(),
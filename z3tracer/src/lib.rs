@@ -38,10 +38,10 @@
 //! * The successive backtracking levels during SMT solving;
 //!
 //! * SAT/SMT conflicts and their causal dependencies in terms of QIs;
-//! ![Conflicts](https://github.com/facebookincubator/smt2utils/blob/main/z3tracer/img/z3_tracer_1.jpg?raw=true)
+//!   ![Conflicts](https://github.com/facebookincubator/smt2utils/blob/main/z3tracer/img/z3_tracer_1.jpg?raw=true)
 //!
 //! * Causal dependencies between QIs.
-//! ![Causal dependencies between QIs](https://github.com/facebookincubator/smt2utils/blob/main/z3tracer/img/z3_tracer_2.jpg?raw=true)
+//!   ![Causal dependencies between QIs](https://github.com/facebookincubator/smt2utils/blob/main/z3tracer/img/z3_tracer_2.jpg?raw=true)
 //!
 //! A tool `z3tracer` based on the library is provided to process a log file `z3.log` from the
 //! command line and generate charts.
@@ -50,23 +50,45 @@
 //! [repository](https://github.com/facebookincubator/smt2utils/tree/main/z3tracer/notebooks)
 //! for additional examples using Jupyter notebooks.
 //!
-//! Currently, this library only supports Z3 v4.8.9.
+//! Currently, this library supports the logs of Z3 v4.8.9 up to v4.13. Logs of other versions
+//! may be parsed with the options `skip_z3_version_check` and `ignore_unknown_commands`.
+//!
+//! Large log files may be memory-mapped rather than buffered by enabling the feature `mmap`
+//! (see `report::open_log_file`).
 //!
 //! More information about Z3 tracing logs can be found in the documentation of the
 //! project [Axiom Profiler](https://github.com/viperproject/axiom-profiler).
 
-#![forbid(unsafe_code)]
+#![cfg_attr(not(feature = "mmap"), forbid(unsafe_code))]
+#![cfg_attr(feature = "mmap", deny(unsafe_code))]
+#![allow(clippy::result_large_err)]
 
 /// Error management.
 pub mod error;
+/// Export of models in the format of Z3 logs.
+pub mod export;
+/// Export of models in JSON.
+pub mod json;
 /// Tokenization of Z3 logs.
 pub mod lexer;
 /// Main analyzer module.
 pub mod model;
 /// Parsing of Z3 logs.
 pub mod parser;
+/// Extraction and export of proofs.
+pub mod proof;
+/// Python bindings.
+#[cfg(feature = "python")]
+pub mod python;
+/// Search of terms in a model.
+pub mod query;
+/// Reduction of SMT-LIB inputs to the quantifiers of heavy instantiation chains.
+pub mod reduce;
 /// Terms and data structures found in Z3 logs.
 pub mod syntax;
+/// JavaScript bindings.
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 pub mod report;
 
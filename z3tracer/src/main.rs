@@ -3,7 +3,12 @@
 
 #![forbid(unsafe_code)]
 
-use z3tracer::{report::*, Model, ModelConfig};
+use z3tracer::{
+    get_instantiations,
+    reduce::{heaviest_quantifiers, reduce_commands},
+    report::*,
+    Model, ModelConfig,
+};
 
 use multiset::HashMultiSet;
 use petgraph::graph::Graph;
@@ -63,6 +68,26 @@ struct Options {
     #[structopt(long)]
     merge_conflicts_in_graphs: bool,
 
+    /// Output a standalone HTML report (for each input file) summarizing quantifier
+    /// instantiations, matching loops, and conflicts.
+    #[structopt(long)]
+    html_report: bool,
+
+    /// Output a reduced copy of the given SMT-LIB input (for each input file) keeping
+    /// only the quantified assertions involved in the heaviest instantiation chains.
+    #[structopt(long, parse(from_os_str))]
+    reproducer: Option<PathBuf>,
+
+    /// How many of the heaviest instantiation chains to keep in --reproducer.
+    #[structopt(long, default_value = "3")]
+    reproducer_chains: usize,
+
+    /// Print the distribution of the lengths of the chains of dependent QIs of each
+    /// quantifier. With several input files, given for increasing BMC bounds, also print
+    /// the quantifiers whose longest chain grows with the bound.
+    #[structopt(long)]
+    chain_depths: bool,
+
     /// How to select "user" instantiations.
     #[structopt(long, default_value = "outputbpl")]
     user_instantiation_prefix: String,
@@ -191,14 +216,59 @@ fn main() {
         options.plot_instantiation_graph = true;
         options.plot_instantiation_graph_with_conflicts = true;
     }
+    let mut chain_depth_runs = Vec::new();
     for input in &options.inputs {
         let file_name = input.to_str().unwrap().to_string();
         eprintln!("Processing {}", file_name);
-        let model = process_file(options.config.clone(), &input).unwrap();
+        let model = match process_file(options.config.clone(), input) {
+            Ok(model) => model,
+            Err(ProcessError::Log { error, model }) => {
+                eprintln!("Error at {:?}: {:?}", error.position, error.error);
+                *model
+            }
+            Err(error) => panic!("{}", error),
+        };
         eprintln!("Done processing {}", file_name);
         eprintln!("Terms: {}", model.terms().len());
         eprintln!("Instantiations: {}", model.instantiations().len());
 
+        if options.html_report {
+            let path = std::path::PathBuf::from(file_name.clone() + ".html");
+            eprintln!("Writing HTML report to {}", path.to_str().unwrap_or(""));
+            let config = HtmlReportConfig {
+                title: file_name.clone(),
+                top_n: options.keep_top_instantiations,
+                ..HtmlReportConfig::default()
+            };
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&path).unwrap());
+            write_html_report(&model, &config, &mut file).unwrap();
+        }
+
+        if let Some(smt_input) = &options.reproducer {
+            let path = std::path::PathBuf::from(file_name.clone() + ".reduced.smt2");
+            eprintln!("Writing reproducer to {}", path.to_str().unwrap_or(""));
+            let content = std::fs::read_to_string(smt_input).unwrap();
+            let commands = smt2parser::parse_commands(&content).unwrap();
+            let quantifiers = heaviest_quantifiers(&model, options.reproducer_chains);
+            let mut file = std::io::BufWriter::new(std::fs::File::create(&path).unwrap());
+            for command in reduce_commands(&commands, &quantifiers) {
+                writeln!(file, "{}", command).unwrap();
+            }
+        }
+
+        if options.chain_depths {
+            let distributions = chain_depth_distributions(&model);
+            for d in &distributions {
+                let histogram = d
+                    .histogram
+                    .iter()
+                    .map(|(depth, count)| format!("{}:{}", depth, count))
+                    .collect::<Vec<_>>();
+                println!("{}\t{}\t{}", d.name, d.max_depth(), histogram.join(" "));
+            }
+            chain_depth_runs.push(distributions);
+        }
+
         if !options.plot_instantiations
             && !options.plot_user_instantiations
             && !options.plot_scopes
@@ -330,7 +400,7 @@ fn main() {
             writeln!(f, "{}", petgraph::dot::Dot::new(&g)).unwrap();
 
             std::process::Command::new("dot")
-                .args(&["-O", "-Tpng", path.to_str().unwrap()])
+                .args(["-O", "-Tpng", path.to_str().unwrap()])
                 .status()
                 .expect("Error running `dot` (is graphviz installed?)");
         }
@@ -352,9 +422,12 @@ fn main() {
             writeln!(f, "{}", petgraph::dot::Dot::new(&g)).unwrap();
 
             std::process::Command::new("dot")
-                .args(&["-O", "-Tpng", path.to_str().unwrap()])
+                .args(["-O", "-Tpng", path.to_str().unwrap()])
                 .status()
                 .expect("Error running `dot` (is graphviz installed?)");
         }
     }
+    for name in growing_chain_depths(&chain_depth_runs) {
+        println!("Chains of dependent QIs grow with the bound: {}", name);
+    }
 }
@@ -1,15 +1,23 @@
 // Copyright (c) Facebook, Inc. and its affiliates
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use once_cell::sync::Lazy;
-use smt2parser::concrete::Symbol;
-use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashSet};
+use once_cell::sync::{Lazy, OnceCell};
+use smt2parser::{
+    concrete::{QualIdentifier, Sort, Symbol},
+    de_bruijn::IndexedTerm,
+    visitors::Identifier,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashSet},
+    sync::atomic::AtomicBool,
+};
 use structopt::StructOpt;
 
 use crate::{
     error::{RawError, RawResult, Result},
     lexer::Lexer,
-    parser::{LogVisitor, Parser, ParserConfig},
+    parser::{LogVisitor, Parser, ParserConfig, Progress},
+    query::TermPattern,
     syntax::{
         Equality, Ident, Literal, MatchedTerm, Meaning, QiFrame, QiInstance, QiKey, Term, VarName,
         Visitor,
@@ -177,12 +185,118 @@ pub struct QuantInstantiation {
     pub frame: QiFrame,
     /// Corresponding "instance" data collected between `[instance]` and `[end-instance]` logs.
     pub instances: Vec<QiInstance>,
+    /// Relative creation time (currently, the line number in the Z3 log).
+    pub timestamp: usize,
+    /// Start and end times of each instance.
+    pub instance_timestamps: Vec<(usize, usize)>,
     /// Dependencies to QI keys (see ScopedTermData).
     pub qi_deps: BTreeSet<QiRef>,
     /// Temporary data reflecting dependencies to a `quant-inst` proof term (idem).
     proof_deps: BTreeSet<ProofRef>,
 }
 
+/// Participation of the instantiations of a quantifier in conflicts.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ConflictParticipation {
+    /// Id of the quantifier term.
+    pub quantifier: Ident,
+    /// Number of QIs that some conflict depends on (directly or transitively).
+    pub useful_instantiations: usize,
+    /// Number of QIs that no conflict depends on.
+    pub useless_instantiations: usize,
+    /// Number of conflicts depending on at least one QI of the quantifier.
+    pub conflicts: usize,
+}
+
+/// Counts of the events attributed to a theory (e.g. `arith`, `array`, `basic`).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TheoryStats {
+    /// Instantiations of theory axioms (`[inst-discovered] theory-solving`).
+    pub instantiations: usize,
+    /// Equalities explained by the theory (`[eq-expl] .. th`).
+    pub equalities: usize,
+    /// Terms interpreted by the theory (`[attach-meaning]`).
+    pub meanings: usize,
+}
+
+/// A step in the explanation of an equality, as logged by `[eq-expl]`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EqualityStep {
+    /// Left-hand side of the step.
+    pub lhs: Ident,
+    /// Right-hand side of the step.
+    pub rhs: Ident,
+    /// Justification of `lhs == rhs` (possibly stated as `rhs == lhs`).
+    pub justification: Equality,
+    /// Relative creation time (currently, the line number in the Z3 log).
+    pub timestamp: usize,
+}
+
+/// A QI produced by E-matching, together with the trigger that matched.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TriggerMatch {
+    /// Id of the quantifier term.
+    pub quantifier: Ident,
+    /// Id of the trigger (i.e. the `pattern` term).
+    pub trigger: Ident,
+    /// Terms bound to the variables of the quantifier (with the variable names, if known).
+    pub bindings: Vec<(Option<Symbol>, Ident)>,
+}
+
+/// Statistics on the QIs produced by a trigger.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TriggerStats {
+    /// Id of the quantifier term.
+    pub quantifier: Ident,
+    /// Id of the trigger.
+    pub trigger: Ident,
+    /// Number of matches.
+    pub matches: usize,
+    /// Number of matches that were actually instantiated.
+    pub instances: usize,
+    /// Number of distinct bindings among the matches.
+    pub distinct_bindings: usize,
+}
+
+/// Cost attributed to a QI.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InstantiationCost {
+    /// Key of the QI.
+    pub key: QiKey,
+    /// Id of the quantifier term.
+    pub quantifier: Ident,
+    /// Number of terms (enodes) produced by the QI.
+    pub terms: usize,
+    /// Number of QIs that depend on this QI, directly or transitively.
+    pub caused_instantiations: usize,
+    /// Number of terms produced by the QI and by the QIs depending on it.
+    pub caused_terms: usize,
+}
+
+/// Comparison of the instantiations of a quantifier between two models.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct QuantifierDiff {
+    /// Name of the quantifier.
+    pub name: String,
+    /// Number of instantiations in each model.
+    pub instantiations: (usize, usize),
+    /// Number of conflicts depending on the quantifier in each model.
+    pub conflicts: (usize, usize),
+    /// Time of the first instantiation in each model.
+    pub first_instantiation: (Option<usize>, Option<usize>),
+}
+
+/// Comparison between two models, e.g. before and after adding a lemma.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ModelDiff {
+    /// Number of processed logs in each model.
+    pub processed_logs: (usize, usize),
+    /// Number of conflicts in each model.
+    pub conflicts: (usize, usize),
+    /// Comparison of each quantifier, sorted by decreasing difference of instantiations.
+    pub quantifiers: Vec<QuantifierDiff>,
+}
+
 /// Main state of the Z3 tracer.
 #[derive(Default, Debug)]
 pub struct Model {
@@ -200,6 +314,83 @@ pub struct Model {
     scopes: Vec<Scope>,
     // Current scope.
     current_scope: Scope,
+    // Event counts indexed by theory name.
+    theory_stats: BTreeMap<String, TheoryStats>,
+    // Number of E-matching rounds.
+    ematching_rounds: usize,
+    // Whether the last `[new-match]` log belongs to the current round of E-matching.
+    in_ematching_round: bool,
+    // Successive `[eq-expl]` logs (with their timestamps) indexed by term.
+    equality_explanations: BTreeMap<Ident, Vec<(usize, Equality)>>,
+    // Name and version of the tool that produced the logs.
+    tool_version: Option<(String, String)>,
+    // Index of terms and instances by timestamp, built lazily.
+    timestamp_index: OnceCell<TimestampIndex>,
+}
+
+/// Terms and QI instances indexed by timestamp.
+#[derive(Default, Debug)]
+struct TimestampIndex {
+    terms: BTreeMap<usize, Ident>,
+    // Instances indexed by the timestamp of their `[instance]` log.
+    instances: BTreeMap<usize, (QiKey, usize)>,
+}
+
+impl QuantifierDiff {
+    /// Difference between the number of instantiations in the second and in the first model.
+    pub fn instantiation_delta(&self) -> i64 {
+        self.instantiations.1 as i64 - self.instantiations.0 as i64
+    }
+}
+
+impl ModelDiff {
+    /// Quantifiers instantiated more often in the second model, biggest regressions first.
+    pub fn regressions(&self) -> impl Iterator<Item = &QuantifierDiff> {
+        self.quantifiers
+            .iter()
+            .filter(|q| q.instantiation_delta() > 0)
+    }
+
+    /// Quantifiers instantiated less often in the second model, biggest improvements first.
+    pub fn improvements(&self) -> impl Iterator<Item = &QuantifierDiff> {
+        self.quantifiers
+            .iter()
+            .rev()
+            .filter(|q| q.instantiation_delta() < 0)
+    }
+}
+
+impl std::fmt::Display for ModelDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Processed logs: {} -> {}",
+            self.processed_logs.0, self.processed_logs.1
+        )?;
+        writeln!(f, "Conflicts: {} -> {}", self.conflicts.0, self.conflicts.1)?;
+        for (title, quantifiers) in [
+            ("Regressions", self.regressions().collect::<Vec<_>>()),
+            ("Improvements", self.improvements().collect::<Vec<_>>()),
+        ] {
+            if quantifiers.is_empty() {
+                continue;
+            }
+            writeln!(f, "{}:", title)?;
+            for q in quantifiers {
+                writeln!(
+                    f,
+                    "  {}: {} -> {} instantiations ({:+}), {} -> {} conflicts",
+                    q.name,
+                    q.instantiations.0,
+                    q.instantiations.1,
+                    q.instantiation_delta(),
+                    q.conflicts.0,
+                    q.conflicts.1
+                )?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Assignment {
@@ -240,6 +431,25 @@ impl Model {
         Parser::new(config, lexer, self).parse()
     }
 
+    /// Process some input while reporting progress every `period` lines. Processing
+    /// stops with the error `RawError::Cancelled` as soon as `cancel` is set.
+    pub fn process_with_progress<R, F>(
+        &mut self,
+        path_name: Option<String>,
+        input: R,
+        period: usize,
+        cancel: &AtomicBool,
+        progress: F,
+    ) -> Result<()>
+    where
+        R: std::io::BufRead,
+        F: FnMut(Progress),
+    {
+        let lexer = Lexer::new(path_name, input);
+        let config = self.config.parser_config.clone();
+        Parser::new(config, lexer, self).parse_with_progress(period, cancel, progress)
+    }
+
     /// All terms in the model.
     pub fn terms(&self) -> &BTreeMap<Ident, TermData> {
         &self.terms
@@ -265,6 +475,90 @@ impl Model {
         &self.current_scope
     }
 
+    /// Name and version of the tool that produced the logs, if known.
+    pub fn tool_version(&self) -> Option<(&str, &str)> {
+        self.tool_version
+            .as_ref()
+            .map(|(s1, s2)| (s1.as_str(), s2.as_str()))
+    }
+
+    /// All the `[eq-expl]` logs, with their timestamps, indexed by term.
+    pub fn equality_explanations(&self) -> &BTreeMap<Ident, Vec<(usize, Equality)>> {
+        &self.equality_explanations
+    }
+
+    /// Event counts indexed by theory name.
+    pub fn theory_stats(&self) -> &BTreeMap<String, TheoryStats> {
+        &self.theory_stats
+    }
+
+    /// Number of E-matching rounds, that is, maximal sequences of `[new-match]` logs
+    /// that are not interrupted by an assignment, a conflict, or a change of scope.
+    pub fn ematching_rounds(&self) -> usize {
+        self.ematching_rounds
+    }
+
+    /// Explain why the terms `id1` and `id2` were equal at the given time, that is, after
+    /// processing the first `timestamp` logs. The result is the chain of steps from
+    /// `id1` to `id2` in the explanation forest built from `[eq-expl]` logs, or `None`
+    /// if the two terms were not known to be equal.
+    pub fn explain_equality(
+        &self,
+        id1: &Ident,
+        id2: &Ident,
+        timestamp: usize,
+    ) -> Option<Vec<EqualityStep>> {
+        let path1 = self.equality_explanation_path(id1, timestamp);
+        let path2 = self.equality_explanation_path(id2, timestamp);
+        // Find the closest common ancestor.
+        let ancestors1 = std::iter::once(id1)
+            .chain(path1.iter().map(|s| &s.rhs))
+            .collect::<Vec<_>>();
+        let (i2, common) = std::iter::once(id2)
+            .chain(path2.iter().map(|s| &s.rhs))
+            .enumerate()
+            .find(|(_, id)| ancestors1.contains(id))?;
+        let i1 = ancestors1.iter().position(|id| *id == common)?;
+        let mut steps = path1;
+        steps.truncate(i1);
+        steps.extend(path2.into_iter().take(i2).rev().map(|s| EqualityStep {
+            lhs: s.rhs,
+            rhs: s.lhs,
+            ..s
+        }));
+        Some(steps)
+    }
+
+    // Follow the most recent `[eq-expl]` logs (before the given time) from `id` up to the
+    // root of its equality class.
+    fn equality_explanation_path(&self, id: &Ident, timestamp: usize) -> Vec<EqualityStep> {
+        let mut steps = Vec::new();
+        let mut visited = BTreeSet::new();
+        let mut current = id.clone();
+        while visited.insert(current.clone()) {
+            let step = self
+                .equality_explanations
+                .get(&current)
+                .and_then(|v| v.iter().rev().find(|(ts, _)| *ts <= timestamp));
+            let (ts, eq) = match step {
+                Some(x) => x,
+                None => break,
+            };
+            let next = match eq.target() {
+                Some(t) => t.clone(),
+                None => break,
+            };
+            steps.push(EqualityStep {
+                lhs: current,
+                rhs: next.clone(),
+                justification: eq.clone(),
+                timestamp: *ts,
+            });
+            current = next;
+        }
+        steps
+    }
+
     /// Construct a max-heap of the (most) instantiated quantified terms.
     pub fn most_instantiated_terms(&self) -> BinaryHeap<(usize, Ident)> {
         self.terms
@@ -287,6 +581,404 @@ impl Model {
             .filter_map(|scope| scope.conflict.as_ref())
     }
 
+    /// Link conflicts to the QIs that they depend on, transitively, and report the
+    /// number of useful vs. useless instantiations for each quantifier. The result is
+    /// sorted by decreasing number of useful instantiations, then by decreasing number
+    /// of useless ones.
+    pub fn conflict_participation(&self) -> Vec<ConflictParticipation> {
+        let mut useful = BTreeSet::new();
+        let mut conflicts = BTreeMap::<&Ident, usize>::new();
+        for conflict in self.conflicts() {
+            let mut deps = BTreeSet::new();
+            let mut queue = conflict.qi_deps.iter().map(|qi| qi.key).collect::<Vec<_>>();
+            while let Some(key) = queue.pop() {
+                if !deps.insert(key) {
+                    continue;
+                }
+                if let Some(inst) = self.instantiations.get(&key) {
+                    queue.extend(inst.qi_deps.iter().map(|qi| qi.key));
+                }
+            }
+            let quantifiers = deps
+                .iter()
+                .filter_map(|key| Some(self.instantiations.get(key)?.frame.quantifier()))
+                .collect::<BTreeSet<_>>();
+            for quantifier in quantifiers {
+                *conflicts.entry(quantifier).or_default() += 1;
+            }
+            useful.extend(deps);
+        }
+        let mut result = BTreeMap::<&Ident, ConflictParticipation>::new();
+        for (key, inst) in &self.instantiations {
+            let quantifier = inst.frame.quantifier();
+            let entry = result
+                .entry(quantifier)
+                .or_insert_with(|| ConflictParticipation {
+                    quantifier: quantifier.clone(),
+                    conflicts: conflicts.get(quantifier).cloned().unwrap_or(0),
+                    ..ConflictParticipation::default()
+                });
+            if useful.contains(key) {
+                entry.useful_instantiations += 1;
+            } else {
+                entry.useless_instantiations += 1;
+            }
+        }
+        let mut result = result.into_values().collect::<Vec<_>>();
+        result.sort_by(|p1, p2| {
+            (p2.useful_instantiations, p2.useless_instantiations)
+                .cmp(&(p1.useful_instantiations, p1.useless_instantiations))
+        });
+        result
+    }
+
+    /// The trigger and the bindings of a QI, when the QI was produced by E-matching.
+    pub fn trigger_match(&self, key: &QiKey) -> Option<TriggerMatch> {
+        match &self.instantiations.get(key)?.frame {
+            QiFrame::NewMatch {
+                quantifier,
+                trigger,
+                terms,
+                ..
+            } => {
+                let var_names = match self.term(quantifier) {
+                    Ok(Term::Quant {
+                        var_names: Some(var_names),
+                        ..
+                    }) => var_names.as_slice(),
+                    _ => &[],
+                };
+                let bindings = terms
+                    .iter()
+                    .enumerate()
+                    .map(|(i, id)| (var_names.get(i).map(|v| v.name.clone()), id.clone()))
+                    .collect();
+                Some(TriggerMatch {
+                    quantifier: quantifier.clone(),
+                    trigger: trigger.clone(),
+                    bindings,
+                })
+            }
+            QiFrame::Discovered { .. } => None,
+        }
+    }
+
+    /// Aggregate statistics on the QIs produced by each trigger, sorted by decreasing
+    /// number of matches.
+    pub fn trigger_stats(&self) -> Vec<TriggerStats> {
+        let mut stats = BTreeMap::<(&Ident, &Ident), (TriggerStats, BTreeSet<&[Ident]>)>::new();
+        for inst in self.instantiations.values() {
+            if let QiFrame::NewMatch {
+                quantifier,
+                trigger,
+                terms,
+                ..
+            } = &inst.frame
+            {
+                let (entry, bindings) = stats.entry((quantifier, trigger)).or_insert_with(|| {
+                    let entry = TriggerStats {
+                        quantifier: quantifier.clone(),
+                        trigger: trigger.clone(),
+                        ..TriggerStats::default()
+                    };
+                    (entry, BTreeSet::new())
+                });
+                entry.matches += 1;
+                if !inst.instances.is_empty() {
+                    entry.instances += 1;
+                }
+                bindings.insert(terms.as_slice());
+            }
+        }
+        let mut stats = stats
+            .into_values()
+            .map(|(entry, bindings)| TriggerStats {
+                distinct_bindings: bindings.len(),
+                ..entry
+            })
+            .collect::<Vec<_>>();
+        stats.sort_by(|s1, s2| s2.matches.cmp(&s1.matches));
+        stats
+    }
+
+    /// Attribute to each QI the number of terms and QIs that it caused, transitively.
+    /// The result is sorted by decreasing number of caused QIs, then caused terms.
+    pub fn instantiation_costs(&self) -> Vec<InstantiationCost> {
+        // Reverse the dependency graph.
+        let mut dependents = BTreeMap::<QiKey, BTreeSet<QiKey>>::new();
+        for (key, inst) in &self.instantiations {
+            for dep in &inst.qi_deps {
+                if dep.key != *key {
+                    dependents.entry(dep.key).or_default().insert(*key);
+                }
+            }
+        }
+        let terms = |inst: &QuantInstantiation| {
+            inst.instances.iter().map(|i| i.enodes.len()).sum::<usize>()
+        };
+        let mut costs = self
+            .instantiations
+            .iter()
+            .map(|(key, inst)| {
+                let mut caused = BTreeSet::new();
+                let mut queue = vec![*key];
+                while let Some(k) = queue.pop() {
+                    for d in dependents.get(&k).into_iter().flatten() {
+                        if d != key && caused.insert(*d) {
+                            queue.push(*d);
+                        }
+                    }
+                }
+                let own_terms = terms(inst);
+                let caused_terms = own_terms
+                    + caused
+                        .iter()
+                        .filter_map(|k| Some(terms(self.instantiations.get(k)?)))
+                        .sum::<usize>();
+                InstantiationCost {
+                    key: *key,
+                    quantifier: inst.frame.quantifier().clone(),
+                    terms: own_terms,
+                    caused_instantiations: caused.len(),
+                    caused_terms,
+                }
+            })
+            .collect::<Vec<_>>();
+        costs.sort_by(|c1, c2| {
+            (c2.caused_instantiations, c2.caused_terms)
+                .cmp(&(c1.caused_instantiations, c1.caused_terms))
+        });
+        costs
+    }
+
+    /// Compare the quantifier instantiations of this model with the ones of `other`.
+    /// Quantifiers are matched by name.
+    pub fn diff(&self, other: &Model) -> ModelDiff {
+        let mut quantifiers = BTreeMap::<String, QuantifierDiff>::new();
+        for (index, model) in [self, other].iter().enumerate() {
+            let conflicts = model
+                .conflict_participation()
+                .into_iter()
+                .map(|p| (p.quantifier, p.conflicts))
+                .collect::<BTreeMap<_, _>>();
+            for (id, data) in &model.terms {
+                if data.instantiations.is_empty() {
+                    continue;
+                }
+                let name = model.quantifier_name(id);
+                let entry = quantifiers
+                    .entry(name.clone())
+                    .or_insert_with(|| QuantifierDiff {
+                        name,
+                        ..QuantifierDiff::default()
+                    });
+                let count = data.instantiations.len();
+                let conflicts = conflicts.get(id).cloned().unwrap_or(0);
+                let first = data
+                    .instantiations
+                    .iter()
+                    .filter_map(|key| Some(model.instantiations.get(key)?.timestamp))
+                    .min();
+                let first_instantiation = |t: Option<usize>| match (t, first) {
+                    (Some(t), Some(f)) => Some(std::cmp::min(t, f)),
+                    (t, f) => t.or(f),
+                };
+                if index == 0 {
+                    entry.instantiations.0 += count;
+                    entry.conflicts.0 += conflicts;
+                    entry.first_instantiation.0 = first_instantiation(entry.first_instantiation.0);
+                } else {
+                    entry.instantiations.1 += count;
+                    entry.conflicts.1 += conflicts;
+                    entry.first_instantiation.1 = first_instantiation(entry.first_instantiation.1);
+                }
+            }
+        }
+        let mut quantifiers = quantifiers.into_values().collect::<Vec<_>>();
+        quantifiers.sort_by_key(|q| std::cmp::Reverse(q.instantiation_delta()));
+        ModelDiff {
+            processed_logs: (self.processed_logs, other.processed_logs),
+            conflicts: (self.conflicts().count(), other.conflicts().count()),
+            quantifiers,
+        }
+    }
+
+    /// Ground instance of the quantified formula for the given instantiation, as an
+    /// SMT-LIB term: the body of the quantifier where each variable is replaced by the
+    /// term bound to it.
+    pub fn instantiated_lemma(&self, key: &QiKey) -> RawResult<String> {
+        let inst = self
+            .instantiations
+            .get(key)
+            .ok_or(RawError::InvalidInstanceKey)?;
+        let quantifier = inst.frame.quantifier();
+        match self.term(quantifier)? {
+            Term::Quant { params, body, .. } if *params == inst.frame.terms().len() => {
+                self.id_to_ground_sexp(inst.frame.terms(), &BTreeMap::new(), body)
+            }
+            _ => Err(RawError::CannotInstantiateQuantifier(quantifier.clone())),
+        }
+    }
+
+    /// SMT-LIB assertions of the ground instances of the given instantiations, e.g. to
+    /// be added as refinement lemmas to an SMT problem.
+    pub fn instantiated_lemmas<'a, I>(&self, keys: I) -> RawResult<Vec<String>>
+    where
+        I: IntoIterator<Item = &'a QiKey>,
+    {
+        keys.into_iter()
+            .map(|key| Ok(format!("(assert {})", self.instantiated_lemma(key)?)))
+            .collect()
+    }
+
+    // Display a term where the free variable of index `i` is replaced by `bindings[i]`.
+    // Variables bound by nested quantifiers are given by `venv`.
+    fn id_to_ground_sexp(
+        &self,
+        bindings: &[Ident],
+        venv: &BTreeMap<u64, Symbol>,
+        id: &Ident,
+    ) -> RawResult<String> {
+        use Term::*;
+        match self.term(id)? {
+            App {
+                meaning: Some(meaning),
+                ..
+            } => Ok(meaning.sexp.clone()),
+            App {
+                name,
+                args,
+                meaning: None,
+            } => {
+                // Z3 logs if-then-else terms as `if`.
+                let name = if name == "if" {
+                    "ite".to_string()
+                } else {
+                    Symbol(name.clone()).to_string()
+                };
+                if args.is_empty() {
+                    Ok(name)
+                } else {
+                    Ok(format!(
+                        "({} {})",
+                        name,
+                        args.iter()
+                            .map(|id| self.id_to_ground_sexp(bindings, venv, id))
+                            .collect::<RawResult<Vec<_>>>()?
+                            .join(" ")
+                    ))
+                }
+            }
+            Var { index } => match venv.get(index) {
+                Some(s) => Ok(s.to_string()),
+                None => {
+                    let id = bindings
+                        .get(*index as usize - venv.len())
+                        .ok_or(RawError::UndefinedIdent(id.clone()))?;
+                    self.id_to_ground_sexp(&[], &BTreeMap::new(), id)
+                }
+            },
+            Quant {
+                params,
+                body,
+                var_names: Some(var_names),
+                ..
+            } => {
+                // Nested quantifiers are printed as universal quantifiers since Z3
+                // eliminates existential quantifiers beforehand.
+                let mut venv = venv
+                    .iter()
+                    .map(|(i, s)| (*i + *params as u64, s.clone()))
+                    .collect::<BTreeMap<_, _>>();
+                for (i, vn) in var_names.iter().enumerate() {
+                    venv.insert(i as u64, vn.name.clone());
+                }
+                let vars = var_names
+                    .iter()
+                    .rev()
+                    .map(|vn| format!("({} {})", vn.name, vn.sort))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                Ok(format!(
+                    "(forall ({}) {})",
+                    vars,
+                    self.id_to_ground_sexp(bindings, &venv, body)?
+                ))
+            }
+            _ => Err(RawError::CannotInstantiateQuantifier(id.clone())),
+        }
+    }
+
+    // Name of a quantifier (or of a builtin theory), defaulting to its identifier.
+    pub(crate) fn quantifier_name(&self, id: &Ident) -> String {
+        match self.term(id) {
+            Ok(Term::Quant { name, .. }) | Ok(Term::Builtin { name: Some(name) }) => {
+                name.to_string()
+            }
+            _ => format!("{:?}", id),
+        }
+    }
+
+    /// All the terms satisfying the given predicate.
+    pub fn find_terms<F>(&self, mut predicate: F) -> impl Iterator<Item = &Ident>
+    where
+        F: FnMut(&Term) -> bool,
+    {
+        self.terms.iter().filter_map(move |(id, data)| {
+            if predicate(&data.term) {
+                Some(id)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// All the terms matching the given pattern.
+    pub fn find_matching_terms<'a>(
+        &'a self,
+        pattern: &'a TermPattern,
+    ) -> impl Iterator<Item = &'a Ident> {
+        self.terms
+            .keys()
+            .filter(move |id| pattern.matches(self, id))
+    }
+
+    /// Term created at the given timestamp (i.e. by the log of this rank), if any.
+    /// Timestamps are indexed lazily the first time this function or
+    /// `instance_at_timestamp` is called after processing some input.
+    pub fn term_at_timestamp(&self, timestamp: usize) -> Option<&Ident> {
+        self.timestamp_index().terms.get(&timestamp)
+    }
+
+    /// QI instance started at the given timestamp (i.e. by an `[instance]` log), if any.
+    /// This is the case of the timestamps found in `TermData::instantiation_timestamps`.
+    ///
+    /// The instantiated quantifier is given by
+    /// `model.instantiations()[key].frame.quantifier()` and the term produced by the
+    /// instance (if logged) is `instance.term`.
+    pub fn instance_at_timestamp(&self, timestamp: usize) -> Option<(&QiKey, &QiInstance)> {
+        let (key, index) = self.timestamp_index().instances.get(&timestamp)?;
+        let (key, inst) = self.instantiations.get_key_value(key)?;
+        Some((key, inst.instances.get(*index)?))
+    }
+
+    fn timestamp_index(&self) -> &TimestampIndex {
+        self.timestamp_index.get_or_init(|| {
+            let mut index = TimestampIndex::default();
+            for (id, data) in &self.terms {
+                if !id.is_builtin() {
+                    index.terms.insert(data.timestamp, id.clone());
+                }
+            }
+            for (key, inst) in &self.instantiations {
+                for (i, (start, _)) in inst.instance_timestamps.iter().enumerate() {
+                    index.instances.insert(*start, (*key, i));
+                }
+            }
+            index
+        })
+    }
+
     /// Retrieve a particular term.
     pub fn term(&self, id: &Ident) -> RawResult<&Term> {
         let t = &self
@@ -484,6 +1176,61 @@ impl Model {
         }
     }
 
+    /// Convert a term into an `smt2parser` term whose bound variables are de Bruijn
+    /// indices, so that it can be compared with an input term converted by
+    /// `to_de_bruijn`. Quantifiers must have variable names. They are converted into
+    /// universal quantifiers without triggers.
+    pub fn id_to_de_bruijn(&self, id: &Ident) -> RawResult<IndexedTerm> {
+        use Term::*;
+        match self.term(id)? {
+            App {
+                meaning: Some(meaning),
+                ..
+            } => smt2parser::parse_term(&meaning.sexp)
+                .map(|term| term.to_de_bruijn())
+                .map_err(|_| RawError::CannotConvertTerm(id.clone())),
+            App {
+                name,
+                args,
+                meaning: None,
+            } => {
+                // Z3 logs if-then-else terms as `if`.
+                let name = if name == "if" { "ite" } else { name };
+                let qual_identifier = QualIdentifier::Simple {
+                    identifier: Identifier::Simple {
+                        symbol: Symbol(name.to_string()),
+                    },
+                };
+                if args.is_empty() {
+                    Ok(IndexedTerm::QualIdentifier(qual_identifier))
+                } else {
+                    Ok(IndexedTerm::Application {
+                        qual_identifier,
+                        arguments: args
+                            .iter()
+                            .map(|id| self.id_to_de_bruijn(id))
+                            .collect::<RawResult<_>>()?,
+                    })
+                }
+            }
+            Var { index } => Ok(IndexedTerm::Var { index: *index }),
+            Quant {
+                body,
+                var_names: Some(var_names),
+                ..
+            } => Ok(IndexedTerm::Forall {
+                // Variable names are listed by increasing index.
+                sorts: var_names
+                    .iter()
+                    .rev()
+                    .map(|vn| parse_sort(&vn.sort))
+                    .collect(),
+                term: Box::new(self.id_to_de_bruijn(body)?),
+            }),
+            _ => Err(RawError::CannotConvertTerm(id.clone())),
+        }
+    }
+
     fn append_id_subterms(&self, deps: &mut BTreeSet<Ident>, id: &Ident) -> RawResult<()> {
         deps.insert(id.clone());
         self.append_term_subterms(deps, self.term(id)?)
@@ -923,6 +1670,14 @@ impl Model {
         }
     }
 
+    fn theory_stats_mut(&mut self, theory: &str) -> &mut TheoryStats {
+        if !self.theory_stats.contains_key(theory) {
+            self.theory_stats
+                .insert(theory.to_string(), TheoryStats::default());
+        }
+        self.theory_stats.get_mut(theory).unwrap()
+    }
+
     fn push_current_scope(&mut self, new_scope: Scope) {
         // TODO: consolidate equalities in all scopes?
         if self.current_scope.needs_consolidation {
@@ -938,6 +1693,7 @@ impl Model {
 impl LogVisitor for &mut Model {
     fn add_term(&mut self, ident: Ident, term: Term) -> RawResult<()> {
         self.processed_logs += 1;
+        self.timestamp_index.take();
         if self.has_log_consistency_checks() {
             term.visit(&mut |id| self.check_ident(id))?;
         }
@@ -1032,6 +1788,21 @@ impl LogVisitor for &mut Model {
             }
         }
 
+        match &frame {
+            QiFrame::Discovered { quantifier, .. } if quantifier.is_builtin() => {
+                if let Some(theory) = &quantifier.namespace {
+                    self.theory_stats_mut(theory).instantiations += 1;
+                }
+            }
+            QiFrame::Discovered { .. } => (),
+            QiFrame::NewMatch { .. } => {
+                if !self.in_ematching_round {
+                    self.ematching_rounds += 1;
+                    self.in_ematching_round = true;
+                }
+            }
+        }
+
         let quantifier = frame.quantifier();
         self.term_data_mut(quantifier)?.instantiations.push(key);
         self.instantiations.insert(
@@ -1039,6 +1810,8 @@ impl LogVisitor for &mut Model {
             QuantInstantiation {
                 frame,
                 instances: Vec::new(),
+                timestamp: self.processed_logs,
+                instance_timestamps: Vec::new(),
                 qi_deps,
                 proof_deps,
             },
@@ -1069,6 +1842,7 @@ impl LogVisitor for &mut Model {
 
     fn end_instance(&mut self) -> RawResult<()> {
         self.processed_logs += 1;
+        self.timestamp_index.take();
         let PendingQiInstance {
             key,
             instance,
@@ -1082,6 +1856,8 @@ impl LogVisitor for &mut Model {
             .get_mut(&key)
             .ok_or(RawError::InvalidInstanceKey)?;
         inst.instances.push(instance);
+        inst.instance_timestamps
+            .push((timestamp, self.processed_logs));
         let quantifier = inst.frame.quantifier().clone();
         self.term_data_mut(&quantifier)?
             .instantiation_timestamps
@@ -1099,6 +1875,10 @@ impl LogVisitor for &mut Model {
         if self.has_log_consistency_checks() {
             eq.visit(&mut |id| self.check_ident(id))?;
         }
+        self.equality_explanations
+            .entry(id.clone())
+            .or_default()
+            .push((self.processed_logs, eq.clone()));
         let (cid, scope_index, qi_deps, proof_deps) = match &eq {
             Root => {
                 // Nothing to do.
@@ -1148,7 +1928,10 @@ impl LogVisitor for &mut Model {
                 }
                 (cid, scope_index, qi_deps, proof_deps)
             }
-            Theory(_, cid) => (cid, 0, Vec::new(), Vec::new()),
+            Theory(theory, cid) => {
+                self.theory_stats_mut(theory).equalities += 1;
+                (cid, 0, Vec::new(), Vec::new())
+            }
             Axiom(cid) => (cid, 0, Vec::new(), Vec::new()),
             Unknown(cid) => (cid, 0, Vec::new(), Vec::new()),
         };
@@ -1158,6 +1941,7 @@ impl LogVisitor for &mut Model {
 
     fn attach_meaning(&mut self, id: Ident, m: Meaning) -> RawResult<()> {
         self.processed_logs += 1;
+        self.theory_stats_mut(&m.theory).meanings += 1;
         match self.term_mut(&id)? {
             Term::App { meaning, .. } => {
                 *meaning = Some(m);
@@ -1198,8 +1982,9 @@ impl LogVisitor for &mut Model {
         Ok(())
     }
 
-    fn tool_version(&mut self, _s1: String, _s2: String) -> RawResult<()> {
+    fn tool_version(&mut self, s1: String, s2: String) -> RawResult<()> {
         self.processed_logs += 1;
+        self.tool_version = Some((s1, s2));
         Ok(())
     }
 
@@ -1210,6 +1995,7 @@ impl LogVisitor for &mut Model {
 
     fn assign(&mut self, lit: Literal, _s: String) -> RawResult<()> {
         self.processed_logs += 1;
+        self.in_ematching_round = false;
         if self.has_log_consistency_checks() {
             lit.visit(&mut |id| self.check_ident(id))?;
         }
@@ -1231,6 +2017,7 @@ impl LogVisitor for &mut Model {
 
     fn conflict(&mut self, lits: Vec<Literal>, _s: String) -> RawResult<()> {
         self.processed_logs += 1;
+        self.in_ematching_round = false;
         if self.has_log_consistency_checks() {
             lits.visit(&mut |id| self.check_ident(id))?;
         }
@@ -1251,6 +2038,7 @@ impl LogVisitor for &mut Model {
 
     fn push(&mut self, level: u64) -> RawResult<()> {
         self.processed_logs += 1;
+        self.in_ematching_round = false;
         if self.has_log_consistency_checks() && level != self.current_scope.level {
             return Err(RawError::InvalidPush(level));
         }
@@ -1266,6 +2054,7 @@ impl LogVisitor for &mut Model {
 
     fn pop(&mut self, num: u64, current_level: u64) -> RawResult<()> {
         self.processed_logs += 1;
+        self.in_ematching_round = false;
         if self.has_log_consistency_checks()
             && (current_level != self.current_scope.level || num > current_level || num == 0)
         {
@@ -1307,4 +2096,25 @@ impl LogVisitor for &mut Model {
         }
         Ok(())
     }
+
+    fn decide_and_or(&mut self, id: Ident, arg: Ident) -> RawResult<()> {
+        self.processed_logs += 1;
+        if self.has_log_consistency_checks() {
+            self.check_ident(&id)?;
+            self.check_ident(&arg)?;
+        }
+        Ok(())
+    }
+}
+
+// Parse the name of a sort, such as `Int` or `(Array Int Int)`.
+fn parse_sort(name: &Symbol) -> Sort {
+    match smt2parser::parse_term(&format!("(as x {})", name.0)) {
+        Ok(smt2parser::concrete::Term::QualIdentifier(QualIdentifier::Sorted { sort, .. })) => sort,
+        _ => Sort::Simple {
+            identifier: Identifier::Simple {
+                symbol: name.clone(),
+            },
+        },
+    }
 }
@@ -1,6 +1,7 @@
 // Copyright (c) Facebook, Inc. and its affiliates
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use structopt::StructOpt;
 
 use crate::{
@@ -20,6 +21,19 @@ pub struct ParserConfig {
     /// Whether to skip the check for unsupported Z3 version.
     #[structopt(long)]
     pub skip_z3_version_check: bool,
+    /// Whether to ignore lines starting with an unknown '[command]' (e.g. produced by a
+    /// more recent version of Z3).
+    #[structopt(long)]
+    pub ignore_unknown_commands: bool,
+}
+
+/// Progress of the parsing of a Z3 trace.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub struct Progress {
+    /// Number of bytes processed.
+    pub bytes: usize,
+    /// Number of lines processed.
+    pub lines: usize,
 }
 
 /// Parser for Z3 traces.
@@ -62,6 +76,8 @@ pub trait LogVisitor {
     fn resolve_lit(&mut self, i: u64, lit: Literal) -> RawResult<()>;
 
     fn resolve_process(&mut self, lit: Literal) -> RawResult<()>;
+
+    fn decide_and_or(&mut self, id: Ident, arg: Ident) -> RawResult<()>;
 }
 
 impl<R, S> Parser<R, S> {
@@ -93,6 +109,32 @@ where
         Ok(())
     }
 
+    /// Parse the input while calling `progress` every `period` lines and at the end.
+    /// Parsing stops with the error `RawError::Cancelled` as soon as `cancel` is set.
+    pub fn parse_with_progress<F>(
+        &mut self,
+        period: usize,
+        cancel: &AtomicBool,
+        mut progress: F,
+    ) -> Result<()>
+    where
+        F: FnMut(Progress),
+    {
+        let mut next_report = period;
+        while self.parse_line().map_err(|e| self.lexer.make_error(e))? {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(self.lexer.make_error(RawError::Cancelled));
+            }
+            let current = self.lexer.progress();
+            if current.lines >= next_report {
+                progress(current);
+                next_report = current.lines + period;
+            }
+        }
+        progress(self.lexer.progress());
+        Ok(())
+    }
+
     /// Parse one line of the input.
     fn parse_line(&mut self) -> RawResult<bool> {
         let lexer = &mut self.lexer;
@@ -302,6 +344,13 @@ where
                 lexer.read_end_of_line()?;
                 Ok(true)
             }
+            "[decide-and-or]" => {
+                let id = lexer.read_ident()?;
+                let arg = lexer.read_ident()?;
+                state.decide_and_or(id, arg)?;
+                lexer.read_end_of_line()?;
+                Ok(true)
+            }
             "[eof]" => {
                 lexer.read_end_of_line()?;
                 Ok(false)
@@ -312,6 +361,12 @@ where
                 lexer.read_end_of_line()?;
                 Ok(true)
             }
+            s if self.config.ignore_unknown_commands && s.starts_with('[') => {
+                // Ignore unknown commands from other versions of Z3.
+                lexer.read_line()?;
+                lexer.read_end_of_line()?;
+                Ok(true)
+            }
             s => Err(RawError::UnknownCommand(s.to_string())),
         }
     }
@@ -0,0 +1,205 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    error::{RawError, RawResult},
+    model::Model,
+    syntax::{Ident, Term},
+};
+
+/// A proof step, as logged by `[mk-proof]`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ProofStep {
+    /// Name of the proof rule (e.g. `mp`, `quant-inst`, `th-lemma`).
+    pub rule: String,
+    /// Proof terms of the premises.
+    pub premises: Vec<Ident>,
+    /// The property being proven.
+    pub conclusion: Ident,
+}
+
+/// The DAG of proof steps leading to a given proof term.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ProofTree {
+    /// The final proof step.
+    root: Ident,
+    /// All the proof steps reachable from the root, indexed by proof term.
+    steps: BTreeMap<Ident, ProofStep>,
+}
+
+impl ProofTree {
+    /// Collect the proof steps leading to the proof term `root`.
+    pub fn new(model: &Model, root: &Ident) -> RawResult<Self> {
+        let mut steps = BTreeMap::new();
+        let mut queue = vec![root.clone()];
+        while let Some(id) = queue.pop() {
+            if steps.contains_key(&id) {
+                continue;
+            }
+            let (name, args, property) = match model.term(&id)? {
+                Term::Proof {
+                    name,
+                    args,
+                    property,
+                } => (name, args, property),
+                _ => return Err(RawError::MissingProof(id)),
+            };
+            let mut premises = Vec::new();
+            for arg in args {
+                if let Term::Proof { .. } = model.term(arg)? {
+                    premises.push(arg.clone());
+                    queue.push(arg.clone());
+                }
+            }
+            let step = ProofStep {
+                rule: name.clone(),
+                premises,
+                conclusion: property.clone(),
+            };
+            steps.insert(id, step);
+        }
+        Ok(Self {
+            root: root.clone(),
+            steps,
+        })
+    }
+
+    /// The final proof step.
+    pub fn root(&self) -> &Ident {
+        &self.root
+    }
+
+    /// All the proof steps, indexed by proof term.
+    pub fn steps(&self) -> &BTreeMap<Ident, ProofStep> {
+        &self.steps
+    }
+
+    /// Proof steps without premises (e.g. asserted formulas and axioms).
+    pub fn leaves(&self) -> impl Iterator<Item = &Ident> {
+        self.steps.iter().filter_map(|(id, step)| {
+            if step.premises.is_empty() {
+                Some(id)
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Proof steps in topological order: premises come before their consequences.
+    pub fn topological_order(&self) -> Vec<&Ident> {
+        let mut order = Vec::new();
+        let mut visited = BTreeSet::new();
+        // Iterative DFS with post-order emission.
+        let mut stack = vec![(&self.root, false)];
+        while let Some((id, expanded)) = stack.pop() {
+            if expanded {
+                order.push(id);
+                continue;
+            }
+            if !visited.insert(id) {
+                continue;
+            }
+            stack.push((id, true));
+            for premise in self.steps[id].premises.iter().rev() {
+                if !visited.contains(premise) {
+                    stack.push((premise, false));
+                }
+            }
+        }
+        order
+    }
+
+    /// Export the proof as a graph in DOT format. Edges go from conclusions to premises.
+    pub fn to_dot(&self, model: &Model) -> RawResult<String> {
+        let venv = BTreeMap::new();
+        let mut lines = vec!["digraph proof {".to_string()];
+        for id in self.topological_order() {
+            let step = &self.steps[id];
+            let conclusion = model.id_to_sexp(&venv, &step.conclusion)?;
+            lines.push(format!(
+                "    \"{:?}\" [label=\"{}\\n{}\"];",
+                id,
+                dot_escape(&step.rule),
+                dot_escape(&conclusion)
+            ));
+            for premise in &step.premises {
+                lines.push(format!("    \"{:?}\" -> \"{:?}\";", id, premise));
+            }
+        }
+        lines.push("}".to_string());
+        Ok(lines.join("\n"))
+    }
+
+    /// Export the proof in JSON, as a list of steps in topological order.
+    pub fn to_json(&self, model: &Model) -> RawResult<serde_json::Value> {
+        let venv = BTreeMap::new();
+        let steps = self
+            .topological_order()
+            .into_iter()
+            .map(|id| {
+                let step = &self.steps[id];
+                Ok(serde_json::json!({
+                    "id": format!("{:?}", id),
+                    "rule": step.rule,
+                    "premises": step
+                        .premises
+                        .iter()
+                        .map(|p| format!("{:?}", p))
+                        .collect::<Vec<_>>(),
+                    "conclusion": model.id_to_sexp(&venv, &step.conclusion)?,
+                }))
+            })
+            .collect::<RawResult<Vec<_>>>()?;
+        Ok(serde_json::json!({
+            "root": format!("{:?}", self.root),
+            "steps": steps,
+        }))
+    }
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[test]
+fn test_proof_tree() {
+    use std::str::FromStr;
+
+    let input = br#"[mk-app] #1 p
+[mk-app] #2 q
+[mk-app] #3 => #1 #2
+[mk-proof] #4 asserted #1
+[mk-proof] #5 asserted #3
+[mk-proof] #6 mp #4 #5 #2
+[eof]
+"#;
+    let mut model = Model::default();
+    model.process(None, &input[..]).unwrap();
+    let id = |s| Ident::from_str(s).unwrap();
+
+    let tree = ProofTree::new(&model, &id("#6")).unwrap();
+    assert_eq!(tree.steps().len(), 3);
+    assert_eq!(tree.steps()[&id("#6")].premises, vec![id("#4"), id("#5")]);
+    assert_eq!(
+        tree.leaves().collect::<Vec<_>>(),
+        vec![&id("#4"), &id("#5")]
+    );
+    assert_eq!(
+        tree.topological_order(),
+        vec![&id("#4"), &id("#5"), &id("#6")]
+    );
+
+    let dot = tree.to_dot(&model).unwrap();
+    assert!(dot.contains("\"#6\" [label=\"mp\\nq\"];"));
+    assert!(dot.contains("\"#6\" -> \"#4\";"));
+
+    let json = tree.to_json(&model).unwrap();
+    assert_eq!(json["root"], "#6");
+    assert_eq!(json["steps"][2]["rule"], "mp");
+    assert_eq!(json["steps"][2]["conclusion"], "q");
+    assert_eq!(json["steps"][1]["conclusion"], "(=> p q)");
+
+    assert!(ProofTree::new(&model, &id("#3")).is_err());
+}
@@ -0,0 +1,105 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::str::FromStr;
+
+use pyo3::{
+    exceptions::{PyIOError, PyValueError},
+    prelude::*,
+};
+
+use crate::{
+    model::{Model, ModelConfig},
+    parser::ParserConfig,
+    report::open_log_file,
+    syntax::QiKey,
+};
+
+/// A Z3 trace, loaded from a log file:
+///
+/// ```python
+/// from z3tracer import Trace
+///
+/// trace = Trace.load("z3.log", skip_z3_version_check=True)
+/// profile = trace.instantiation_profile()
+/// for cost in profile["instantiations"][:10]:
+///     print(cost["key"], trace.instantiated_lemma(cost["key"]))
+/// ```
+///
+/// JSON documents are returned as Python dictionaries.
+#[pyclass(name = "Trace", module = "z3tracer", frozen)]
+pub struct PyTrace {
+    model: Model,
+}
+
+#[pymethods]
+impl PyTrace {
+    /// Parse a Z3 log file.
+    #[staticmethod]
+    #[pyo3(signature = (path, skip_z3_version_check = false, ignore_invalid_lines = false))]
+    fn load(
+        path: std::path::PathBuf,
+        skip_z3_version_check: bool,
+        ignore_invalid_lines: bool,
+    ) -> PyResult<Self> {
+        let config = ModelConfig {
+            parser_config: ParserConfig {
+                skip_z3_version_check,
+                ignore_invalid_lines,
+                ..ParserConfig::default()
+            },
+            ..ModelConfig::default()
+        };
+        let file = open_log_file(&path).map_err(|error| PyIOError::new_err(error.to_string()))?;
+        let mut model = Model::new(config);
+        model
+            .process(path.to_str().map(String::from), file)
+            .map_err(|error| PyValueError::new_err(error.to_string()))?;
+        Ok(Self { model })
+    }
+
+    /// Summary of the trace, as by `Model::to_json`.
+    fn summary<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let value = self.model.to_json().map_err(raw_error)?;
+        to_python(py, &value)
+    }
+
+    /// Instantiation profile of the trace, as by `Model::instantiation_profile_json`.
+    fn instantiation_profile<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        let value = self.model.instantiation_profile_json().map_err(raw_error)?;
+        to_python(py, &value)
+    }
+
+    /// Number of quantifier instantiations.
+    fn num_instantiations(&self) -> usize {
+        self.model.instantiations().len()
+    }
+
+    /// Number of conflicts.
+    fn num_conflicts(&self) -> usize {
+        self.model.conflicts().count()
+    }
+
+    /// Ground instance of the quantified formula of an instantiation, given by its key
+    /// such as `0x5614d5f6d550`, as an SMT-LIB term.
+    fn instantiated_lemma(&self, key: &str) -> PyResult<String> {
+        let key = QiKey::from_str(key).map_err(raw_error)?;
+        self.model.instantiated_lemma(&key).map_err(raw_error)
+    }
+}
+
+fn raw_error<E: std::fmt::Display>(error: E) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+// Convert a JSON document into Python values with the `json` module.
+fn to_python<'py>(py: Python<'py>, value: &serde_json::Value) -> PyResult<Bound<'py, PyAny>> {
+    py.import("json")?
+        .call_method1("loads", (value.to_string(),))
+}
+
+#[pymodule]
+fn z3tracer(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyTrace>()?;
+    Ok(())
+}
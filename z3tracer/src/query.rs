@@ -0,0 +1,184 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::{
+    error::{RawError, RawResult},
+    model::Model,
+    syntax::{Ident, Term},
+};
+
+/// A pattern over terms, constraining the head symbol, the arity, and the arguments
+/// of function applications.
+///
+/// Patterns can be parsed from strings:
+/// * `_` matches any term;
+/// * `f` matches any application of the symbol `f`;
+/// * `(f p1 .. pn)` matches the applications of `f` to `n` arguments matching the
+///   patterns `p1` .. `pn`;
+/// * `(_ p1 .. pn)` matches the applications of any symbol to `n` arguments matching
+///   the patterns `p1` .. `pn`.
+///
+/// ```
+/// # use std::str::FromStr;
+/// use z3tracer::query::TermPattern;
+/// let pattern = TermPattern::from_str("(Write _ (Select _ _) _)").unwrap();
+/// assert_eq!(
+///     pattern,
+///     TermPattern::app("Write")
+///         .with_arity(3)
+///         .with_arg(1, TermPattern::app("Select").with_arity(2))
+/// );
+/// ```
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct TermPattern {
+    /// Head symbol of the application, if constrained.
+    pub name: Option<String>,
+    /// Number of arguments, if constrained.
+    pub arity: Option<usize>,
+    /// Constraints on arguments, indexed by position.
+    pub args: Vec<(usize, TermPattern)>,
+}
+
+impl TermPattern {
+    /// Pattern matching any term.
+    pub fn any() -> Self {
+        Self::default()
+    }
+
+    /// Pattern matching any application of the given symbol.
+    pub fn app<S: Into<String>>(name: S) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..Self::default()
+        }
+    }
+
+    /// Constrain the number of arguments.
+    pub fn with_arity(mut self, arity: usize) -> Self {
+        self.arity = Some(arity);
+        self
+    }
+
+    /// Constrain the argument at position `index`.
+    pub fn with_arg(mut self, index: usize, pattern: TermPattern) -> Self {
+        self.args.push((index, pattern));
+        self
+    }
+
+    fn is_any(&self) -> bool {
+        self.name.is_none() && self.arity.is_none() && self.args.is_empty()
+    }
+
+    /// Whether the term `id` of the model matches the pattern.
+    pub fn matches(&self, model: &Model, id: &Ident) -> bool {
+        if self.is_any() {
+            return true;
+        }
+        let (name, args) = match model.term(id) {
+            Ok(Term::App { name, args, .. }) => (name, args),
+            _ => return false,
+        };
+        if matches!(&self.name, Some(n) if n != name) {
+            return false;
+        }
+        if matches!(self.arity, Some(n) if n != args.len()) {
+            return false;
+        }
+        self.args
+            .iter()
+            .all(|(index, pattern)| match args.get(*index) {
+                Some(arg) => pattern.matches(model, arg),
+                None => false,
+            })
+    }
+
+    fn parse<'a, I>(tokens: &mut std::iter::Peekable<I>) -> RawResult<Self>
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        match tokens.next() {
+            Some("(") => {
+                let mut pattern = match tokens.next() {
+                    Some("_") => Self::any(),
+                    Some(name) if name != "(" && name != ")" => Self::app(name),
+                    _ => return Err(RawError::InvalidTermPattern("missing head symbol".into())),
+                };
+                let mut arity = 0;
+                while tokens.peek() != Some(&")") {
+                    if tokens.peek().is_none() {
+                        return Err(RawError::InvalidTermPattern("missing ')'".into()));
+                    }
+                    let arg = Self::parse(tokens)?;
+                    if !arg.is_any() {
+                        pattern.args.push((arity, arg));
+                    }
+                    arity += 1;
+                }
+                tokens.next();
+                Ok(pattern.with_arity(arity))
+            }
+            Some("_") => Ok(Self::any()),
+            Some(")") => Err(RawError::InvalidTermPattern("unexpected ')'".into())),
+            Some(name) => Ok(Self::app(name)),
+            None => Err(RawError::InvalidTermPattern("empty pattern".into())),
+        }
+    }
+}
+
+impl std::str::FromStr for TermPattern {
+    type Err = RawError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.replace('(', " ( ").replace(')', " ) ");
+        let mut tokens = s.split_whitespace().peekable();
+        let pattern = Self::parse(&mut tokens)?;
+        match tokens.next() {
+            None => Ok(pattern),
+            Some(token) => Err(RawError::InvalidTermPattern(format!(
+                "unexpected token {}",
+                token
+            ))),
+        }
+    }
+}
+
+#[test]
+fn test_term_pattern() {
+    use std::str::FromStr;
+
+    let input = br#"[mk-app] #1 a
+[mk-app] #2 i
+[mk-app] #3 v
+[mk-app] #4 Write #1 #2 #3
+[mk-app] #5 Write #4 #2 #1
+[mk-app] #6 Read #5 #2
+[eof]
+"#;
+    let mut model = Model::default();
+    model.process(None, &input[..]).unwrap();
+    let find = |s| {
+        let pattern = TermPattern::from_str(s).unwrap();
+        model
+            .find_matching_terms(&pattern)
+            .map(|id| format!("{:?}", id))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(find("Write"), ["#4", "#5"]);
+    assert_eq!(find("(Write (Write _ _ _) _ _)"), ["#5"]);
+    assert_eq!(find("(Write _ _ v)"), ["#4"]);
+    assert_eq!(find("(_ _ i)"), ["#6"]);
+    assert_eq!(find("_").len(), 6);
+    assert!(find("(Write _ _)").is_empty());
+
+    assert_eq!(
+        model
+            .find_terms(|t| matches!(t, Term::App { args, .. } if args.is_empty()))
+            .count(),
+        3
+    );
+
+    assert!(TermPattern::from_str("(Write _").is_err());
+    assert!(TermPattern::from_str("Write)").is_err());
+    assert!(TermPattern::from_str("()").is_err());
+    assert!(TermPattern::from_str("").is_err());
+}
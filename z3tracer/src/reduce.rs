@@ -0,0 +1,102 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Reduction of an SMT-LIB input to the quantifiers of the heaviest instantiation chains,
+//! e.g. to attach a small reproducer to a solver bug report or to tune triggers.
+//!
+//! Quantifiers of the log are matched with the quantified assertions of the input by
+//! name, that is, by their `:qid` annotation.
+
+use crate::{model::Model, report::ModelExt, syntax::QiKey};
+use smt2parser::{
+    concrete::{Command, Term},
+    rewriter::free_symbols,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The names of the quantifiers involved in the `chains` most costly QIs, as given by
+/// `Model::instantiation_costs`: the QIs that caused them and the QIs they caused,
+/// transitively.
+pub fn heaviest_quantifiers(model: &Model, chains: usize) -> BTreeSet<String> {
+    let mut dependents = BTreeMap::<QiKey, BTreeSet<QiKey>>::new();
+    for (key, inst) in model.instantiations() {
+        for dep in &inst.qi_deps {
+            dependents.entry(dep.key).or_default().insert(*key);
+        }
+    }
+    let mut causes = BTreeSet::new();
+    let mut caused = BTreeSet::new();
+    for cost in model.instantiation_costs().into_iter().take(chains) {
+        let mut queue = vec![cost.key];
+        while let Some(key) = queue.pop() {
+            if causes.insert(key) {
+                if let Some(inst) = model.instantiations().get(&key) {
+                    queue.extend(inst.qi_deps.iter().map(|qi| qi.key));
+                }
+            }
+        }
+        let mut queue = vec![cost.key];
+        while let Some(key) = queue.pop() {
+            if caused.insert(key) {
+                queue.extend(dependents.get(&key).into_iter().flatten());
+            }
+        }
+    }
+    causes
+        .union(&caused)
+        .filter_map(|key| model.key2name(key))
+        .collect()
+}
+
+/// Remove the quantified assertions of a script that contain none of the given
+/// quantifiers, then the declarations of functions and constants that are no longer
+/// used. Assertions with a quantifier without `:qid` are kept, since they cannot be
+/// matched with the log.
+pub fn reduce_commands(commands: &[Command], quantifiers: &BTreeSet<String>) -> Vec<Command> {
+    let commands = commands
+        .iter()
+        .filter(|command| match command {
+            Command::Assert { term } => is_relevant(term, quantifiers),
+            _ => true,
+        })
+        .collect::<Vec<_>>();
+    let mut used = BTreeSet::new();
+    for command in &commands {
+        match command {
+            Command::Assert { term }
+            | Command::DefineFun { term, .. }
+            | Command::DefineFunRec { term, .. } => used.extend(free_symbols(term)),
+            Command::DefineFunsRec { funs } => {
+                used.extend(funs.iter().flat_map(|(_, term)| free_symbols(term)))
+            }
+            Command::GetValue { terms } => used.extend(terms.iter().flat_map(free_symbols)),
+            _ => (),
+        }
+    }
+    commands
+        .into_iter()
+        .filter(|command| match command {
+            Command::DeclareConst { symbol, .. } | Command::DeclareFun { symbol, .. } => {
+                used.contains(&symbol.0)
+            }
+            _ => true,
+        })
+        .cloned()
+        .collect()
+}
+
+// Whether an assertion is ground or contains a quantifier that is given or unnamed.
+fn is_relevant(term: &Term, quantifiers: &BTreeSet<String>) -> bool {
+    let mut names = Vec::new();
+    let mut queue = vec![term];
+    while let Some(term) = queue.pop() {
+        if matches!(term, Term::Forall { .. } | Term::Exists { .. }) {
+            names.push(term.qid());
+        }
+        queue.extend(term.children());
+    }
+    names.is_empty()
+        || names
+            .iter()
+            .any(|name| name.map_or(true, |name| quantifiers.contains(name)))
+}
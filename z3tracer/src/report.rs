@@ -1,22 +1,105 @@
 // Copyright (c) Facebook, Inc. and its affiliates
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-
 use super::{model::*, syntax::*};
 use plotters::prelude::*;
 use std::collections::*;
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// Reader returned by `open_log_file`.
+#[cfg(feature = "mmap")]
+pub type LogReader = std::io::Cursor<memmap2::Mmap>;
+/// Reader returned by `open_log_file`.
+#[cfg(not(feature = "mmap"))]
+pub type LogReader = std::io::BufReader<std::fs::File>;
+
+/// Open a log file for processing. With the feature `mmap`, the file is memory-mapped
+/// and parsed directly from the mapping, which avoids copying multi-gigabyte logs
+/// through an intermediate buffer.
+#[cfg(feature = "mmap")]
+#[allow(unsafe_code)]
+pub fn open_log_file(path: &std::path::Path) -> std::io::Result<LogReader> {
+    let file = std::fs::File::open(path)?;
+    // SAFETY: Z3 logs are not expected to be modified while being processed.
+    let mmap = unsafe { memmap2::Mmap::map(&file)? };
+    Ok(std::io::Cursor::new(mmap))
+}
+
+/// Open a log file for processing. With the feature `mmap`, the file is memory-mapped
+/// and parsed directly from the mapping, which avoids copying multi-gigabyte logs
+/// through an intermediate buffer.
+#[cfg(not(feature = "mmap"))]
+pub fn open_log_file(path: &std::path::Path) -> std::io::Result<LogReader> {
+    Ok(std::io::BufReader::new(std::fs::File::open(path)?))
+}
+
+/// Error returned by `process_file` and `process_file_with_progress`.
+#[derive(Debug, thiserror::Error)]
+pub enum ProcessError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// The log is ill-formed or truncated. The model of the log up to the error is
+    /// kept, e.g. to analyze the log of an interrupted run.
+    #[error("{error}")]
+    Log {
+        error: crate::error::Error,
+        model: Box<Model>,
+    },
+    #[error("Processing was cancelled")]
+    Cancelled,
+}
+
+impl ProcessError {
+    fn new(error: crate::error::Error, model: Model) -> Self {
+        match error.error {
+            crate::error::RawError::Cancelled => ProcessError::Cancelled,
+            _ => ProcessError::Log {
+                error,
+                model: Box::new(model),
+            },
+        }
+    }
+}
+
 /// Helper function to process files.
-pub fn process_file(config: ModelConfig, path: &std::path::Path) -> std::io::Result<Model> {
-    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+pub fn process_file(
+    config: ModelConfig,
+    path: &std::path::Path,
+) -> std::result::Result<Model, ProcessError> {
+    let file = open_log_file(path)?;
     // Inject non-default configurations here with Model::new(config).
     let mut model = Model::new(config);
-    if let Err(le) = model.process(path.to_str().map(String::from), file) {
-        eprintln!("Error at {:?}: {:?}", le.position, le.error);
+    match model.process(path.to_str().map(String::from), file) {
+        Ok(()) => Ok(model),
+        Err(error) => Err(ProcessError::new(error, model)),
+    }
+}
+
+/// Helper function to process files while reporting progress every `period` lines.
+/// Processing stops early with `ProcessError::Cancelled` as soon as `cancel` is set.
+pub fn process_file_with_progress<F>(
+    config: ModelConfig,
+    path: &std::path::Path,
+    period: usize,
+    cancel: &std::sync::atomic::AtomicBool,
+    progress: F,
+) -> std::result::Result<Model, ProcessError>
+where
+    F: FnMut(crate::parser::Progress),
+{
+    let file = open_log_file(path)?;
+    let mut model = Model::new(config);
+    match model.process_with_progress(
+        path.to_str().map(String::from),
+        file,
+        period,
+        cancel,
+        progress,
+    ) {
+        Ok(()) => Ok(model),
+        Err(error) => Err(ProcessError::new(error, model)),
     }
-    Ok(model)
 }
 
 /// Helper trait for models.
@@ -333,3 +416,311 @@ where
         .draw()?;
     Ok(())
 }
+
+/// A possible matching loop: a chain of QIs of the same quantifier where each QI
+/// depends on the previous one, i.e. matches a term produced by it or uses an
+/// equality that depends on it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MatchingLoop {
+    /// Id of the quantifier term.
+    pub quantifier: Ident,
+    /// Length of the longest chain of dependent QIs of the quantifier.
+    pub depth: usize,
+}
+
+/// Find the quantifiers with chains of dependent QIs of length at least `threshold`,
+/// sorted by decreasing length.
+pub fn find_matching_loops(model: &Model, threshold: usize) -> Vec<MatchingLoop> {
+    let mut max_depths = BTreeMap::new();
+    for (quantifier, depth) in chain_depths(model).into_values() {
+        let max_depth = max_depths.entry(quantifier).or_insert(0);
+        *max_depth = std::cmp::max(*max_depth, depth);
+    }
+    let mut loops = max_depths
+        .into_iter()
+        .filter(|(_, depth)| *depth >= threshold)
+        .map(|(quantifier, depth)| MatchingLoop {
+            quantifier: quantifier.clone(),
+            depth,
+        })
+        .collect::<Vec<_>>();
+    loops.sort_by_key(|l| std::cmp::Reverse(l.depth));
+    loops
+}
+
+/// The quantifier of each QI (except builtin ones) and the length of the longest chain
+/// of dependent QIs of the same quantifier ending with it.
+fn chain_depths(model: &Model) -> BTreeMap<&QiKey, (&Ident, usize)> {
+    let mut instantiations = model.instantiations().iter().collect::<Vec<_>>();
+    instantiations.sort_by_key(|(_, qi)| qi.timestamp);
+    // QIs that produced each term (i.e. attached it as an enode).
+    let mut producers = BTreeMap::new();
+    for (key, qi) in &instantiations {
+        for instance in &qi.instances {
+            for id in &instance.enodes {
+                producers.entry(id).or_insert(*key);
+            }
+        }
+    }
+    let mut depths = BTreeMap::new();
+    for (key, qi) in instantiations {
+        let quantifier = qi.frame.quantifier();
+        if quantifier.is_builtin() {
+            continue;
+        }
+        let mut deps = qi.qi_deps.iter().map(|d| &d.key).collect::<Vec<_>>();
+        if let QiFrame::NewMatch { used, .. } = &qi.frame {
+            for u in used {
+                if let MatchedTerm::Trigger(id) = u {
+                    deps.extend(producers.get(id));
+                }
+            }
+        }
+        let depth = 1 + deps
+            .into_iter()
+            .filter(|k| {
+                matches!(model.instantiations().get(k), Some(dep) if dep.frame.quantifier() == quantifier)
+            })
+            .filter_map(|k| depths.get(k).map(|(_, depth)| *depth))
+            .max()
+            .unwrap_or(0);
+        depths.insert(key, (quantifier, depth));
+    }
+    depths
+}
+
+/// Distribution of the lengths of the chains of dependent QIs of a quantifier, as in
+/// `find_matching_loops`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ChainDepths {
+    /// Id of the quantifier term.
+    pub quantifier: Ident,
+    /// Name of the quantifier.
+    pub name: String,
+    /// Number of QIs of the quantifier ending a chain of each length.
+    pub histogram: BTreeMap<usize, usize>,
+}
+
+impl ChainDepths {
+    /// Length of the longest chain.
+    pub fn max_depth(&self) -> usize {
+        self.histogram.keys().next_back().cloned().unwrap_or(0)
+    }
+}
+
+/// The distribution of the lengths of the chains of dependent QIs of each quantifier,
+/// sorted by decreasing length of the longest chain.
+pub fn chain_depth_distributions(model: &Model) -> Vec<ChainDepths> {
+    let mut distributions = BTreeMap::new();
+    for (quantifier, depth) in chain_depths(model).into_values() {
+        let entry = distributions
+            .entry(quantifier)
+            .or_insert_with(|| ChainDepths {
+                quantifier: quantifier.clone(),
+                name: model.quantifier_name(quantifier),
+                histogram: BTreeMap::new(),
+            });
+        *entry.histogram.entry(depth).or_insert(0) += 1;
+    }
+    let mut distributions = distributions.into_values().collect::<Vec<_>>();
+    distributions.sort_by_key(|d| std::cmp::Reverse(d.max_depth()));
+    distributions
+}
+
+/// Given the chain depth distributions of the logs of the same problem unrolled to
+/// increasing BMC bounds, the names of the quantifiers whose longest chain grows at every
+/// bound. This is a strong sign of a matching loop. Quantifiers are matched by name, and
+/// a quantifier missing at some bound has no chain there.
+pub fn growing_chain_depths(runs: &[Vec<ChainDepths>]) -> Vec<String> {
+    if runs.len() < 2 {
+        return Vec::new();
+    }
+    let max_depths = runs
+        .iter()
+        .map(|run| {
+            run.iter()
+                .map(|d| (d.name.as_str(), d.max_depth()))
+                .collect::<BTreeMap<_, _>>()
+        })
+        .collect::<Vec<_>>();
+    let names = max_depths
+        .iter()
+        .flat_map(|depths| depths.keys().cloned())
+        .collect::<BTreeSet<_>>();
+    names
+        .into_iter()
+        .filter(|name| {
+            let depths = max_depths
+                .iter()
+                .map(|depths| depths.get(name).cloned().unwrap_or(0))
+                .collect::<Vec<_>>();
+            depths.windows(2).all(|w| w[0] < w[1])
+        })
+        .map(String::from)
+        .collect()
+}
+
+/// Options for `write_html_report`.
+#[derive(Debug, Clone)]
+pub struct HtmlReportConfig {
+    /// Title of the report.
+    pub title: String,
+    /// How many quantifiers and conflicts to list.
+    pub top_n: usize,
+    /// Minimal length of a chain of self-dependent QIs to report a matching loop.
+    pub matching_loop_threshold: usize,
+}
+
+impl Default for HtmlReportConfig {
+    fn default() -> Self {
+        Self {
+            title: "Z3 quantifier instantiations".to_string(),
+            top_n: 10,
+            matching_loop_threshold: 10,
+        }
+    }
+}
+
+/// Style sheet of the HTML reports.
+pub const HTML_STYLE: &str =
+    "body { font-family: sans-serif; } table { border-collapse: collapse; } \
+     td, th { border: 1px solid #ccc; padding: 2px 8px; } .warning { color: #b00; }";
+
+/// Render a standalone HTML report: top quantifiers, instantiation timeline,
+/// matching-loop warnings, and conflict summaries.
+pub fn write_html_report<W: std::io::Write>(
+    model: &Model,
+    config: &HtmlReportConfig,
+    out: &mut W,
+) -> Result<()> {
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{}</title>", html_escape(&config.title))?;
+    writeln!(out, "<style>{}</style>", HTML_STYLE)?;
+    writeln!(out, "</head>\n<body>")?;
+    writeln!(out, "<h1>{}</h1>", html_escape(&config.title))?;
+    write_html_sections(model, config, out)?;
+    writeln!(out, "</body>\n</html>")?;
+    Ok(())
+}
+
+/// Render the body of an HTML report, without title, so that it can be embedded in
+/// another page.
+pub fn write_html_sections<W: std::io::Write>(
+    model: &Model,
+    config: &HtmlReportConfig,
+    out: &mut W,
+) -> Result<()> {
+    let name = |id: &Ident| model.id2name(id).unwrap_or_else(|| format!("{:?}", id));
+    let conflicts = model.conflicts().collect::<Vec<_>>();
+    let participation = model
+        .conflict_participation()
+        .into_iter()
+        .map(|p| (p.quantifier.clone(), p))
+        .collect::<BTreeMap<_, _>>();
+
+    writeln!(out, "<ul>")?;
+    if let Some((tool, version)) = model.tool_version() {
+        writeln!(
+            out,
+            "<li>Tool: {} {}</li>",
+            html_escape(tool),
+            html_escape(version)
+        )?;
+    }
+    writeln!(out, "<li>Processed logs: {}</li>", model.processed_logs())?;
+    writeln!(out, "<li>Terms: {}</li>", model.terms().len())?;
+    writeln!(
+        out,
+        "<li>Instantiations: {}</li>",
+        model.instantiations().len()
+    )?;
+    writeln!(out, "<li>Conflicts: {}</li>", conflicts.len())?;
+    writeln!(out, "</ul>")?;
+
+    // Top quantifiers.
+    writeln!(out, "<h2>Top quantifiers</h2>")?;
+    writeln!(out, "<table>")?;
+    writeln!(
+        out,
+        "<tr><th>Quantifier</th><th>Instantiations</th><th>Useful</th><th>Conflicts</th></tr>"
+    )?;
+    for (count, id) in IntoIterSorted::from(model.most_instantiated_terms()).take(config.top_n) {
+        let (useful, conflicts) = participation
+            .get(&id)
+            .map(|p| (p.useful_instantiations, p.conflicts))
+            .unwrap_or((0, 0));
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            html_escape(&name(&id)),
+            count,
+            useful,
+            conflicts
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    // Instantiation timeline.
+    writeln!(out, "<h2>Instantiation timeline</h2>")?;
+    let mut svg = String::new();
+    {
+        let root = SVGBackend::with_string(&mut svg, (1024, 600)).into_drawing_area();
+        let times = crate::get_instantiations(model);
+        plot_instantiations(root, &times, "Instantiations", config.top_n)?;
+    }
+    writeln!(out, "{}", svg)?;
+
+    // Matching loops.
+    writeln!(out, "<h2>Matching loops</h2>")?;
+    let loops = find_matching_loops(model, config.matching_loop_threshold);
+    if loops.is_empty() {
+        writeln!(out, "<p>No matching loop detected.</p>")?;
+    } else {
+        writeln!(out, "<ul>")?;
+        for l in loops {
+            writeln!(
+                out,
+                "<li class=\"warning\">{}: chain of {} dependent instantiations</li>",
+                html_escape(&name(&l.quantifier)),
+                l.depth
+            )?;
+        }
+        writeln!(out, "</ul>")?;
+    }
+
+    // Conflicts.
+    writeln!(out, "<h2>Conflicts</h2>")?;
+    writeln!(out, "<table>")?;
+    writeln!(
+        out,
+        "<tr><th>Time</th><th>Literals</th><th>Quantifiers</th></tr>"
+    )?;
+    for conflict in conflicts.iter().take(config.top_n) {
+        let quantifiers = conflict
+            .qi_deps
+            .iter()
+            .filter_map(|d| model.key2name(&d.key))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>()
+            .join(", ");
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+            conflict.timestamp,
+            conflict.lits.len(),
+            html_escape(&quantifiers)
+        )?;
+    }
+    writeln!(out, "</table>")?;
+    Ok(())
+}
+
+/// Escape the special characters of HTML in a text.
+pub fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -6,10 +6,10 @@ use smt2parser::concrete::Symbol;
 
 /// An identifier such as `#45` or `foo#23`.
 /// * Namespace-only identifiers such
-/// as `foo#` are also allowed for Z3 primitive objects.
+///   as `foo#` are also allowed for Z3 primitive objects.
 /// * `#` is used for true and false literals.
 /// * An implicit version number is added to disambiguate identifiers
-/// re-used by Z3.
+///   re-used by Z3.
 #[derive(Eq, PartialEq, Ord, PartialOrd, Clone, Default, Hash)]
 pub struct Ident {
     pub namespace: Option<String>,
@@ -129,6 +129,14 @@ impl QiFrame {
             Discovered { quantifier, .. } | NewMatch { quantifier, .. } => quantifier,
         }
     }
+
+    /// Ids of the terms bound to the variables of the quantifier.
+    pub fn terms(&self) -> &[Ident] {
+        use QiFrame::*;
+        match self {
+            Discovered { terms, .. } | NewMatch { terms, .. } => terms,
+        }
+    }
 }
 
 /// Data specific to an instance of a quantifier instantiation (i.e. gathered
@@ -160,6 +168,17 @@ pub enum Equality {
     Unknown(Ident),
 }
 
+impl Equality {
+    /// The term that an `[eq-expl]` log makes equal to its subject, if any.
+    pub fn target(&self) -> Option<&Ident> {
+        use Equality::*;
+        match self {
+            Root => None,
+            Literal(_, t) | Congruence(_, t) | Theory(_, t) | Axiom(t) | Unknown(t) => Some(t),
+        }
+    }
+}
+
 impl Ident {
     /// Whether an identifier is the special builtin value.
     pub fn is_builtin(&self) -> bool {
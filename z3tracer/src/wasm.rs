@@ -0,0 +1,90 @@
+// Copyright (c) Facebook, Inc. and its affiliates
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use std::str::FromStr;
+
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    model::{Model, ModelConfig},
+    parser::ParserConfig,
+    syntax::QiKey,
+};
+
+/// A Z3 trace, loaded from the text of a log file:
+///
+/// ```js
+/// import init, { Trace } from "./pkg/z3tracer.js";
+///
+/// await init();
+/// const trace = new Trace(await file.text(), true);
+/// const profile = trace.instantiationProfile();
+/// for (const cost of profile.instantiations.slice(0, 10)) {
+///   console.log(cost.key, trace.instantiatedLemma(cost.key));
+/// }
+/// ```
+///
+/// JSON documents are returned as JavaScript objects.
+#[wasm_bindgen(js_name = Trace)]
+pub struct WasmTrace {
+    model: Model,
+}
+
+#[wasm_bindgen(js_class = Trace)]
+impl WasmTrace {
+    /// Parse the text of a Z3 log file.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        text: &str,
+        skip_z3_version_check: Option<bool>,
+        ignore_invalid_lines: Option<bool>,
+    ) -> Result<WasmTrace, JsError> {
+        let config = ModelConfig {
+            parser_config: ParserConfig {
+                skip_z3_version_check: skip_z3_version_check.unwrap_or(false),
+                ignore_invalid_lines: ignore_invalid_lines.unwrap_or(false),
+                ..ParserConfig::default()
+            },
+            ..ModelConfig::default()
+        };
+        let mut model = Model::new(config);
+        model.process(None, text.as_bytes())?;
+        Ok(Self { model })
+    }
+
+    /// Summary of the trace, as by `Model::to_json`.
+    pub fn summary(&self) -> Result<JsValue, JsError> {
+        to_js(&self.model.to_json()?)
+    }
+
+    /// Instantiation profile of the trace, as by `Model::instantiation_profile_json`.
+    #[wasm_bindgen(js_name = instantiationProfile)]
+    pub fn instantiation_profile(&self) -> Result<JsValue, JsError> {
+        to_js(&self.model.instantiation_profile_json()?)
+    }
+
+    /// Number of quantifier instantiations.
+    #[wasm_bindgen(js_name = numInstantiations)]
+    pub fn num_instantiations(&self) -> usize {
+        self.model.instantiations().len()
+    }
+
+    /// Number of conflicts.
+    #[wasm_bindgen(js_name = numConflicts)]
+    pub fn num_conflicts(&self) -> usize {
+        self.model.conflicts().count()
+    }
+
+    /// Ground instance of the quantified formula of an instantiation, given by its key
+    /// such as `0x5614d5f6d550`, as an SMT-LIB term.
+    #[wasm_bindgen(js_name = instantiatedLemma)]
+    pub fn instantiated_lemma(&self, key: &str) -> Result<String, JsError> {
+        let key = QiKey::from_str(key)?;
+        Ok(self.model.instantiated_lemma(&key)?)
+    }
+}
+
+// Convert a JSON document into JavaScript values with `JSON.parse`.
+fn to_js(value: &serde_json::Value) -> Result<JsValue, JsError> {
+    js_sys::JSON::parse(&value.to_string()).map_err(|_| JsError::new("invalid JSON document"))
+}
@@ -4,19 +4,31 @@
 use std::{
     collections::{BTreeMap, BinaryHeap},
     str::FromStr,
+    sync::atomic::{AtomicBool, Ordering},
 };
 
-use z3tracer::{syntax::Ident, Model, ModelConfig};
+use smt2parser::concrete::Symbol;
+use z3tracer::{
+    error::RawError,
+    model::{InstantiationCost, TriggerStats},
+    reduce::{heaviest_quantifiers, reduce_commands},
+    report::{
+        self, chain_depth_distributions, find_matching_loops, growing_chain_depths, open_log_file,
+        process_file_with_progress, write_html_report, HtmlReportConfig, ProcessError,
+    },
+    syntax::{Equality, Ident, QiKey},
+    Model, ModelConfig,
+};
 
 fn process_file(path: &str) -> anyhow::Result<Model> {
-    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let file = open_log_file(std::path::Path::new(path))?;
     let mut model = Model::default();
     model.process(Some(path.to_string()), file)?;
     Ok(model)
 }
 
 fn process_file_with_line_skipping(path: &str) -> anyhow::Result<Model> {
-    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+    let file = open_log_file(std::path::Path::new(path))?;
     let mut config = ModelConfig::default();
     config.parser_config.ignore_invalid_lines = true;
     let mut model = Model::new(config);
@@ -131,3 +143,635 @@ fn test_truncated_log_file() {
     assert!(process_file("tests/data/file4.log").is_err());
     assert!(process_file_with_line_skipping("tests/data/file4.log").is_err());
 }
+
+#[test]
+fn test_log_file_from_recent_z3() -> anyhow::Result<()> {
+    // This file was generated by Z3 4.8.12 with trace=true and proof=true.
+    let model = process_file("tests/data/file5.log")?;
+    assert_eq!(model.terms().len(), 841);
+    assert_eq!(model.instantiations().len(), 28);
+    assert_eq!(model.scopes().len(), 2);
+    assert_eq!(model.conflicts().count(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_decide_and_or() -> anyhow::Result<()> {
+    let input = br#"[tool-version] Z3 4.12.2
+[mk-app] #1 a
+[mk-app] #2 b
+[mk-app] #3 or #1 #2
+[decide-and-or] #3 #1
+[eof]
+"#;
+    let mut model = Model::default();
+    model.process(None, &input[..])?;
+    assert_eq!(model.terms().len(), 3);
+    Ok(())
+}
+
+#[test]
+fn test_unknown_commands() {
+    let input = br#"[tool-version] Z3 4.12.2
+[mk-app] #1 a
+[some-future-command] #1 1 2 3
+[mk-app] #2 b
+[eof]
+"#;
+    let mut model = Model::default();
+    assert!(model.process(None, &input[..]).is_err());
+
+    let mut config = ModelConfig::default();
+    config.parser_config.ignore_unknown_commands = true;
+    let mut model = Model::new(config);
+    model.process(None, &input[..]).unwrap();
+    assert_eq!(model.terms().len(), 2);
+}
+
+#[test]
+fn test_conflict_participation() -> anyhow::Result<()> {
+    let model = process_file("tests/data/file5.log")?;
+    let participation = model.conflict_participation();
+    assert_eq!(
+        participation
+            .iter()
+            .map(|p| p.useful_instantiations + p.useless_instantiations)
+            .sum::<usize>(),
+        model.instantiations().len()
+    );
+    let top = &participation[0];
+    assert_eq!(top.quantifier, Ident::from_str("#68").unwrap());
+    assert_eq!(top.useful_instantiations, 1);
+    assert_eq!(top.useless_instantiations, 1);
+    assert_eq!(top.conflicts, 1);
+    assert!(participation[1..]
+        .iter()
+        .all(|p| p.useful_instantiations == 0 && p.conflicts == 0));
+    Ok(())
+}
+
+#[test]
+fn test_theory_stats() -> anyhow::Result<()> {
+    let model = process_file("tests/data/file5.log")?;
+    let stats = model.theory_stats();
+    assert_eq!(stats.keys().collect::<Vec<_>>(), ["arith", "basic", "bv"]);
+    assert_eq!(stats["arith"].instantiations, 15);
+    assert_eq!(stats["arith"].meanings, 11);
+    assert_eq!(stats["basic"].instantiations, 11);
+    assert_eq!(stats["bv"].meanings, 18);
+    assert_eq!(model.ematching_rounds(), 2);
+
+    let input = br#"[mk-app] #1 x
+[mk-app] #2 y
+[eq-expl] #1 th arith ; #2
+[eof]
+"#;
+    let mut model = Model::default();
+    model.process(None, &input[..])?;
+    assert_eq!(model.theory_stats()["arith"].equalities, 1);
+    Ok(())
+}
+
+#[test]
+fn test_progress_and_cancellation() -> anyhow::Result<()> {
+    let path = "tests/data/file5.log";
+    let size = std::fs::metadata(path)?.len() as usize;
+
+    let cancel = AtomicBool::new(false);
+    let mut reports = Vec::new();
+    let mut model = Model::default();
+    model.process_with_progress(
+        Some(path.to_string()),
+        std::io::BufReader::new(std::fs::File::open(path)?),
+        100,
+        &cancel,
+        |p| reports.push(p),
+    )?;
+    assert_eq!(reports.len(), 11);
+    assert_eq!(reports.last().unwrap().bytes, size);
+    assert!(reports.windows(2).all(|w| w[0].lines < w[1].lines));
+
+    let mut model = Model::default();
+    let error = model
+        .process_with_progress(
+            Some(path.to_string()),
+            std::io::BufReader::new(std::fs::File::open(path)?),
+            100,
+            &cancel,
+            |p| cancel.store(p.lines >= 500, Ordering::Relaxed),
+        )
+        .unwrap_err();
+    assert_eq!(error.error, RawError::Cancelled);
+    assert_eq!(error.position.line, 501);
+
+    cancel.store(true, Ordering::Relaxed);
+    let result = process_file_with_progress(
+        ModelConfig::default(),
+        std::path::Path::new(path),
+        100,
+        &cancel,
+        |_| (),
+    );
+    assert!(matches!(result, Err(ProcessError::Cancelled)));
+    Ok(())
+}
+
+#[test]
+fn test_process_file_errors() {
+    let path = std::path::Path::new("tests/data/file4.log");
+    match report::process_file(ModelConfig::default(), path) {
+        Err(ProcessError::Log { error, model }) => {
+            assert_ne!(error.error, RawError::Cancelled);
+            assert!(!model.terms().is_empty());
+        }
+        result => panic!("unexpected result: {:?}", result.map(|_| ())),
+    }
+    let path = std::path::Path::new("tests/data/missing.log");
+    assert!(matches!(
+        report::process_file(ModelConfig::default(), path),
+        Err(ProcessError::Io(_))
+    ));
+}
+
+#[test]
+fn test_explain_equality() -> anyhow::Result<()> {
+    let input = br#"[mk-app] #1 a
+[mk-app] #2 b
+[mk-app] #3 c
+[mk-app] #4 d
+[eq-expl] #1 ax ; #2
+[eq-expl] #3 th arith ; #2
+[eq-expl] #2 root
+[eq-expl] #4 ax ; #3
+[eof]
+"#;
+    let mut model = Model::default();
+    model.process(None, &input[..])?;
+    let id = |s| Ident::from_str(s).unwrap();
+
+    let steps = model
+        .explain_equality(&id("#1"), &id("#4"), model.processed_logs())
+        .unwrap();
+    assert_eq!(
+        steps
+            .iter()
+            .map(|s| (s.lhs.clone(), s.rhs.clone(), s.timestamp))
+            .collect::<Vec<_>>(),
+        vec![
+            (id("#1"), id("#2"), 5),
+            (id("#2"), id("#3"), 6),
+            (id("#3"), id("#4"), 8)
+        ]
+    );
+    assert_eq!(
+        steps[1].justification,
+        Equality::Theory("arith".into(), id("#2"))
+    );
+
+    assert_eq!(
+        model.explain_equality(&id("#1"), &id("#1"), 8),
+        Some(Vec::new())
+    );
+    assert_eq!(model.explain_equality(&id("#1"), &id("#4"), 7), None);
+    Ok(())
+}
+
+#[test]
+fn test_trigger_analysis() -> anyhow::Result<()> {
+    let model = process_file("tests/data/file5.log")?;
+    let id = |s| Ident::from_str(s).unwrap();
+
+    let stats = model.trigger_stats();
+    assert_eq!(
+        stats,
+        vec![TriggerStats {
+            quantifier: id("#68"),
+            trigger: id("#10"),
+            matches: 2,
+            instances: 2,
+            distinct_bindings: 2,
+        }]
+    );
+
+    let key = QiKey::from_str("0x5614d5f6d550").unwrap();
+    let m = model.trigger_match(&key).unwrap();
+    assert_eq!(m.quantifier, id("#68"));
+    assert_eq!(m.trigger, id("#10"));
+    assert_eq!(m.bindings, vec![(Some(Symbol("x".into())), id("#12"))]);
+    Ok(())
+}
+
+#[test]
+fn test_instantiation_costs() -> anyhow::Result<()> {
+    let model = process_file("tests/data/file3.log")?;
+    let costs = model.instantiation_costs();
+    assert_eq!(costs.len(), model.instantiations().len());
+    assert_eq!(
+        costs[0],
+        InstantiationCost {
+            key: QiKey::from_str("0x7fb1373465d8").unwrap(),
+            quantifier: Ident::from_str("#342").unwrap(),
+            terms: 12,
+            caused_instantiations: 181,
+            caused_terms: 440,
+        }
+    );
+    assert_eq!(
+        costs.iter().filter(|c| c.caused_instantiations > 0).count(),
+        8
+    );
+    Ok(())
+}
+
+#[test]
+fn test_export_log() -> anyhow::Result<()> {
+    let model = process_file("tests/data/file5.log")?;
+    let mut output = Vec::new();
+    z3tracer::export::write_log(&model, &mut output)?;
+
+    let mut model2 = Model::default();
+    model2.process(None, &output[..])?;
+    assert_eq!(model2.tool_version(), Some(("Z3", "4.8.12")));
+    assert_eq!(model2.terms().len(), model.terms().len());
+    assert_eq!(model2.instantiations().len(), model.instantiations().len());
+    assert_eq!(model2.trigger_stats(), model.trigger_stats());
+    assert_eq!(model2.scopes().len(), model.scopes().len());
+    assert_eq!(model2.conflicts().count(), model.conflicts().count());
+
+    let mut output2 = Vec::new();
+    z3tracer::export::write_log(&model2, &mut output2)?;
+    assert_eq!(output, output2);
+    Ok(())
+}
+
+#[test]
+fn test_model_diff() -> anyhow::Result<()> {
+    let model = process_file("tests/data/file5.log")?;
+    let diff = model.diff(&model);
+    assert_eq!(diff.conflicts, (1, 1));
+    assert!(diff.regressions().next().is_none());
+    assert!(diff.improvements().next().is_none());
+
+    let input = br#"[mk-app] #1 true
+[mk-quant] #2 pf 1 #1
+[inst-discovered] MBQI 0x1 #2 ;
+[inst-discovered] MBQI 0x2 #2 ;
+[inst-discovered] MBQI 0x3 #2 ;
+[inst-discovered] theory-solving 0 arith# ; #1
+[eof]
+"#;
+    let mut other = Model::default();
+    other.process(None, &input[..])?;
+    let diff = model.diff(&other);
+    assert_eq!(
+        diff.regressions().map(|q| &q.name).collect::<Vec<_>>(),
+        ["pf"]
+    );
+    let pf = diff.regressions().next().unwrap();
+    assert_eq!(pf.instantiations, (2, 3));
+    assert_eq!(pf.conflicts, (1, 0));
+    assert_eq!(pf.first_instantiation.1, Some(3));
+    assert_eq!(
+        diff.improvements().map(|q| &q.name).collect::<Vec<_>>(),
+        ["arith", "basic"]
+    );
+    assert!(diff
+        .to_string()
+        .contains("  pf: 2 -> 3 instantiations (+1), 1 -> 0 conflicts\n"));
+    Ok(())
+}
+
+#[test]
+fn test_instantiated_lemmas() -> anyhow::Result<()> {
+    let input = br#"[mk-app] #1 true
+[mk-var] #2 1
+[mk-var] #3 0
+[mk-app] #4 h #2 #3
+[mk-var] #5 0
+[mk-var] #6 2
+[mk-app] #7 k #5 #6
+[mk-quant] #8 k!5 1 #7
+[attach-var-names] #8 (|z| ; |Int|)
+[mk-app] #9 if #3 #4 #8
+[mk-app] #10 f #2 #3
+[mk-app] #11 pattern #10
+[mk-quant] #12 ax 2 #11 #9
+[attach-var-names] #12 (|y| ; |Bool|) (|x| ; |Int|)
+[mk-app] #13 Int
+[attach-meaning] #13 arith 3
+[mk-app] #14 f #13 #1
+[new-match] 0x1 #12 #11 #1 #13 ; #14
+[inst-discovered] theory-solving 0x2 arith# ; #14
+[eof]
+"#;
+    let mut model = Model::default();
+    model.process(None, &input[..])?;
+    let keys = model.instantiations().keys().collect::<Vec<_>>();
+    assert_eq!(
+        model.instantiated_lemmas(keys.iter().take(1).cloned())?,
+        ["(assert (ite true (h 3 true) (forall ((z Int)) (k z 3))))"]
+    );
+    assert!(model.instantiated_lemma(keys[1]).is_err());
+
+    let quantifier = smt2parser::parse_term(
+        "(forall ((x Int) (y Bool)) (ite y (h x y) (forall ((z Int)) (k z x))))",
+    )?;
+    assert_eq!(
+        model.id_to_de_bruijn(&Ident::from_str("#12")?)?,
+        quantifier.to_de_bruijn()
+    );
+    assert_eq!(
+        model.id_to_de_bruijn(&Ident::from_str("#14")?)?,
+        smt2parser::parse_term("(f 3 true)")?.to_de_bruijn()
+    );
+    Ok(())
+}
+
+#[test]
+fn test_timestamp_lookup() -> anyhow::Result<()> {
+    let model = process_file("tests/data/file5.log")?;
+    let quantifier = Ident::from_str("#68")?;
+    let term = Ident::from_str("#644")?;
+    assert_eq!(
+        model.term_at_timestamp(model.term_data(&term)?.timestamp),
+        Some(&term)
+    );
+
+    let timestamps = &model.term_data(&quantifier)?.instantiation_timestamps;
+    assert_eq!(timestamps.len(), 2);
+    for timestamp in timestamps {
+        let (key, instance) = model.instance_at_timestamp(*timestamp).unwrap();
+        assert_eq!(model.instantiations()[key].frame.quantifier(), &quantifier);
+        assert!(instance.term.is_some());
+    }
+    let (key, instance) = model.instance_at_timestamp(timestamps[0]).unwrap();
+    assert_eq!(key, &QiKey::from_str("0x5614d5f6d550")?);
+    assert_eq!(instance.term, Some(term));
+    assert!(model.instance_at_timestamp(0).is_none());
+    Ok(())
+}
+
+#[test]
+fn test_html_report() -> anyhow::Result<()> {
+    // A matching loop: `f(x)` triggers the creation of `f(g(x))`.
+    let mut input = r#"[mk-app] #1 a
+[mk-var] #2 0
+[mk-app] #3 f #2
+[mk-app] #4 pattern #3
+[mk-app] #5 g #2
+[mk-app] #6 f #5
+[mk-quant] #7 loop 1 #4 #6
+[attach-var-names] #7 (|x| ; |Int|)
+[mk-app] #8 f #1
+"#
+    .to_string();
+    let mut previous = (1, 8);
+    for i in 0..5 {
+        let (arg, term) = previous;
+        let g = 10 + 2 * i;
+        input += &format!(
+            "[new-match] {:#x} #7 #4 #{} ; #{}\n\
+             [mk-app] #{} g #{}\n\
+             [mk-app] #{} f #{}\n\
+             [instance] {:#x} #{} ; 1\n\
+             [attach-enode] #{} 1\n\
+             [attach-enode] #{} 1\n\
+             [end-of-instance]\n",
+            i + 1,
+            arg,
+            term,
+            g,
+            arg,
+            g + 1,
+            g,
+            i + 1,
+            g + 1,
+            g,
+            g + 1
+        );
+        previous = (g, g + 1);
+    }
+    input += "[eof]\n";
+    let mut model = Model::default();
+    model.process(None, input.as_bytes())?;
+    assert_eq!(model.instantiations().len(), 5);
+    let loops = find_matching_loops(&model, 3);
+    assert_eq!(loops.len(), 1);
+    assert_eq!(loops[0].quantifier, Ident::from_str("#7")?);
+    assert_eq!(loops[0].depth, 5);
+    assert!(find_matching_loops(&model, 6).is_empty());
+
+    let config = HtmlReportConfig {
+        matching_loop_threshold: 3,
+        ..HtmlReportConfig::default()
+    };
+    let mut html = Vec::new();
+    write_html_report(&model, &config, &mut html).unwrap();
+    let html = String::from_utf8(html)?;
+    assert!(html.contains("<tr><td>loop</td><td>5</td><td>0</td><td>0</td></tr>"));
+    assert!(html.contains("<svg"));
+    assert!(html.contains("loop: chain of 5 dependent instantiations"));
+
+    let model = process_file("tests/data/file5.log")?;
+    let mut html = Vec::new();
+    write_html_report(&model, &HtmlReportConfig::default(), &mut html).unwrap();
+    let html = String::from_utf8(html)?;
+    assert!(html.contains("<li>Conflicts: 1</li>"));
+    assert!(html.contains("No matching loop detected."));
+    Ok(())
+}
+
+#[test]
+fn test_reproducer() -> anyhow::Result<()> {
+    // A matching loop of 3 QIs, and an unrelated QI.
+    let mut input = r#"[mk-app] #1 a
+[mk-var] #2 0
+[mk-app] #3 f #2
+[mk-app] #4 pattern #3
+[mk-app] #5 g #2
+[mk-app] #6 f #5
+[mk-quant] #7 loop 1 #4 #6
+[attach-var-names] #7 (|x| ; |Int|)
+[mk-app] #8 f #1
+[mk-app] #30 h #2
+[mk-app] #31 pattern #30
+[mk-app] #32 k #2
+[mk-quant] #33 other 1 #31 #32
+[attach-var-names] #33 (|x| ; |Int|)
+[mk-app] #34 h #1
+[new-match] 0x10 #33 #31 #1 ; #34
+[mk-app] #35 k #1
+[instance] 0x10 #35 ; 1
+[attach-enode] #35 1
+[end-of-instance]
+"#
+    .to_string();
+    let mut previous = (1, 8);
+    for i in 0..3 {
+        let (arg, term) = previous;
+        let g = 10 + 2 * i;
+        input += &format!(
+            "[new-match] {:#x} #7 #4 #{} ; #{}\n\
+             [mk-app] #{} g #{}\n\
+             [mk-app] #{} f #{}\n\
+             [instance] {:#x} #{} ; 1\n\
+             [attach-enode] #{} 1\n\
+             [attach-enode] #{} 1\n\
+             [end-of-instance]\n",
+            i + 1,
+            arg,
+            term,
+            g,
+            arg,
+            g + 1,
+            g,
+            i + 1,
+            g + 1,
+            g,
+            g + 1
+        );
+        previous = (g, g + 1);
+    }
+    input += "[eof]\n";
+    let mut model = Model::default();
+    model.process(None, input.as_bytes())?;
+    assert_eq!(model.instantiations().len(), 4);
+    let quantifiers = heaviest_quantifiers(&model, 1);
+    assert_eq!(quantifiers.into_iter().collect::<Vec<_>>(), vec!["loop"]);
+
+    let commands = smt2parser::parse_commands(
+        r#"
+(declare-fun a () Int)
+(declare-fun f (Int) Int)
+(declare-fun g (Int) Int)
+(declare-fun h (Int) Int)
+(declare-fun k (Int) Bool)
+(assert (forall ((x Int)) (! (= (f x) (f (g x))) :pattern ((f x)) :qid loop)))
+(assert (forall ((x Int)) (! (k x) :pattern ((h x)) :qid other)))
+(assert (forall ((x Int)) (> (g x) 0)))
+(assert (> (f a) (h a)))
+(check-sat)
+"#,
+    )?;
+    let reduced = reduce_commands(&commands, &heaviest_quantifiers(&model, 1));
+    assert_eq!(
+        reduced
+            .iter()
+            .map(|command| command.to_string())
+            .collect::<Vec<_>>(),
+        vec![
+            "(declare-fun a () Int)",
+            "(declare-fun f (Int) Int)",
+            "(declare-fun g (Int) Int)",
+            "(declare-fun h (Int) Int)",
+            "(assert (forall ((x Int)) (! (= (f x) (f (g x))) :pattern ((f x)) :qid loop)))",
+            "(assert (forall ((x Int)) (> (g x) 0)))",
+            "(assert (> (f a) (h a)))",
+            "(check-sat)",
+        ]
+    );
+    assert_eq!(
+        reduce_commands(&commands, &heaviest_quantifiers(&model, 4)).len(),
+        commands.len()
+    );
+    Ok(())
+}
+
+// A log with a matching loop of the given length, where `f(x)` triggers the creation of
+// `f(g(x))`, and a quantifier instantiated once.
+fn matching_loop_log(length: usize) -> String {
+    let mut input = r#"[mk-app] #1 a
+[mk-var] #2 0
+[mk-app] #3 f #2
+[mk-app] #4 pattern #3
+[mk-app] #5 g #2
+[mk-app] #6 f #5
+[mk-quant] #7 loop 1 #4 #6
+[attach-var-names] #7 (|x| ; |Int|)
+[mk-app] #8 f #1
+[mk-app] #50 h #2
+[mk-app] #51 pattern #50
+[mk-quant] #52 once 1 #51 #1
+[attach-var-names] #52 (|x| ; |Int|)
+[new-match] 0x100 #52 #51 #1 ; #1
+[instance] 0x100 ; 1
+[end-of-instance]
+"#
+    .to_string();
+    let mut previous = (1, 8);
+    for i in 0..length {
+        let (arg, term) = previous;
+        let g = 10 + 2 * i;
+        input += &format!(
+            "[new-match] {:#x} #7 #4 #{} ; #{}\n\
+             [mk-app] #{} g #{}\n\
+             [mk-app] #{} f #{}\n\
+             [instance] {:#x} #{} ; 1\n\
+             [attach-enode] #{} 1\n\
+             [attach-enode] #{} 1\n\
+             [end-of-instance]\n",
+            i + 1,
+            arg,
+            term,
+            g,
+            arg,
+            g + 1,
+            g,
+            i + 1,
+            g + 1,
+            g,
+            g + 1
+        );
+        previous = (g, g + 1);
+    }
+    input + "[eof]\n"
+}
+
+#[test]
+fn test_chain_depths() -> anyhow::Result<()> {
+    let runs = (2..5)
+        .map(|length| {
+            let mut model = Model::default();
+            model.process(None, matching_loop_log(length).as_bytes())?;
+            Ok(chain_depth_distributions(&model))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let depths = &runs[2];
+    assert_eq!(depths.len(), 2);
+    assert_eq!(depths[0].name, "loop");
+    assert_eq!(depths[0].max_depth(), 4);
+    assert_eq!(
+        depths[0].histogram,
+        BTreeMap::from([(1, 1), (2, 1), (3, 1), (4, 1)])
+    );
+    assert_eq!(depths[1].name, "once");
+    assert_eq!(depths[1].histogram, BTreeMap::from([(1, 1)]));
+    assert_eq!(growing_chain_depths(&runs), vec!["loop"]);
+    assert!(growing_chain_depths(&runs[..1]).is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_json_export() -> anyhow::Result<()> {
+    let model = process_file("tests/data/file5.log")?;
+    let json = model.to_json()?;
+    assert_eq!(json["version"], smt2parser::schema::SCHEMA_VERSION);
+    assert_eq!(json["tool"]["version"], "4.8.12");
+    assert_eq!(json["instantiations"], model.instantiations().len());
+    assert_eq!(json["theories"]["arith"]["instantiations"], 15);
+    let quantifiers = json["quantifiers"].as_array().unwrap();
+    assert_eq!(quantifiers[0]["name"], "arith");
+    assert_eq!(quantifiers[0]["formula"], serde_json::Value::Null);
+    assert_eq!(quantifiers[2]["id"], "#68");
+    assert_eq!(quantifiers[2]["instantiations"], 2);
+
+    let profile = model.instantiation_profile_json()?;
+    assert_eq!(profile["quantifiers"][0]["name"], "pf");
+    assert_eq!(profile["quantifiers"][0]["useful_instantiations"], 1);
+    assert_eq!(profile["triggers"][0]["pattern"], "(pattern (p _0))");
+    assert_eq!(
+        profile["instantiations"].as_array().unwrap().len(),
+        model.instantiations().len()
+    );
+    Ok(())
+}